@@ -0,0 +1,593 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::functions::BasicFunctions;
+use crate::{Device, Model};
+
+mod tcp;
+
+pub use tcp::TcpAllReduce;
+
+// Sums buffers across the workers of a data-parallel group. All workers call
+// all_reduce with buffers of the same length in the same order, and receive
+// the sum of the buffers of rank 0, 1, ... added in this order.
+pub trait AllReduce {
+    fn rank(&self) -> usize;
+    fn world_size(&self) -> usize;
+    fn all_reduce(&mut self, values: &mut [f32]);
+
+    // Replaces the values with those of rank 0. The other ranks contribute
+    // zeros, so the sum is exactly the values of rank 0.
+    fn broadcast(&mut self, values: &mut [f32]) {
+        if self.rank() != 0 {
+            values.iter_mut().for_each(|v| *v = 0.);
+        }
+        self.all_reduce(values);
+    }
+}
+
+struct GroupState {
+    buffers: Vec<Option<Vec<f32>>>,
+    result: Vec<f32>,
+    generation: u64,
+    aborted: bool,
+}
+
+struct Group {
+    state: Mutex<GroupState>,
+    cond: Condvar,
+}
+
+impl Group {
+    // The state is consistent even if a rank panicked while holding the lock,
+    // because the panic aborts the group.
+    fn lock(&self) -> MutexGuard<'_, GroupState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn abort(&self) {
+        self.lock().aborted = true;
+        self.cond.notify_all();
+    }
+}
+
+// Aborts the group if the rank panics during an all-reduce.
+struct AbortOnPanic<'a>(&'a Group);
+
+impl<'a> Drop for AbortOnPanic<'a> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.abort();
+        }
+    }
+}
+
+// All-reduce between threads of the same process. If a rank panics in
+// all_reduce or is dropped, the group is aborted and the other ranks panic
+// in all_reduce instead of waiting for it forever.
+pub struct InProcessAllReduce {
+    group: Arc<Group>,
+    rank: usize,
+    world_size: usize,
+}
+
+impl InProcessAllReduce {
+    // Returns the handles of ranks 0, 1, ..., one for each thread.
+    pub fn group(world_size: usize) -> Vec<InProcessAllReduce> {
+        assert!(world_size != 0);
+        let group = Arc::new(Group {
+            state: Mutex::new(GroupState {
+                buffers: vec![None; world_size],
+                result: vec![],
+                generation: 0,
+                aborted: false,
+            }),
+            cond: Condvar::new(),
+        });
+        (0..world_size)
+            .map(|rank| InProcessAllReduce {
+                group: Arc::clone(&group),
+                rank,
+                world_size,
+            })
+            .collect()
+    }
+}
+
+impl AllReduce for InProcessAllReduce {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn world_size(&self) -> usize {
+        self.world_size
+    }
+
+    fn all_reduce(&mut self, values: &mut [f32]) {
+        let _abort = AbortOnPanic(&self.group);
+        let mut state = self.group.lock();
+        assert!(!state.aborted, "all-reduce group is aborted");
+        let generation = state.generation;
+        state.buffers[self.rank] = Some(values.to_vec());
+        if state.buffers.iter().all(|buffer| buffer.is_some()) {
+            let mut buffers = state
+                .buffers
+                .iter_mut()
+                .map(|buffer| buffer.take().unwrap());
+            let mut result = buffers.next().unwrap();
+            for buffer in buffers {
+                assert!(
+                    buffer.len() == result.len(),
+                    "all-reduce buffers have different lengths"
+                );
+                sum_into(&mut result, &buffer);
+            }
+            state.result = result;
+            state.generation += 1;
+            self.group.cond.notify_all();
+        } else {
+            // The result is replaced only after all ranks join the next
+            // all-reduce, so it is still available when this rank wakes up.
+            while state.generation == generation {
+                assert!(
+                    !state.aborted,
+                    "all-reduce group is aborted while rank {} is waiting",
+                    self.rank
+                );
+                state = self
+                    .group
+                    .cond
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        values.copy_from_slice(&state.result);
+    }
+}
+
+impl Drop for InProcessAllReduce {
+    // The dropped rank never joins an all-reduce again.
+    fn drop(&mut self) {
+        self.group.abort();
+    }
+}
+
+fn sum_into(dst: &mut [f32], src: &[f32]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d += s;
+    }
+}
+
+// Trains replicas of a master model on several devices. Each replica
+// calculates the gradients of its part of the batch, and the gradients
+// averaged over all replicas of the group are added to the master, which is
+// updated by the optimizer as usual.
+//
+// With TcpAllReduce, each process holds a DataParallel and the gradients are
+// averaged across processes. The masters start with the values of rank 0, and
+// every process updates its own master with the same gradients, so the
+// masters stay identical.
+pub struct DataParallel<'dev, M, R = InProcessAllReduce> {
+    master: M,
+    replicas: Vec<M>,
+    reducers: Vec<R>,
+    _device: std::marker::PhantomData<&'dev Device<'dev>>,
+}
+
+impl<'dev, M> DataParallel<'dev, M>
+where
+    M: Model<'dev> + Send,
+{
+    // build creates a model with the same parameters on the given device. The
+    // replicas start with the values of the master.
+    pub fn new<F>(master: M, devices: &[&'dev Device<'dev>], build: F) -> Self
+    where
+        F: Fn(&'dev Device<'dev>) -> M,
+    {
+        let reducers = InProcessAllReduce::group(devices.len());
+        DataParallel::with_all_reduce(master, devices, build, reducers)
+    }
+}
+
+impl<'dev, M, R> DataParallel<'dev, M, R>
+where
+    M: Model<'dev> + Send,
+    R: AllReduce + Send,
+{
+    // Uses one reducer for each device. The reducers may belong to a group
+    // larger than the local devices.
+    pub fn with_all_reduce<F>(
+        master: M,
+        devices: &[&'dev Device<'dev>],
+        build: F,
+        reducers: Vec<R>,
+    ) -> Self
+    where
+        F: Fn(&'dev Device<'dev>) -> M,
+    {
+        assert!(
+            !devices.is_empty(),
+            "DataParallel requires at least one device"
+        );
+        assert!(
+            reducers.len() == devices.len(),
+            "DataParallel has {} devices, but {} reducers are given",
+            devices.len(),
+            reducers.len()
+        );
+        let replicas = devices.iter().map(|&device| build(device)).collect();
+        let mut dp = DataParallel {
+            master,
+            replicas,
+            reducers,
+            _device: std::marker::PhantomData,
+        };
+        let shapes = dp
+            .master
+            .parameters()
+            .iter()
+            .map(|param| param.shape())
+            .collect::<Vec<_>>();
+        for replica in &dp.replicas {
+            let replica_shapes = replica
+                .parameters()
+                .iter()
+                .map(|param| param.shape())
+                .collect::<Vec<_>>();
+            assert!(
+                replica_shapes == shapes,
+                "the replica has parameters {:?}, but the master has {:?}",
+                replica_shapes,
+                shapes
+            );
+        }
+        dp.broadcast_master();
+        dp.synchronize();
+        dp
+    }
+
+    pub fn master(&self) -> &M {
+        &self.master
+    }
+
+    pub fn master_mut(&mut self) -> &mut M {
+        &mut self.master
+    }
+
+    pub fn replicas(&self) -> &[M] {
+        &self.replicas
+    }
+
+    pub fn num_replicas(&self) -> usize {
+        self.replicas.len()
+    }
+
+    // Ranks of the local replicas in the whole group, e.g. to select the part
+    // of the batch.
+    pub fn ranks(&self) -> Vec<usize> {
+        self.reducers.iter().map(|r| r.rank()).collect()
+    }
+
+    // Runs f with the index and the replica of each local device, each in its
+    // own thread. f builds the graph of the part of the batch and calls
+    // backward(). Then the averaged gradients are added to the master, and
+    // the gradients of the replicas are reset.
+    //
+    // The gradients of all ranks are averaged with the same weight, so the
+    // result is the gradient of the mean loss of the whole batch only if every
+    // rank takes the mean over the same number of samples. With uneven parts,
+    // f should scale the loss of n_i samples by world_size * n_i / n.
+    pub fn run<F>(&mut self, f: F)
+    where
+        F: Fn(usize, &mut M) + Sync,
+    {
+        let f = &f;
+        thread::scope(|s| {
+            let handles = self
+                .replicas
+                .iter_mut()
+                .enumerate()
+                .map(|(i, replica)| s.spawn(move || f(i, replica)))
+                .collect::<Vec<_>>();
+            for handle in handles {
+                if let Err(e) = handle.join() {
+                    std::panic::resume_unwind(e);
+                }
+            }
+        });
+        let mut reduced = thread::scope(|s| {
+            let handles = self
+                .replicas
+                .iter_mut()
+                .zip(&mut self.reducers)
+                .map(|(replica, reducer)| {
+                    s.spawn(move || {
+                        let mut values = vec![];
+                        for param in replica.parameters_mut() {
+                            values.extend(param.gradient.to_vec());
+                            param.reset_gradient();
+                        }
+                        reducer.all_reduce(&mut values);
+                        values
+                    })
+                })
+                .collect::<Vec<_>>();
+            // All local replicas receive the same result.
+            let mut results = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>();
+            results.swap_remove(0)
+        });
+        let scale = 1. / self.reducers[0].world_size() as f32;
+        for v in reduced.iter_mut() {
+            *v *= scale;
+        }
+        let mut offset = 0;
+        for param in self.master.parameters_mut() {
+            let size = param.shape().size() as usize;
+            let device = param.gradient.device();
+            let g = device.new_tensor_by_slice(param.shape(), &reduced[offset..offset + size]);
+            param.gradient += g;
+            offset += size;
+        }
+    }
+
+    // Replaces the values of the master with those of rank 0. All local
+    // reducers join the broadcast, each in its own thread.
+    fn broadcast_master(&mut self) {
+        let values = self
+            .master
+            .parameters()
+            .iter()
+            .flat_map(|param| param.value.to_vec())
+            .collect::<Vec<_>>();
+        let values = &values;
+        let values = thread::scope(|s| {
+            let handles = self
+                .reducers
+                .iter_mut()
+                .map(|reducer| {
+                    s.spawn(move || {
+                        let mut values = values.clone();
+                        reducer.broadcast(&mut values);
+                        values
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut results = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>();
+            results.swap_remove(0)
+        });
+        let mut offset = 0;
+        for param in self.master.parameters_mut() {
+            let size = param.shape().size() as usize;
+            let device = param.value.device();
+            device.reset_tensor_by_slice(&mut param.value, &values[offset..offset + size]);
+            offset += size;
+        }
+    }
+
+    // Copies the values of the master to the replicas, e.g. after the
+    // optimizer updates the master.
+    pub fn synchronize(&mut self) {
+        let values = self
+            .master
+            .parameters()
+            .iter()
+            .map(|param| param.value.to_vec())
+            .collect::<Vec<_>>();
+        for replica in &mut self.replicas {
+            for (param, values) in replica.parameters_mut().into_iter().zip(&values) {
+                let device = param.value.device();
+                device.reset_tensor_by_slice(&mut param.value, values);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::{AllReduce, DataParallel, InProcessAllReduce, TcpAllReduce};
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::optimizers as O;
+    use crate::{initializers as I, Device, Node, Optimizer, Parameter};
+
+    const X: [f32; 12] = [1., 2., 0.5, -1., 0., 3., 2., -2., 1., 0.5, 1.5, -0.5];
+    const T: [f32; 8] = [1., 0., -1., 2., 0.5, 0.5, 3., -1.];
+
+    fn build<'dev>(device: &'dev Device<'dev>) -> Vec<Parameter<'dev>> {
+        vec![
+            device.new_parameter(shape![2, 3], &I::Uniform::new(-1., 1.)),
+            device.new_parameter(shape![2], &I::Constant::new(0.5)),
+        ]
+    }
+
+    // Mean squared error of the samples [lower, upper).
+    fn loss<'arg, 'dev>(
+        device: &'dev Device<'dev>,
+        params: &'arg mut [Parameter<'dev>],
+        lower: usize,
+        upper: usize,
+    ) -> Node<'arg, 'dev> {
+        let n = (upper - lower) as u32;
+        let x = device.new_tensor_by_slice(shape![3; n], &X[lower * 3..upper * 3]);
+        let t = device.new_tensor_by_slice(shape![2; n], &T[lower * 2..upper * 2]);
+        let (w, b) = params.split_at_mut(1);
+        let w = Node::from(&mut w[0]);
+        let b = Node::from(&mut b[0]);
+        let d = w.matmul(Node::from(x)) + b - Node::from(t);
+        (&d * &d).sum(0).batch_sum() * (1. / n as f32)
+    }
+
+    #[test]
+    fn check_data_parallel() {
+        let dev = D::Naive::new();
+        let replica_devs = vec![D::Naive::new(), D::Naive::new()];
+        let devices = replica_devs.iter().collect::<Vec<&Device>>();
+        let mut single = build(&dev);
+        let mut master = build(&dev);
+        let values = single[0].value.to_vec();
+        dev.reset_tensor_by_slice(&mut master[0].value, &values);
+        let mut dp = DataParallel::new(master, &devices, build);
+        let mut opt_single = O::SGD::new(0.1);
+        let mut opt_dp = O::SGD::new(0.1);
+        for _ in 0..3 {
+            loss(&dev, &mut single, 0, 4).backward();
+            dp.run(|i, replica| {
+                let device = replica[0].value.device();
+                loss(device, replica, i * 2, i * 2 + 2).backward();
+            });
+            for (p, q) in single.iter().zip(dp.master()) {
+                let expected = p.gradient.to_vec();
+                let g = q.gradient.to_vec();
+                assert_vector_ulps_eq!(expected, g, epsilon = 1e-5, max_ulps = 16);
+            }
+            opt_single.update_model(&mut single);
+            opt_dp.update_model(dp.master_mut());
+            dp.synchronize();
+        }
+        let replica_value = dp.replicas()[1][0].value.to_vec();
+        let master_value = dp.master()[0].value.to_vec();
+        assert_eq!(master_value, replica_value);
+    }
+
+    #[test]
+    fn check_in_process_all_reduce() {
+        let results = thread::scope(|s| {
+            let handles = InProcessAllReduce::group(3)
+                .into_iter()
+                .map(|mut reducer| {
+                    s.spawn(move || {
+                        let mut results = vec![];
+                        for n in 0..20 {
+                            let k = (reducer.rank() + n) as f32;
+                            let mut values = vec![k, 2. * k];
+                            reducer.all_reduce(&mut values);
+                            results.push(values);
+                        }
+                        results
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        for results in &results {
+            for (n, values) in results.iter().enumerate() {
+                let k = (3 * n + 3) as f32;
+                assert_eq!(&vec![k, 2. * k], values);
+            }
+        }
+    }
+
+    #[test]
+    fn check_in_process_all_reduce_abort() {
+        // Rank 1 fails the length check, or panics while rank 0 waits.
+        let joined = thread::scope(|s| {
+            let handles = InProcessAllReduce::group(2)
+                .into_iter()
+                .map(|mut reducer| {
+                    s.spawn(move || {
+                        let mut values = vec![0.; reducer.rank() + 1];
+                        reducer.all_reduce(&mut values);
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().is_ok())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(vec![false, false], joined);
+
+        // Rank 1 panics before joining the all-reduce.
+        let joined = thread::scope(|s| {
+            let handles = InProcessAllReduce::group(2)
+                .into_iter()
+                .map(|mut reducer| {
+                    s.spawn(move || {
+                        if reducer.rank() == 1 {
+                            panic!("rank 1 fails");
+                        }
+                        reducer.all_reduce(&mut [1.]);
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().is_ok())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(vec![false, false], joined);
+    }
+
+    #[test]
+    fn check_tcp_data_parallel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let results = thread::scope(|s| {
+            let handles = (0..2)
+                .map(|rank| {
+                    let listener = if rank == 0 {
+                        Some(listener.try_clone().unwrap())
+                    } else {
+                        None
+                    };
+                    s.spawn(move || {
+                        let reducer = match listener {
+                            Some(listener) => TcpAllReduce::listen(listener, 2).unwrap(),
+                            None => TcpAllReduce::connect(addr, rank, 2).unwrap(),
+                        };
+                        let dev = D::Naive::new();
+                        let replica_dev = D::Naive::new();
+                        // Each rank initializes its master randomly.
+                        let mut dp = DataParallel::with_all_reduce(
+                            build(&dev),
+                            &[&replica_dev],
+                            build,
+                            vec![reducer],
+                        );
+                        let lower = dp.ranks()[0] * 2;
+                        dp.run(|_, replica| {
+                            loss(&replica_dev, replica, lower, lower + 2).backward();
+                        });
+                        let value = dp.master()[0].value.to_vec();
+                        let grad = dp.master()[0].gradient.to_vec();
+                        (value, grad)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        // The masters start with the values of rank 0.
+        let (initial, grad) = &results[0];
+        assert_eq!(results[1].0, *initial);
+
+        // Gradients of the in-process run with one replica for each rank.
+        let dev = D::Naive::new();
+        let replica_devs = vec![D::Naive::new(), D::Naive::new()];
+        let devices = replica_devs.iter().collect::<Vec<&Device>>();
+        let mut master = build(&dev);
+        dev.reset_tensor_by_slice(&mut master[0].value, initial);
+        let mut dp = DataParallel::new(master, &devices, build);
+        dp.run(|i, replica| {
+            let device = replica[0].value.device();
+            loss(device, replica, i * 2, i * 2 + 2).backward();
+        });
+        let expected = dp.master()[0].gradient.to_vec();
+        assert_eq!(expected, *grad);
+        assert_eq!(expected, results[1].1);
+    }
+}
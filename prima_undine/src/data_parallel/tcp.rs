@@ -0,0 +1,136 @@
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::{sum_into, AllReduce};
+
+// All-reduce over TCP, e.g. between processes on localhost. Rank 0 accepts
+// a connection from each other rank, sums the buffers and sends back the
+// result. Values are sent as little-endian f32.
+pub struct TcpAllReduce {
+    rank: usize,
+    world_size: usize,
+    // Rank 0 holds the streams of ranks 1, 2, ..., the others the stream to
+    // rank 0.
+    streams: Vec<TcpStream>,
+}
+
+fn write_u32(stream: &mut TcpStream, x: u32) -> io::Result<()> {
+    stream.write_all(&x.to_le_bytes())
+}
+
+fn read_u32(stream: &mut TcpStream) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn write_values(stream: &mut TcpStream, values: &[f32]) -> io::Result<()> {
+    let bytes = values
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+    write_u32(stream, values.len() as u32)?;
+    stream.write_all(&bytes)
+}
+
+fn read_values(stream: &mut TcpStream, values: &mut [f32]) -> io::Result<()> {
+    let len = read_u32(stream)? as usize;
+    if len != values.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "all-reduce buffers have different lengths: {} and {}",
+                values.len(),
+                len
+            ),
+        ));
+    }
+    let mut bytes = vec![0; len * 4];
+    stream.read_exact(&mut bytes)?;
+    for (v, b) in values.iter_mut().zip(bytes.chunks(4)) {
+        *v = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    }
+    Ok(())
+}
+
+impl TcpAllReduce {
+    // Creates rank 0, waiting until all other ranks are connected.
+    pub fn listen(listener: TcpListener, world_size: usize) -> io::Result<TcpAllReduce> {
+        assert!(world_size != 0);
+        let mut streams = (1..world_size).map(|_| None).collect::<Vec<_>>();
+        for _ in 1..world_size {
+            let (mut stream, _) = listener.accept()?;
+            stream.set_nodelay(true)?;
+            let rank = read_u32(&mut stream)? as usize;
+            if rank == 0 || rank >= world_size || streams[rank - 1].is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid rank {} is connected", rank),
+                ));
+            }
+            streams[rank - 1] = Some(stream);
+        }
+        Ok(TcpAllReduce {
+            rank: 0,
+            world_size,
+            streams: streams.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+
+    // Creates a rank other than 0, connected to the address of rank 0.
+    pub fn connect<A: ToSocketAddrs>(
+        addr: A,
+        rank: usize,
+        world_size: usize,
+    ) -> io::Result<TcpAllReduce> {
+        assert!(
+            rank != 0 && rank < world_size,
+            "rank {} cannot connect to a group of size {}",
+            rank,
+            world_size
+        );
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        write_u32(&mut stream, rank as u32)?;
+        Ok(TcpAllReduce {
+            rank,
+            world_size,
+            streams: vec![stream],
+        })
+    }
+
+    pub fn try_all_reduce(&mut self, values: &mut [f32]) -> io::Result<()> {
+        if self.rank == 0 {
+            let mut buffer = vec![0.; values.len()];
+            for stream in &mut self.streams {
+                read_values(stream, &mut buffer)?;
+                sum_into(values, &buffer);
+            }
+            for stream in &mut self.streams {
+                write_values(stream, values)?;
+            }
+        } else {
+            let stream = &mut self.streams[0];
+            write_values(stream, values)?;
+            read_values(stream, values)?;
+        }
+        Ok(())
+    }
+}
+
+impl AllReduce for TcpAllReduce {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn world_size(&self) -> usize {
+        self.world_size
+    }
+
+    fn all_reduce(&mut self, values: &mut [f32]) {
+        if let Err(e) = self.try_all_reduce(values) {
+            panic!("all-reduce of rank {} failed: {}", self.rank, e);
+        }
+    }
+}
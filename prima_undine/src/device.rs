@@ -1,14 +1,48 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ffi::c_void;
 use std::fmt;
+use std::mem;
+use std::ptr;
 use std::sync::atomic::AtomicPtr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::device_impl::{
     DeviceImpl, FunctionBwImpl, FunctionFwF32Impl, FunctionFwImpl, FunctionFwU32Impl,
+    FunctionReadBytesImpl, FunctionWriteBytesImpl,
 };
+use crate::error::OrPanic;
 use crate::memory_pool::MemoryPool;
-use crate::{Initializer, Parameter, Shape, Tensor};
+use crate::random::RandomizerState;
+use crate::{DType, Element, Error, Initializer, Parameter, Randomizer, Result, Shape, Tensor};
+
+// Kernels for dtypes other than f32 are registered as "<name>:<dtype>".
+fn impl_name(name: &str, dtype: DType) -> Cow<'_, str> {
+    if dtype == DType::F32 {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("{}:{}", name, dtype))
+    }
+}
+
+fn to_bytes<T: Element>(values: &[T]) -> Vec<u8> {
+    let mut bytes = vec![0; mem::size_of_val(values)];
+    unsafe {
+        ptr::copy_nonoverlapping(
+            values.as_ptr() as *const u8,
+            bytes.as_mut_ptr(),
+            bytes.len(),
+        );
+    }
+    bytes
+}
+
+fn from_bytes<T: Element>(bytes: &[u8]) -> Vec<T> {
+    bytes
+        .chunks_exact(mem::size_of::<T>())
+        .map(|b| unsafe { ptr::read_unaligned(b.as_ptr() as *const T) })
+        .collect()
+}
 
 pub struct Device<'dev>
 where
@@ -20,6 +54,9 @@ where
     pub(crate) fw_u32_impl: HashMap<String, Box<dyn FunctionFwU32Impl + 'dev>>,
     pub(crate) fw_f32_impl: HashMap<String, Box<dyn FunctionFwF32Impl + 'dev>>,
     pub(crate) bw_impl: HashMap<String, Box<dyn FunctionBwImpl + 'dev>>,
+    pub(crate) read_bytes_impl: HashMap<String, Box<dyn FunctionReadBytesImpl + 'dev>>,
+    pub(crate) write_bytes_impl: HashMap<String, Box<dyn FunctionWriteBytesImpl + 'dev>>,
+    pub(crate) randomizer: Option<Arc<Mutex<Box<dyn Randomizer>>>>,
 }
 
 impl<'dev> Device<'dev> {
@@ -32,6 +69,9 @@ impl<'dev> Device<'dev> {
             bw_impl: HashMap::new(),
             fw_u32_impl: HashMap::new(),
             fw_f32_impl: HashMap::new(),
+            read_bytes_impl: HashMap::new(),
+            write_bytes_impl: HashMap::new(),
+            randomizer: None,
         }
     }
 
@@ -51,6 +91,79 @@ impl<'dev> Device<'dev> {
         self.bw_impl.insert(name.to_string(), Box::new(func));
     }
 
+    pub fn register_fw_impl_for<T: FunctionFwImpl + 'dev>(
+        &mut self,
+        dtype: DType,
+        name: &str,
+        func: T,
+    ) {
+        self.fw_impl
+            .insert(impl_name(name, dtype).into_owned(), Box::new(func));
+    }
+
+    pub fn register_fw_f32_impl_for<T: FunctionFwF32Impl + 'dev>(
+        &mut self,
+        dtype: DType,
+        name: &str,
+        func: T,
+    ) {
+        self.fw_f32_impl
+            .insert(impl_name(name, dtype).into_owned(), Box::new(func));
+    }
+
+    pub fn register_bw_impl_for<T: FunctionBwImpl + 'dev>(
+        &mut self,
+        dtype: DType,
+        name: &str,
+        func: T,
+    ) {
+        self.bw_impl
+            .insert(impl_name(name, dtype).into_owned(), Box::new(func));
+    }
+
+    pub fn register_read_bytes_impl_for<T: FunctionReadBytesImpl + 'dev>(
+        &mut self,
+        dtype: DType,
+        name: &str,
+        func: T,
+    ) {
+        self.read_bytes_impl
+            .insert(impl_name(name, dtype).into_owned(), Box::new(func));
+    }
+
+    pub fn register_write_bytes_impl_for<T: FunctionWriteBytesImpl + 'dev>(
+        &mut self,
+        dtype: DType,
+        name: &str,
+        func: T,
+    ) {
+        self.write_bytes_impl
+            .insert(impl_name(name, dtype).into_owned(), Box::new(func));
+    }
+
+    pub fn register_randomizer(&mut self, randomizer: Arc<Mutex<Box<dyn Randomizer>>>) {
+        self.randomizer = Some(randomizer);
+    }
+
+    // Returns None if no randomizer is registered or it does not support
+    // saving its state.
+    pub fn randomizer_state(&self) -> Option<RandomizerState> {
+        match &self.randomizer {
+            Some(randomizer) => randomizer.lock().unwrap().state(),
+            None => None,
+        }
+    }
+
+    pub fn set_randomizer_state(&self, state: &RandomizerState) -> Result<()> {
+        match &self.randomizer {
+            Some(randomizer) => randomizer.lock().unwrap().set_state(state),
+            None => Err(Error::InvalidArgument {
+                op: "set_randomizer_state".to_string(),
+                message: "randomizer is not registered".to_string(),
+            }),
+        }
+    }
+
     pub fn call_fw_impl(
         &self,
         name: &str,
@@ -59,11 +172,26 @@ impl<'dev> Device<'dev> {
         f32data: &[f32],
         y: &mut [&mut Tensor],
     ) {
-        if let Some(fw) = self.fw_impl.get(name) {
-            fw.call(xs, u32data, f32data, y);
-        } else {
-            panic!("{} is not implemented", name);
-        }
+        self.try_call_fw_impl(name, xs, u32data, f32data, y)
+            .or_panic();
+    }
+
+    pub fn try_call_fw_impl(
+        &self,
+        name: &str,
+        xs: &[&Tensor],
+        u32data: &[u32],
+        f32data: &[f32],
+        y: &mut [&mut Tensor],
+    ) -> Result<()> {
+        let dtype = y.first().map_or(DType::F32, |y| y.dtype);
+        let name = impl_name(name, dtype);
+        let fw = self
+            .fw_impl
+            .get(name.as_ref())
+            .ok_or_else(|| self.not_implemented(&name))?;
+        fw.call(xs, u32data, f32data, y);
+        Ok(())
     }
 
     pub fn call_fw_u32_impl(
@@ -74,11 +202,26 @@ impl<'dev> Device<'dev> {
         f32data: &[f32],
         y: &mut [u32],
     ) {
-        if let Some(fw) = self.fw_u32_impl.get(name) {
-            fw.call(xs, u32data, f32data, y);
-        } else {
-            panic!("{} is not implemented", name);
-        }
+        self.try_call_fw_u32_impl(name, xs, u32data, f32data, y)
+            .or_panic();
+    }
+
+    pub fn try_call_fw_u32_impl(
+        &self,
+        name: &str,
+        xs: &[&Tensor],
+        u32data: &[u32],
+        f32data: &[f32],
+        y: &mut [u32],
+    ) -> Result<()> {
+        let dtype = xs.first().map_or(DType::F32, |x| x.dtype);
+        let name = impl_name(name, dtype);
+        let fw = self
+            .fw_u32_impl
+            .get(name.as_ref())
+            .ok_or_else(|| self.not_implemented(&name))?;
+        fw.call(xs, u32data, f32data, y);
+        Ok(())
     }
 
     pub fn call_fw_f32_impl(
@@ -89,13 +232,29 @@ impl<'dev> Device<'dev> {
         f32data: &[f32],
         y: &mut [f32],
     ) {
-        if let Some(fw) = self.fw_f32_impl.get(name) {
-            fw.call(xs, u32data, f32data, y);
-        } else {
-            panic!("{} is not implemented", name);
-        }
+        self.try_call_fw_f32_impl(name, xs, u32data, f32data, y)
+            .or_panic();
+    }
+
+    pub fn try_call_fw_f32_impl(
+        &self,
+        name: &str,
+        xs: &[&Tensor],
+        u32data: &[u32],
+        f32data: &[f32],
+        y: &mut [f32],
+    ) -> Result<()> {
+        let dtype = xs.first().map_or(DType::F32, |x| x.dtype);
+        let name = impl_name(name, dtype);
+        let fw = self
+            .fw_f32_impl
+            .get(name.as_ref())
+            .ok_or_else(|| self.not_implemented(&name))?;
+        fw.call(xs, u32data, f32data, y);
+        Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn call_bw_impl(
         &self,
         name: &str,
@@ -106,10 +265,62 @@ impl<'dev> Device<'dev> {
         f32data: &[f32],
         gx: &mut Tensor,
     ) {
-        if let Some(bw) = self.bw_impl.get(name) {
-            bw.call(xs, ys, gys, u32data, f32data, gx);
-        } else {
-            panic!("{} is not implemented", name);
+        self.try_call_bw_impl(name, xs, ys, gys, u32data, f32data, gx)
+            .or_panic();
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_call_bw_impl(
+        &self,
+        name: &str,
+        xs: &[&Tensor],
+        ys: &[&Tensor],
+        gys: &[&Tensor],
+        u32data: &[u32],
+        f32data: &[f32],
+        gx: &mut Tensor,
+    ) -> Result<()> {
+        let name = impl_name(name, gx.dtype);
+        let bw = self
+            .bw_impl
+            .get(name.as_ref())
+            .ok_or_else(|| self.not_implemented(&name))?;
+        bw.call(xs, ys, gys, u32data, f32data, gx);
+        Ok(())
+    }
+
+    pub fn call_read_bytes_impl(&self, name: &str, x: &Tensor, data: &mut [u8]) {
+        self.try_call_read_bytes_impl(name, x, data).or_panic();
+    }
+
+    pub fn try_call_read_bytes_impl(&self, name: &str, x: &Tensor, data: &mut [u8]) -> Result<()> {
+        let name = impl_name(name, x.dtype);
+        let f = self
+            .read_bytes_impl
+            .get(name.as_ref())
+            .ok_or_else(|| self.not_implemented(&name))?;
+        f.call(x, data);
+        Ok(())
+    }
+
+    pub fn call_write_bytes_impl(&self, name: &str, data: &[u8], y: &mut Tensor) {
+        self.try_call_write_bytes_impl(name, data, y).or_panic();
+    }
+
+    pub fn try_call_write_bytes_impl(&self, name: &str, data: &[u8], y: &mut Tensor) -> Result<()> {
+        let name = impl_name(name, y.dtype);
+        let f = self
+            .write_bytes_impl
+            .get(name.as_ref())
+            .ok_or_else(|| self.not_implemented(&name))?;
+        f.call(data, y);
+        Ok(())
+    }
+
+    fn not_implemented(&self, name: &str) -> Error {
+        Error::NotImplemented {
+            kernel: name.to_string(),
+            device: self.identifier(),
         }
     }
 
@@ -129,6 +340,10 @@ impl<'dev> Device<'dev> {
         Tensor::new(self, shape)
     }
 
+    pub fn new_tensor_with_dtype(&'dev self, shape: Shape, dtype: DType) -> Tensor<'dev> {
+        Tensor::new_with_dtype(self, shape, dtype)
+    }
+
     pub fn new_tensor_by_constant(&'dev self, shape: Shape, k: f32) -> Tensor<'dev> {
         let mut tensor = self.new_tensor(shape);
         tensor.alloc();
@@ -143,8 +358,16 @@ impl<'dev> Device<'dev> {
         tensor
     }
 
+    pub fn new_tensor_by_data<T: Element>(&'dev self, shape: Shape, values: &[T]) -> Tensor<'dev> {
+        assert!(shape.size() as usize == values.len());
+        let mut tensor = self.new_tensor_with_dtype(shape, T::DTYPE);
+        tensor.alloc();
+        self.call_write_bytes_impl("reset_tensor_by_bytes_impl", &to_bytes(values), &mut tensor);
+        tensor
+    }
+
     pub fn copy_tensor(&'dev self, source: &Tensor) -> Tensor<'dev> {
-        let mut tensor = self.new_tensor(source.shape);
+        let mut tensor = self.new_tensor_with_dtype(source.shape, source.dtype);
         tensor.alloc();
         self.reset_tensor_by_tensor(&mut tensor, source);
         tensor
@@ -163,7 +386,16 @@ impl<'dev> Device<'dev> {
         self.call_fw_impl("reset_tensor_impl", &[], &[], &[k], &mut [x]);
     }
 
+    pub fn cast_tensor(&'dev self, x: &Tensor, dtype: DType) -> Tensor<'dev> {
+        assert!(x.device() == self);
+        let mut y = self.new_tensor_with_dtype(x.shape, dtype);
+        y.alloc();
+        self.call_fw_impl("cast_impl", &[x], &[], &[], &mut [&mut y]);
+        y
+    }
+
     pub fn reset_tensor_by_slice(&self, x: &mut Tensor, values: &[f32]) {
+        assert!(x.dtype == DType::F32);
         assert!(x.shape.size() as usize == values.len());
         self.call_fw_impl("reset_tensor_by_slice_impl", &[], &[], values, &mut [x]);
     }
@@ -172,17 +404,31 @@ impl<'dev> Device<'dev> {
         assert!(x.valid());
         assert!(source.valid());
         assert!(x.shape.size() == source.shape.size());
+        assert!(x.dtype == source.dtype);
         self.call_fw_impl("reset_tensor_by_tensor_impl", &[source], &[], &[], &mut [x]);
     }
 
     pub fn tensor_to_vector(&self, x: &Tensor) -> Vec<f32> {
+        if x.dtype != DType::F32 {
+            return self.tensor_to_vector(&x.device().cast_tensor(x, DType::F32));
+        }
         let mut ret = vec![0.; x.shape.size() as usize];
         self.call_fw_f32_impl("tensor_to_vector_impl", &[x], &[], &[], ret.as_mut_slice());
         ret
     }
 
+    pub fn tensor_to_data<T: Element>(&self, x: &Tensor) -> Vec<T> {
+        assert!(x.dtype == T::DTYPE);
+        let mut bytes = vec![0; (x.shape.size() * x.dtype.size()) as usize];
+        self.call_read_bytes_impl("tensor_to_bytes_impl", x, &mut bytes);
+        from_bytes(&bytes)
+    }
+
     pub fn tensor_to_float(&self, x: &Tensor) -> f32 {
         assert!(x.shape.size() == 1);
+        if x.dtype != DType::F32 {
+            return self.tensor_to_float(&x.device().cast_tensor(x, DType::F32));
+        }
         let mut ret = [0.];
         self.call_fw_f32_impl("tensor_to_vector_impl", &[x], &[], &[], &mut ret);
         ret[0]
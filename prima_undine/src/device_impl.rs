@@ -21,6 +21,16 @@ pub trait FunctionFwF32Impl: Sync + Send {
     fn call(&self, xs: &[&Tensor], u32data: &[u32], f32data: &[f32], ys: &mut [f32]);
 }
 
+// Copies the raw elements of a tensor of any dtype to and from host memory,
+// in the native byte order of the element type.
+pub trait FunctionReadBytesImpl: Sync + Send {
+    fn call(&self, x: &Tensor, data: &mut [u8]);
+}
+
+pub trait FunctionWriteBytesImpl: Sync + Send {
+    fn call(&self, data: &[u8], y: &mut Tensor);
+}
+
 pub trait FunctionBwImpl: Sync + Send {
     fn call(
         &self,
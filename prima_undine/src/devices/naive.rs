@@ -13,18 +13,20 @@ mod batch_slice;
 mod batch_sum;
 mod broadcast;
 mod concat;
-//mod conv2d;
+mod conv2d;
 //mod copy_tensor;
 mod cos;
 mod div;
 mod elu;
 mod exp;
 mod flip;
+mod fused_elementwise;
 mod identity;
 mod ln;
 mod logsumexp;
 mod matmul;
 mod max;
+mod max_pool2d;
 mod min;
 mod mul;
 mod mul_assign;
@@ -37,6 +39,7 @@ mod prelu;
 mod random;
 mod reset_tensor;
 mod sigmoid;
+mod simd;
 mod sin;
 mod slice;
 mod softplus;
@@ -50,14 +53,22 @@ mod tensor_to_vector;
 mod transpose;
 mod triangular_l;
 mod triangular_u;
+mod typed;
 
 use crate::device_impl::DeviceImpl;
-use crate::random::DefaultRandomizer;
-use crate::{Device, Randomizer};
+use crate::random::{DefaultRandomizer, SeededRandomizer};
+use crate::{bf16, f16, DType, Device, Randomizer};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::ffi::c_void;
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::sync::{Arc, Mutex};
 
+// Device calculating on the CPU. All functions support f32, and f16 and bf16
+// are calculated by the f32 kernels. f64 supports the arithmetic operators,
+// sqrt, abs, sin, cos, tan, exp, ln, tanh, sigmoid, sum, broadcast and
+// matmul with their gradients, and i32 and u32 support add, sub, mul, sum and
+// matmul without gradients. Other kernels are not implemented: the try_ functions return
+// Error::NotImplemented and the others panic.
 pub struct Naive {}
 
 impl DeviceImpl for Naive {
@@ -79,10 +90,37 @@ impl DeviceImpl for Naive {
 
 impl<'dev> Naive {
     pub fn new() -> Device<'dev> {
+        Naive::with_randomizer(Box::new(DefaultRandomizer::new()))
+    }
+
+    pub fn with_seed(seed: u64) -> Device<'dev> {
+        Naive::with_randomizer(Box::new(SeededRandomizer::new(seed)))
+    }
+
+    pub fn with_randomizer(randomizer: Box<dyn Randomizer>) -> Device<'dev> {
+        Naive::build(randomizer, None)
+    }
+
+    pub fn with_threads(num_threads: usize) -> Device<'dev> {
+        Naive::with_randomizer_and_threads(Box::new(DefaultRandomizer::new()), num_threads)
+    }
+
+    pub fn with_randomizer_and_threads(
+        randomizer: Box<dyn Randomizer>,
+        num_threads: usize,
+    ) -> Device<'dev> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        Naive::build(randomizer, Some(Arc::new(pool)))
+    }
+
+    fn build(randomizer: Box<dyn Randomizer>, pool: Option<Arc<ThreadPool>>) -> Device<'dev> {
         let mut dev = Device::new(Naive {});
 
-        let randomizer: Arc<Mutex<Box<dyn Randomizer>>> =
-            Arc::new(Mutex::new(Box::new(DefaultRandomizer::new())));
+        let randomizer = Arc::new(Mutex::new(randomizer));
+        dev.register_randomizer(Arc::clone(&randomizer));
 
         // initializers
 
@@ -115,6 +153,10 @@ impl<'dev> Naive {
             "random_uniform_impl",
             random::RandomUniformImpl::new(Arc::clone(&randomizer)),
         );
+        dev.register_fw_impl(
+            "random_log_normal_impl",
+            random::RandomLogNormalImpl::new(Arc::clone(&randomizer)),
+        );
 
         // assign
 
@@ -137,107 +179,176 @@ impl<'dev> Naive {
 
         // arithmetic
 
-        dev.register_fw_impl("neg_fw_impl", neg::NegFwImpl::new());
-
-        dev.register_fw_impl("add_fw_impl", add::AddFwImpl::new());
-        dev.register_bw_impl("add_bw_a_impl", add::AddBwAImpl::new());
-        dev.register_bw_impl("add_bw_b_impl", add::AddBwBImpl::new());
-        dev.register_fw_impl("add_const_fw_impl", add::AddConstFwImpl::new());
-        dev.register_bw_impl("add_const_bw_impl", add::AddConstBwImpl::new());
-        dev.register_fw_impl("add_scalar_fw_impl", add::AddScalarFwImpl::new());
-
-        dev.register_fw_impl("sub_fw_impl", sub::SubFwImpl::new());
-        dev.register_bw_impl("sub_bw_a_impl", sub::SubBwAImpl::new());
-        dev.register_bw_impl("sub_bw_b_impl", sub::SubBwBImpl::new());
-        dev.register_fw_impl("sub_const_l_fw_impl", sub::SubConstLFwImpl::new());
-        dev.register_bw_impl("sub_const_l_bw_impl", sub::SubConstLBwImpl::new());
-        dev.register_fw_impl("sub_const_r_fw_impl", sub::SubConstRFwImpl::new());
-        dev.register_bw_impl("sub_const_r_bw_impl", sub::SubConstRBwImpl::new());
-        dev.register_fw_impl("sub_scalar_l_fw_impl", sub::SubScalarLFwImpl::new());
-        dev.register_fw_impl("sub_scalar_r_fw_impl", sub::SubScalarRFwImpl::new());
-
-        dev.register_fw_impl("mul_fw_impl", mul::MulFwImpl::new());
-        dev.register_bw_impl("mul_bw_a_impl", mul::MulBwAImpl::new());
-        dev.register_bw_impl("mul_bw_b_impl", mul::MulBwBImpl::new());
-        dev.register_fw_impl("mul_const_fw_impl", mul::MulConstFwImpl::new());
-        dev.register_bw_impl("mul_const_bw_impl", mul::MulConstBwImpl::new());
-        dev.register_fw_impl("mul_scalar_fw_impl", mul::MulScalarFwImpl::new());
-
-        dev.register_fw_impl("div_fw_impl", div::DivFwImpl::new());
-        dev.register_bw_impl("div_bw_a_impl", div::DivBwAImpl::new());
-        dev.register_bw_impl("div_bw_b_impl", div::DivBwBImpl::new());
-        dev.register_fw_impl("div_const_l_fw_impl", div::DivConstLFwImpl::new());
-        dev.register_bw_impl("div_const_l_bw_impl", div::DivConstLBwImpl::new());
-        dev.register_fw_impl("div_const_r_fw_impl", div::DivConstRFwImpl::new());
-        dev.register_bw_impl("div_const_r_bw_impl", div::DivConstRBwImpl::new());
-        dev.register_fw_impl("div_scalar_l_fw_impl", div::DivScalarLFwImpl::new());
-        dev.register_fw_impl("div_scalar_r_fw_impl", div::DivScalarRFwImpl::new());
+        dev.register_fw_impl("neg_fw_impl", neg::NegFwImpl::new(pool.clone()));
+
+        dev.register_fw_impl("add_fw_impl", add::AddFwImpl::new(pool.clone()));
+        dev.register_bw_impl("add_bw_a_impl", add::AddBwAImpl::new(pool.clone()));
+        dev.register_bw_impl("add_bw_b_impl", add::AddBwBImpl::new(pool.clone()));
+        dev.register_fw_impl("add_const_fw_impl", add::AddConstFwImpl::new(pool.clone()));
+        dev.register_bw_impl("add_const_bw_impl", add::AddConstBwImpl::new(pool.clone()));
+        dev.register_fw_impl(
+            "add_scalar_fw_impl",
+            add::AddScalarFwImpl::new(pool.clone()),
+        );
+
+        dev.register_fw_impl("sub_fw_impl", sub::SubFwImpl::new(pool.clone()));
+        dev.register_bw_impl("sub_bw_a_impl", sub::SubBwAImpl::new(pool.clone()));
+        dev.register_bw_impl("sub_bw_b_impl", sub::SubBwBImpl::new(pool.clone()));
+        dev.register_fw_impl(
+            "sub_const_l_fw_impl",
+            sub::SubConstLFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "sub_const_l_bw_impl",
+            sub::SubConstLBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "sub_const_r_fw_impl",
+            sub::SubConstRFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "sub_const_r_bw_impl",
+            sub::SubConstRBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "sub_scalar_l_fw_impl",
+            sub::SubScalarLFwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "sub_scalar_r_fw_impl",
+            sub::SubScalarRFwImpl::new(pool.clone()),
+        );
+
+        dev.register_fw_impl("mul_fw_impl", mul::MulFwImpl::new(pool.clone()));
+        dev.register_bw_impl("mul_bw_a_impl", mul::MulBwAImpl::new(pool.clone()));
+        dev.register_bw_impl("mul_bw_b_impl", mul::MulBwBImpl::new(pool.clone()));
+        dev.register_fw_impl("mul_const_fw_impl", mul::MulConstFwImpl::new(pool.clone()));
+        dev.register_bw_impl("mul_const_bw_impl", mul::MulConstBwImpl::new(pool.clone()));
+        dev.register_fw_impl(
+            "mul_scalar_fw_impl",
+            mul::MulScalarFwImpl::new(pool.clone()),
+        );
+
+        dev.register_fw_impl("div_fw_impl", div::DivFwImpl::new(pool.clone()));
+        dev.register_bw_impl("div_bw_a_impl", div::DivBwAImpl::new(pool.clone()));
+        dev.register_bw_impl("div_bw_b_impl", div::DivBwBImpl::new(pool.clone()));
+        dev.register_fw_impl(
+            "div_const_l_fw_impl",
+            div::DivConstLFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "div_const_l_bw_impl",
+            div::DivConstLBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "div_const_r_fw_impl",
+            div::DivConstRFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "div_const_r_bw_impl",
+            div::DivConstRBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "div_scalar_l_fw_impl",
+            div::DivScalarLFwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "div_scalar_r_fw_impl",
+            div::DivScalarRFwImpl::new(pool.clone()),
+        );
 
         // basic
 
-        dev.register_fw_impl("powf_fw_impl", powf::PowfFwImpl::new());
+        dev.register_fw_impl("powf_fw_impl", powf::PowfFwImpl::new(pool.clone()));
         dev.register_bw_impl("powf_bw_a_impl", powf::PowfBwAImpl::new());
         dev.register_bw_impl("powf_bw_b_impl", powf::PowfBwBImpl::new());
-        dev.register_fw_impl("powf_const_l_fw_impl", powf::PowfConstLFwImpl::new());
-        dev.register_bw_impl("powf_const_l_bw_impl", powf::PowfConstLBwImpl::new());
-        dev.register_fw_impl("powf_const_r_fw_impl", powf::PowfConstRFwImpl::new());
-        dev.register_bw_impl("powf_const_r_bw_impl", powf::PowfConstRBwImpl::new());
-        dev.register_fw_impl("powf_scalar_l_fw_impl", powf::PowfScalarLFwImpl::new());
-        dev.register_fw_impl("powf_scalar_r_fw_impl", powf::PowfScalarRFwImpl::new());
+        dev.register_fw_impl(
+            "powf_const_l_fw_impl",
+            powf::PowfConstLFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "powf_const_l_bw_impl",
+            powf::PowfConstLBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "powf_const_r_fw_impl",
+            powf::PowfConstRFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "powf_const_r_bw_impl",
+            powf::PowfConstRBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "powf_scalar_l_fw_impl",
+            powf::PowfScalarLFwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "powf_scalar_r_fw_impl",
+            powf::PowfScalarRFwImpl::new(pool.clone()),
+        );
 
-        dev.register_fw_impl("sqrt_fw_impl", sqrt::SqrtFwImpl::new());
-        dev.register_bw_impl("sqrt_bw_impl", sqrt::SqrtBwImpl::new());
+        dev.register_fw_impl("sqrt_fw_impl", sqrt::SqrtFwImpl::new(pool.clone()));
+        dev.register_bw_impl("sqrt_bw_impl", sqrt::SqrtBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("abs_fw_impl", abs::AbsFwImpl::new());
-        dev.register_bw_impl("abs_bw_impl", abs::AbsBwImpl::new());
+        dev.register_fw_impl("abs_fw_impl", abs::AbsFwImpl::new(pool.clone()));
+        dev.register_bw_impl("abs_bw_impl", abs::AbsBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("powi_fw_impl", powi::PowiFwImpl::new());
-        dev.register_bw_impl("powi_bw_impl", powi::PowiBwImpl::new());
+        dev.register_fw_impl("powi_fw_impl", powi::PowiFwImpl::new(pool.clone()));
+        dev.register_bw_impl("powi_bw_impl", powi::PowiBwImpl::new(pool.clone()));
 
         // trigonometric
 
-        dev.register_fw_impl("sin_fw_impl", sin::SinFwImpl::new());
-        dev.register_bw_impl("sin_bw_impl", sin::SinBwImpl::new());
+        dev.register_fw_impl("sin_fw_impl", sin::SinFwImpl::new(pool.clone()));
+        dev.register_bw_impl("sin_bw_impl", sin::SinBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("cos_fw_impl", cos::CosFwImpl::new());
-        dev.register_bw_impl("cos_bw_impl", cos::CosBwImpl::new());
+        dev.register_fw_impl("cos_fw_impl", cos::CosFwImpl::new(pool.clone()));
+        dev.register_bw_impl("cos_bw_impl", cos::CosBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("tan_fw_impl", tan::TanFwImpl::new());
-        dev.register_bw_impl("tan_bw_impl", tan::TanBwImpl::new());
+        dev.register_fw_impl("tan_fw_impl", tan::TanFwImpl::new(pool.clone()));
+        dev.register_bw_impl("tan_bw_impl", tan::TanBwImpl::new(pool.clone()));
 
         // exp
 
-        dev.register_fw_impl("exp_fw_impl", exp::ExpFwImpl::new());
-        dev.register_bw_impl("exp_bw_impl", exp::ExpBwImpl::new());
+        dev.register_fw_impl("exp_fw_impl", exp::ExpFwImpl::new(pool.clone()));
+        dev.register_bw_impl("exp_bw_impl", exp::ExpBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("ln_fw_impl", ln::LnFwImpl::new());
-        dev.register_bw_impl("ln_bw_impl", ln::LnBwImpl::new());
+        dev.register_fw_impl("ln_fw_impl", ln::LnFwImpl::new(pool.clone()));
+        dev.register_bw_impl("ln_bw_impl", ln::LnBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("tanh_fw_impl", tanh::TanhFwImpl::new());
-        dev.register_bw_impl("tanh_bw_impl", tanh::TanhBwImpl::new());
+        dev.register_fw_impl("tanh_fw_impl", tanh::TanhFwImpl::new(pool.clone()));
+        dev.register_bw_impl("tanh_bw_impl", tanh::TanhBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("sigmoid_fw_impl", sigmoid::SigmoidFwImpl::new());
-        dev.register_bw_impl("sigmoid_bw_impl", sigmoid::SigmoidBwImpl::new());
+        dev.register_fw_impl("sigmoid_fw_impl", sigmoid::SigmoidFwImpl::new(pool.clone()));
+        dev.register_bw_impl("sigmoid_bw_impl", sigmoid::SigmoidBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("softplus_fw_impl", softplus::SoftplusFwImpl::new());
+        dev.register_fw_impl(
+            "softplus_fw_impl",
+            softplus::SoftplusFwImpl::new(pool.clone()),
+        );
 
         // reduction
 
-        dev.register_fw_impl("sum_fw_impl", sum::SumFwImpl::new());
+        dev.register_fw_impl("sum_fw_impl", sum::SumFwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("logsumexp_fw_impl", logsumexp::LogsumexpFwImpl::new());
+        dev.register_fw_impl(
+            "logsumexp_fw_impl",
+            logsumexp::LogsumexpFwImpl::new(pool.clone()),
+        );
 
-        dev.register_fw_impl("max_fw_impl", max::MaxFwImpl::new());
+        dev.register_fw_impl("max_fw_impl", max::MaxFwImpl::new(pool.clone()));
         dev.register_bw_impl("max_bw_impl", max::MaxBwImpl::new());
 
-        dev.register_fw_impl("min_fw_impl", min::MinFwImpl::new());
+        dev.register_fw_impl("min_fw_impl", min::MinFwImpl::new(pool.clone()));
         dev.register_bw_impl("min_bw_impl", min::MinBwImpl::new());
 
-        dev.register_fw_impl("broadcast_fw_impl", broadcast::BroadcastFwImpl::new());
+        dev.register_fw_impl(
+            "broadcast_fw_impl",
+            broadcast::BroadcastFwImpl::new(pool.clone()),
+        );
 
         // matrix
 
-        dev.register_fw_impl("matmul_fw_impl", matmul::MatmulFwImpl::new());
+        dev.register_fw_impl("matmul_fw_impl", matmul::MatmulFwImpl::new(pool.clone()));
         dev.register_bw_impl("matmul_bw_a_impl", matmul::MatmulBwAImpl::new());
         dev.register_bw_impl("matmul_bw_b_impl", matmul::MatmulBwBImpl::new());
 
@@ -276,19 +387,21 @@ impl<'dev> Naive {
 
         // ramp
 
-        dev.register_fw_impl("prelu_fw_impl", prelu::PReLUFwImpl::new());
-        dev.register_bw_impl("prelu_bw_impl", prelu::PReLUBwImpl::new());
+        dev.register_fw_impl("prelu_fw_impl", prelu::PReLUFwImpl::new(pool.clone()));
+        dev.register_bw_impl("prelu_bw_impl", prelu::PReLUBwImpl::new(pool.clone()));
 
-        dev.register_fw_impl("elu_fw_impl", elu::EluFwImpl::new());
-        dev.register_bw_impl("elu_bw_impl", elu::EluBwImpl::new());
+        dev.register_fw_impl("elu_fw_impl", elu::EluFwImpl::new(pool.clone()));
+        dev.register_bw_impl("elu_bw_impl", elu::EluBwImpl::new(pool.clone()));
 
         // manipulation
 
         dev.register_fw_impl("slice_fw_impl", slice::SliceFwImpl::new());
         dev.register_bw_impl("slice_bw_impl", slice::SliceBwImpl::new());
 
-        dev.register_fw_impl("pick_fw_impl", pick::PickFwImpl::new());
-        dev.register_bw_impl("pick_bw_impl", pick::PickBwImpl::new());
+        dev.register_fw_impl("pick_fw_impl", pick::PickFwImpl::new(pool.clone()));
+        dev.register_bw_impl("pick_bw_impl", pick::PickBwImpl::new(pool.clone()));
+        dev.register_fw_impl("pick_by_fw_impl", pick::PickByFwImpl::new(pool.clone()));
+        dev.register_bw_impl("pick_by_bw_impl", pick::PickByBwImpl::new(pool.clone()));
 
         dev.register_fw_impl("concat_fw_impl", concat::ConcatFwImpl::new());
 
@@ -301,12 +414,413 @@ impl<'dev> Naive {
 
         dev.register_fw_impl("batch_pick_fw_impl", batch_pick::BatchPickFwImpl::new());
         dev.register_bw_impl("batch_pick_bw_impl", batch_pick::BatchPickBwImpl::new());
+        dev.register_fw_impl(
+            "batch_pick_by_fw_impl",
+            batch_pick::BatchPickByFwImpl::new(),
+        );
+        dev.register_bw_impl(
+            "batch_pick_by_bw_impl",
+            batch_pick::BatchPickByBwImpl::new(),
+        );
 
         dev.register_fw_impl("batch_slice_fw_impl", batch_slice::BatchSliceFwImpl::new());
         dev.register_bw_impl("batch_slice_bw_impl", batch_slice::BatchSliceBwImpl::new());
 
         dev.register_fw_impl("batch_sum_fw_impl", batch_sum::BatchSumFwImpl::new());
 
+        // convolution
+
+        dev.register_fw_impl("conv2d_fw_impl", conv2d::Conv2dFwImpl::new(pool.clone()));
+        dev.register_bw_impl("conv2d_bw_x_impl", conv2d::Conv2dBwXImpl::new(pool.clone()));
+        dev.register_bw_impl("conv2d_bw_w_impl", conv2d::Conv2dBwWImpl::new(pool.clone()));
+
+        dev.register_fw_impl(
+            "max_pool2d_fw_impl",
+            max_pool2d::MaxPool2dFwImpl::new(pool.clone()),
+        );
+        dev.register_bw_impl(
+            "max_pool2d_bw_impl",
+            max_pool2d::MaxPool2dBwImpl::new(pool.clone()),
+        );
+        dev.register_fw_impl(
+            "max_pool2d_pick_fw_impl",
+            max_pool2d::MaxPool2dPickFwImpl::new(pool.clone()),
+        );
+
+        // fusion
+
+        dev.register_fw_impl(
+            "fused_elementwise_fw_impl",
+            fused_elementwise::FusedElementwiseFwImpl::new(pool.clone()),
+        );
+
+        // other dtypes
+
+        dev.register_fw_impl("cast_impl", typed::CastImpl::<f32>::new(pool.clone()));
+
+        let fw_names = dev
+            .fw_impl
+            .keys()
+            .filter(|name| !name.contains(':'))
+            .cloned()
+            .collect::<Vec<String>>();
+        let bw_names = dev
+            .bw_impl
+            .keys()
+            .filter(|name| !name.contains(':'))
+            .cloned()
+            .collect::<Vec<String>>();
+        for &dtype in &[DType::F16, DType::BF16] {
+            for name in &fw_names {
+                dev.register_fw_impl_for(dtype, name, typed::PromotedFwImpl::new(name));
+            }
+            for name in &bw_names {
+                dev.register_bw_impl_for(dtype, name, typed::PromotedBwImpl::new(name));
+            }
+        }
+        dev.register_fw_impl_for(
+            DType::F16,
+            "reset_tensor_impl",
+            typed::ResetTensorImpl::<f16>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(
+            DType::F16,
+            "cast_impl",
+            typed::CastImpl::<f16>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(
+            DType::BF16,
+            "reset_tensor_impl",
+            typed::ResetTensorImpl::<bf16>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(
+            DType::BF16,
+            "cast_impl",
+            typed::CastImpl::<bf16>::new(pool.clone()),
+        );
+
+        for &dtype in &[
+            DType::F64,
+            DType::F16,
+            DType::BF16,
+            DType::I32,
+            DType::U32,
+            DType::U8,
+        ] {
+            dev.register_fw_impl_for(
+                dtype,
+                "reset_tensor_by_tensor_impl",
+                typed::ResetTensorByTensorImpl::new(),
+            );
+        }
+        for &dtype in &[
+            DType::F32,
+            DType::F64,
+            DType::F16,
+            DType::BF16,
+            DType::I32,
+            DType::U32,
+            DType::U8,
+        ] {
+            dev.register_write_bytes_impl_for(
+                dtype,
+                "reset_tensor_by_bytes_impl",
+                typed::ResetTensorByBytesImpl::new(),
+            );
+            dev.register_read_bytes_impl_for(
+                dtype,
+                "tensor_to_bytes_impl",
+                typed::TensorToBytesImpl::new(),
+            );
+        }
+        dev.register_fw_impl_for(
+            DType::U8,
+            "reset_tensor_impl",
+            typed::ResetTensorImpl::<u8>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(
+            DType::U8,
+            "cast_impl",
+            typed::CastImpl::<u8>::new(pool.clone()),
+        );
+
+        Naive::register_numeric_impls::<f64>(&mut dev, &pool);
+        Naive::register_numeric_impls::<i32>(&mut dev, &pool);
+        Naive::register_numeric_impls::<u32>(&mut dev, &pool);
+
+        let f64_ = DType::F64;
+        dev.register_fw_impl_for(
+            f64_,
+            "neg_fw_impl",
+            typed::FwXImpl::new(pool.clone(), |x: f64| -x),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sub_fw_impl",
+            typed::FwAbImpl::new(pool.clone(), |a: f64, b| a - b),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "div_fw_impl",
+            typed::FwAbImpl::new(pool.clone(), |a: f64, b| a / b),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sub_const_l_fw_impl",
+            typed::FwConstImpl::new(pool.clone(), |x: f64, k| k - x),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sub_const_r_fw_impl",
+            typed::FwConstImpl::new(pool.clone(), |x: f64, k| x - k),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "div_const_l_fw_impl",
+            typed::FwConstImpl::new(pool.clone(), |x: f64, k| k / x),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "div_const_r_fw_impl",
+            typed::FwConstImpl::new(pool.clone(), |x: f64, k| x / k),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sub_scalar_l_fw_impl",
+            typed::FwAbImpl::new_scalar(pool.clone(), |x: f64, k| k - x),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sub_scalar_r_fw_impl",
+            typed::FwAbImpl::new_scalar(pool.clone(), |x: f64, k| x - k),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "div_scalar_l_fw_impl",
+            typed::FwAbImpl::new_scalar(pool.clone(), |x: f64, k| k / x),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "div_scalar_r_fw_impl",
+            typed::FwAbImpl::new_scalar(pool.clone(), |x: f64, k| x / k),
+        );
+
+        dev.register_fw_impl_for(
+            f64_,
+            "sqrt_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::sqrt),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "sqrt_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |_x: f64, y, gy| gy * 0.5 / y),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "abs_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::abs),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sin_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::sin),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "sin_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |x: f64, _y, gy| gy * x.cos()),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "cos_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::cos),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "cos_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |x: f64, _y, gy| -gy * x.sin()),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "tan_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::tan),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "tan_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |_x: f64, y, gy| gy * (1. + y * y)),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "exp_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::exp),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "exp_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |_x: f64, y, gy| gy * y),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "ln_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::ln),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "ln_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |x: f64, _y, gy| gy / x),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "tanh_fw_impl",
+            typed::FwXImpl::new(pool.clone(), f64::tanh),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "tanh_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |_x: f64, y, gy| gy * (1. - y * y)),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sigmoid_fw_impl",
+            typed::FwXImpl::new(pool.clone(), |x: f64| 0.5 + 0.5 * (0.5 * x).tanh()),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "sigmoid_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |_x: f64, y, gy| gy * y * (1. - y)),
+        );
+
+        // Backward kernels of the f64 operators above, and the functions used
+        // by the backward calculation.
+        type AbOp = fn(f64, f64, f64, f64) -> f64;
+        let ab_ops: [(&str, AbOp); 8] = [
+            ("add_bw_a_impl", |_a, _b, _y, gy| gy),
+            ("add_bw_b_impl", |_a, _b, _y, gy| gy),
+            ("sub_bw_a_impl", |_a, _b, _y, gy| gy),
+            ("sub_bw_b_impl", |_a, _b, _y, gy| -gy),
+            ("mul_bw_a_impl", |_a, b, _y, gy| b * gy),
+            ("mul_bw_b_impl", |a, _b, _y, gy| a * gy),
+            ("div_bw_a_impl", |_a, b, _y, gy| gy / b),
+            ("div_bw_b_impl", |_a, b, y, gy| -(gy * y / b)),
+        ];
+        for &(name, op) in &ab_ops {
+            dev.register_bw_impl_for(f64_, name, typed::BwAbImpl::new(pool.clone(), op));
+        }
+        let const_ops: [(&str, AbOp); 6] = [
+            ("add_const_bw_impl", |_x, _y, gy, _k| gy),
+            ("sub_const_l_bw_impl", |_x, _y, gy, _k| -gy),
+            ("sub_const_r_bw_impl", |_x, _y, gy, _k| gy),
+            ("mul_const_bw_impl", |_x, _y, gy, k| gy * k),
+            ("div_const_l_bw_impl", |x, y, gy, _k| -gy * y / x),
+            ("div_const_r_bw_impl", |_x, _y, gy, k| gy / k),
+        ];
+        for &(name, op) in &const_ops {
+            dev.register_bw_impl_for(f64_, name, typed::BwConstImpl::new(pool.clone(), op));
+        }
+        dev.register_bw_impl_for(
+            f64_,
+            "abs_bw_impl",
+            typed::BwXImpl::new(pool.clone(), |x: f64, _y, gy| {
+                gy * ((x > 0.) as i32 - (x < 0.) as i32) as f64
+            }),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "matmul_bw_a_impl",
+            typed::MatmulBwAImpl::<f64>::new(pool.clone()),
+        );
+        dev.register_bw_impl_for(
+            f64_,
+            "matmul_bw_b_impl",
+            typed::MatmulBwBImpl::<f64>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "add_assign_impl",
+            typed::AddAssignImpl::<f64>::new(pool.clone(), false),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "sub_assign_impl",
+            typed::AddAssignImpl::<f64>::new(pool.clone(), true),
+        );
+        dev.register_fw_impl_for(
+            f64_,
+            "broadcast_fw_impl",
+            typed::BroadcastFwImpl::<f64>::new(pool.clone()),
+        );
+
+        dev.register_fw_impl_for(
+            DType::I32,
+            "neg_fw_impl",
+            typed::FwXImpl::new(pool.clone(), i32::wrapping_neg),
+        );
+        dev.register_fw_impl_for(
+            DType::I32,
+            "sub_fw_impl",
+            typed::FwAbImpl::new(pool.clone(), i32::wrapping_sub),
+        );
+        dev.register_fw_impl_for(
+            DType::U32,
+            "sub_fw_impl",
+            typed::FwAbImpl::new(pool.clone(), u32::wrapping_sub),
+        );
+
         dev
     }
+
+    // Kernels shared by f64 and the integer dtypes.
+    fn register_numeric_impls<T: typed::Numeric>(
+        dev: &mut Device<'dev>,
+        pool: &Option<Arc<ThreadPool>>,
+    ) {
+        let dtype = T::DTYPE;
+        dev.register_fw_impl_for(
+            dtype,
+            "reset_tensor_impl",
+            typed::ResetTensorImpl::<T>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(dtype, "cast_impl", typed::CastImpl::<T>::new(pool.clone()));
+        dev.register_fw_impl_for(
+            dtype,
+            "add_fw_impl",
+            typed::FwAbImpl::new(pool.clone(), |a: T, b| a + b),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "mul_fw_impl",
+            typed::FwAbImpl::new(pool.clone(), |a: T, b| a * b),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "add_const_fw_impl",
+            typed::FwConstImpl::new(pool.clone(), |x: T, k| x + k),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "mul_const_fw_impl",
+            typed::FwConstImpl::new(pool.clone(), |x: T, k| x * k),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "add_scalar_fw_impl",
+            typed::FwAbImpl::new_scalar(pool.clone(), |x: T, k| x + k),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "mul_scalar_fw_impl",
+            typed::FwAbImpl::new_scalar(pool.clone(), |x: T, k| x * k),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "sum_fw_impl",
+            typed::SumFwImpl::<T>::new(pool.clone()),
+        );
+        dev.register_fw_impl_for(
+            dtype,
+            "matmul_fw_impl",
+            typed::MatmulFwImpl::<T>::new(pool.clone()),
+        );
+    }
 }
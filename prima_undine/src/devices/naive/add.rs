@@ -1,65 +1,9 @@
-use crate::device_impl::FunctionBwImpl;
 use crate::Tensor;
 
 define_naive_fw_ab_impl!(AddFwImpl, |a: f32, b: f32| { a + b });
 
-define_empty_impl!(AddBwAImpl);
-impl FunctionBwImpl for AddBwAImpl {
-    fn call(
-        &self,
-        _xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let gy = gys[0];
-        let ga = gx;
-        let volume = gy.shape.volume() as usize;
-        let a_shift = if ga.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pgy = const_ptr!(gy);
-            let mut pga = mut_ptr!(ga);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pga.add(i) += *pgy.add(i);
-                }
-                pgy = pgy.add(volume);
-                pga = pga.add(a_shift);
-            }
-        }
-    }
-}
-
-define_empty_impl!(AddBwBImpl);
-impl FunctionBwImpl for AddBwBImpl {
-    fn call(
-        &self,
-        _xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let gy = gys[0];
-        let gb = gx;
-        let volume = gy.shape.volume() as usize;
-        let b_shift = if gb.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pgy = const_ptr!(gy);
-            let mut pgb = mut_ptr!(gb);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pgb.add(i) += *pgy.add(i);
-                }
-                pgy = pgy.add(volume);
-                pgb = pgb.add(b_shift);
-            }
-        }
-    }
-}
+define_naive_bw_ab_impl!(AddBwAImpl, |_a: f32, _b: f32, _y: f32, gy: f32| { gy });
+define_naive_bw_ab_impl!(AddBwBImpl, |_a: f32, _b: f32, _y: f32, gy: f32| { gy });
 
 define_naive_fw_const_impl!(AddConstFwImpl, |x: f32, k: f32| { x + k });
 define_naive_bw_const_impl!(AddConstBwImpl, |_x: f32, _y: f32, gy: f32, _k: f32| { gy });
@@ -69,6 +13,7 @@ define_naive_fw_scalar_impl!(AddScalarFwImpl, |x: f32, k: f32| { x + k });
 mod tests {
     use crate::devices as D;
     use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
 
     #[test]
     fn check_add_const_fw() {
@@ -252,4 +197,24 @@ mod tests {
         assert_vector_ulps_eq!(ga_data, ga.to_vec());
         assert_vector_ulps_eq!(gb_data, gb.to_vec());
     }
+
+    #[test]
+    fn check_add_fw_threads() {
+        let dev1 = D::Naive::new();
+        let dev2 = D::Naive::with_threads(4);
+        let a_data = generate_values(100 * 300 * 4);
+        let b_data = generate_values(100 * 300);
+        let a1 = dev1.new_tensor_by_slice(shape![100, 300; 4], &a_data);
+        let b1 = dev1.new_tensor_by_slice(shape![100, 300], &b_data);
+        let a2 = dev2.new_tensor_by_slice(shape![100, 300; 4], &a_data);
+        let b2 = dev2.new_tensor_by_slice(shape![100, 300], &b_data);
+        let mut y1 = dev1.new_tensor(shape![100, 300; 4]);
+        y1.alloc();
+        let mut y2 = dev2.new_tensor(shape![100, 300; 4]);
+        y2.alloc();
+        dev1.call_fw_impl("add_fw_impl", &[&b1, &a1], &[], &[], &mut [&mut y1]);
+        dev2.call_fw_impl("add_fw_impl", &[&b2, &a2], &[], &[], &mut [&mut y2]);
+        let (y1, y2) = (y1.to_vec(), y2.to_vec());
+        assert_vector_ulps_eq!(y1, y2);
+    }
 }
@@ -1,25 +1,43 @@
+use super::common::read_ids;
 use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
 use crate::Tensor;
 
+fn batch_pick_fw(x: &Tensor, ids: &[u32], y: &mut Tensor) {
+    let bs = y.shape.batch() as usize;
+    let span = x.shape.volume() as usize;
+    unsafe {
+        let px = const_ptr!(x);
+        let py = mut_ptr!(y);
+        for batch in 0..bs {
+            let src = px.add(span * ids[batch] as usize);
+            let dest = py.add(span * batch);
+            for i in 0..span {
+                *dest.add(i) = *src.add(i)
+            }
+        }
+    }
+}
+
+fn batch_pick_bw(gy: &Tensor, ids: &[u32], gx: &mut Tensor) {
+    let bs = gy.shape.batch() as usize;
+    let span = gx.shape.volume() as usize;
+    unsafe {
+        let pgy = const_ptr!(gy);
+        let pgx = mut_ptr!(gx);
+        for batch in 0..bs {
+            let src = pgy.add(span * batch);
+            let dest = pgx.add(span * ids[batch] as usize);
+            for i in 0..span {
+                *dest.add(i) += *src.add(i)
+            }
+        }
+    }
+}
+
 define_empty_impl!(BatchPickFwImpl);
 impl FunctionFwImpl for BatchPickFwImpl {
     fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
-        let x = xs[0];
-        let ids = u32data;
-        let y = &mut ys[0];
-        let bs = y.shape.batch() as usize;
-        let span = x.shape.volume() as usize;
-        unsafe {
-            let px = const_ptr!(x);
-            let py = mut_ptr!(y);
-            for batch in 0..bs {
-                let src = px.add(span * ids[batch] as usize);
-                let dest = py.add(span * batch);
-                for i in 0..span {
-                    *dest.add(i) = *src.add(i)
-                }
-            }
-        }
+        batch_pick_fw(xs[0], u32data, ys[0]);
     }
 }
 
@@ -34,21 +52,32 @@ impl FunctionBwImpl for BatchPickBwImpl {
         _f32data: &[f32],
         gx: &mut Tensor,
     ) {
-        let gy = gys[0];
-        let ids = u32data;
-        let bs = gy.shape.batch() as usize;
-        let span = gx.shape.volume() as usize;
-        unsafe {
-            let pgy = const_ptr!(gy);
-            let pgx = mut_ptr!(gx);
-            for batch in 0..bs {
-                let src = pgy.add(span * batch);
-                let dest = pgx.add(span * ids[batch] as usize);
-                for i in 0..span {
-                    *dest.add(i) += *src.add(i)
-                }
-            }
-        }
+        batch_pick_bw(gys[0], u32data, gx);
+    }
+}
+
+// The ids are given as xs[1] (forward) or xs[0] (backward).
+define_empty_impl!(BatchPickByFwImpl);
+impl FunctionFwImpl for BatchPickByFwImpl {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let ids = read_ids("batch_pick", xs[1], xs[0].shape.batch());
+        batch_pick_fw(xs[0], &ids, ys[0]);
+    }
+}
+
+define_empty_impl!(BatchPickByBwImpl);
+impl FunctionBwImpl for BatchPickByBwImpl {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        _u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let ids = read_ids("batch_pick", xs[0], gx.shape.batch());
+        batch_pick_bw(gys[0], &ids, gx);
     }
 }
 
@@ -1,7 +1,10 @@
+use std::cmp;
+
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
 use crate::device_impl::FunctionFwImpl;
 use crate::Tensor;
 
-define_empty_impl!(BroadcastFwImpl);
+define_parallel_impl!(BroadcastFwImpl);
 impl FunctionFwImpl for BroadcastFwImpl {
     fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
         let x = xs[0];
@@ -11,17 +14,20 @@ impl FunctionFwImpl for BroadcastFwImpl {
         let repeat = x.shape.size() as usize;
         let skip1 = y.shape.lower_volume(dim) as usize;
         let skip2 = skip1 * size as usize;
+        let grain = cmp::max(ELEMENTWISE_GRAIN / size as usize, 1);
         unsafe {
-            let src = const_ptr!(x);
-            let dest = mut_ptr!(y);
-            for i in 0..repeat {
-                let mut offset = i % skip1 + (i / skip1) * skip2;
-                let tmp = *src.add(i);
-                for _ in 0..size {
-                    *dest.add(offset) = tmp;
-                    offset += skip1;
+            let src = Shared(const_ptr!(x));
+            let dest = Shared(mut_ptr!(y));
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                for i in begin..end {
+                    let mut offset = i % skip1 + (i / skip1) * skip2;
+                    let tmp = *src.0.add(i);
+                    for _ in 0..size {
+                        *dest.0.add(offset) = tmp;
+                        offset += skip1;
+                    }
                 }
-            }
+            });
         }
     }
 }
@@ -29,7 +35,8 @@ impl FunctionFwImpl for BroadcastFwImpl {
 #[cfg(test)]
 mod tests {
     use crate::functions::BasicFunctions;
-    use crate::{devices as D, Shape};
+    use crate::test_utils::generate_values;
+    use crate::{devices as D, Device, Shape};
 
     #[test]
     fn check_broadcast_fw() {
@@ -155,4 +162,17 @@ mod tests {
             assert_vector_ulps_eq!(tc.3, y.to_vec());
         }
     }
+
+    #[test]
+    fn check_broadcast_fw_threads() {
+        let x_data = generate_values(1000 * 40);
+        let run = |dev: &Device| {
+            let x = dev.new_tensor_by_slice(shape![1000; 40], &x_data);
+            let mut y = dev.new_tensor(shape![1000, 4; 40]);
+            y.alloc();
+            dev.call_fw_impl("broadcast_fw_impl", &[&x], &[1, 4], &[], &mut [&mut y]);
+            y.to_vec()
+        };
+        assert_eq!(run(&D::Naive::new()), run(&D::Naive::with_threads(4)));
+    }
 }
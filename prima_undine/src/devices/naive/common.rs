@@ -16,9 +16,103 @@ macro_rules! mut_ptr {
     };
 }
 
+use std::cmp;
+use std::sync::Arc;
+
+use rayon::ThreadPool;
+
+use crate::{DType, Tensor};
+
+// Raw pointers are neither Send nor Sync. Kernels only write to disjoint
+// ranges from each worker, so sharing them is safe.
+#[derive(Clone, Copy)]
+pub struct Shared<T>(pub T);
+
+unsafe impl<T> Send for Shared<T> {}
+unsafe impl<T> Sync for Shared<T> {}
+
+// Calls f(begin, end) for contiguous ranges covering 0..size. Each range has
+// at least `grain` elements, and the ranges are distributed over the pool.
+pub fn parallel_for<F>(pool: &Option<Arc<ThreadPool>>, size: usize, grain: usize, f: F)
+where
+    F: Fn(usize, usize) + Send + Sync,
+{
+    match pool {
+        Some(pool) if size > grain && pool.current_num_threads() > 1 => {
+            let grain = cmp::max(grain, 1);
+            let n_tasks = cmp::min(pool.current_num_threads(), size.div_ceil(grain));
+            let chunk = size.div_ceil(n_tasks);
+            let f = &f;
+            pool.scope(|s| {
+                for t in 0..n_tasks {
+                    let begin = t * chunk;
+                    let end = cmp::min(size, begin + chunk);
+                    if begin < end {
+                        s.spawn(move |_| f(begin, end));
+                    }
+                }
+            });
+        }
+        _ => f(0, size),
+    }
+}
+
+pub const ELEMENTWISE_GRAIN: usize = 1 << 14;
+
+// Reads the ids of pick or batch_pick from a u32 or i32 tensor, checking that
+// each of them is less than `upper`.
+pub fn read_ids(op: &str, ids: &Tensor, upper: u32) -> Vec<u32> {
+    let size = ids.shape.size() as usize;
+    let values = unsafe {
+        let p = const_ptr!(ids);
+        match ids.dtype() {
+            DType::U32 => (0..size)
+                .map(|i| (p as *const u32).add(i).read_unaligned() as i64)
+                .collect::<Vec<i64>>(),
+            DType::I32 => (0..size)
+                .map(|i| (p as *const i32).add(i).read_unaligned() as i64)
+                .collect::<Vec<i64>>(),
+            dtype => panic!("{}: ids must be u32 or i32, but {} is given", op, dtype),
+        }
+    };
+    values
+        .into_iter()
+        .map(|id| {
+            assert!(
+                0 <= id && id < upper as i64,
+                "{}: id {} is out of range [0, {})",
+                op,
+                id,
+                upper
+            );
+            id as u32
+        })
+        .collect()
+}
+
+macro_rules! define_parallel_impl {
+    ( $name:ident ) => {
+        pub struct $name {
+            pool: Option<std::sync::Arc<rayon::ThreadPool>>,
+        }
+        impl $name {
+            pub fn new(pool: Option<std::sync::Arc<rayon::ThreadPool>>) -> $name {
+                $name { pool }
+            }
+        }
+    };
+}
+
 macro_rules! define_naive_fw_x_impl {
     ( $name:ident , $op:expr ) => {
-        define_empty_impl!($name);
+        define_naive_fw_x_impl!($name, $op, |px: *const f32, py: *mut f32, size: usize| {
+            for i in 0..size {
+                *py.add(i) = $op(*px.add(i));
+            }
+        });
+    };
+    ( $name:ident , $op:expr , $simd_op:expr ) => {
+        define_parallel_impl!($name);
         impl crate::device_impl::FunctionFwImpl for $name {
             fn call(
                 &self,
@@ -27,15 +121,24 @@ macro_rules! define_naive_fw_x_impl {
                 _f32data: &[f32],
                 ys: &mut [&mut crate::Tensor],
             ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+                use crate::devices::naive::simd;
                 let x = xs[0];
                 let y = &mut ys[0];
                 let size = y.shape.size() as usize;
+                let simd_enabled = simd::enabled();
                 unsafe {
-                    let px = const_ptr!(x);
-                    let py = mut_ptr!(y);
-                    for i in 0..size {
-                        *py.add(i) = $op(*px.add(i));
-                    }
+                    let px = Shared(const_ptr!(x));
+                    let py = Shared(mut_ptr!(y));
+                    parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                        if simd_enabled {
+                            $simd_op(px.0.add(begin), py.0.add(begin), end - begin);
+                        } else {
+                            for i in begin..end {
+                                *py.0.add(i) = $op(*px.0.add(i));
+                            }
+                        }
+                    });
                 }
             }
         }
@@ -44,7 +147,7 @@ macro_rules! define_naive_fw_x_impl {
 
 macro_rules! define_naive_bw_x_impl {
     ( $name:ident , $op:expr ) => {
-        define_empty_impl!($name);
+        define_parallel_impl!($name);
         impl crate::device_impl::FunctionBwImpl for $name {
             fn call(
                 &self,
@@ -55,18 +158,21 @@ macro_rules! define_naive_bw_x_impl {
                 _f32data: &[f32],
                 gx: &mut crate::Tensor,
             ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
                 let x = xs[0];
                 let y = ys[0];
                 let gy = gys[0];
                 let size = gy.shape.size() as usize;
                 unsafe {
-                    let px = const_ptr!(x);
-                    let py = const_ptr!(y);
-                    let pgy = const_ptr!(gy);
-                    let pgx = mut_ptr!(gx);
-                    for i in 0..size {
-                        *pgx.add(i) += $op(*px.add(i), *py.add(i), *pgy.add(i));
-                    }
+                    let px = Shared(const_ptr!(x));
+                    let py = Shared(const_ptr!(y));
+                    let pgy = Shared(const_ptr!(gy));
+                    let pgx = Shared(mut_ptr!(gx));
+                    parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                        for i in begin..end {
+                            *pgx.0.add(i) += $op(*px.0.add(i), *py.0.add(i), *pgy.0.add(i));
+                        }
+                    });
                 }
             }
         }
@@ -75,7 +181,7 @@ macro_rules! define_naive_bw_x_impl {
 
 macro_rules! define_naive_fw_ab_impl {
     ( $name:ident , $op:expr ) => {
-        define_empty_impl!($name);
+        define_parallel_impl!($name);
         impl crate::device_impl::FunctionFwImpl for $name {
             fn call(
                 &self,
@@ -84,24 +190,33 @@ macro_rules! define_naive_fw_ab_impl {
                 _f32data: &[f32],
                 ys: &mut [&mut Tensor],
             ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
                 let a = xs[0];
                 let b = xs[1];
                 let y = &mut ys[0];
                 let volume = y.shape.volume() as usize;
+                let size = y.shape.size() as usize;
                 let a_shift = if a.shape.batch() == 1 { 0 } else { volume };
                 let b_shift = if b.shape.batch() == 1 { 0 } else { volume };
                 unsafe {
-                    let mut pa = const_ptr!(a);
-                    let mut pb = const_ptr!(b);
-                    let mut py = mut_ptr!(y);
-                    for _ in 0..y.shape.batch() {
-                        for i in 0..volume {
-                            *py.add(i) = $op(*pa.add(i), *pb.add(i));
+                    let pa = Shared(const_ptr!(a));
+                    let pb = Shared(const_ptr!(b));
+                    let py = Shared(mut_ptr!(y));
+                    parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                        let mut batch = begin / volume;
+                        let mut i = begin % volume;
+                        for n in begin..end {
+                            *py.0.add(n) = $op(
+                                *pa.0.add(batch * a_shift + i),
+                                *pb.0.add(batch * b_shift + i),
+                            );
+                            i += 1;
+                            if i == volume {
+                                i = 0;
+                                batch += 1;
+                            }
                         }
-                        pa = pa.add(a_shift);
-                        pb = pb.add(b_shift);
-                        py = py.add(volume);
-                    }
+                    });
                 }
             }
         }
@@ -110,7 +225,7 @@ macro_rules! define_naive_fw_ab_impl {
 
 macro_rules! define_naive_fw_const_impl {
     ( $name:ident , $op:expr ) => {
-        define_empty_impl!($name);
+        define_parallel_impl!($name);
         impl crate::device_impl::FunctionFwImpl for $name {
             fn call(
                 &self,
@@ -119,16 +234,19 @@ macro_rules! define_naive_fw_const_impl {
                 f32data: &[f32],
                 ys: &mut [&mut crate::Tensor],
             ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
                 let x = xs[0];
                 let k = f32data[0];
                 let y = &mut ys[0];
                 let size = y.shape.size() as usize;
                 unsafe {
-                    let px = const_ptr!(x);
-                    let py = mut_ptr!(y);
-                    for i in 0..size {
-                        *py.add(i) = $op(*px.add(i), k);
-                    }
+                    let px = Shared(const_ptr!(x));
+                    let py = Shared(mut_ptr!(y));
+                    parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                        for i in begin..end {
+                            *py.0.add(i) = $op(*px.0.add(i), k);
+                        }
+                    });
                 }
             }
         }
@@ -137,7 +255,7 @@ macro_rules! define_naive_fw_const_impl {
 
 macro_rules! define_naive_bw_const_impl {
     ( $name:ident , $op:expr ) => {
-        define_empty_impl!($name);
+        define_parallel_impl!($name);
         impl crate::device_impl::FunctionBwImpl for $name {
             fn call(
                 &self,
@@ -148,19 +266,22 @@ macro_rules! define_naive_bw_const_impl {
                 f32data: &[f32],
                 gx: &mut crate::Tensor,
             ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
                 let x = xs[0];
                 let y = ys[0];
                 let gy = gys[0];
                 let k = f32data[0];
                 let size = gy.shape.size() as usize;
                 unsafe {
-                    let px = const_ptr!(x);
-                    let py = const_ptr!(y);
-                    let pgy = const_ptr!(gy);
-                    let pgx = mut_ptr!(gx);
-                    for i in 0..size {
-                        *pgx.add(i) += $op(*px.add(i), *py.add(i), *pgy.add(i), k);
-                    }
+                    let px = Shared(const_ptr!(x));
+                    let py = Shared(const_ptr!(y));
+                    let pgy = Shared(const_ptr!(gy));
+                    let pgx = Shared(mut_ptr!(gx));
+                    parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                        for i in begin..end {
+                            *pgx.0.add(i) += $op(*px.0.add(i), *py.0.add(i), *pgy.0.add(i), k);
+                        }
+                    });
                 }
             }
         }
@@ -169,7 +290,7 @@ macro_rules! define_naive_bw_const_impl {
 
 macro_rules! define_naive_fw_scalar_impl {
     ( $name:ident , $op:expr ) => {
-        define_empty_impl!($name);
+        define_parallel_impl!($name);
         impl crate::device_impl::FunctionFwImpl for $name {
             fn call(
                 &self,
@@ -178,24 +299,83 @@ macro_rules! define_naive_fw_scalar_impl {
                 _f32data: &[f32],
                 ys: &mut [&mut Tensor],
             ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
                 let x = xs[0];
                 let k = xs[1];
                 let y = &mut ys[0];
                 let volume = y.shape.volume() as usize;
+                let size = y.shape.size() as usize;
                 let x_shift = if x.shape.batch() == 1 { 0 } else { volume };
                 let k_shift = if k.shape.batch() == 1 { 0 } else { 1 };
                 unsafe {
-                    let mut px = const_ptr!(x);
-                    let mut pk = const_ptr!(k);
-                    let mut py = mut_ptr!(y);
-                    for _ in 0..y.shape.batch() {
-                        for i in 0..volume {
-                            *py.add(i) = $op(*px.add(i), *pk);
+                    let px = Shared(const_ptr!(x));
+                    let pk = Shared(const_ptr!(k));
+                    let py = Shared(mut_ptr!(y));
+                    parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                        let mut batch = begin / volume;
+                        let mut i = begin % volume;
+                        for n in begin..end {
+                            *py.0.add(n) =
+                                $op(*px.0.add(batch * x_shift + i), *pk.0.add(batch * k_shift));
+                            i += 1;
+                            if i == volume {
+                                i = 0;
+                                batch += 1;
+                            }
                         }
-                        px = px.add(x_shift);
-                        pk = pk.add(k_shift);
-                        py = py.add(volume);
-                    }
+                    });
+                }
+            }
+        }
+    };
+}
+
+// Backward of a binary operation with batch broadcasting. `$op(a, b, y, gy)`
+// is added to the gradient. Each element of the gradient accumulates over
+// the batches in order, so broadcasted arguments give the same sum with any
+// number of threads.
+macro_rules! define_naive_bw_ab_impl {
+    ( $name:ident , $op:expr ) => {
+        define_parallel_impl!($name);
+        impl crate::device_impl::FunctionBwImpl for $name {
+            fn call(
+                &self,
+                xs: &[&crate::Tensor],
+                ys: &[&crate::Tensor],
+                gys: &[&crate::Tensor],
+                _u32data: &[u32],
+                _f32data: &[f32],
+                gx: &mut crate::Tensor,
+            ) {
+                use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+                let a = xs[0];
+                let b = xs[1];
+                let y = ys[0];
+                let gy = gys[0];
+                let volume = gy.shape.volume() as usize;
+                let bs = gy.shape.batch() as usize;
+                let a_shift = if a.shape.batch() == 1 { 0 } else { volume };
+                let b_shift = if b.shape.batch() == 1 { 0 } else { volume };
+                let gx_shift = if gx.shape.batch() == 1 { 0 } else { volume };
+                unsafe {
+                    let pa = Shared(const_ptr!(a));
+                    let pb = Shared(const_ptr!(b));
+                    let py = Shared(const_ptr!(y));
+                    let pgy = Shared(const_ptr!(gy));
+                    let pgx = Shared(mut_ptr!(gx));
+                    let grain = std::cmp::max(ELEMENTWISE_GRAIN / bs, 1);
+                    parallel_for(&self.pool, volume, grain, |begin, end| {
+                        for batch in 0..bs {
+                            for i in begin..end {
+                                *pgx.0.add(batch * gx_shift + i) += $op(
+                                    *pa.0.add(batch * a_shift + i),
+                                    *pb.0.add(batch * b_shift + i),
+                                    *py.0.add(batch * volume + i),
+                                    *pgy.0.add(batch * volume + i),
+                                );
+                            }
+                        }
+                    });
                 }
             }
         }
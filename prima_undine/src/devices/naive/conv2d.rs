@@ -0,0 +1,455 @@
+use std::cmp;
+
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
+use crate::{Shape, Tensor};
+
+// Calls `f(x_index, w_index, y_index)` for every product term of the output
+// channel `o` of the batch `b`. The filter is applied flipped in both spatial
+// dimensions (true convolution).
+fn conv2d_foreach<F: FnMut(usize, usize, usize)>(
+    x: Shape,
+    w: Shape,
+    y: Shape,
+    u32data: &[u32],
+    b: usize,
+    o: usize,
+    mut f: F,
+) {
+    let padding0 = u32data[0] as isize;
+    let padding1 = u32data[1] as isize;
+    let stride0 = u32data[2] as isize;
+    let stride1 = u32data[3] as isize;
+    let dilation0 = u32data[4] as isize;
+    let dilation1 = u32data[5] as isize;
+    let x0 = x[0] as isize;
+    let x1 = x[1] as isize;
+    let w0 = w[0] as usize;
+    let w1 = w[1] as usize;
+    let y0 = y[0] as usize;
+    let y1 = y[1] as usize;
+    let chs = x[2] as usize;
+    let skip_x = if x.has_batch() {
+        x.volume() as usize
+    } else {
+        0
+    };
+    let skip_w = if w.has_batch() {
+        w.volume() as usize
+    } else {
+        0
+    };
+    let skip_y = y.volume() as usize;
+    for j1 in 0..y1 {
+        for j0 in 0..y0 {
+            let y_index = b * skip_y + j0 + y0 * (j1 + y1 * o);
+            for c in 0..chs {
+                for k1 in 0..w1 {
+                    let i1 = j1 as isize * stride1 + k1 as isize * dilation1 - padding1;
+                    if i1 < 0 || i1 >= x1 {
+                        continue;
+                    }
+                    for k0 in 0..w0 {
+                        let i0 = j0 as isize * stride0 + k0 as isize * dilation0 - padding0;
+                        if i0 < 0 || i0 >= x0 {
+                            continue;
+                        }
+                        let x_index = b * skip_x
+                            + i0 as usize
+                            + x0 as usize * (i1 as usize + x1 as usize * c);
+                        let w_index =
+                            b * skip_w + (w0 - 1 - k0) + w0 * ((w1 - 1 - k1) + w1 * (c + chs * o));
+                        f(x_index, w_index, y_index);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Number of (batch, output channel) pairs processed by each thread.
+fn conv2d_grain(w: Shape, y: Shape) -> usize {
+    let terms = (y[0] * y[1] * w[0] * w[1] * w[2]) as usize;
+    cmp::max(ELEMENTWISE_GRAIN / cmp::max(terms, 1), 1)
+}
+
+// Each output channel of each batch is written by one thread.
+define_parallel_impl!(Conv2dFwImpl);
+impl FunctionFwImpl for Conv2dFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let w = xs[1];
+        let y = &mut ys[0];
+        y.reset(0.);
+        let bs = y.shape.batch() as usize;
+        let outs = y.shape[2] as usize;
+        let grain = conv2d_grain(w.shape, y.shape);
+        unsafe {
+            let px = Shared(const_ptr!(x));
+            let pw = Shared(const_ptr!(w));
+            let py = Shared(mut_ptr!(y));
+            let y_shape = y.shape;
+            parallel_for(&self.pool, bs * outs, grain, |begin, end| {
+                for n in begin..end {
+                    conv2d_foreach(
+                        x.shape,
+                        w.shape,
+                        y_shape,
+                        u32data,
+                        n / outs,
+                        n % outs,
+                        |xi, wi, yi| {
+                            *py.0.add(yi) += *px.0.add(xi) * *pw.0.add(wi);
+                        },
+                    );
+                }
+            });
+        }
+    }
+}
+
+// All output channels write to the same gradient of the input, so only the
+// batches are distributed over the threads, and only if the input has them.
+define_parallel_impl!(Conv2dBwXImpl);
+impl FunctionBwImpl for Conv2dBwXImpl {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let x = xs[0];
+        let w = xs[1];
+        let gy = gys[0];
+        let bs = gy.shape.batch() as usize;
+        let outs = gy.shape[2] as usize;
+        let pool = if x.shape.has_batch() {
+            &self.pool
+        } else {
+            &None
+        };
+        let grain = cmp::max(conv2d_grain(w.shape, gy.shape) / outs, 1);
+        unsafe {
+            let pw = Shared(const_ptr!(w));
+            let pgy = Shared(const_ptr!(gy));
+            let pgx = Shared(mut_ptr!(gx));
+            parallel_for(pool, bs, grain, |begin, end| {
+                for b in begin..end {
+                    for o in 0..outs {
+                        conv2d_foreach(x.shape, w.shape, gy.shape, u32data, b, o, |xi, wi, yi| {
+                            *pgx.0.add(xi) += *pgy.0.add(yi) * *pw.0.add(wi);
+                        });
+                    }
+                }
+            });
+        }
+    }
+}
+
+// Each output channel owns its part of the gradient of the filter. The
+// batches are accumulated in order within each thread.
+define_parallel_impl!(Conv2dBwWImpl);
+impl FunctionBwImpl for Conv2dBwWImpl {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let x = xs[0];
+        let w = xs[1];
+        let gy = gys[0];
+        let gw = gx;
+        let bs = gy.shape.batch() as usize;
+        let outs = gy.shape[2] as usize;
+        let grain = cmp::max(conv2d_grain(w.shape, gy.shape) / bs, 1);
+        unsafe {
+            let px = Shared(const_ptr!(x));
+            let pgy = Shared(const_ptr!(gy));
+            let pgw = Shared(mut_ptr!(gw));
+            parallel_for(&self.pool, outs, grain, |begin, end| {
+                for b in 0..bs {
+                    for o in begin..end {
+                        conv2d_foreach(x.shape, w.shape, gy.shape, u32data, b, o, |xi, wi, yi| {
+                            *pgw.0.add(wi) += *pgy.0.add(yi) * *px.0.add(xi);
+                        });
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
+    use crate::{devices as D, Device, Shape};
+
+    #[test]
+    fn check_conv2d_fw() {
+        struct TestCase(Shape, Vec<u32>, Shape, Vec<f32>);
+        let test_cases = vec![
+            TestCase(
+                shape![3, 3],
+                vec![0, 0, 1, 1, 1, 1],
+                shape![3, 3],
+                vec![-30., -32., -34., -40., -42., -44., -50., -52., -54.],
+            ),
+            TestCase(
+                shape![3, 3],
+                vec![1, 1, 2, 2, 1, 1],
+                shape![3, 3],
+                vec![-16., -20., -8., -23., -42., -9., 47., 24., 21.],
+            ),
+            TestCase(
+                shape![2, 2],
+                vec![0, 1, 1, 1, 2, 1],
+                shape![3, 6],
+                vec![
+                    -7., -10., -13., -21., -23., -25., -31., -33., -35., -41., -43., -45., -51.,
+                    -53., -55., 21., 22., 23.,
+                ],
+            ),
+        ];
+        let x_data = (1..=25).map(|x| x as f32).collect::<Vec<f32>>();
+        let w_data = vec![-2., -1., 0., 1., 2., -2., -1., 0., 1.];
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![5, 5], &x_data);
+        for tc in &test_cases {
+            let w = dev.new_tensor_by_slice(tc.0, &w_data[..tc.0.size() as usize]);
+            let mut y = dev.new_tensor(tc.2);
+            y.alloc();
+            dev.call_fw_impl("conv2d_fw_impl", &[&x, &w], &tc.1, &[], &mut [&mut y]);
+            assert_vector_ulps_eq!(tc.3, y.to_vec());
+        }
+    }
+
+    #[test]
+    fn check_conv2d_fw_channels_batch() {
+        let x_data = (1..=36).map(|x| x as f32).collect::<Vec<f32>>();
+        let w_data = vec![
+            -2., -1., 0., 1., 2., -2., -1., 0., 1., 2., -2., -1., 0., 1., 2., -2.,
+        ];
+        let y_data = vec![
+            -22., -25., -31., -34., 23., 24., 26., 27., -76., -79., -85., -88., 41., 42., 44., 45.,
+        ];
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3, 3, 2; 2], &x_data);
+        let w = dev.new_tensor_by_slice(shape![2, 2, 2, 2], &w_data);
+        let mut y = dev.new_tensor(shape![2, 2, 2; 2]);
+        y.alloc();
+        dev.call_fw_impl(
+            "conv2d_fw_impl",
+            &[&x, &w],
+            &[0, 0, 1, 1, 1, 1],
+            &[],
+            &mut [&mut y],
+        );
+        assert_vector_ulps_eq!(y_data, y.to_vec());
+    }
+
+    #[test]
+    fn check_conv2d_fw_batch_filter() {
+        let x_data = vec![1., 2., 3., 4., 5., 6., 7., 8., 9.];
+        let w_data = vec![-2., -1., 0., 1., 2., -2., -1., 0.];
+        let y_data = vec![-13., -15., -19., -21., 0., -1., -3., -4.];
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3, 3], &x_data);
+        let w = dev.new_tensor_by_slice(shape![2, 2; 2], &w_data);
+        let mut y = dev.new_tensor(shape![2, 2; 2]);
+        y.alloc();
+        dev.call_fw_impl(
+            "conv2d_fw_impl",
+            &[&x, &w],
+            &[0, 0, 1, 1, 1, 1],
+            &[],
+            &mut [&mut y],
+        );
+        assert_vector_ulps_eq!(y_data, y.to_vec());
+    }
+
+    #[test]
+    fn check_conv2d_bw() {
+        struct TestCase(Vec<u32>, Vec<f32>, Vec<f32>);
+        let test_cases = vec![
+            TestCase(
+                vec![0, 0, 1, 1, 1, 1],
+                vec![
+                    2., 3., 3., -1., -2., 0., 1., 2., 7., 1., 0., 0., -2., 0., -5., -1., -2., -4.,
+                    2., -2., 1., 0., -3., -6., -5.,
+                ],
+                vec![349., 331., 313., 259., 241., 223., 169., 151., 133.],
+            ),
+            TestCase(
+                vec![1, 1, 2, 2, 1, 1],
+                vec![
+                    3., -2., 5., -3., 7., 0., 0., -1., -2., -2., 3., -2., 5., -3., 7., 0., 0., -1.,
+                    -2., -2., 3., -2., 5., -3., 7.,
+                ],
+                vec![81., 165., 133., 121., 247., 199., 81., 165., 133.],
+            ),
+        ];
+        let x_data = (1..=25).map(|x| x as f32).collect::<Vec<f32>>();
+        let w_data = vec![-2., -1., 0., 1., 2., -2., -1., 0., 1.];
+        let gy_data = vec![1., 2., 3., 1., 2., 3., 1., 2., 3.];
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![5, 5], &x_data);
+        let w = dev.new_tensor_by_slice(shape![3, 3], &w_data);
+        let gy = dev.new_tensor_by_slice(shape![3, 3], &gy_data);
+        for tc in &test_cases {
+            let y = dev.new_tensor_by_constant(shape![3, 3], 0.);
+            let mut gx = dev.new_tensor_by_constant(shape![5, 5], 1.);
+            let mut gw = dev.new_tensor_by_constant(shape![3, 3], 1.);
+            dev.call_bw_impl(
+                "conv2d_bw_x_impl",
+                &[&x, &w],
+                &[&y],
+                &[&gy],
+                &tc.0,
+                &[],
+                &mut gx,
+            );
+            dev.call_bw_impl(
+                "conv2d_bw_w_impl",
+                &[&x, &w],
+                &[&y],
+                &[&gy],
+                &tc.0,
+                &[],
+                &mut gw,
+            );
+            assert_vector_ulps_eq!(tc.1, gx.to_vec());
+            assert_vector_ulps_eq!(tc.2, gw.to_vec());
+        }
+    }
+
+    #[test]
+    fn check_conv2d_bw_channels_batch() {
+        let x_data = (1..=36).map(|x| x as f32).collect::<Vec<f32>>();
+        let w_data = vec![
+            -2., -1., 0., 1., 2., -2., -1., 0., 1., 2., -2., -1., 0., 1., 2., -2.,
+        ];
+        let gy_data = vec![
+            1., 2., 3., 1., 2., 3., 1., 2., 3., 1., 2., 3., 1., 2., 3., 1.,
+        ];
+        let gx_data = vec![
+            0., -4., -5., 6., 2., -4., 0., -1., 1., -3., -2., 5., -1., -3., 8., -4., 7., 3., 3.,
+            -2., -3., -1., -5., -1., 5., -1., -4., -1., -4., 4., -10., 9., 2., 0., 0., 7.,
+        ];
+        let gw_data = vec![
+            277., 261., 229., 213., 421., 405., 373., 357., 231., 216., 186., 171., 366., 351.,
+            321., 306.,
+        ];
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3, 3, 2; 2], &x_data);
+        let w = dev.new_tensor_by_slice(shape![2, 2, 2, 2], &w_data);
+        let y = dev.new_tensor_by_constant(shape![2, 2, 2; 2], 0.);
+        let gy = dev.new_tensor_by_slice(shape![2, 2, 2; 2], &gy_data);
+        let mut gx = dev.new_tensor_by_constant(shape![3, 3, 2; 2], 1.);
+        let mut gw = dev.new_tensor_by_constant(shape![2, 2, 2, 2], 1.);
+        let params = [0, 0, 1, 1, 1, 1];
+        dev.call_bw_impl(
+            "conv2d_bw_x_impl",
+            &[&x, &w],
+            &[&y],
+            &[&gy],
+            &params,
+            &[],
+            &mut gx,
+        );
+        dev.call_bw_impl(
+            "conv2d_bw_w_impl",
+            &[&x, &w],
+            &[&y],
+            &[&gy],
+            &params,
+            &[],
+            &mut gw,
+        );
+        assert_vector_ulps_eq!(gx_data, gx.to_vec());
+        assert_vector_ulps_eq!(gw_data, gw.to_vec());
+    }
+
+    #[test]
+    fn check_conv2d_bw_batch_filter() {
+        let x_data = vec![1., 2., 3., 4., 5., 6., 7., 8., 9.];
+        let w_data = vec![-2., -1., 0., 1., 2., -2., -1., 0.];
+        let gy_data = vec![1., 2., 3., 1., 2., 3., 1., 2.];
+        let gx_data = vec![2., 1., -2., -1., -5., 1., -4., -8., 3.];
+        let gw_data = vec![51., 44., 30., 23., 55., 47., 31., 23.];
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3, 3], &x_data);
+        let w = dev.new_tensor_by_slice(shape![2, 2; 2], &w_data);
+        let y = dev.new_tensor_by_constant(shape![2, 2; 2], 0.);
+        let gy = dev.new_tensor_by_slice(shape![2, 2; 2], &gy_data);
+        let mut gx = dev.new_tensor_by_constant(shape![3, 3], 1.);
+        let mut gw = dev.new_tensor_by_constant(shape![2, 2; 2], 1.);
+        let params = [0, 0, 1, 1, 1, 1];
+        dev.call_bw_impl(
+            "conv2d_bw_x_impl",
+            &[&x, &w],
+            &[&y],
+            &[&gy],
+            &params,
+            &[],
+            &mut gx,
+        );
+        dev.call_bw_impl(
+            "conv2d_bw_w_impl",
+            &[&x, &w],
+            &[&y],
+            &[&gy],
+            &params,
+            &[],
+            &mut gw,
+        );
+        assert_vector_ulps_eq!(gx_data, gx.to_vec());
+        assert_vector_ulps_eq!(gw_data, gw.to_vec());
+    }
+
+    #[test]
+    fn check_conv2d_threads() {
+        let x_data = generate_values(16 * 16 * 8 * 4);
+        let w_data = generate_values(3 * 3 * 8 * 16);
+        let gy_data = generate_values(16 * 16 * 16 * 4);
+        let u32data = [1, 1, 1, 1, 1, 1];
+        let run = |dev: &Device| {
+            let x = dev.new_tensor_by_slice(shape![16, 16, 8; 4], &x_data);
+            let w = dev.new_tensor_by_slice(shape![3, 3, 8, 16], &w_data);
+            let mut y = dev.new_tensor(shape![16, 16, 16; 4]);
+            y.alloc();
+            dev.call_fw_impl("conv2d_fw_impl", &[&x, &w], &u32data, &[], &mut [&mut y]);
+            let gy = dev.new_tensor_by_slice(shape![16, 16, 16; 4], &gy_data);
+            let mut gx = dev.new_tensor_by_constant(shape![16, 16, 8; 4], 1.);
+            let mut gw = dev.new_tensor_by_constant(shape![3, 3, 8, 16], 1.);
+            let xs = [&x, &w];
+            dev.call_bw_impl(
+                "conv2d_bw_x_impl",
+                &xs,
+                &[&y],
+                &[&gy],
+                &u32data,
+                &[],
+                &mut gx,
+            );
+            dev.call_bw_impl(
+                "conv2d_bw_w_impl",
+                &xs,
+                &[&y],
+                &[&gy],
+                &u32data,
+                &[],
+                &mut gw,
+            );
+            (y.to_vec(), gx.to_vec(), gw.to_vec())
+        };
+        assert_eq!(run(&D::Naive::new()), run(&D::Naive::with_threads(4)));
+    }
+}
@@ -1,75 +1,11 @@
-use crate::device_impl::FunctionBwImpl;
 use crate::Tensor;
 
 define_naive_fw_ab_impl!(DivFwImpl, |a: f32, b: f32| { a / b });
 
-define_empty_impl!(DivBwAImpl);
-impl FunctionBwImpl for DivBwAImpl {
-    fn call(
-        &self,
-        xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let b = xs[1];
-        let gy = gys[0];
-        let ga = gx;
-        let volume = gy.shape.volume() as usize;
-        let a_shift = if ga.shape.batch() == 1 { 0 } else { volume };
-        let b_shift = if b.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pb = const_ptr!(b);
-            let mut pgy = const_ptr!(gy);
-            let mut pga = mut_ptr!(ga);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pga.add(i) += *pgy.add(i) / *pb.add(i);
-                }
-                pb = pb.add(b_shift);
-                pgy = pgy.add(volume);
-                pga = pga.add(a_shift);
-            }
-        }
-    }
-}
-
-define_empty_impl!(DivBwBImpl);
-impl FunctionBwImpl for DivBwBImpl {
-    fn call(
-        &self,
-        xs: &[&Tensor],
-        ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let b = xs[1];
-        let y = ys[0];
-        let gy = gys[0];
-        let gb = gx;
-        let volume = gy.shape.volume() as usize;
-        let b_shift = if gb.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pb = const_ptr!(b);
-            let mut py = const_ptr!(y);
-            let mut pgy = const_ptr!(gy);
-            let mut pgb = mut_ptr!(gb);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pgb.add(i) -= *pgy.add(i) * *py.add(i) / *pb.add(i);
-                }
-                pb = pb.add(b_shift);
-                py = py.add(volume);
-                pgy = pgy.add(volume);
-                pgb = pgb.add(b_shift);
-            }
-        }
-    }
-}
+define_naive_bw_ab_impl!(DivBwAImpl, |_a: f32, b: f32, _y: f32, gy: f32| { gy / b });
+define_naive_bw_ab_impl!(DivBwBImpl, |_a: f32, b: f32, y: f32, gy: f32| {
+    -(gy * y / b)
+});
 
 define_naive_fw_const_impl!(DivConstLFwImpl, |x: f32, k: f32| { k / x });
 define_naive_bw_const_impl!(DivConstLBwImpl, |x: f32, y: f32, gy: f32, _k: f32| {
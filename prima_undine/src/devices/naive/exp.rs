@@ -1,10 +1,11 @@
-define_naive_fw_x_impl!(ExpFwImpl, |x: f32| x.exp());
+define_naive_fw_x_impl!(ExpFwImpl, |x: f32| x.exp(), simd::exp);
 define_naive_bw_x_impl!(ExpBwImpl, |_x: f32, y: f32, gy: f32| gy * y);
 
 #[cfg(test)]
 mod tests {
     use crate::devices as D;
     use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
 
     #[test]
     fn check_exp_fw() {
@@ -34,4 +35,25 @@ mod tests {
         dev.call_bw_impl("exp_bw_impl", &[&x], &[&y], &[&gy], &[], &[], &mut gx);
         assert_vector_ulps_eq!(gx_data, gx.to_vec());
     }
+
+    #[test]
+    fn check_exp_threads() {
+        let dev1 = D::Naive::new();
+        let dev2 = D::Naive::with_threads(4);
+        let x_data = generate_values(100000);
+        let gy_data = generate_values(100000);
+        let mut results = vec![];
+        for dev in &[&dev1, &dev2] {
+            let x = dev.new_tensor_by_slice(shape![1000, 100], &x_data);
+            let gy = dev.new_tensor_by_slice(shape![1000, 100], &gy_data);
+            let mut y = dev.new_tensor(shape![1000, 100]);
+            y.alloc();
+            dev.call_fw_impl("exp_fw_impl", &[&x], &[], &[], &mut [&mut y]);
+            let mut gx = dev.new_tensor_by_constant(shape![1000, 100], 1.);
+            dev.call_bw_impl("exp_bw_impl", &[&x], &[&y], &[&gy], &[], &[], &mut gx);
+            results.push((y.to_vec(), gx.to_vec()));
+        }
+        assert_vector_ulps_eq!(results[0].0, results[1].0);
+        assert_vector_ulps_eq!(results[0].1, results[1].1);
+    }
 }
@@ -0,0 +1,135 @@
+use crate::devices::naive::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+use crate::devices::naive::simd;
+use crate::{Elementwise, Tensor};
+
+// Calculates a chain of elementwise operations over arguments of the same
+// shape. u32data holds 3 words for each operation: the operation code and
+// the two operands. Operand i < xs.len() refers to xs[i], and the others to
+// the result of the operation i - xs.len(). f32data holds the constant of
+// each operation. The result of the last operation is written to ys[0].
+//
+// Each operation uses the same formula and the same SIMD ranges as its own
+// kernel, so the results are identical to calculating the chain one by one.
+define_parallel_impl!(FusedElementwiseFwImpl);
+impl crate::device_impl::FunctionFwImpl for FusedElementwiseFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let ops = u32data
+            .chunks(3)
+            .zip(f32data)
+            .map(|(code, &k)| {
+                (
+                    Elementwise::decode(code[0], k),
+                    code[1] as usize,
+                    code[2] as usize,
+                )
+            })
+            .collect::<Vec<(Elementwise, usize, usize)>>();
+        let y = &mut ys[0];
+        let size = y.shape.size() as usize;
+        let simd_enabled = simd::enabled();
+        unsafe {
+            let pxs = xs.iter().map(|x| Shared(const_ptr!(x))).collect::<Vec<_>>();
+            let py = Shared(mut_ptr!(y));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                let len = end - begin;
+                let mut regs = vec![vec![0.; len]; ops.len()];
+                for (i, &(op, a, b)) in ops.iter().enumerate() {
+                    let (done, rest) = regs.split_at_mut(i);
+                    let dst = &mut rest[0];
+                    let operand = |j: usize| -> *const f32 {
+                        if j < pxs.len() {
+                            pxs[j].0.add(begin)
+                        } else {
+                            done[j - pxs.len()].as_ptr()
+                        }
+                    };
+                    let pa = operand(a);
+                    let pb = if op.num_args() == 2 { operand(b) } else { pa };
+                    let pd = dst.as_mut_ptr();
+                    match op {
+                        Elementwise::Exp if simd_enabled => simd::exp(pa, pd, len),
+                        Elementwise::Ln if simd_enabled => simd::ln(pa, pd, len),
+                        Elementwise::Tanh if simd_enabled => simd::tanh(pa, pd, len),
+                        Elementwise::Sigmoid if simd_enabled => simd::sigmoid(pa, pd, len),
+                        _ => {
+                            for n in 0..len {
+                                *pd.add(n) = calculate(op, *pa.add(n), *pb.add(n));
+                            }
+                        }
+                    }
+                }
+                let last = &regs[ops.len() - 1];
+                for (n, &v) in last.iter().enumerate() {
+                    *py.0.add(begin + n) = v;
+                }
+            });
+        }
+    }
+}
+
+// Same formulas as the kernels of the operations.
+fn calculate(op: Elementwise, a: f32, b: f32) -> f32 {
+    match op {
+        Elementwise::Neg => -a,
+        Elementwise::Exp => a.exp(),
+        Elementwise::Ln => a.ln(),
+        Elementwise::Tanh => a.tanh(),
+        Elementwise::Sigmoid => 0.5 + 0.5 * (0.5 * a).tanh(),
+        Elementwise::Sqrt => a.sqrt(),
+        Elementwise::Sin => a.sin(),
+        Elementwise::Cos => a.cos(),
+        Elementwise::Abs => a.abs(),
+        Elementwise::Add => a + b,
+        Elementwise::Sub => a - b,
+        Elementwise::Mul => a * b,
+        Elementwise::Div => a / b,
+        Elementwise::AddConst(k) => a + k,
+        Elementwise::SubConstL(k) => k - a,
+        Elementwise::SubConstR(k) => a - k,
+        Elementwise::MulConst(k) => a * k,
+        Elementwise::DivConstL(k) => k / a,
+        Elementwise::DivConstR(k) => a / k,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::Elementwise;
+
+    #[test]
+    fn check_fused_elementwise_fw() {
+        let dev = D::Naive::new();
+        let n = 37;
+        let a_data = (0..n).map(|i| i as f32 * 0.1 - 1.).collect::<Vec<f32>>();
+        let b_data = (0..n).map(|i| 2. - i as f32 * 0.05).collect::<Vec<f32>>();
+        let a = dev.new_tensor_by_slice(shape![n as u32], &a_data);
+        let b = dev.new_tensor_by_slice(shape![n as u32], &b_data);
+        // 3 * tanh(a + b) - a
+        let ops = [
+            (Elementwise::Add, 0, 1),
+            (Elementwise::Tanh, 2, 0),
+            (Elementwise::MulConst(3.), 3, 0),
+            (Elementwise::Sub, 4, 0),
+        ];
+        let mut u32data = vec![];
+        let mut f32data = vec![];
+        for &(op, x, y) in &ops {
+            let (code, k) = op.encode();
+            u32data.extend(&[code, x, y]);
+            f32data.push(k);
+        }
+        let mut y = dev.new_tensor(shape![n as u32]);
+        y.alloc();
+        dev.call_fw_impl(
+            "fused_elementwise_fw_impl",
+            &[&a, &b],
+            &u32data,
+            &f32data,
+            &mut [&mut y],
+        );
+        let expected = (3. * (&a + &b).tanh() - &a).to_vec();
+        assert_eq!(expected, y.to_vec());
+    }
+}
@@ -1,4 +1,4 @@
-define_naive_fw_x_impl!(LnFwImpl, |x: f32| x.ln());
+define_naive_fw_x_impl!(LnFwImpl, |x: f32| x.ln(), simd::ln);
 define_naive_bw_x_impl!(LnBwImpl, |x: f32, _y: f32, gy: f32| gy / x);
 
 #[cfg(test)]
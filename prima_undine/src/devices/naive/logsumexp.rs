@@ -1,7 +1,10 @@
+use std::cmp;
+
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
 use crate::device_impl::FunctionFwImpl;
 use crate::Tensor;
 
-define_empty_impl!(LogsumexpFwImpl);
+define_parallel_impl!(LogsumexpFwImpl);
 impl FunctionFwImpl for LogsumexpFwImpl {
     fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
         let x = xs[0];
@@ -11,23 +14,26 @@ impl FunctionFwImpl for LogsumexpFwImpl {
         let repeat = y.shape.size() as usize;
         let skip1 = y.shape.lower_volume(dim) as usize;
         let skip2 = skip1 * n;
+        let grain = cmp::max(1, ELEMENTWISE_GRAIN / cmp::max(1, n));
         unsafe {
-            let src = const_ptr!(x);
-            let dest = mut_ptr!(y);
-            for i in 0..repeat {
-                let mut offset = i % skip1 + (i / skip1) * skip2;
-                let mut tmp = *src.add(offset) as f64;
-                for _ in 1..n {
-                    offset += skip1;
-                    let arg = *src.add(offset) as f64;
-                    tmp = if tmp > arg {
-                        tmp + (1. + (arg - tmp).exp()).ln()
-                    } else {
-                        arg + (1. + (tmp - arg).exp()).ln()
-                    };
+            let src = Shared(const_ptr!(x));
+            let dest = Shared(mut_ptr!(y));
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                for i in begin..end {
+                    let mut offset = i % skip1 + (i / skip1) * skip2;
+                    let mut tmp = *src.0.add(offset) as f64;
+                    for _ in 1..n {
+                        offset += skip1;
+                        let arg = *src.0.add(offset) as f64;
+                        tmp = if tmp > arg {
+                            tmp + (1. + (arg - tmp).exp()).ln()
+                        } else {
+                            arg + (1. + (tmp - arg).exp()).ln()
+                        };
+                    }
+                    *dest.0.add(i) = tmp as f32;
                 }
-                *dest.add(i) = tmp as f32;
-            }
+            });
         }
     }
 }
@@ -36,6 +42,7 @@ impl FunctionFwImpl for LogsumexpFwImpl {
 mod tests {
     use crate::devices as D;
     use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
 
     #[test]
     fn check_logsumexp_fw() {
@@ -105,4 +112,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn check_logsumexp_fw_threads() {
+        let dev1 = D::Naive::new();
+        let dev2 = D::Naive::with_threads(4);
+        let x_data = generate_values(64 * 300 * 20);
+        let x1 = dev1.new_tensor_by_slice(shape![64, 300; 20], &x_data);
+        let x2 = dev2.new_tensor_by_slice(shape![64, 300; 20], &x_data);
+        for dim in 0..3 {
+            let y_shape = shape![64, 300; 20].resize_dim(dim, 1);
+            let mut y1 = dev1.new_tensor(y_shape);
+            y1.alloc();
+            let mut y2 = dev2.new_tensor(y_shape);
+            y2.alloc();
+            dev1.call_fw_impl("logsumexp_fw_impl", &[&x1], &[dim], &[], &mut [&mut y1]);
+            dev2.call_fw_impl("logsumexp_fw_impl", &[&x2], &[dim], &[], &mut [&mut y2]);
+            let (y1, y2) = (y1.to_vec(), y2.to_vec());
+            assert_vector_ulps_eq!(y1, y2);
+        }
+    }
 }
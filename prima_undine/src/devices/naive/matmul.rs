@@ -1,10 +1,12 @@
 use std::cmp;
 
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+use super::simd;
 use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
 use crate::functions::BasicDeviceFunctions;
 use crate::Tensor;
 
-define_empty_impl!(MatmulFwImpl);
+define_parallel_impl!(MatmulFwImpl);
 impl FunctionFwImpl for MatmulFwImpl {
     fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
         let a = xs[0];
@@ -13,20 +15,44 @@ impl FunctionFwImpl for MatmulFwImpl {
         let d1 = a.shape[0] as usize;
         let d2 = a.shape[1] as usize;
         let d3 = b.shape[1] as usize;
-        let bs = y.shape.batch();
+        let bs = y.shape.batch() as usize;
         let size = d1 * d3;
         let skip_a = if a.shape.has_batch() { d1 * d2 } else { 0 };
         let skip_b = if b.shape.has_batch() { d2 * d3 } else { 0 };
+        // each task computes a block of columns of one batch
+        let simd_enabled = simd::enabled();
+        let width = if simd_enabled { 64 } else { 8 };
+        let n_blocks = d3.div_ceil(width);
+        let grain = cmp::max(1, ELEMENTWISE_GRAIN / cmp::max(1, d1 * d2 * width));
         unsafe {
-            let mut pa = const_ptr!(a);
-            let mut pb = const_ptr!(b);
-            let mut py = mut_ptr!(y);
-            for _ in 0..bs {
-                for n in 0..size {
-                    *py.add(n) = 0.0;
-                }
-                for k in (0..d3).step_by(8) {
-                    let ek = cmp::min(k + 8, d3);
+            let pa = Shared(const_ptr!(a));
+            let pb = Shared(const_ptr!(b));
+            let py = Shared(mut_ptr!(y));
+            parallel_for(&self.pool, bs * n_blocks, grain, |begin, end| {
+                for t in begin..end {
+                    let batch = t / n_blocks;
+                    let k = (t % n_blocks) * width;
+                    let ek = cmp::min(k + width, d3);
+                    let pa = pa.0.add(batch * skip_a);
+                    let pb = pb.0.add(batch * skip_b);
+                    let py = py.0.add(batch * size);
+                    for n in k * d1..ek * d1 {
+                        *py.add(n) = 0.0;
+                    }
+                    if simd_enabled {
+                        simd::sgemm(
+                            d1,
+                            ek - k,
+                            d2,
+                            pa,
+                            d1,
+                            pb.add(k * d2),
+                            d2,
+                            py.add(k * d1),
+                            d1,
+                        );
+                        continue;
+                    }
                     for i in (0..d1).step_by(8) {
                         let ei = cmp::min(i + 8, d1);
                         for j in (0..d2).step_by(8) {
@@ -45,10 +71,7 @@ impl FunctionFwImpl for MatmulFwImpl {
                         }
                     }
                 }
-                py = py.add(size);
-                pa = pa.add(skip_a);
-                pb = pb.add(skip_b);
-            }
+            });
         }
     }
 }
@@ -93,6 +116,7 @@ impl FunctionBwImpl for MatmulBwBImpl {
 mod tests {
     use crate::devices as D;
     use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
 
     #[test]
     fn check_matmul_fw_aa() {
@@ -339,4 +363,30 @@ mod tests {
         assert_vector_ulps_eq!(ga_data, ga.to_vec());
         assert_vector_ulps_eq!(gb_data, gb.to_vec());
     }
+
+    #[test]
+    fn check_matmul_fw_threads() {
+        let dev1 = D::Naive::new();
+        let dev2 = D::Naive::with_threads(4);
+        let a_data = generate_values(37 * 45 * 3);
+        let b_data = generate_values(45 * 70 * 3);
+        for &(sa, sb) in &[
+            (shape![37, 45; 3], shape![45, 70; 3]),
+            (shape![37, 45], shape![45, 70; 3]),
+            (shape![37, 45; 3], shape![45, 70]),
+        ] {
+            let a1 = dev1.new_tensor_by_slice(sa, &a_data[..sa.size() as usize]);
+            let b1 = dev1.new_tensor_by_slice(sb, &b_data[..sb.size() as usize]);
+            let a2 = dev2.new_tensor_by_slice(sa, &a_data[..sa.size() as usize]);
+            let b2 = dev2.new_tensor_by_slice(sb, &b_data[..sb.size() as usize]);
+            let mut y1 = dev1.new_tensor(shape![37, 70; 3]);
+            y1.alloc();
+            let mut y2 = dev2.new_tensor(shape![37, 70; 3]);
+            y2.alloc();
+            dev1.call_fw_impl("matmul_fw_impl", &[&a1, &b1], &[], &[], &mut [&mut y1]);
+            dev2.call_fw_impl("matmul_fw_impl", &[&a2, &b2], &[], &[], &mut [&mut y2]);
+            let (y1, y2) = (y1.to_vec(), y2.to_vec());
+            assert_vector_ulps_eq!(y1, y2);
+        }
+    }
 }
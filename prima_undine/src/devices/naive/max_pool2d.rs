@@ -0,0 +1,285 @@
+use std::cmp;
+
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
+use crate::{Shape, Tensor};
+
+// Calls `f(y_index, argmax_x_index)` for every output of the planes
+// `begin..end` (channels and batches) of the pooling. Padded positions are
+// treated as negative infinity.
+#[allow(clippy::too_many_arguments)]
+unsafe fn max_pool2d_foreach<F: FnMut(usize, usize)>(
+    px: *const f32,
+    x: Shape,
+    y: Shape,
+    u32data: &[u32],
+    begin: usize,
+    end: usize,
+    mut f: F,
+) {
+    let window0 = u32data[0] as usize;
+    let window1 = u32data[1] as usize;
+    let padding0 = u32data[2] as isize;
+    let padding1 = u32data[3] as isize;
+    let stride0 = u32data[4] as isize;
+    let stride1 = u32data[5] as isize;
+    let x0 = x[0] as isize;
+    let x1 = x[1] as isize;
+    let y0 = y[0] as usize;
+    let y1 = y[1] as usize;
+    let x_skip = (x0 * x1) as usize;
+    let y_skip = y0 * y1;
+    for r in begin..end {
+        let px = px.add(r * x_skip);
+        for j1 in 0..y1 {
+            for j0 in 0..y0 {
+                let mut best = None;
+                for k1 in 0..window1 {
+                    let i1 = j1 as isize * stride1 + k1 as isize - padding1;
+                    if i1 < 0 || i1 >= x1 {
+                        continue;
+                    }
+                    for k0 in 0..window0 {
+                        let i0 = j0 as isize * stride0 + k0 as isize - padding0;
+                        if i0 < 0 || i0 >= x0 {
+                            continue;
+                        }
+                        let i = (i0 + x0 * i1) as usize;
+                        match best {
+                            Some(b) if *px.add(b) >= *px.add(i) => {}
+                            _ => best = Some(i),
+                        }
+                    }
+                }
+                if let Some(b) = best {
+                    f(r * y_skip + j0 + y0 * j1, r * x_skip + b);
+                }
+            }
+        }
+    }
+}
+
+// Number of planes processed by each thread. Each plane of the output and of
+// the gradient is written by one thread.
+fn max_pool2d_grain(y: Shape, u32data: &[u32]) -> usize {
+    let terms = (y[0] * y[1] * u32data[0] * u32data[1]) as usize;
+    cmp::max(ELEMENTWISE_GRAIN / cmp::max(terms, 1), 1)
+}
+
+define_parallel_impl!(MaxPool2dFwImpl);
+impl FunctionFwImpl for MaxPool2dFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let y = &mut ys[0];
+        y.reset(f32::NEG_INFINITY);
+        let repeat = (y.shape.size() / (y.shape[0] * y.shape[1])) as usize;
+        let grain = max_pool2d_grain(y.shape, u32data);
+        unsafe {
+            let px = Shared(const_ptr!(x));
+            let py = Shared(mut_ptr!(y));
+            let y_shape = y.shape;
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                max_pool2d_foreach(px.0, x.shape, y_shape, u32data, begin, end, |yi, xi| {
+                    *py.0.add(yi) = *px.0.add(xi);
+                });
+            });
+        }
+    }
+}
+
+define_parallel_impl!(MaxPool2dBwImpl);
+impl FunctionBwImpl for MaxPool2dBwImpl {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let x = xs[0];
+        let gy = gys[0];
+        let repeat = (gy.shape.size() / (gy.shape[0] * gy.shape[1])) as usize;
+        let grain = max_pool2d_grain(gy.shape, u32data);
+        unsafe {
+            let px = Shared(const_ptr!(x));
+            let pgy = Shared(const_ptr!(gy));
+            let pgx = Shared(mut_ptr!(gx));
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                max_pool2d_foreach(px.0, x.shape, gy.shape, u32data, begin, end, |yi, xi| {
+                    *pgx.0.add(xi) += *pgy.0.add(yi);
+                });
+            });
+        }
+    }
+}
+
+// Picks the elements of xs[1] at the maxima of the windows of xs[0], which is
+// the transpose of the backward calculation. Used by higher-order gradients.
+define_parallel_impl!(MaxPool2dPickFwImpl);
+impl FunctionFwImpl for MaxPool2dPickFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let z = xs[1];
+        let y = &mut ys[0];
+        y.reset(0.);
+        let repeat = (y.shape.size() / (y.shape[0] * y.shape[1])) as usize;
+        let grain = max_pool2d_grain(y.shape, u32data);
+        unsafe {
+            let px = Shared(const_ptr!(x));
+            let pz = Shared(const_ptr!(z));
+            let py = Shared(mut_ptr!(y));
+            let y_shape = y.shape;
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                max_pool2d_foreach(px.0, x.shape, y_shape, u32data, begin, end, |yi, xi| {
+                    *py.0.add(yi) = *pz.0.add(xi);
+                });
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
+    use crate::{devices as D, Device, Shape};
+
+    #[test]
+    fn check_max_pool2d_fw() {
+        struct TestCase(Shape, Vec<f32>, Vec<u32>, Shape, Vec<f32>);
+        let test_cases = vec![
+            TestCase(
+                shape![4, 4],
+                vec![
+                    4., 11., 10., 13., 12., 3., 6., 0., 1., 15., 14., 5., 2., 8., 9., 7.,
+                ],
+                vec![2, 2, 0, 0, 2, 2],
+                shape![2, 2],
+                vec![12., 13., 15., 14.],
+            ),
+            TestCase(
+                shape![5, 4],
+                vec![
+                    14., 11., 5., 7., 9., 18., 13., 16., 1., 0., 6., 8., 2., 10., 3., 4., 12., 15.,
+                    19., 17.,
+                ],
+                vec![3, 2, 1, 0, 2, 1],
+                shape![3, 3],
+                vec![18., 16., 9., 18., 16., 10., 12., 19., 19.],
+            ),
+            TestCase(
+                shape![3, 3, 2; 2],
+                vec![
+                    17., 14., 10., 2., 32., 23., 7., 34., 0., 5., 26., 18., 22., 19., 21., 33.,
+                    12., 16., 20., 9., 13., 8., 6., 15., 31., 1., 3., 11., 4., 29., 35., 28., 25.,
+                    27., 24., 30.,
+                ],
+                vec![2, 2, 1, 1, 2, 2],
+                shape![2, 2, 2; 2],
+                vec![
+                    17., 14., 7., 34., 5., 26., 33., 21., 20., 13., 31., 15., 11., 29., 35., 30.,
+                ],
+            ),
+        ];
+        let dev = D::Naive::new();
+        for tc in &test_cases {
+            let x = dev.new_tensor_by_slice(tc.0, &tc.1);
+            let mut y = dev.new_tensor(tc.3);
+            y.alloc();
+            dev.call_fw_impl("max_pool2d_fw_impl", &[&x], &tc.2, &[], &mut [&mut y]);
+            assert_vector_ulps_eq!(tc.4, y.to_vec());
+        }
+    }
+
+    #[test]
+    fn check_max_pool2d_bw() {
+        struct TestCase(Shape, Vec<f32>, Vec<u32>, Shape, Vec<f32>);
+        let test_cases = vec![
+            TestCase(
+                shape![4, 4],
+                vec![
+                    4., 11., 10., 13., 12., 3., 6., 0., 1., 15., 14., 5., 2., 8., 9., 7.,
+                ],
+                vec![2, 2, 0, 0, 2, 2],
+                shape![2, 2],
+                vec![
+                    1., 1., 1., 3., 2., 1., 1., 1., 1., 4., 5., 1., 1., 1., 1., 1.,
+                ],
+            ),
+            TestCase(
+                shape![5, 4],
+                vec![
+                    14., 11., 5., 7., 9., 18., 13., 16., 1., 0., 6., 8., 2., 10., 3., 4., 12., 15.,
+                    19., 17.,
+                ],
+                vec![3, 2, 1, 0, 2, 1],
+                shape![3, 3],
+                vec![
+                    1., 1., 1., 1., 4., 6., 1., 8., 1., 1., 1., 1., 1., 7., 1., 1., 8., 1., 18., 1.,
+                ],
+            ),
+            TestCase(
+                shape![3, 3, 2; 2],
+                vec![
+                    17., 14., 10., 2., 32., 23., 7., 34., 0., 5., 26., 18., 22., 19., 21., 33.,
+                    12., 16., 20., 9., 13., 8., 6., 15., 31., 1., 3., 11., 4., 29., 35., 28., 25.,
+                    27., 24., 30.,
+                ],
+                vec![2, 2, 1, 1, 2, 2],
+                shape![2, 2, 2; 2],
+                vec![
+                    2., 3., 1., 1., 1., 1., 4., 5., 1., 6., 7., 1., 1., 1., 9., 8., 1., 1., 10.,
+                    1., 11., 1., 1., 13., 12., 1., 1., 14., 1., 15., 16., 1., 1., 1., 1., 17.,
+                ],
+            ),
+        ];
+        let dev = D::Naive::new();
+        for tc in &test_cases {
+            let x = dev.new_tensor_by_slice(tc.0, &tc.1);
+            let mut y = dev.new_tensor(tc.3);
+            y.alloc();
+            dev.call_fw_impl("max_pool2d_fw_impl", &[&x], &tc.2, &[], &mut [&mut y]);
+            let gy_data = (1..=tc.3.size()).map(|x| x as f32).collect::<Vec<f32>>();
+            let gy = dev.new_tensor_by_slice(tc.3, &gy_data);
+            let mut gx = dev.new_tensor_by_constant(tc.0, 1.);
+            dev.call_bw_impl(
+                "max_pool2d_bw_impl",
+                &[&x],
+                &[&y],
+                &[&gy],
+                &tc.2,
+                &[],
+                &mut gx,
+            );
+            assert_vector_ulps_eq!(tc.4, gx.to_vec());
+        }
+    }
+
+    #[test]
+    fn check_max_pool2d_threads() {
+        let x_data = generate_values(32 * 32 * 16 * 4);
+        let gy_data = generate_values(16 * 16 * 16 * 4);
+        let u32data = [2, 2, 0, 0, 2, 2];
+        let run = |dev: &Device| {
+            let x = dev.new_tensor_by_slice(shape![32, 32, 16; 4], &x_data);
+            let mut y = dev.new_tensor(shape![16, 16, 16; 4]);
+            y.alloc();
+            dev.call_fw_impl("max_pool2d_fw_impl", &[&x], &u32data, &[], &mut [&mut y]);
+            let gy = dev.new_tensor_by_slice(shape![16, 16, 16; 4], &gy_data);
+            let mut gx = dev.new_tensor_by_constant(shape![32, 32, 16; 4], 1.);
+            dev.call_bw_impl(
+                "max_pool2d_bw_impl",
+                &[&x],
+                &[&y],
+                &[&gy],
+                &u32data,
+                &[],
+                &mut gx,
+            );
+            (y.to_vec(), gx.to_vec())
+        };
+        assert_eq!(run(&D::Naive::new()), run(&D::Naive::with_threads(4)));
+    }
+}
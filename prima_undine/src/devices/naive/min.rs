@@ -1,7 +1,10 @@
+use std::cmp;
+
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
 use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
 use crate::Tensor;
 
-define_empty_impl!(MinFwImpl);
+define_parallel_impl!(MinFwImpl);
 impl FunctionFwImpl for MinFwImpl {
     fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
         let x = xs[0];
@@ -11,20 +14,23 @@ impl FunctionFwImpl for MinFwImpl {
         let repeat = y.shape.size() as usize;
         let skip1 = y.shape.lower_volume(dim) as usize;
         let skip2 = skip1 * n;
+        let grain = cmp::max(1, ELEMENTWISE_GRAIN / cmp::max(1, n));
         unsafe {
-            let px = const_ptr!(x);
-            let py = mut_ptr!(y);
-            for i in 0..repeat {
-                let mut offset = i % skip1 + (i / skip1) * skip2;
-                let mut tmp = *px.add(offset);
-                for _ in 0..n {
-                    if *px.add(offset) < tmp {
-                        tmp = *px.add(offset);
+            let px = Shared(const_ptr!(x));
+            let py = Shared(mut_ptr!(y));
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                for i in begin..end {
+                    let mut offset = i % skip1 + (i / skip1) * skip2;
+                    let mut tmp = *px.0.add(offset);
+                    for _ in 0..n {
+                        if *px.0.add(offset) < tmp {
+                            tmp = *px.0.add(offset);
+                        }
+                        offset += skip1;
                     }
-                    offset += skip1;
+                    *py.0.add(i) = tmp;
                 }
-                *py.add(i) = tmp;
-            }
+            });
         }
     }
 }
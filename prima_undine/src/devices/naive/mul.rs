@@ -1,73 +1,9 @@
-use crate::device_impl::FunctionBwImpl;
 use crate::Tensor;
 
 define_naive_fw_ab_impl!(MulFwImpl, |a: f32, b: f32| { a * b });
 
-define_empty_impl!(MulBwAImpl);
-impl FunctionBwImpl for MulBwAImpl {
-    fn call(
-        &self,
-        xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let b = xs[1];
-        let gy = gys[0];
-        let ga = gx;
-        let volume = gy.shape.volume() as usize;
-        let a_shift = if ga.shape.batch() == 1 { 0 } else { volume };
-        let b_shift = if b.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pb = const_ptr!(b);
-            let mut pgy = const_ptr!(gy);
-            let mut pga = mut_ptr!(ga);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pga.add(i) += *pb.add(i) * *pgy.add(i);
-                }
-                pb = pb.add(b_shift);
-                pgy = pgy.add(volume);
-                pga = pga.add(a_shift);
-            }
-        }
-    }
-}
-
-define_empty_impl!(MulBwBImpl);
-impl FunctionBwImpl for MulBwBImpl {
-    fn call(
-        &self,
-        xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let a = xs[0];
-        let gy = gys[0];
-        let gb = gx;
-        let volume = gy.shape.volume() as usize;
-        let a_shift = if a.shape.batch() == 1 { 0 } else { volume };
-        let b_shift = if gb.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pa = const_ptr!(a);
-            let mut pgy = const_ptr!(gy);
-            let mut pgb = mut_ptr!(gb);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pgb.add(i) += *pa.add(i) * *pgy.add(i);
-                }
-                pa = pa.add(a_shift);
-                pgy = pgy.add(volume);
-                pgb = pgb.add(b_shift);
-            }
-        }
-    }
-}
+define_naive_bw_ab_impl!(MulBwAImpl, |_a: f32, b: f32, _y: f32, gy: f32| { b * gy });
+define_naive_bw_ab_impl!(MulBwBImpl, |a: f32, _b: f32, _y: f32, gy: f32| { a * gy });
 
 define_naive_fw_const_impl!(MulConstFwImpl, |x: f32, k: f32| { x * k });
 define_naive_bw_const_impl!(MulConstBwImpl, |_x: f32, _y: f32, gy: f32, k: f32| {
@@ -79,6 +15,8 @@ define_naive_fw_scalar_impl!(MulScalarFwImpl, |x: f32, k: f32| { x * k });
 mod tests {
     use crate::devices as D;
     use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
+    use crate::Device;
 
     #[test]
     fn check_mul_const_fw() {
@@ -293,4 +231,24 @@ mod tests {
         assert_vector_ulps_eq!(ga_data, ga.to_vec());
         assert_vector_ulps_eq!(gb_data, gb.to_vec());
     }
+
+    #[test]
+    fn check_mul_bw_threads() {
+        let a_data = generate_values(100 * 300);
+        let b_data = generate_values(100 * 300 * 4);
+        let gy_data = generate_values(100 * 300 * 4);
+        let run = |dev: &Device| {
+            let a = dev.new_tensor_by_slice(shape![100, 300], &a_data);
+            let b = dev.new_tensor_by_slice(shape![100, 300; 4], &b_data);
+            let y = dev.new_tensor_by_constant(shape![100, 300; 4], 0.);
+            let gy = dev.new_tensor_by_slice(shape![100, 300; 4], &gy_data);
+            let mut ga = dev.new_tensor_by_constant(shape![100, 300], 1.);
+            let mut gb = dev.new_tensor_by_constant(shape![100, 300; 4], 1.);
+            let xs = [&a, &b];
+            dev.call_bw_impl("mul_bw_a_impl", &xs, &[&y], &[&gy], &[], &[], &mut ga);
+            dev.call_bw_impl("mul_bw_b_impl", &xs, &[&y], &[&gy], &[], &[], &mut gb);
+            (ga.to_vec(), gb.to_vec())
+        };
+        assert_eq!(run(&D::Naive::new()), run(&D::Naive::with_threads(4)));
+    }
 }
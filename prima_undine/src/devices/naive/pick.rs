@@ -1,41 +1,88 @@
+use std::cmp;
+use std::sync::Arc;
+
+use rayon::ThreadPool;
+
+use super::common::{parallel_for, read_ids, Shared, ELEMENTWISE_GRAIN};
 use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
 use crate::Tensor;
 
-define_empty_impl!(PickFwImpl);
-impl FunctionFwImpl for PickFwImpl {
-    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
-        let x = xs[0];
-        let dim = u32data[0];
-        let ids = &u32data[1..];
-        let y = &mut ys[0];
-        let bs = y.shape.batch() as usize;
-        let skip_x = if x.shape.has_batch() {
-            x.shape.volume() as usize
-        } else {
-            0
-        };
-        let skip_i = if ids.len() > 1 { 1 } else { 0 };
-        let base = y.shape.lower_volume(dim) as usize;
-        let skip = base * x.shape[dim] as usize;
-        let repeat = y.shape.volume() as usize / base;
-        unsafe {
-            let px = const_ptr!(x);
-            let mut dest = mut_ptr!(y);
+// The rows of `base` elements are distributed over the threads, and each row
+// is processed for all batches in order, so the gradient of an unbatched
+// argument accumulates in the same order with any number of threads.
+fn pick_fw(pool: &Option<Arc<ThreadPool>>, x: &Tensor, ids: &[u32], dim: u32, y: &mut Tensor) {
+    let bs = y.shape.batch() as usize;
+    let skip_x = if x.shape.has_batch() {
+        x.shape.volume() as usize
+    } else {
+        0
+    };
+    let skip_i = if ids.len() > 1 { 1 } else { 0 };
+    let base = y.shape.lower_volume(dim) as usize;
+    let skip = base * x.shape[dim] as usize;
+    let repeat = y.shape.volume() as usize / base;
+    let grain = cmp::max(ELEMENTWISE_GRAIN / (base * bs), 1);
+    unsafe {
+        let px = Shared(const_ptr!(x));
+        let py = Shared(mut_ptr!(y));
+        parallel_for(pool, repeat, grain, |begin, end| {
             for batch in 0..bs {
-                let src = px.add(batch * skip_x + base * ids[batch * skip_i] as usize);
-                for i in 0..repeat {
+                let src =
+                    px.0.add(batch * skip_x + base * ids[batch * skip_i] as usize);
+                let dest = py.0.add(batch * repeat * base);
+                for i in begin..end {
                     let sp = src.add(skip * i);
+                    let dp = dest.add(base * i);
                     for j in 0..base {
-                        *dest = *sp.add(j);
-                        dest = dest.add(1);
+                        *dp.add(j) = *sp.add(j);
                     }
                 }
             }
-        }
+        });
+    }
+}
+
+fn pick_bw(pool: &Option<Arc<ThreadPool>>, gy: &Tensor, ids: &[u32], dim: u32, gx: &mut Tensor) {
+    let bs = gy.shape.batch() as usize;
+    let skip_x = if gx.shape.has_batch() {
+        gx.shape.volume() as usize
+    } else {
+        0
+    };
+    let skip_i = if ids.len() > 1 { 1 } else { 0 };
+    let base = gy.shape.lower_volume(dim) as usize;
+    let skip = base * gx.shape[dim] as usize;
+    let repeat = gy.shape.volume() as usize / base;
+    let grain = cmp::max(ELEMENTWISE_GRAIN / (base * bs), 1);
+    unsafe {
+        let pgy = Shared(const_ptr!(gy));
+        let pgx = Shared(mut_ptr!(gx));
+        parallel_for(pool, repeat, grain, |begin, end| {
+            for batch in 0..bs {
+                let src = pgy.0.add(batch * repeat * base);
+                let dest = pgx
+                    .0
+                    .add(batch * skip_x + base * ids[batch * skip_i] as usize);
+                for i in begin..end {
+                    let sp = src.add(base * i);
+                    let dp = dest.add(skip * i);
+                    for j in 0..base {
+                        *dp.add(j) += *sp.add(j);
+                    }
+                }
+            }
+        });
+    }
+}
+
+define_parallel_impl!(PickFwImpl);
+impl FunctionFwImpl for PickFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        pick_fw(&self.pool, xs[0], &u32data[1..], u32data[0], ys[0]);
     }
 }
 
-define_empty_impl!(PickBwImpl);
+define_parallel_impl!(PickBwImpl);
 impl FunctionBwImpl for PickBwImpl {
     fn call(
         &self,
@@ -46,40 +93,42 @@ impl FunctionBwImpl for PickBwImpl {
         _f32data: &[f32],
         gx: &mut Tensor,
     ) {
-        let gy = gys[0];
+        pick_bw(&self.pool, gys[0], &u32data[1..], u32data[0], gx);
+    }
+}
+
+// The ids are given as xs[1] (forward) or xs[0] (backward).
+define_parallel_impl!(PickByFwImpl);
+impl FunctionFwImpl for PickByFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
         let dim = u32data[0];
-        let ids = &u32data[1..];
-        let bs = gy.shape.batch() as usize;
-        let skip_x = if gx.shape.has_batch() {
-            gx.shape.volume() as usize
-        } else {
-            0
-        };
-        let skip_i = if ids.len() > 1 { 1 } else { 0 };
-        let base = gy.shape.lower_volume(dim) as usize;
-        let skip = base * gx.shape[dim] as usize;
-        let repeat = gy.shape.volume() as usize / base;
-        unsafe {
-            let mut src = const_ptr!(gy);
-            let pgx = mut_ptr!(gx);
-            for batch in 0..bs {
-                let dest = pgx.add(batch * skip_x + base * ids[batch * skip_i] as usize);
-                for i in 0..repeat {
-                    let dp = dest.add(skip * i);
-                    for j in 0..base {
-                        *dp.add(j) += *src;
-                        src = src.add(1);
-                    }
-                }
-            }
-        }
+        let ids = read_ids("pick", xs[1], xs[0].shape[dim]);
+        pick_fw(&self.pool, xs[0], &ids, dim, ys[0]);
+    }
+}
+
+define_parallel_impl!(PickByBwImpl);
+impl FunctionBwImpl for PickByBwImpl {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let dim = u32data[0];
+        let ids = read_ids("pick", xs[0], gx.shape[dim]);
+        pick_bw(&self.pool, gys[0], &ids, dim, gx);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::functions::BasicFunctions;
-    use crate::{devices as D, Shape};
+    use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+    use crate::test_utils::generate_values;
+    use crate::{devices as D, Device, Shape};
 
     #[test]
     fn check_pick_fw_nn() {
@@ -272,4 +321,49 @@ mod tests {
             assert_vector_ulps_eq!(tc.4, gx.to_vec());
         }
     }
+
+    #[test]
+    fn check_pick_threads() {
+        let x_data = generate_values(10 * 5000);
+        let gy_data = generate_values(5000 * 8);
+        let u32data = vec![0, 3, 1, 4, 1, 5, 9, 2, 6];
+        let run = |dev: &Device| {
+            let x = dev.new_tensor_by_slice(shape![10, 5000], &x_data);
+            let mut y = dev.new_tensor(shape![1, 5000; 8]);
+            y.alloc();
+            dev.call_fw_impl("pick_fw_impl", &[&x], &u32data, &[], &mut [&mut y]);
+            let gy = dev.new_tensor_by_slice(shape![1, 5000; 8], &gy_data);
+            let mut gx = dev.new_tensor_by_constant(shape![10, 5000], 1.);
+            dev.call_bw_impl("pick_bw_impl", &[], &[], &[&gy], &u32data, &[], &mut gx);
+            (y.to_vec(), gx.to_vec())
+        };
+        assert_eq!(run(&D::Naive::new()), run(&D::Naive::with_threads(4)));
+    }
+
+    #[test]
+    fn check_pick_by() {
+        let dev = D::Naive::new();
+        let x_data = (0..12).map(|i| i as f32).collect::<Vec<f32>>();
+        let x = dev.new_tensor_by_slice(shape![2, 2; 3], &x_data);
+        let ids_u32 = dev.new_tensor_by_data(shape![; 3], &[1u32, 0, 1]);
+        let ids_i32 = dev.new_tensor_by_data(shape![; 3], &[1i32, 0, 1]);
+        let expected = x.pick(&[1, 0, 1], 1).to_vec();
+        assert_eq!(expected, x.pick_by(&ids_u32, 1).to_vec());
+        assert_eq!(expected, x.pick_by(&ids_i32, 1).to_vec());
+        let gy = dev.new_tensor_by_slice(shape![2; 3], &[1., 2., 3., 4., 5., 6.]);
+        let mut gx1 = dev.new_tensor_by_constant(shape![2, 2; 3], 1.);
+        let mut gx2 = dev.new_tensor_by_constant(shape![2, 2; 3], 1.);
+        dev.pick_bw(&gy, &[1, 0, 1], 1, &mut gx1);
+        dev.pick_by_bw(&gy, &ids_i32, 1, &mut gx2);
+        assert_eq!(gx1.to_vec(), gx2.to_vec());
+    }
+
+    #[test]
+    #[should_panic(expected = "pick: id -1 is out of range [0, 2)")]
+    fn check_pick_by_out_of_range() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_constant(shape![2, 2], 0.);
+        let ids = dev.new_tensor_by_data(shape![], &[-1i32]);
+        x.pick_by(&ids, 0);
+    }
 }
@@ -1,7 +1,8 @@
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
 use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
 use crate::Tensor;
 
-define_empty_impl!(PowiFwImpl);
+define_parallel_impl!(PowiFwImpl);
 impl FunctionFwImpl for PowiFwImpl {
     fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
         let x = xs[0];
@@ -9,16 +10,18 @@ impl FunctionFwImpl for PowiFwImpl {
         let y = &mut ys[0];
         let size = y.shape.size() as usize;
         unsafe {
-            let px = const_ptr!(x);
-            let py = mut_ptr!(y);
-            for i in 0..size {
-                *py.add(i) = (*px.add(i)).powi(n);
-            }
+            let px = Shared(const_ptr!(x));
+            let py = Shared(mut_ptr!(y));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    *py.0.add(i) = (*px.0.add(i)).powi(n);
+                }
+            });
         }
     }
 }
 
-define_empty_impl!(PowiBwImpl);
+define_parallel_impl!(PowiBwImpl);
 impl FunctionBwImpl for PowiBwImpl {
     fn call(
         &self,
@@ -34,12 +37,14 @@ impl FunctionBwImpl for PowiBwImpl {
         let n = u32data[0] as i32;
         let size = gy.shape.size() as usize;
         unsafe {
-            let px = const_ptr!(x);
-            let pgy = const_ptr!(gy);
-            let pgx = mut_ptr!(gx);
-            for i in 0..size {
-                *pgx.add(i) += *pgy.add(i) * n as f32 * (*px.add(i)).powi(n - 1);
-            }
+            let px = Shared(const_ptr!(x));
+            let pgy = Shared(const_ptr!(gy));
+            let pgx = Shared(mut_ptr!(gx));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    *pgx.0.add(i) += *pgy.0.add(i) * n as f32 * (*px.0.add(i)).powi(n - 1);
+                }
+            });
         }
     }
 }
@@ -87,3 +87,29 @@ impl FunctionFwImpl for RandomUniformImpl {
         }
     }
 }
+
+pub struct RandomLogNormalImpl {
+    randomizer: Arc<Mutex<Box<dyn Randomizer>>>,
+}
+
+impl RandomLogNormalImpl {
+    pub fn new(randomizer: Arc<Mutex<Box<dyn Randomizer>>>) -> RandomLogNormalImpl {
+        RandomLogNormalImpl { randomizer }
+    }
+}
+
+impl FunctionFwImpl for RandomLogNormalImpl {
+    fn call(&self, _xs: &[&Tensor], _u32data: &[u32], f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let mean = f32data[0];
+        let sd = f32data[1];
+        let y = &mut ys[0];
+        unsafe {
+            let py = mut_ptr!(y);
+            let yslice = slice::from_raw_parts_mut(py, y.shape.size() as usize);
+            self.randomizer
+                .lock()
+                .unwrap()
+                .fill_log_normal(mean, sd, yslice)
+        }
+    }
+}
@@ -1,4 +1,8 @@
-define_naive_fw_x_impl!(SigmoidFwImpl, |x: f32| 0.5 + 0.5 * (0.5 * x).tanh());
+define_naive_fw_x_impl!(
+    SigmoidFwImpl,
+    |x: f32| 0.5 + 0.5 * (0.5 * x).tanh(),
+    simd::sigmoid
+);
 define_naive_bw_x_impl!(SigmoidBwImpl, |_x: f32, y: f32, gy: f32| gy * y * (1. - y));
 
 #[cfg(test)]
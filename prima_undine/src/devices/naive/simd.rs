@@ -0,0 +1,414 @@
+// Vectorized kernels used by the Naive device when the running CPU supports
+// AVX2 and FMA. Callers must check `enabled()` before calling any other
+// function in this module.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[cfg(target_arch = "x86_64")]
+pub fn enabled() -> bool {
+    is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn enabled() -> bool {
+    false
+}
+
+// GEMM
+
+const MR: usize = 16;
+const NR: usize = 6;
+const KC: usize = 256;
+const MC: usize = 96;
+
+// C += A * B, all matrices are column-major.
+#[cfg(target_arch = "x86_64")]
+#[allow(clippy::too_many_arguments)]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn sgemm(
+    m: usize,
+    n: usize,
+    k: usize,
+    a: *const f32,
+    lda: usize,
+    b: *const f32,
+    ldb: usize,
+    c: *mut f32,
+    ldc: usize,
+) {
+    let n_panels = n.div_ceil(NR);
+    let mut packed_a = vec![0.; MC * KC];
+    let mut packed_b = vec![0.; n_panels * NR * KC];
+    for pc in (0..k).step_by(KC) {
+        let kc = std::cmp::min(KC, k - pc);
+        pack_b(kc, n, b.add(pc), ldb, &mut packed_b);
+        for ic in (0..m).step_by(MC) {
+            let mc = std::cmp::min(MC, m - ic);
+            pack_a(mc, kc, a.add(ic + pc * lda), lda, &mut packed_a);
+            for jr in 0..n_panels {
+                let nr = std::cmp::min(NR, n - jr * NR);
+                let pb = packed_b.as_ptr().add(jr * NR * kc);
+                for ir in 0..mc.div_ceil(MR) {
+                    let mr = std::cmp::min(MR, mc - ir * MR);
+                    let pa = packed_a.as_ptr().add(ir * MR * kc);
+                    let pc_ = c.add(ic + ir * MR + jr * NR * ldc);
+                    micro_kernel(kc, pa, pb, pc_, ldc, mr, nr);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn pack_a(mc: usize, kc: usize, a: *const f32, lda: usize, dest: &mut [f32]) {
+    let mut d = dest.as_mut_ptr();
+    for ir in (0..mc).step_by(MR) {
+        let mr = std::cmp::min(MR, mc - ir);
+        for p in 0..kc {
+            let src = a.add(ir + p * lda);
+            for i in 0..mr {
+                *d.add(i) = *src.add(i);
+            }
+            for i in mr..MR {
+                *d.add(i) = 0.;
+            }
+            d = d.add(MR);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn pack_b(kc: usize, n: usize, b: *const f32, ldb: usize, dest: &mut [f32]) {
+    let mut d = dest.as_mut_ptr();
+    for jr in (0..n).step_by(NR) {
+        let nr = std::cmp::min(NR, n - jr);
+        for p in 0..kc {
+            for j in 0..nr {
+                *d.add(j) = *b.add(p + (jr + j) * ldb);
+            }
+            for j in nr..NR {
+                *d.add(j) = 0.;
+            }
+            d = d.add(NR);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn micro_kernel(
+    kc: usize,
+    pa: *const f32,
+    pb: *const f32,
+    c: *mut f32,
+    ldc: usize,
+    mr: usize,
+    nr: usize,
+) {
+    let mut acc = [[_mm256_setzero_ps(); 2]; NR];
+    for p in 0..kc {
+        let a0 = _mm256_loadu_ps(pa.add(p * MR));
+        let a1 = _mm256_loadu_ps(pa.add(p * MR + 8));
+        for (j, acc_j) in acc.iter_mut().enumerate() {
+            let bj = _mm256_broadcast_ss(&*pb.add(p * NR + j));
+            acc_j[0] = _mm256_fmadd_ps(a0, bj, acc_j[0]);
+            acc_j[1] = _mm256_fmadd_ps(a1, bj, acc_j[1]);
+        }
+    }
+    if mr == MR {
+        for (j, acc_j) in acc.iter().enumerate().take(nr) {
+            let cj = c.add(j * ldc);
+            _mm256_storeu_ps(cj, _mm256_add_ps(_mm256_loadu_ps(cj), acc_j[0]));
+            _mm256_storeu_ps(
+                cj.add(8),
+                _mm256_add_ps(_mm256_loadu_ps(cj.add(8)), acc_j[1]),
+            );
+        }
+    } else {
+        let mut tmp = [0f32; MR];
+        for (j, acc_j) in acc.iter().enumerate().take(nr) {
+            _mm256_storeu_ps(tmp.as_mut_ptr(), acc_j[0]);
+            _mm256_storeu_ps(tmp.as_mut_ptr().add(8), acc_j[1]);
+            let cj = c.add(j * ldc);
+            for (i, t) in tmp.iter().enumerate().take(mr) {
+                *cj.add(i) += t;
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn sgemm(
+    _m: usize,
+    _n: usize,
+    _k: usize,
+    _a: *const f32,
+    _lda: usize,
+    _b: *const f32,
+    _ldb: usize,
+    _c: *mut f32,
+    _ldc: usize,
+) {
+    unreachable!();
+}
+
+// element-wise functions
+//
+// The polynomial approximations follow Cephes. Lanes whose input is out of
+// the supported range (including NaN and infinity) are recomputed by the
+// scalar function, so special values behave exactly as in the scalar kernels.
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn exp_core(x: __m256) -> __m256 {
+    let fx = _mm256_floor_ps(_mm256_fmadd_ps(
+        x,
+        _mm256_set1_ps(std::f32::consts::LOG2_E),
+        _mm256_set1_ps(0.5),
+    ));
+    let x = _mm256_fnmadd_ps(fx, _mm256_set1_ps(0.693_359_4), x);
+    let x = _mm256_fnmadd_ps(fx, _mm256_set1_ps(-2.121_944_4e-4), x);
+    let z = _mm256_mul_ps(x, x);
+    let mut y = _mm256_set1_ps(1.987_569_1e-4);
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(1.398_2e-3));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(8.333_452e-3));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(4.166_579_6e-2));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(1.666_666_5e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(5e-1));
+    y = _mm256_fmadd_ps(y, z, x);
+    y = _mm256_add_ps(y, _mm256_set1_ps(1.));
+    let n = _mm256_add_epi32(_mm256_cvttps_epi32(fx), _mm256_set1_epi32(127));
+    let pow2n = _mm256_castsi256_ps(_mm256_slli_epi32(n, 23));
+    _mm256_mul_ps(y, pow2n)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn exp_m256(x: __m256) -> (__m256, __m256) {
+    let valid = _mm256_and_ps(
+        _mm256_cmp_ps(x, _mm256_set1_ps(-87.0), _CMP_GE_OQ),
+        _mm256_cmp_ps(x, _mm256_set1_ps(88.0), _CMP_LE_OQ),
+    );
+    (exp_core(x), valid)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn ln_m256(x: __m256) -> (__m256, __m256) {
+    let valid = _mm256_and_ps(
+        _mm256_cmp_ps(x, _mm256_set1_ps(f32::MIN_POSITIVE), _CMP_GE_OQ),
+        _mm256_cmp_ps(x, _mm256_set1_ps(f32::MAX), _CMP_LE_OQ),
+    );
+    let xi = _mm256_castps_si256(x);
+    let emm0 = _mm256_sub_epi32(_mm256_srli_epi32(xi, 23), _mm256_set1_epi32(127));
+    let mut e = _mm256_add_ps(_mm256_cvtepi32_ps(emm0), _mm256_set1_ps(1.));
+    let mant = _mm256_or_si256(
+        _mm256_and_si256(xi, _mm256_set1_epi32(0x007f_ffff)),
+        _mm256_set1_epi32(0x3f00_0000),
+    );
+    let mut x = _mm256_castsi256_ps(mant);
+    let mask = _mm256_cmp_ps(
+        x,
+        _mm256_set1_ps(std::f32::consts::FRAC_1_SQRT_2),
+        _CMP_LT_OQ,
+    );
+    let tmp = _mm256_and_ps(x, mask);
+    x = _mm256_sub_ps(x, _mm256_set1_ps(1.));
+    e = _mm256_sub_ps(e, _mm256_and_ps(_mm256_set1_ps(1.), mask));
+    x = _mm256_add_ps(x, tmp);
+    let z = _mm256_mul_ps(x, x);
+    let mut y = _mm256_set1_ps(7.037_683_6e-2);
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(-1.151_461e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(1.167_699_9e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(-1.242_014_1e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(1.424_932_3e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(-1.666_805_8e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(2.000_071_5e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(-2.499_999_4e-1));
+    y = _mm256_fmadd_ps(y, x, _mm256_set1_ps(3.333_333e-1));
+    y = _mm256_mul_ps(_mm256_mul_ps(y, x), z);
+    y = _mm256_fmadd_ps(e, _mm256_set1_ps(-2.121_944_4e-4), y);
+    y = _mm256_fnmadd_ps(z, _mm256_set1_ps(0.5), y);
+    x = _mm256_add_ps(x, y);
+    x = _mm256_fmadd_ps(e, _mm256_set1_ps(0.693_359_4), x);
+    (x, valid)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn tanh_m256(x: __m256) -> (__m256, __m256) {
+    let valid = _mm256_cmp_ps(x, x, _CMP_ORD_Q);
+    let sign = _mm256_and_ps(x, _mm256_set1_ps(-0.));
+    // tanh(10) is 1 in single precision.
+    let z = _mm256_min_ps(
+        _mm256_andnot_ps(_mm256_set1_ps(-0.), x),
+        _mm256_set1_ps(10.),
+    );
+    // |x| >= 0.625
+    let s = exp_core(_mm256_add_ps(z, z));
+    let large = _mm256_sub_ps(
+        _mm256_set1_ps(1.),
+        _mm256_div_ps(_mm256_set1_ps(2.), _mm256_add_ps(s, _mm256_set1_ps(1.))),
+    );
+    let large = _mm256_or_ps(large, sign);
+    // |x| < 0.625
+    let s = _mm256_mul_ps(x, x);
+    let mut y = _mm256_set1_ps(-5.704_988_7e-3);
+    y = _mm256_fmadd_ps(y, s, _mm256_set1_ps(2.063_909e-2));
+    y = _mm256_fmadd_ps(y, s, _mm256_set1_ps(-5.373_971_6e-2));
+    y = _mm256_fmadd_ps(y, s, _mm256_set1_ps(1.333_144_2e-1));
+    y = _mm256_fmadd_ps(y, s, _mm256_set1_ps(-3.333_328e-1));
+    let small = _mm256_fmadd_ps(_mm256_mul_ps(y, s), x, x);
+    let is_small = _mm256_cmp_ps(z, _mm256_set1_ps(0.625), _CMP_LT_OQ);
+    (_mm256_blendv_ps(large, small, is_small), valid)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn sigmoid_m256(x: __m256) -> (__m256, __m256) {
+    let half = _mm256_set1_ps(0.5);
+    let (t, valid) = tanh_m256(_mm256_mul_ps(half, x));
+    (_mm256_fmadd_ps(half, t, half), valid)
+}
+
+macro_rules! define_simd_map {
+    ( $name:ident , $vop:ident , $op:expr ) => {
+        #[cfg(target_arch = "x86_64")]
+        #[target_feature(enable = "avx2,fma")]
+        pub unsafe fn $name(px: *const f32, py: *mut f32, size: usize) {
+            let mut i = 0;
+            while i + 8 <= size {
+                let (y, valid) = $vop(_mm256_loadu_ps(px.add(i)));
+                _mm256_storeu_ps(py.add(i), y);
+                if _mm256_movemask_ps(valid) != 0xff {
+                    for j in i..i + 8 {
+                        *py.add(j) = $op(*px.add(j));
+                    }
+                }
+                i += 8;
+            }
+            for j in i..size {
+                *py.add(j) = $op(*px.add(j));
+            }
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        pub unsafe fn $name(_px: *const f32, _py: *mut f32, _size: usize) {
+            unreachable!();
+        }
+    };
+}
+
+define_simd_map!(exp, exp_m256, |x: f32| x.exp());
+define_simd_map!(ln, ln_m256, |x: f32| x.ln());
+define_simd_map!(tanh, tanh_m256, |x: f32| x.tanh());
+define_simd_map!(sigmoid, sigmoid_m256, |x: f32| 0.5 + 0.5 * (0.5 * x).tanh());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_map(f: unsafe fn(*const f32, *mut f32, usize), op: fn(f32) -> f32, xs: &[f32]) {
+        let mut ys = vec![0.; xs.len()];
+        unsafe {
+            f(xs.as_ptr(), ys.as_mut_ptr(), xs.len());
+        }
+        for (x, y) in xs.iter().zip(&ys) {
+            let expected = op(*x);
+            if expected.is_nan() {
+                assert!(y.is_nan());
+            } else {
+                assert!(
+                    approx::relative_eq!(expected, *y, max_relative = 4. * std::f32::EPSILON),
+                    "x = {}, expected = {}, actual = {}",
+                    x,
+                    expected,
+                    y
+                );
+            }
+        }
+    }
+
+    fn inputs(lower: f32, upper: f32) -> Vec<f32> {
+        let n = 10007;
+        let mut xs: Vec<f32> = (0..n)
+            .map(|i| lower + (upper - lower) * i as f32 / (n - 1) as f32)
+            .collect();
+        xs.extend(&[
+            0.,
+            -0.,
+            std::f32::NAN,
+            std::f32::INFINITY,
+            -std::f32::INFINITY,
+        ]);
+        xs
+    }
+
+    #[test]
+    fn check_simd_exp() {
+        if enabled() {
+            check_map(exp, |x| x.exp(), &inputs(-100., 100.));
+        }
+    }
+
+    #[test]
+    fn check_simd_ln() {
+        if enabled() {
+            let mut xs = inputs(-1., 1000.);
+            xs.extend((0..1000).map(|i| 1. + (i as f32 - 500.) * 1e-6));
+            xs.extend(&[1e-40, 1e-30, 1e30, std::f32::MAX]);
+            check_map(ln, |x| x.ln(), &xs);
+        }
+    }
+
+    #[test]
+    fn check_simd_tanh() {
+        if enabled() {
+            check_map(tanh, |x| x.tanh(), &inputs(-20., 20.));
+            check_map(tanh, |x| x.tanh(), &inputs(-1., 1.));
+        }
+    }
+
+    #[test]
+    fn check_simd_sigmoid() {
+        if enabled() {
+            check_map(
+                sigmoid,
+                |x| 0.5 + 0.5 * (0.5 * x).tanh(),
+                &inputs(-20., 20.),
+            );
+        }
+    }
+
+    #[test]
+    fn check_simd_sgemm() {
+        if !enabled() {
+            return;
+        }
+        for &(m, n, k) in &[
+            (1, 1, 1),
+            (16, 6, 3),
+            (17, 7, 5),
+            (100, 33, 300),
+            (3, 200, 9),
+        ] {
+            let a: Vec<f32> = (0..m * k).map(|i| ((i * 37) % 17) as f32 - 8.).collect();
+            let b: Vec<f32> = (0..k * n).map(|i| ((i * 11) % 13) as f32 - 6.).collect();
+            let mut c = vec![1.; m * n];
+            unsafe {
+                sgemm(m, n, k, a.as_ptr(), m, b.as_ptr(), k, c.as_mut_ptr(), m);
+            }
+            for i in 0..m {
+                for j in 0..n {
+                    let mut expected = 1.;
+                    for p in 0..k {
+                        expected += a[i + p * m] * b[p + j * k];
+                    }
+                    assert_eq!(expected, c[i + j * m]);
+                }
+            }
+        }
+    }
+}
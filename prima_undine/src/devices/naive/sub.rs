@@ -1,65 +1,9 @@
-use crate::device_impl::FunctionBwImpl;
 use crate::Tensor;
 
 define_naive_fw_ab_impl!(SubFwImpl, |a: f32, b: f32| { a - b });
 
-define_empty_impl!(SubBwAImpl);
-impl FunctionBwImpl for SubBwAImpl {
-    fn call(
-        &self,
-        _xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let gy = gys[0];
-        let ga = gx;
-        let volume = gy.shape.volume() as usize;
-        let a_shift = if ga.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pgy = const_ptr!(gy);
-            let mut pga = mut_ptr!(ga);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pga.add(i) += *pgy.add(i);
-                }
-                pgy = pgy.add(volume);
-                pga = pga.add(a_shift);
-            }
-        }
-    }
-}
-
-define_empty_impl!(SubBwBImpl);
-impl FunctionBwImpl for SubBwBImpl {
-    fn call(
-        &self,
-        _xs: &[&Tensor],
-        _ys: &[&Tensor],
-        gys: &[&Tensor],
-        _u32data: &[u32],
-        _f32data: &[f32],
-        gx: &mut Tensor,
-    ) {
-        let gy = gys[0];
-        let gb = gx;
-        let volume = gy.shape.volume() as usize;
-        let b_shift = if gb.shape.batch() == 1 { 0 } else { volume };
-        unsafe {
-            let mut pgy = const_ptr!(gy);
-            let mut pgb = mut_ptr!(gb);
-            for _ in 0..gy.shape.batch() {
-                for i in 0..volume {
-                    *pgb.add(i) -= *pgy.add(i);
-                }
-                pgy = pgy.add(volume);
-                pgb = pgb.add(b_shift);
-            }
-        }
-    }
-}
+define_naive_bw_ab_impl!(SubBwAImpl, |_a: f32, _b: f32, _y: f32, gy: f32| { gy });
+define_naive_bw_ab_impl!(SubBwBImpl, |_a: f32, _b: f32, _y: f32, gy: f32| { -gy });
 
 define_naive_fw_const_impl!(SubConstLFwImpl, |x: f32, k: f32| { k - x });
 define_naive_bw_const_impl!(SubConstLBwImpl, |_x: f32, _y: f32, gy: f32, _k: f32| {
@@ -1,4 +1,4 @@
-define_naive_fw_x_impl!(TanhFwImpl, |x: f32| x.tanh());
+define_naive_fw_x_impl!(TanhFwImpl, |x: f32| x.tanh(), simd::tanh);
 define_naive_bw_x_impl!(TanhBwImpl, |_x: f32, y: f32, gy: f32| gy * (1. - y * y));
 
 #[cfg(test)]
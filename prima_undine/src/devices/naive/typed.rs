@@ -0,0 +1,992 @@
+// Kernels for tensors whose dtype is not f32.
+//
+// Non-f32 tensors are stored in the same Vec<f32> buffers as f32 tensors, so
+// f64 values are not guaranteed to be aligned and are accessed through
+// unaligned reads and writes. The loops are distributed over the thread pool
+// of the device as those of the f32 kernels.
+
+use std::cmp;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul};
+use std::sync::Arc;
+
+use half::{bf16, f16};
+use rayon::ThreadPool;
+
+use super::common::{parallel_for, Shared, ELEMENTWISE_GRAIN};
+use crate::device_impl::{
+    FunctionBwImpl, FunctionFwImpl, FunctionReadBytesImpl, FunctionWriteBytesImpl,
+};
+use crate::{DType, Element, Tensor};
+
+macro_rules! typed_const_ptr {
+    ( $tensor:expr, $t:ty ) => {
+        const_ptr!($tensor) as *const $t
+    };
+}
+
+macro_rules! typed_mut_ptr {
+    ( $tensor:expr, $t:ty ) => {
+        mut_ptr!($tensor) as *mut $t
+    };
+}
+
+pub trait Numeric: Element + Add<Output = Self> + Mul<Output = Self> {}
+
+impl<T: Element + Add<Output = T> + Mul<Output = T>> Numeric for T {}
+
+// transfer
+
+define_empty_impl!(ResetTensorByBytesImpl);
+impl FunctionWriteBytesImpl for ResetTensorByBytesImpl {
+    fn call(&self, data: &[u8], y: &mut Tensor) {
+        assert!(data.len() == (y.shape.size() * y.dtype().size()) as usize);
+        unsafe {
+            let py = mut_ptr!(y) as *mut u8;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), py, data.len());
+        }
+    }
+}
+
+define_empty_impl!(ResetTensorByTensorImpl);
+impl FunctionFwImpl for ResetTensorByTensorImpl {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let y = &mut ys[0];
+        let words = y.storage_size() as usize;
+        unsafe {
+            let px = const_ptr!(x);
+            let py = mut_ptr!(y);
+            std::ptr::copy(px, py, words);
+        }
+    }
+}
+
+define_empty_impl!(TensorToBytesImpl);
+impl FunctionReadBytesImpl for TensorToBytesImpl {
+    fn call(&self, x: &Tensor, data: &mut [u8]) {
+        assert!(data.len() == (x.shape.size() * x.dtype().size()) as usize);
+        unsafe {
+            let px = const_ptr!(x) as *const u8;
+            std::ptr::copy_nonoverlapping(px, data.as_mut_ptr(), data.len());
+        }
+    }
+}
+
+pub struct ResetTensorImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Element> ResetTensorImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> ResetTensorImpl<T> {
+        ResetTensorImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Element> FunctionFwImpl for ResetTensorImpl<T> {
+    fn call(&self, _xs: &[&Tensor], _u32data: &[u32], f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let k = T::from_f64(f32data[0] as f64);
+        let y = &mut ys[0];
+        let size = y.shape.size() as usize;
+        unsafe {
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    py.0.add(i).write_unaligned(k);
+                }
+            });
+        }
+    }
+}
+
+unsafe fn cast<S: Element, T: Element>(pool: &Option<Arc<ThreadPool>>, x: &Tensor, y: &mut Tensor) {
+    let size = y.shape.size() as usize;
+    let px = Shared(typed_const_ptr!(x, S));
+    let py = Shared(typed_mut_ptr!(y, T));
+    parallel_for(pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+        for i in begin..end {
+            py.0.add(i)
+                .write_unaligned(T::from_f64(px.0.add(i).read_unaligned().to_f64()));
+        }
+    });
+}
+
+pub struct CastImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Element> CastImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> CastImpl<T> {
+        CastImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Element> FunctionFwImpl for CastImpl<T> {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let y = &mut ys[0];
+        let pool = &self.pool;
+        unsafe {
+            match x.dtype {
+                DType::F32 => cast::<f32, T>(pool, x, y),
+                DType::F64 => cast::<f64, T>(pool, x, y),
+                DType::F16 => cast::<f16, T>(pool, x, y),
+                DType::BF16 => cast::<bf16, T>(pool, x, y),
+                DType::I32 => cast::<i32, T>(pool, x, y),
+                DType::U32 => cast::<u32, T>(pool, x, y),
+                DType::U8 => cast::<u8, T>(pool, x, y),
+            }
+        }
+    }
+}
+
+// element-wise
+
+pub struct FwXImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    op: fn(T) -> T,
+}
+
+impl<T: Element> FwXImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, op: fn(T) -> T) -> FwXImpl<T> {
+        FwXImpl { pool, op }
+    }
+}
+
+impl<T: Element> FunctionFwImpl for FwXImpl<T> {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let y = &mut ys[0];
+        let size = y.shape.size() as usize;
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    py.0.add(i)
+                        .write_unaligned((self.op)(px.0.add(i).read_unaligned()));
+                }
+            });
+        }
+    }
+}
+
+pub struct BwXImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    op: fn(T, T, T) -> T,
+}
+
+impl<T: Element> BwXImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, op: fn(T, T, T) -> T) -> BwXImpl<T> {
+        BwXImpl { pool, op }
+    }
+}
+
+impl<T: Numeric> FunctionBwImpl for BwXImpl<T> {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        ys: &[&Tensor],
+        gys: &[&Tensor],
+        _u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let x = xs[0];
+        let y = ys[0];
+        let gy = gys[0];
+        let size = gy.shape.size() as usize;
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_const_ptr!(y, T));
+            let pgy = Shared(typed_const_ptr!(gy, T));
+            let pgx = Shared(typed_mut_ptr!(gx, T));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    let g = (self.op)(
+                        px.0.add(i).read_unaligned(),
+                        py.0.add(i).read_unaligned(),
+                        pgy.0.add(i).read_unaligned(),
+                    );
+                    pgx.0
+                        .add(i)
+                        .write_unaligned(pgx.0.add(i).read_unaligned() + g);
+                }
+            });
+        }
+    }
+}
+
+pub struct FwConstImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    op: fn(T, T) -> T,
+}
+
+impl<T: Element> FwConstImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, op: fn(T, T) -> T) -> FwConstImpl<T> {
+        FwConstImpl { pool, op }
+    }
+}
+
+impl<T: Element> FunctionFwImpl for FwConstImpl<T> {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let k = T::from_f64(f32data[0] as f64);
+        let y = &mut ys[0];
+        let size = y.shape.size() as usize;
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    py.0.add(i)
+                        .write_unaligned((self.op)(px.0.add(i).read_unaligned(), k));
+                }
+            });
+        }
+    }
+}
+
+// `op(x, y, gy, k)` is added to the gradient.
+pub struct BwConstImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    op: fn(T, T, T, T) -> T,
+}
+
+impl<T: Element> BwConstImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, op: fn(T, T, T, T) -> T) -> BwConstImpl<T> {
+        BwConstImpl { pool, op }
+    }
+}
+
+impl<T: Numeric> FunctionBwImpl for BwConstImpl<T> {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        ys: &[&Tensor],
+        gys: &[&Tensor],
+        _u32data: &[u32],
+        f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let x = xs[0];
+        let y = ys[0];
+        let gy = gys[0];
+        let k = T::from_f64(f32data[0] as f64);
+        let size = gy.shape.size() as usize;
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_const_ptr!(y, T));
+            let pgy = Shared(typed_const_ptr!(gy, T));
+            let pgx = Shared(typed_mut_ptr!(gx, T));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for i in begin..end {
+                    let g = (self.op)(
+                        px.0.add(i).read_unaligned(),
+                        py.0.add(i).read_unaligned(),
+                        pgy.0.add(i).read_unaligned(),
+                        k,
+                    );
+                    pgx.0
+                        .add(i)
+                        .write_unaligned(pgx.0.add(i).read_unaligned() + g);
+                }
+            });
+        }
+    }
+}
+
+// Used for both elementwise and scalar operations. The second operand is
+// broadcasted when its batch size is 1, and `scalar` selects whether it has
+// one value per batch instead of one per element.
+pub struct FwAbImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    op: fn(T, T) -> T,
+    scalar: bool,
+}
+
+impl<T: Element> FwAbImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, op: fn(T, T) -> T) -> FwAbImpl<T> {
+        FwAbImpl {
+            pool,
+            op,
+            scalar: false,
+        }
+    }
+
+    pub fn new_scalar(pool: Option<Arc<ThreadPool>>, op: fn(T, T) -> T) -> FwAbImpl<T> {
+        FwAbImpl {
+            pool,
+            op,
+            scalar: true,
+        }
+    }
+}
+
+impl<T: Element> FunctionFwImpl for FwAbImpl<T> {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let a = xs[0];
+        let b = xs[1];
+        let y = &mut ys[0];
+        let volume = y.shape.volume() as usize;
+        let size = y.shape.size() as usize;
+        let a_shift = if a.shape.batch() == 1 { 0 } else { volume };
+        let b_shift = match (b.shape.batch() == 1, self.scalar) {
+            (true, _) => 0,
+            (false, true) => 1,
+            (false, false) => volume,
+        };
+        unsafe {
+            let pa = Shared(typed_const_ptr!(a, T));
+            let pb = Shared(typed_const_ptr!(b, T));
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, size, ELEMENTWISE_GRAIN, |begin, end| {
+                for n in begin..end {
+                    let (batch, i) = (n / volume, n % volume);
+                    let bi = if self.scalar { 0 } else { i };
+                    py.0.add(n).write_unaligned((self.op)(
+                        pa.0.add(batch * a_shift + i).read_unaligned(),
+                        pb.0.add(batch * b_shift + bi).read_unaligned(),
+                    ));
+                }
+            });
+        }
+    }
+}
+
+// Backward of a binary elementwise operation with batch broadcasting.
+// `op(a, b, y, gy)` is added to the gradient, accumulating over the batches
+// in order as the f32 kernels.
+pub struct BwAbImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    op: fn(T, T, T, T) -> T,
+}
+
+impl<T: Element> BwAbImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, op: fn(T, T, T, T) -> T) -> BwAbImpl<T> {
+        BwAbImpl { pool, op }
+    }
+}
+
+impl<T: Numeric> FunctionBwImpl for BwAbImpl<T> {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        ys: &[&Tensor],
+        gys: &[&Tensor],
+        _u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let a = xs[0];
+        let b = xs[1];
+        let y = ys[0];
+        let gy = gys[0];
+        let volume = gy.shape.volume() as usize;
+        let bs = gy.shape.batch() as usize;
+        let a_shift = if a.shape.batch() == 1 { 0 } else { volume };
+        let b_shift = if b.shape.batch() == 1 { 0 } else { volume };
+        let gx_shift = if gx.shape.batch() == 1 { 0 } else { volume };
+        let grain = cmp::max(ELEMENTWISE_GRAIN / bs, 1);
+        unsafe {
+            let pa = Shared(typed_const_ptr!(a, T));
+            let pb = Shared(typed_const_ptr!(b, T));
+            let py = Shared(typed_const_ptr!(y, T));
+            let pgy = Shared(typed_const_ptr!(gy, T));
+            let pgx = Shared(typed_mut_ptr!(gx, T));
+            parallel_for(&self.pool, volume, grain, |begin, end| {
+                for batch in 0..bs {
+                    for i in begin..end {
+                        let g = (self.op)(
+                            pa.0.add(batch * a_shift + i).read_unaligned(),
+                            pb.0.add(batch * b_shift + i).read_unaligned(),
+                            py.0.add(batch * volume + i).read_unaligned(),
+                            pgy.0.add(batch * volume + i).read_unaligned(),
+                        );
+                        let pg = pgx.0.add(batch * gx_shift + i);
+                        pg.write_unaligned(pg.read_unaligned() + g);
+                    }
+                }
+            });
+        }
+    }
+}
+
+// Adds the argument to the tensor, or subtracts it if `negate` is set. The
+// argument is broadcasted if its batch size is 1, and is summed up over the
+// minibatch if the batch size of the tensor is 1.
+pub struct AddAssignImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    negate: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Numeric> AddAssignImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>, negate: bool) -> AddAssignImpl<T> {
+        AddAssignImpl {
+            pool,
+            negate,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Numeric> FunctionFwImpl for AddAssignImpl<T> {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let y = &mut ys[0];
+        let volume = x.shape.volume() as usize;
+        let x_skip = if x.shape.has_batch() { volume } else { 0 };
+        let y_skip = if y.shape.has_batch() { volume } else { 0 };
+        let bs = cmp::max(x.shape.batch(), y.shape.batch()) as usize;
+        let sign = T::from_f64(if self.negate { -1. } else { 1. });
+        let grain = cmp::max(ELEMENTWISE_GRAIN / bs, 1);
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, volume, grain, |begin, end| {
+                for batch in 0..bs {
+                    for i in begin..end {
+                        let x = px.0.add(batch * x_skip + i).read_unaligned();
+                        let py = py.0.add(batch * y_skip + i);
+                        py.write_unaligned(py.read_unaligned() + sign * x);
+                    }
+                }
+            });
+        }
+    }
+}
+
+// reduction and matrix
+
+pub struct SumFwImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Numeric> SumFwImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> SumFwImpl<T> {
+        SumFwImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Numeric> FunctionFwImpl for SumFwImpl<T> {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let dim = u32data[0];
+        let y = &mut ys[0];
+        let n = x.shape[dim] as usize;
+        let repeat = y.shape.size() as usize;
+        let skip1 = y.shape.lower_volume(dim) as usize;
+        let skip2 = skip1 * n;
+        let grain = cmp::max(1, ELEMENTWISE_GRAIN / cmp::max(1, n));
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                for i in begin..end {
+                    let mut offset = i % skip1 + (i / skip1) * skip2;
+                    let mut tmp = T::default();
+                    for _ in 0..n {
+                        tmp = tmp + px.0.add(offset).read_unaligned();
+                        offset += skip1;
+                    }
+                    py.0.add(i).write_unaligned(tmp);
+                }
+            });
+        }
+    }
+}
+
+pub struct BroadcastFwImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Element> BroadcastFwImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> BroadcastFwImpl<T> {
+        BroadcastFwImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Element> FunctionFwImpl for BroadcastFwImpl<T> {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let x = xs[0];
+        let dim = u32data[0];
+        let size = u32data[1] as usize;
+        let y = &mut ys[0];
+        let repeat = x.shape.size() as usize;
+        let skip1 = y.shape.lower_volume(dim) as usize;
+        let skip2 = skip1 * size;
+        let grain = cmp::max(ELEMENTWISE_GRAIN / cmp::max(1, size), 1);
+        unsafe {
+            let px = Shared(typed_const_ptr!(x, T));
+            let py = Shared(typed_mut_ptr!(y, T));
+            parallel_for(&self.pool, repeat, grain, |begin, end| {
+                for i in begin..end {
+                    let mut offset = i % skip1 + (i / skip1) * skip2;
+                    let tmp = px.0.add(i).read_unaligned();
+                    for _ in 0..size {
+                        py.0.add(offset).write_unaligned(tmp);
+                        offset += skip1;
+                    }
+                }
+            });
+        }
+    }
+}
+
+// Adds a * b to y, where a is d1 x d2 and b is d2 x d3 and each of them is
+// transposed if the flag is set. `skip_*` are the strides between batches, 0
+// for broadcasted matrices. Each task calculates whole columns of y, and the
+// batches are accumulated in order.
+unsafe fn matmul_add<T: Numeric>(
+    pool: &Option<Arc<ThreadPool>>,
+    (pa, ta, skip_a): (*const T, bool, usize),
+    (pb, tb, skip_b): (*const T, bool, usize),
+    (py, skip_y): (*mut T, usize),
+    (d1, d2, d3): (usize, usize, usize),
+    bs: usize,
+) {
+    let pa = Shared(pa);
+    let pb = Shared(pb);
+    let py = Shared(py);
+    let grain = cmp::max(1, ELEMENTWISE_GRAIN / cmp::max(1, d1 * d2 * bs));
+    parallel_for(pool, d3, grain, |begin, end| {
+        for batch in 0..bs {
+            let pa = pa.0.add(batch * skip_a);
+            let pb = pb.0.add(batch * skip_b);
+            let py = py.0.add(batch * skip_y);
+            for k in begin..end {
+                for i in 0..d1 {
+                    let mut tmp = py.add(i + k * d1).read_unaligned();
+                    for j in 0..d2 {
+                        let a = if ta {
+                            pa.add(j + i * d2)
+                        } else {
+                            pa.add(i + j * d1)
+                        };
+                        let b = if tb {
+                            pb.add(k + j * d3)
+                        } else {
+                            pb.add(j + k * d2)
+                        };
+                        tmp = tmp + a.read_unaligned() * b.read_unaligned();
+                    }
+                    py.add(i + k * d1).write_unaligned(tmp);
+                }
+            }
+        }
+    });
+}
+
+pub struct MatmulFwImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Numeric> MatmulFwImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> MatmulFwImpl<T> {
+        MatmulFwImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Numeric> FunctionFwImpl for MatmulFwImpl<T> {
+    fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let a = xs[0];
+        let b = xs[1];
+        let y = &mut ys[0];
+        let d1 = a.shape[0] as usize;
+        let d2 = a.shape[1] as usize;
+        let d3 = b.shape[1] as usize;
+        let skip_a = if a.shape.has_batch() { d1 * d2 } else { 0 };
+        let skip_b = if b.shape.has_batch() { d2 * d3 } else { 0 };
+        let bs = y.shape.batch() as usize;
+        unsafe {
+            let py = typed_mut_ptr!(y, T);
+            for i in 0..d1 * d3 * bs {
+                py.add(i).write_unaligned(T::default());
+            }
+            matmul_add(
+                &self.pool,
+                (typed_const_ptr!(a, T), false, skip_a),
+                (typed_const_ptr!(b, T), false, skip_b),
+                (py, d1 * d3),
+                (d1, d2, d3),
+                bs,
+            );
+        }
+    }
+}
+
+pub struct MatmulBwAImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Numeric> MatmulBwAImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> MatmulBwAImpl<T> {
+        MatmulBwAImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Numeric> FunctionBwImpl for MatmulBwAImpl<T> {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        _u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        // ga += gy * b^T
+        let b = xs[1];
+        let gy = gys[0];
+        let d1 = gy.shape[0] as usize;
+        let d2 = gy.shape[1] as usize;
+        let d3 = b.shape[0] as usize;
+        let skip_b = if b.shape.has_batch() { d2 * d3 } else { 0 };
+        let skip_ga = if gx.shape.has_batch() { d1 * d3 } else { 0 };
+        unsafe {
+            matmul_add(
+                &self.pool,
+                (typed_const_ptr!(gy, T), false, d1 * d2),
+                (typed_const_ptr!(b, T), true, skip_b),
+                (typed_mut_ptr!(gx, T), skip_ga),
+                (d1, d2, d3),
+                gy.shape.batch() as usize,
+            );
+        }
+    }
+}
+
+pub struct MatmulBwBImpl<T> {
+    pool: Option<Arc<ThreadPool>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Numeric> MatmulBwBImpl<T> {
+    pub fn new(pool: Option<Arc<ThreadPool>>) -> MatmulBwBImpl<T> {
+        MatmulBwBImpl {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Numeric> FunctionBwImpl for MatmulBwBImpl<T> {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        _ys: &[&Tensor],
+        gys: &[&Tensor],
+        _u32data: &[u32],
+        _f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        // gb += a^T * gy
+        let a = xs[0];
+        let gy = gys[0];
+        let d1 = a.shape[1] as usize;
+        let d2 = a.shape[0] as usize;
+        let d3 = gy.shape[1] as usize;
+        let skip_a = if a.shape.has_batch() { d1 * d2 } else { 0 };
+        let skip_gb = if gx.shape.has_batch() { d1 * d3 } else { 0 };
+        unsafe {
+            matmul_add(
+                &self.pool,
+                (typed_const_ptr!(a, T), true, skip_a),
+                (typed_const_ptr!(gy, T), false, d2 * d3),
+                (typed_mut_ptr!(gx, T), skip_gb),
+                (d1, d2, d3),
+                gy.shape.batch() as usize,
+            );
+        }
+    }
+}
+
+// half precision
+//
+// f16 and bf16 tensors are computed by the f32 kernel with the same name:
+// arguments are widened to f32, and results are rounded back after the f32
+// kernel has accumulated them.
+
+// Tensors of other dtypes, e.g. the ids of pick_by, are passed as they are.
+fn widen<'dev>(x: &Tensor<'dev>) -> Tensor<'dev> {
+    if x.dtype().is_half() {
+        x.device().cast_tensor(x, DType::F32)
+    } else {
+        x.device().copy_tensor(x)
+    }
+}
+
+fn narrow(x: &Tensor, y: &mut Tensor) {
+    y.device()
+        .call_fw_impl("cast_impl", &[x], &[], &[], &mut [y]);
+}
+
+pub struct PromotedFwImpl {
+    name: String,
+}
+
+impl PromotedFwImpl {
+    pub fn new(name: &str) -> PromotedFwImpl {
+        PromotedFwImpl {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl FunctionFwImpl for PromotedFwImpl {
+    fn call(&self, xs: &[&Tensor], u32data: &[u32], f32data: &[f32], ys: &mut [&mut Tensor]) {
+        let device = ys[0].device();
+        let xs32 = xs.iter().map(|x| widen(x)).collect::<Vec<Tensor>>();
+        let mut ys32 = ys.iter().map(|y| widen(y)).collect::<Vec<Tensor>>();
+        device.call_fw_impl(
+            &self.name,
+            &xs32.iter().collect::<Vec<&Tensor>>(),
+            u32data,
+            f32data,
+            &mut ys32.iter_mut().collect::<Vec<&mut Tensor>>(),
+        );
+        for (y32, y) in ys32.iter().zip(ys.iter_mut()) {
+            narrow(y32, y);
+        }
+    }
+}
+
+pub struct PromotedBwImpl {
+    name: String,
+}
+
+impl PromotedBwImpl {
+    pub fn new(name: &str) -> PromotedBwImpl {
+        PromotedBwImpl {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl FunctionBwImpl for PromotedBwImpl {
+    fn call(
+        &self,
+        xs: &[&Tensor],
+        ys: &[&Tensor],
+        gys: &[&Tensor],
+        u32data: &[u32],
+        f32data: &[f32],
+        gx: &mut Tensor,
+    ) {
+        let device = gx.device();
+        let xs32 = xs.iter().map(|x| widen(x)).collect::<Vec<Tensor>>();
+        let ys32 = ys.iter().map(|y| widen(y)).collect::<Vec<Tensor>>();
+        let gys32 = gys.iter().map(|gy| widen(gy)).collect::<Vec<Tensor>>();
+        let mut gx32 = widen(gx);
+        device.call_bw_impl(
+            &self.name,
+            &xs32.iter().collect::<Vec<&Tensor>>(),
+            &ys32.iter().collect::<Vec<&Tensor>>(),
+            &gys32.iter().collect::<Vec<&Tensor>>(),
+            u32data,
+            f32data,
+            &mut gx32,
+        );
+        narrow(&gx32, gx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::{DType, Node};
+
+    #[test]
+    fn check_data_roundtrip() {
+        let dev = D::Naive::new();
+        let f64_data = vec![1e-300, 0.1, -2.5, 1e300, 3., 7.];
+        let x = dev.new_tensor_by_data(shape![3; 2], &f64_data);
+        assert_eq!(DType::F64, x.dtype());
+        assert_eq!(f64_data, dev.tensor_to_data::<f64>(&x));
+        let i32_data = vec![-3, 0, 7, i32::MAX, i32::MIN];
+        let x = dev.new_tensor_by_data(shape![5], &i32_data);
+        assert_eq!(i32_data, dev.tensor_to_data::<i32>(&x));
+        let u8_data = vec![1, 0, 0, 1, 1];
+        let x = dev.new_tensor_by_data(shape![5], &u8_data);
+        assert_eq!(u8_data, dev.tensor_to_data::<u8>(&x));
+        let y = dev.copy_tensor(&x);
+        assert_eq!(u8_data, dev.tensor_to_data::<u8>(&y));
+    }
+
+    #[test]
+    fn check_cast() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![4], &[1.5, -2., 3., 0.25]);
+        let y = dev.cast_tensor(&x, DType::F64);
+        assert_eq!(vec![1.5, -2., 3., 0.25], dev.tensor_to_data::<f64>(&y));
+        let z = dev.cast_tensor(&y, DType::I32);
+        assert_eq!(vec![1, -2, 3, 0], dev.tensor_to_data::<i32>(&z));
+        assert_eq!(vec![1., -2., 3., 0.], z.to_vec());
+    }
+
+    #[test]
+    fn check_f64_functions() {
+        let dev = D::Naive::new();
+        let a = dev.new_tensor_by_data(shape![2, 2], &[1f64, 2., 3., 4.]);
+        let b = dev.new_tensor_by_data(shape![2, 2; 2], &[1e-10f64, 0., 0., 1., 2., 0., 0., 2.]);
+        let y = (&a + &b).exp().ln();
+        assert_eq!(DType::F64, y.dtype());
+        let expected = [1. + 1e-10, 2., 3., 5., 3., 2., 3., 6.];
+        for (e, v) in expected.iter().zip(dev.tensor_to_data::<f64>(&y)) {
+            assert!((e - v).abs() < 1e-13);
+        }
+        let y = a.matmul(&b);
+        assert_eq!(
+            vec![1e-10, 2e-10, 3., 4., 2., 4., 6., 8.],
+            dev.tensor_to_data::<f64>(&y)
+        );
+        let y = b.sum(0);
+        assert_eq!(vec![1e-10, 1., 2., 2.], dev.tensor_to_data::<f64>(&y));
+    }
+
+    // Compares the gradients of f for f64 arguments with those for f32.
+    fn check_f64_gradients<F>(f: F)
+    where
+        F: for<'arg, 'dev> Fn(&Node<'arg, 'dev>, &Node<'arg, 'dev>) -> Node<'arg, 'dev>,
+    {
+        let dev = D::Naive::new();
+        let a_data = [0.5, 1.5, 2., 0.25];
+        let b_data = [1.5, 0.5, 0.75, 2., 1., 3., 2., 0.25];
+        let mut grads = vec![];
+        for &dtype in &[DType::F32, DType::F64] {
+            let a = dev.new_tensor_by_slice(shape![2, 2], &a_data).cast(dtype);
+            let b = dev
+                .new_tensor_by_slice(shape![2, 2; 2], &b_data)
+                .cast(dtype);
+            let (a, b) = (Node::from(&a), Node::from(&b));
+            let y = f(&a, &b);
+            y.forward();
+            assert_eq!(dtype, y.inner_value().dtype());
+            grads.push(
+                y.gradients(&[&a, &b])
+                    .iter()
+                    .map(|g| dev.cast_tensor(g, DType::F64))
+                    .map(|g| dev.tensor_to_data::<f64>(&g))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        for (g32, g64) in grads[0].iter().zip(&grads[1]) {
+            for (e, v) in g32.iter().zip(g64) {
+                assert!((e - v).abs() <= 1e-5 * e.abs().max(1.), "{} != {}", e, v);
+            }
+        }
+    }
+
+    #[test]
+    fn check_f64_backward() {
+        check_f64_gradients(|a, b| a + b);
+        check_f64_gradients(|a, b| a - b);
+        check_f64_gradients(|a, b| a * b);
+        check_f64_gradients(|a, b| a / b);
+        check_f64_gradients(|a, b| (a + 2.) * (3. - b) + (b - 1.) * 2.);
+        check_f64_gradients(|a, b| (4. / a + a / 4.) * b);
+        check_f64_gradients(|a, b| (a - 1.).abs() * b.sqrt());
+        check_f64_gradients(|a, b| a.sin() * b.cos() + a.tan());
+        check_f64_gradients(|a, b| a.exp() * b.ln());
+        check_f64_gradients(|a, b| a.tanh() * b.sigmoid());
+        check_f64_gradients(|a, b| a.matmul(b) + b.matmul(a));
+        check_f64_gradients(|a, b| (a * b).sum(1).broadcast(1, 2) * b);
+    }
+
+    #[test]
+    fn check_typed_threads() {
+        let dev1 = D::Naive::new();
+        let dev4 = D::Naive::with_threads(4);
+        let data = (0..40000)
+            .map(|i| (i % 101) as f64 * 0.01 - 0.5)
+            .collect::<Vec<f64>>();
+        let values = [&dev1, &dev4]
+            .iter()
+            .map(|dev| {
+                let x = dev.new_tensor_by_data(shape![200, 200], &data);
+                let y = (x.exp() * 2. + &x).matmul(&x).sum(1);
+                dev.tensor_to_data::<f64>(&y)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values[0], values[1]);
+    }
+
+    #[test]
+    fn check_i32_functions() {
+        let dev = D::Naive::new();
+        let a = dev.new_tensor_by_data(shape![2, 2], &[1i32, 2, 3, 4]);
+        let b = dev.new_tensor_by_data(shape![2, 2], &[5i32, -6, 7, -8]);
+        assert_eq!(vec![6, -4, 10, -4], dev.tensor_to_data::<i32>(&(&a + &b)));
+        assert_eq!(
+            vec![19, -22, 43, -50],
+            dev.tensor_to_data::<i32>(&b.matmul(&a))
+        );
+        assert_eq!(vec![3, 7], dev.tensor_to_data::<i32>(&a.sum(0)));
+    }
+
+    #[test]
+    fn check_half_functions() {
+        let dev = D::Naive::new();
+        for &dtype in &[DType::F16, DType::BF16] {
+            let a = dev
+                .new_tensor_by_slice(shape![2, 2], &[1., 2., 3., 4.])
+                .cast(dtype);
+            let b = dev
+                .new_tensor_by_slice(shape![2, 2; 2], &[1., 0., 0., 1., 0.5, 0.25, -1., 2.])
+                .cast(dtype);
+            let y = a.matmul(&b) + &b;
+            assert_eq!(dtype, y.dtype());
+            assert_eq!(vec![2., 2., 3., 5., 1.75, 2.25, 4., 8.], y.to_vec());
+            assert_eq!(vec![4., 8., 4., 12.], y.sum(0).to_vec());
+            let y = a.exp().ln();
+            for (e, v) in [1., 2., 3., 4.].iter().zip(y.to_vec()) {
+                assert!((e - v).abs() < 0.05 * e);
+            }
+        }
+    }
+
+    #[test]
+    fn check_half_accumulation() {
+        let dev = D::Naive::new();
+        let x = dev
+            .new_tensor_by_constant(shape![4096], 1.)
+            .cast(DType::F16);
+        assert_eq!(2048, x.storage_size());
+        // 2048 + 1 is rounded to 2048 when accumulated in f16.
+        assert_eq!(4096., x.sum(0).to_float());
+    }
+
+    #[test]
+    #[should_panic(expected = "exp_fw_impl:u8 is not implemented")]
+    fn check_not_implemented() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_data(shape![2], &[1u8, 0]);
+        let _ = x.exp();
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use half::{bf16, f16};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DType {
+    #[default]
+    F32,
+    F64,
+    F16,
+    BF16,
+    I32,
+    U32,
+    // also used for boolean masks (0 or 1)
+    U8,
+}
+
+impl DType {
+    pub fn size(&self) -> u32 {
+        match self {
+            DType::F32 | DType::I32 | DType::U32 => 4,
+            DType::F64 => 8,
+            DType::F16 | DType::BF16 => 2,
+            DType::U8 => 1,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DType::F32 => "f32",
+            DType::F64 => "f64",
+            DType::F16 => "f16",
+            DType::BF16 => "bf16",
+            DType::I32 => "i32",
+            DType::U32 => "u32",
+            DType::U8 => "u8",
+        }
+    }
+
+    pub fn is_half(&self) -> bool {
+        *self == DType::F16 || *self == DType::BF16
+    }
+
+    // Number of 32-bit words required to store `size` elements.
+    pub fn words(&self, size: u32) -> u32 {
+        (size * self.size()).div_ceil(4)
+    }
+}
+
+impl fmt::Display for DType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub trait Element: Copy + Default + Send + Sync + 'static {
+    const DTYPE: DType;
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_element {
+    ( $t:ty , $dtype:expr ) => {
+        impl Element for $t {
+            const DTYPE: DType = $dtype;
+            fn from_f64(x: f64) -> $t {
+                x as $t
+            }
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        }
+    };
+}
+
+impl_element!(f32, DType::F32);
+impl_element!(f64, DType::F64);
+impl_element!(i32, DType::I32);
+impl_element!(u32, DType::U32);
+impl_element!(u8, DType::U8);
+
+macro_rules! impl_half_element {
+    ( $t:ty , $dtype:expr ) => {
+        impl Element for $t {
+            const DTYPE: DType = $dtype;
+            fn from_f64(x: f64) -> $t {
+                <$t>::from_f64(x)
+            }
+            fn to_f64(self) -> f64 {
+                <$t>::to_f64(self)
+            }
+        }
+    };
+}
+
+impl_half_element!(f16, DType::F16);
+impl_half_element!(bf16, DType::BF16);
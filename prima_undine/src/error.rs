@@ -0,0 +1,122 @@
+use std::error;
+use std::fmt;
+use std::result;
+
+use crate::{DType, Shape};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    ShapeMismatch {
+        op: String,
+        lhs: Shape,
+        rhs: Shape,
+    },
+    InvalidShape {
+        op: String,
+        shape: Shape,
+    },
+    InvalidArgument {
+        op: String,
+        message: String,
+    },
+    DTypeMismatch {
+        op: String,
+        lhs: DType,
+        rhs: DType,
+    },
+    DeviceMismatch {
+        op: String,
+    },
+    NotImplemented {
+        kernel: String,
+        device: String,
+    },
+    // An error raised while adding a node to a graph, with the chain of
+    // argument nodes (operator name, label and shape) leading to it.
+    Graph {
+        error: Box<Error>,
+        trace: Vec<String>,
+    },
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    // Replaces the operation name, e.g. to report the operator that called
+    // a shape function.
+    pub fn with_op(self, name: &str) -> Error {
+        let op = name.to_string();
+        match self {
+            Error::ShapeMismatch { lhs, rhs, .. } => Error::ShapeMismatch { op, lhs, rhs },
+            Error::InvalidShape { shape, .. } => Error::InvalidShape { op, shape },
+            Error::InvalidArgument { message, .. } => Error::InvalidArgument { op, message },
+            Error::DTypeMismatch { lhs, rhs, .. } => Error::DTypeMismatch { op, lhs, rhs },
+            Error::DeviceMismatch { .. } => Error::DeviceMismatch { op },
+            e @ Error::NotImplemented { .. } => e,
+            Error::Graph { error, trace } => Error::Graph {
+                error: Box::new(error.with_op(name)),
+                trace,
+            },
+        }
+    }
+
+    // Returns the underlying error without the graph context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Graph { error, .. } => error.root(),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ShapeMismatch { op, lhs, rhs } => {
+                write!(f, "{}: shape mismatch: {:?} and {:?}", op, lhs, rhs)
+            }
+            Error::InvalidShape { op, shape } => write!(f, "{}: invalid shape: {:?}", op, shape),
+            Error::InvalidArgument { op, message } => write!(f, "{}: {}", op, message),
+            Error::DTypeMismatch { op, lhs, rhs } => {
+                write!(f, "{}: dtype mismatch: {} and {}", op, lhs, rhs)
+            }
+            Error::DeviceMismatch { op } => write!(f, "{}: arguments are on different devices", op),
+            Error::NotImplemented { kernel, device } => {
+                write!(f, "{} is not implemented on {}", kernel, device)
+            }
+            Error::Graph { error, trace } => {
+                write!(f, "{}", error)?;
+                if !trace.is_empty() {
+                    write!(f, "\narguments:")?;
+                    for line in trace {
+                        write!(f, "\n  {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Graph { error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+// Used by the infallible functions to panic with a readable message.
+pub(crate) trait OrPanic<T> {
+    fn or_panic(self) -> T;
+}
+
+impl<T> OrPanic<T> for Result<T> {
+    fn or_panic(self) -> T {
+        match self {
+            Ok(value) => value,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
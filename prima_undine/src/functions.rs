@@ -3,7 +3,9 @@ macro_rules! define_fw_ab {
         fn $f(&self, a: &Tensor, b: &Tensor) -> Tensor {
             assert!(a.device() == self);
             assert!(b.device() == self);
-            let mut y = self.new_tensor(shape_ops::$sop(a.shape, b.shape));
+            assert!(a.dtype() == b.dtype());
+            let mut y =
+                self.new_tensor_with_dtype(shape_ops::$sop(a.shape, b.shape).or_panic(), a.dtype());
             y.alloc();
             self.call_fw_impl($f_impl, &[a, b], &[], &[], &mut [&mut y]);
             y
@@ -21,7 +23,7 @@ macro_rules! define_bw_ab_a {
             assert!(ga.device() == self);
             assert!(a.shape == ga.shape);
             assert!(y.shape == gy.shape);
-            assert!(y.shape == shape_ops::$sop(a.shape, b.shape));
+            assert!(y.shape == shape_ops::$sop(a.shape, b.shape).or_panic());
             self.call_bw_impl($f_impl, &[a, b], &[y], &[gy], &[], &[], ga);
         }
     };
@@ -37,7 +39,7 @@ macro_rules! define_bw_ab_b {
             assert!(gb.device() == self);
             assert!(b.shape == gb.shape);
             assert!(y.shape == gy.shape);
-            assert!(y.shape == shape_ops::$sop(a.shape, b.shape));
+            assert!(y.shape == shape_ops::$sop(a.shape, b.shape).or_panic());
             self.call_bw_impl($f_impl, &[a, b], &[y], &[gy], &[], &[], gb);
         }
     };
@@ -47,7 +49,7 @@ macro_rules! define_fw_const {
     ( $f:ident , $f_impl:expr ) => {
         fn $f(&self, x: &Tensor, k: f32) -> Tensor {
             assert!(x.device() == self);
-            let mut y = self.new_tensor(x.shape);
+            let mut y = self.new_tensor_with_dtype(x.shape, x.dtype());
             y.alloc();
             self.call_fw_impl($f_impl, &[x], &[], &[k], &mut [&mut y]);
             y
@@ -74,7 +76,7 @@ macro_rules! define_fw_x {
     ( $f:ident , $f_impl:expr ) => {
         fn $f(&self, x: &Tensor) -> Tensor {
             assert!(x.device() == self);
-            let mut y = self.new_tensor(x.shape);
+            let mut y = self.new_tensor_with_dtype(x.shape, x.dtype());
             y.alloc();
             self.call_fw_impl($f_impl, &[x], &[], &[], &mut [&mut y]);
             y
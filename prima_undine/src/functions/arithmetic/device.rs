@@ -1,3 +1,4 @@
+use crate::error::OrPanic;
 use crate::{shape_ops, Device, Tensor};
 
 pub trait ArithmeticDeviceFunctions {
@@ -47,12 +48,14 @@ impl<'dev> ArithmeticDeviceFunctions for Device<'dev> {
     fn add_assign(&self, x: &Tensor, y: &mut Tensor) {
         assert!(x.device() == self);
         assert!(y.device() == self);
+        assert!(x.dtype() == y.dtype());
         self.call_fw_impl("add_assign_impl", &[x], &[], &[], &mut [y]);
     }
 
     fn sub_assign(&self, x: &Tensor, y: &mut Tensor) {
         assert!(x.device() == self);
         assert!(y.device() == self);
+        assert!(x.dtype() == y.dtype());
         self.call_fw_impl("sub_assign_impl", &[x], &[], &[], &mut [y]);
     }
 
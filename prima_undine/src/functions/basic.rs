@@ -4,7 +4,7 @@ mod tensor;
 
 use std::borrow::Borrow;
 
-use crate::Shape;
+use crate::{DType, Result, Shape};
 
 pub trait BasicFunctions
 where
@@ -60,8 +60,8 @@ where
 
     fn matmul<T: Borrow<Self>>(&self, rhs: T) -> Self;
     fn transpose(&self) -> Self;
-    //fn permute_dims(&self, perm: &[u32]) -> Self;
-    //fn flip(&self, dim: u32) -> Self;
+    fn permute_dims(&self, perm: &[u32]) -> Self;
+    fn flip(&self, dim: u32) -> Self;
     fn triangular_l(&self, k: u32) -> Self;
     fn triangular_u(&self, k: u32) -> Self;
 
@@ -77,6 +77,9 @@ where
     fn slice(&self, dim: u32, lower: u32, upper: u32) -> Self;
     fn split(&self, dim: u32, n: u32) -> Vec<Self>;
     fn pick(&self, ids: &[u32], dim: u32) -> Self;
+    // ids is a u32 or i32 tensor (or node) with one id in each batch, which
+    // is read on the device.
+    fn pick_by<T: Borrow<Self>>(&self, ids: T, dim: u32) -> Self;
     fn concat(xs: &[&Self], dim: u32) -> Self;
     fn reshape(&self, shape: Shape) -> Self;
     fn flatten(&self) -> Self;
@@ -87,17 +90,95 @@ where
     fn batch_slice(&self, lower: u32, upper: u32) -> Self;
     fn batch_split(&self, n: u32) -> Vec<Self>;
     fn batch_pick(&self, ids: &[u32]) -> Self;
+    fn batch_pick_by<T: Borrow<Self>>(&self, ids: T) -> Self;
     fn batch_concat(xs: &[&Self]) -> Self;
 
     // others
 
-    //fn reshape(&self, shape: Shape) -> Self;
-    //fn flatten(&self) -> Self;
-
-    //fn stop_gradient(&self, dim: u32) -> Self;
+    fn stop_gradient(&self) -> Self;
+    fn cast(&self, dtype: DType) -> Self;
 
     // convolution
 
-    // fn conv2d<T: Borrow<Self>>(&self, w: T, padding0: u32, padding1: u32, stride0: u32, stride1: u32, dilation0: u32, dilation1: u32) -> Self;
-    // fn max_pool2d<T: Borrow<Self>>(&self, w: T, padding0: u32, padding1: u32, stride0: u32, stride1: u32, dilation0: u32, dilation1: u32) -> Self;
+    #[allow(clippy::too_many_arguments)]
+    fn conv2d<T: Borrow<Self>>(
+        &self,
+        w: T,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Self;
+    fn max_pool2d(
+        &self,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Self;
+
+    // fallible versions that check their arguments before calculation
+
+    fn try_add<T: Borrow<Self>>(&self, rhs: T) -> Result<Self>;
+    fn try_sub<T: Borrow<Self>>(&self, rhs: T) -> Result<Self>;
+    fn try_mul<T: Borrow<Self>>(&self, rhs: T) -> Result<Self>;
+    fn try_div<T: Borrow<Self>>(&self, rhs: T) -> Result<Self>;
+    fn try_pow<T: Borrow<Self>>(&self, k: T) -> Result<Self>;
+    fn try_sqrt(&self) -> Result<Self>;
+    fn try_abs(&self) -> Result<Self>;
+    fn try_sin(&self) -> Result<Self>;
+    fn try_cos(&self) -> Result<Self>;
+    fn try_tan(&self) -> Result<Self>;
+    fn try_exp(&self) -> Result<Self>;
+    fn try_ln(&self) -> Result<Self>;
+    fn try_tanh(&self) -> Result<Self>;
+    fn try_sigmoid(&self) -> Result<Self>;
+    fn try_softplus(&self) -> Result<Self>;
+    fn try_sum(&self, dim: u32) -> Result<Self>;
+    fn try_max(&self, dim: u32) -> Result<Self>;
+    fn try_min(&self, dim: u32) -> Result<Self>;
+    fn try_broadcast(&self, dim: u32, size: u32) -> Result<Self>;
+    fn try_logsumexp(&self, dim: u32) -> Result<Self>;
+    fn try_ln_softmax(&self, dim: u32) -> Result<Self>;
+    fn try_softmax(&self, dim: u32) -> Result<Self>;
+    fn try_softmax_cross_entropy<T: Borrow<Self>>(&self, t: T, dim: u32) -> Result<Self>;
+    fn try_sparse_softmax_cross_entropy(&self, ids: &[u32], dim: u32) -> Result<Self>;
+    fn try_matmul<T: Borrow<Self>>(&self, rhs: T) -> Result<Self>;
+    fn try_transpose(&self) -> Result<Self>;
+    fn try_permute_dims(&self, perm: &[u32]) -> Result<Self>;
+    fn try_slice(&self, dim: u32, lower: u32, upper: u32) -> Result<Self>;
+    fn try_split(&self, dim: u32, n: u32) -> Result<Vec<Self>>;
+    fn try_pick(&self, ids: &[u32], dim: u32) -> Result<Self>;
+    fn try_pick_by<T: Borrow<Self>>(&self, ids: T, dim: u32) -> Result<Self>;
+    fn try_concat(xs: &[&Self], dim: u32) -> Result<Self>;
+    fn try_reshape(&self, shape: Shape) -> Result<Self>;
+    fn try_batch_slice(&self, lower: u32, upper: u32) -> Result<Self>;
+    fn try_batch_split(&self, n: u32) -> Result<Vec<Self>>;
+    fn try_batch_pick(&self, ids: &[u32]) -> Result<Self>;
+    fn try_batch_pick_by<T: Borrow<Self>>(&self, ids: T) -> Result<Self>;
+    fn try_batch_concat(xs: &[&Self]) -> Result<Self>;
+    #[allow(clippy::too_many_arguments)]
+    fn try_conv2d<T: Borrow<Self>>(
+        &self,
+        w: T,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Result<Self>;
+    fn try_max_pool2d(
+        &self,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Result<Self>;
 }
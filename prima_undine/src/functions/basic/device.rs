@@ -1,3 +1,4 @@
+use crate::error::OrPanic;
 use crate::{shape_ops, Device, Tensor};
 
 pub trait BasicDeviceFunctions {
@@ -67,14 +68,16 @@ pub trait BasicDeviceFunctions {
 
     fn matmul_fw(&self, a: &Tensor, b: &Tensor) -> Tensor;
     fn transpose_fw(&self, x: &Tensor) -> Tensor;
-    //fn permute_dims(&self, x: &Tensor, perm: &[u32]) -> Tensor;
-    //fn flip(&self, x: &Tensor, dim: u32) -> Tensor;
+    fn permute_dims_fw(&self, x: &Tensor, perm: &[u32]) -> Tensor<'_>;
+    fn flip_fw(&self, x: &Tensor, dim: u32) -> Tensor<'_>;
     fn triangular_l_fw(&self, x: &Tensor, k: u32) -> Tensor;
     fn triangular_u_fw(&self, x: &Tensor, k: u32) -> Tensor;
 
     fn matmul_bw_a(&self, a: &Tensor, b: &Tensor, y: &Tensor, gy: &Tensor, ga: &mut Tensor);
     fn matmul_bw_b(&self, a: &Tensor, b: &Tensor, y: &Tensor, gy: &Tensor, gb: &mut Tensor);
     fn transpose_bw(&self, x: &Tensor, y: &Tensor, gy: &Tensor, gx: &mut Tensor);
+    fn permute_dims_bw(&self, gy: &Tensor, perm: &[u32], gx: &mut Tensor);
+    fn flip_bw(&self, gy: &Tensor, dim: u32, gx: &mut Tensor);
     fn triangular_l_bw(&self, x: &Tensor, y: &Tensor, gy: &Tensor, k: u32, gx: &mut Tensor);
     fn triangular_u_bw(&self, x: &Tensor, y: &Tensor, gy: &Tensor, k: u32, gx: &mut Tensor);
 
@@ -95,6 +98,10 @@ pub trait BasicDeviceFunctions {
     fn slice_bw(&self, gy: &Tensor, dim: u32, lower: u32, gx: &mut Tensor);
     fn pick_bw(&self, gy: &Tensor, ids: &[u32], dim: u32, gx: &mut Tensor);
 
+    // The ids are a u32 or i32 tensor with one id in each batch.
+    fn pick_by_fw(&self, x: &Tensor, ids: &Tensor, dim: u32) -> Tensor<'_>;
+    fn pick_by_bw(&self, gy: &Tensor, ids: &Tensor, dim: u32, gx: &mut Tensor);
+
     // batch
 
     fn batch_sum_fw(&self, x: &Tensor) -> Tensor;
@@ -104,6 +111,98 @@ pub trait BasicDeviceFunctions {
 
     fn batch_slice_bw(&self, gy: &Tensor, lower: u32, gx: &mut Tensor);
     fn batch_pick_bw(&self, gy: &Tensor, ids: &[u32], gx: &mut Tensor);
+
+    fn batch_pick_by_fw(&self, x: &Tensor, ids: &Tensor) -> Tensor<'_>;
+    fn batch_pick_by_bw(&self, gy: &Tensor, ids: &Tensor, gx: &mut Tensor);
+
+    // convolution
+
+    #[allow(clippy::too_many_arguments)]
+    fn conv2d_fw(
+        &self,
+        x: &Tensor,
+        w: &Tensor,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Tensor<'_>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn conv2d_bw_x(
+        &self,
+        x: &Tensor,
+        w: &Tensor,
+        y: &Tensor,
+        gy: &Tensor,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+        gx: &mut Tensor,
+    );
+
+    #[allow(clippy::too_many_arguments)]
+    fn conv2d_bw_w(
+        &self,
+        x: &Tensor,
+        w: &Tensor,
+        y: &Tensor,
+        gy: &Tensor,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+        gw: &mut Tensor,
+    );
+
+    #[allow(clippy::too_many_arguments)]
+    fn max_pool2d_fw(
+        &self,
+        x: &Tensor,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Tensor<'_>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn max_pool2d_bw(
+        &self,
+        x: &Tensor,
+        y: &Tensor,
+        gy: &Tensor,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        gx: &mut Tensor,
+    );
+
+    // Picks the elements of z at the maxima of the windows of x. This is the
+    // transpose of max_pool2d_bw with respect to gy.
+    #[allow(clippy::too_many_arguments)]
+    fn max_pool2d_pick_fw(
+        &self,
+        x: &Tensor,
+        z: &Tensor,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Tensor<'_>;
 }
 
 impl<'dev> BasicDeviceFunctions for Device<'dev> {
@@ -140,7 +239,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn powi_fw(&self, x: &Tensor, k: i32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(x.shape);
+        let mut y = self.new_tensor_with_dtype(x.shape, x.dtype());
         y.alloc();
         self.call_fw_impl("powi_fw_impl", &[x], &[k as u32], &[], &mut [&mut y]);
         y
@@ -195,7 +294,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn sum_fw(&self, x: &Tensor, dim: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(x.shape.resize_dim(dim, 1));
+        let mut y = self.new_tensor_with_dtype(x.shape.resize_dim(dim, 1), x.dtype());
         y.alloc();
         self.call_fw_impl("sum_fw_impl", &[x], &[dim], &[], &mut [&mut y]);
         y
@@ -203,7 +302,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn max_fw(&self, x: &Tensor, dim: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(x.shape.resize_dim(dim, 1));
+        let mut y = self.new_tensor_with_dtype(x.shape.resize_dim(dim, 1), x.dtype());
         y.alloc();
         self.call_fw_impl("max_fw_impl", &[x], &[dim], &[], &mut [&mut y]);
         y
@@ -211,7 +310,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn min_fw(&self, x: &Tensor, dim: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(x.shape.resize_dim(dim, 1));
+        let mut y = self.new_tensor_with_dtype(x.shape.resize_dim(dim, 1), x.dtype());
         y.alloc();
         self.call_fw_impl("min_fw_impl", &[x], &[dim], &[], &mut [&mut y]);
         y
@@ -219,7 +318,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn logsumexp_fw(&self, x: &Tensor, dim: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(x.shape.resize_dim(dim, 1));
+        let mut y = self.new_tensor_with_dtype(x.shape.resize_dim(dim, 1), x.dtype());
         y.alloc();
         self.call_fw_impl("logsumexp_fw_impl", &[x], &[dim], &[], &mut [&mut y]);
         y
@@ -227,7 +326,10 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn broadcast_fw(&self, x: &Tensor, dim: u32, size: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(shape_ops::broadcast(x.shape, dim, size));
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::broadcast(x.shape, dim, size).or_panic(),
+            x.dtype(),
+        );
         y.alloc();
         self.call_fw_impl("broadcast_fw_impl", &[x], &[dim, size], &[], &mut [&mut y]);
         y
@@ -260,7 +362,8 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
     fn matmul_fw(&self, a: &Tensor, b: &Tensor) -> Tensor {
         assert!(a.device() == self);
         assert!(b.device() == self);
-        let mut y = self.new_tensor(shape_ops::matmul(a.shape, b.shape));
+        let mut y =
+            self.new_tensor_with_dtype(shape_ops::matmul(a.shape, b.shape).or_panic(), a.dtype());
         y.alloc();
         self.call_fw_impl("matmul_fw_impl", &[a, b], &[], &[], &mut [&mut y]);
         y
@@ -268,17 +371,34 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn transpose_fw(&self, x: &Tensor) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(shape_ops::transpose(x.shape));
+        let mut y = self.new_tensor_with_dtype(shape_ops::transpose(x.shape).or_panic(), x.dtype());
         y.alloc();
         self.call_fw_impl("transpose_fw_impl", &[x], &[], &[], &mut [&mut y]);
         y
     }
 
+    fn permute_dims_fw(&self, x: &Tensor, perm: &[u32]) -> Tensor<'_> {
+        assert!(x.device() == self);
+        let mut y = self
+            .new_tensor_with_dtype(shape_ops::permute_dims(x.shape, perm).or_panic(), x.dtype());
+        y.alloc();
+        self.call_fw_impl("permute_dims_fw_impl", &[x], perm, &[], &mut [&mut y]);
+        y
+    }
+
+    fn flip_fw(&self, x: &Tensor, dim: u32) -> Tensor<'_> {
+        assert!(x.device() == self);
+        let mut y = self.new_tensor_with_dtype(x.shape, x.dtype());
+        y.alloc();
+        self.call_fw_impl("flip_fw_impl", &[x], &[dim], &[], &mut [&mut y]);
+        y
+    }
+
     fn triangular_l_fw(&self, x: &Tensor, k: u32) -> Tensor {
         assert!(x.device() == self);
         let xs = x.shape;
         assert!(xs.is_matrix() && xs[0] == xs[1]);
-        let mut y = self.new_tensor(xs);
+        let mut y = self.new_tensor_with_dtype(xs, x.dtype());
         y.alloc();
         self.call_fw_impl("triangular_l_fw_impl", &[x], &[k], &[], &mut [&mut y]);
         y
@@ -287,7 +407,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
     fn triangular_u_fw(&self, x: &Tensor, k: u32) -> Tensor {
         assert!(x.device() == self);
         assert!(x.shape.is_matrix() && x.shape[0] == x.shape[1]);
-        let mut y = self.new_tensor(x.shape);
+        let mut y = self.new_tensor_with_dtype(x.shape, x.dtype());
         y.alloc();
         self.call_fw_impl("triangular_u_fw_impl", &[x], &[k], &[], &mut [&mut y]);
         y
@@ -301,7 +421,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
         assert!(ga.device() == self);
         assert!(a.shape == ga.shape);
         assert!(y.shape == gy.shape);
-        assert!(y.shape == shape_ops::matmul(a.shape, b.shape));
+        assert!(y.shape == shape_ops::matmul(a.shape, b.shape).or_panic());
         self.call_bw_impl("matmul_bw_a_impl", &[a, b], &[y], &[gy], &[], &[], ga);
     }
 
@@ -313,7 +433,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
         assert!(gb.device() == self);
         assert!(b.shape == gb.shape);
         assert!(y.shape == gy.shape);
-        assert!(y.shape == shape_ops::matmul(a.shape, b.shape));
+        assert!(y.shape == shape_ops::matmul(a.shape, b.shape).or_panic());
         self.call_bw_impl("matmul_bw_b_impl", &[a, b], &[y], &[gy], &[], &[], gb);
     }
 
@@ -324,10 +444,24 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
         assert!(gx.device() == self);
         assert!(x.shape == gx.shape);
         assert!(y.shape == gy.shape);
-        assert!(y.shape == shape_ops::transpose(x.shape));
+        assert!(y.shape == shape_ops::transpose(x.shape).or_panic());
         self.call_bw_impl("transpose_bw_impl", &[x], &[y], &[gy], &[], &[], gx);
     }
 
+    fn permute_dims_bw(&self, gy: &Tensor, perm: &[u32], gx: &mut Tensor) {
+        assert!(gy.device() == self);
+        assert!(gx.device() == self);
+        assert!(shape_ops::permute_dims(gx.shape, perm).or_panic() == gy.shape);
+        self.call_bw_impl("permute_dims_bw_impl", &[], &[], &[gy], perm, &[], gx);
+    }
+
+    fn flip_bw(&self, gy: &Tensor, dim: u32, gx: &mut Tensor) {
+        assert!(gy.device() == self);
+        assert!(gx.device() == self);
+        assert!(gx.shape == gy.shape);
+        self.call_bw_impl("flip_bw_impl", &[], &[], &[gy], &[dim], &[], gx);
+    }
+
     fn triangular_l_bw(&self, x: &Tensor, y: &Tensor, gy: &Tensor, k: u32, gx: &mut Tensor) {
         assert!(x.device() == self);
         assert!(y.device() == self);
@@ -362,7 +496,10 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn slice_fw(&self, x: &Tensor, dim: u32, lower: u32, upper: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(shape_ops::slice(x.shape, dim, lower, upper));
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::slice(x.shape, dim, lower, upper).or_panic(),
+            x.dtype(),
+        );
         y.alloc();
         self.call_fw_impl("slice_fw_impl", &[x], &[dim, lower], &[], &mut [&mut y]);
         y
@@ -373,7 +510,8 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
         let mut u32data = vec![0; ids.len() + 1];
         u32data[0] = dim;
         u32data[1..].clone_from_slice(ids);
-        let mut y = self.new_tensor(shape_ops::pick(x.shape, ids, dim));
+        let mut y =
+            self.new_tensor_with_dtype(shape_ops::pick(x.shape, ids, dim).or_panic(), x.dtype());
         y.alloc();
         self.call_fw_impl("pick_fw_impl", &[x], &u32data, &[], &mut [&mut y]);
         y
@@ -386,7 +524,8 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
             assert!(x.device() == self);
             shapes.push(x.shape);
         }
-        let mut y = self.new_tensor(shape_ops::concat(&shapes, dim));
+        let mut y =
+            self.new_tensor_with_dtype(shape_ops::concat(&shapes, dim).or_panic(), xs[0].dtype());
         y.alloc();
         self.call_fw_impl("concat_fw_impl", xs, &[dim], &[], &mut [&mut y]);
         y
@@ -396,25 +535,45 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
         assert!(gy.device() == self);
         assert!(gx.device() == self);
         let sy = gy.shape;
-        assert!(shape_ops::slice(gx.shape, dim, lower, lower + sy[dim]) == sy);
+        assert!(shape_ops::slice(gx.shape, dim, lower, lower + sy[dim]).or_panic() == sy);
         self.call_bw_impl("slice_bw_impl", &[], &[], &[gy], &[dim, lower], &[], gx);
     }
 
     fn pick_bw(&self, gy: &Tensor, ids: &[u32], dim: u32, gx: &mut Tensor) {
         assert!(gy.device() == self);
         assert!(gx.device() == self);
-        assert!(shape_ops::pick(gx.shape, ids, dim) == gy.shape);
+        assert!(shape_ops::pick(gx.shape, ids, dim).or_panic() == gy.shape);
         let mut u32data = vec![0; ids.len() + 1];
         u32data[0] = dim;
         u32data[1..].clone_from_slice(ids);
         self.call_bw_impl("pick_bw_impl", &[], &[], &[gy], &u32data, &[], gx);
     }
 
+    fn pick_by_fw(&self, x: &Tensor, ids: &Tensor, dim: u32) -> Tensor<'_> {
+        assert!(x.device() == self);
+        assert!(ids.device() == self);
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::pick_by(x.shape, ids.shape, dim).or_panic(),
+            x.dtype(),
+        );
+        y.alloc();
+        self.call_fw_impl("pick_by_fw_impl", &[x, ids], &[dim], &[], &mut [&mut y]);
+        y
+    }
+
+    fn pick_by_bw(&self, gy: &Tensor, ids: &Tensor, dim: u32, gx: &mut Tensor) {
+        assert!(gy.device() == self);
+        assert!(ids.device() == self);
+        assert!(gx.device() == self);
+        assert!(shape_ops::pick_by(gx.shape, ids.shape, dim).or_panic() == gy.shape);
+        self.call_bw_impl("pick_by_bw_impl", &[ids], &[], &[gy], &[dim], &[], gx);
+    }
+
     // batch
 
     fn batch_sum_fw(&self, x: &Tensor) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(x.shape.resize_batch(1));
+        let mut y = self.new_tensor_with_dtype(x.shape.resize_batch(1), x.dtype());
         y.alloc();
         self.call_fw_impl("batch_sum_fw_impl", &[x], &[], &[], &mut [&mut y]);
         y
@@ -422,7 +581,10 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn batch_slice_fw(&self, x: &Tensor, lower: u32, upper: u32) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(shape_ops::batch_slice(x.shape, lower, upper));
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::batch_slice(x.shape, lower, upper).or_panic(),
+            x.dtype(),
+        );
         y.alloc();
         self.call_fw_impl(
             "batch_slice_fw_impl",
@@ -436,7 +598,8 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 
     fn batch_pick_fw(&self, x: &Tensor, ids: &[u32]) -> Tensor {
         assert!(x.device() == self);
-        let mut y = self.new_tensor(shape_ops::batch_pick(x.shape, ids));
+        let mut y =
+            self.new_tensor_with_dtype(shape_ops::batch_pick(x.shape, ids).or_panic(), x.dtype());
         y.alloc();
         self.call_fw_impl("batch_pick_fw_impl", &[x], ids, &[], &mut [&mut y]);
         y
@@ -449,7 +612,8 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
             assert!(x.device() == self);
             shapes.push(x.shape);
         }
-        let mut y = self.new_tensor(shape_ops::batch_concat(&shapes));
+        let mut y =
+            self.new_tensor_with_dtype(shape_ops::batch_concat(&shapes).or_panic(), xs[0].dtype());
         y.alloc();
         self.call_fw_impl("batch_concat_fw_impl", xs, &[], &[], &mut [&mut y]);
         y
@@ -459,15 +623,244 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
         assert!(gy.device() == self);
         assert!(gx.device() == self);
         let sy = gy.shape;
-        assert!(shape_ops::batch_slice(gx.shape, lower, lower + sy.batch()) == sy);
-        self.call_bw_impl("slice_bw_impl", &[], &[], &[gy], &[lower], &[], gx);
+        assert!(shape_ops::batch_slice(gx.shape, lower, lower + sy.batch()).or_panic() == sy);
+        self.call_bw_impl("batch_slice_bw_impl", &[], &[], &[gy], &[lower], &[], gx);
     }
 
     fn batch_pick_bw(&self, gy: &Tensor, ids: &[u32], gx: &mut Tensor) {
         assert!(gy.device() == self);
         assert!(gx.device() == self);
-        assert!(shape_ops::batch_pick(gx.shape, ids) == gy.shape);
-        self.call_bw_impl("pick_bw_impl", &[], &[], &[gy], ids, &[], gx);
+        assert!(shape_ops::batch_pick(gx.shape, ids).or_panic() == gy.shape);
+        self.call_bw_impl("batch_pick_bw_impl", &[], &[], &[gy], ids, &[], gx);
+    }
+
+    fn batch_pick_by_fw(&self, x: &Tensor, ids: &Tensor) -> Tensor<'_> {
+        assert!(x.device() == self);
+        assert!(ids.device() == self);
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::batch_pick_by(x.shape, ids.shape).or_panic(),
+            x.dtype(),
+        );
+        y.alloc();
+        self.call_fw_impl("batch_pick_by_fw_impl", &[x, ids], &[], &[], &mut [&mut y]);
+        y
+    }
+
+    fn batch_pick_by_bw(&self, gy: &Tensor, ids: &Tensor, gx: &mut Tensor) {
+        assert!(gy.device() == self);
+        assert!(ids.device() == self);
+        assert!(gx.device() == self);
+        assert!(shape_ops::batch_pick_by(gx.shape, ids.shape).or_panic() == gy.shape);
+        self.call_bw_impl("batch_pick_by_bw_impl", &[ids], &[], &[gy], &[], &[], gx);
+    }
+
+    // convolution
+
+    fn conv2d_fw(
+        &self,
+        x: &Tensor,
+        w: &Tensor,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Tensor<'_> {
+        assert!(x.device() == self);
+        assert!(w.device() == self);
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::conv2d(
+                x.shape, w.shape, padding0, padding1, stride0, stride1, dilation0, dilation1,
+            )
+            .or_panic(),
+            x.dtype(),
+        );
+        y.alloc();
+        self.call_fw_impl(
+            "conv2d_fw_impl",
+            &[x, w],
+            &[padding0, padding1, stride0, stride1, dilation0, dilation1],
+            &[],
+            &mut [&mut y],
+        );
+        y
+    }
+
+    fn conv2d_bw_x(
+        &self,
+        x: &Tensor,
+        w: &Tensor,
+        y: &Tensor,
+        gy: &Tensor,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+        gx: &mut Tensor,
+    ) {
+        assert!(x.device() == self);
+        assert!(w.device() == self);
+        assert!(y.device() == self);
+        assert!(gy.device() == self);
+        assert!(gx.device() == self);
+        assert!(x.shape == gx.shape);
+        assert!(y.shape == gy.shape);
+        assert!(
+            y.shape
+                == shape_ops::conv2d(
+                    x.shape, w.shape, padding0, padding1, stride0, stride1, dilation0, dilation1,
+                )
+                .or_panic()
+        );
+        self.call_bw_impl(
+            "conv2d_bw_x_impl",
+            &[x, w],
+            &[y],
+            &[gy],
+            &[padding0, padding1, stride0, stride1, dilation0, dilation1],
+            &[],
+            gx,
+        );
+    }
+
+    fn conv2d_bw_w(
+        &self,
+        x: &Tensor,
+        w: &Tensor,
+        y: &Tensor,
+        gy: &Tensor,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+        gw: &mut Tensor,
+    ) {
+        assert!(x.device() == self);
+        assert!(w.device() == self);
+        assert!(y.device() == self);
+        assert!(gy.device() == self);
+        assert!(gw.device() == self);
+        assert!(w.shape == gw.shape);
+        assert!(y.shape == gy.shape);
+        assert!(
+            y.shape
+                == shape_ops::conv2d(
+                    x.shape, w.shape, padding0, padding1, stride0, stride1, dilation0, dilation1,
+                )
+                .or_panic()
+        );
+        self.call_bw_impl(
+            "conv2d_bw_w_impl",
+            &[x, w],
+            &[y],
+            &[gy],
+            &[padding0, padding1, stride0, stride1, dilation0, dilation1],
+            &[],
+            gw,
+        );
+    }
+
+    fn max_pool2d_fw(
+        &self,
+        x: &Tensor,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Tensor<'_> {
+        assert!(x.device() == self);
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::pool2d(
+                x.shape, window0, window1, padding0, padding1, stride0, stride1,
+            )
+            .or_panic(),
+            x.dtype(),
+        );
+        y.alloc();
+        self.call_fw_impl(
+            "max_pool2d_fw_impl",
+            &[x],
+            &[window0, window1, padding0, padding1, stride0, stride1],
+            &[],
+            &mut [&mut y],
+        );
+        y
+    }
+
+    fn max_pool2d_bw(
+        &self,
+        x: &Tensor,
+        y: &Tensor,
+        gy: &Tensor,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        gx: &mut Tensor,
+    ) {
+        assert!(x.device() == self);
+        assert!(y.device() == self);
+        assert!(gy.device() == self);
+        assert!(gx.device() == self);
+        assert!(x.shape == gx.shape);
+        assert!(y.shape == gy.shape);
+        assert!(
+            y.shape
+                == shape_ops::pool2d(
+                    x.shape, window0, window1, padding0, padding1, stride0, stride1,
+                )
+                .or_panic()
+        );
+        self.call_bw_impl(
+            "max_pool2d_bw_impl",
+            &[x],
+            &[y],
+            &[gy],
+            &[window0, window1, padding0, padding1, stride0, stride1],
+            &[],
+            gx,
+        );
+    }
+
+    fn max_pool2d_pick_fw(
+        &self,
+        x: &Tensor,
+        z: &Tensor,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Tensor<'_> {
+        assert!(x.device() == self);
+        assert!(z.device() == self);
+        assert!(x.shape == z.shape);
+        let mut y = self.new_tensor_with_dtype(
+            shape_ops::pool2d(
+                x.shape, window0, window1, padding0, padding1, stride0, stride1,
+            )
+            .or_panic(),
+            z.dtype(),
+        );
+        y.alloc();
+        self.call_fw_impl(
+            "max_pool2d_pick_fw_impl",
+            &[x, z],
+            &[window0, window1, padding0, padding1, stride0, stride1],
+            &[],
+            &mut [&mut y],
+        );
+        y
     }
 }
 
@@ -475,6 +868,7 @@ impl<'dev> BasicDeviceFunctions for Device<'dev> {
 mod tests {
     use super::BasicDeviceFunctions;
     use crate::devices as D;
+    use crate::functions::BasicFunctions;
 
     #[test]
     fn check_argmax() {
@@ -488,4 +882,39 @@ mod tests {
         assert_eq!(y1_data, y1.to_vec());
         assert_eq!(y2_data, y2.to_vec());
     }
+
+    #[test]
+    fn check_permute_dims() {
+        let dev = D::Naive::new();
+        let x_data = vec![1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., 12.];
+        let y_data = vec![1., 3., 5., 2., 4., 6., 7., 9., 11., 8., 10., 12.];
+        let x = dev.new_tensor_by_slice(shape![2, 3; 2], &x_data);
+        let y = dev.permute_dims_fw(&x, &[1, 2, 0]);
+        assert_eq!(shape![3, 1, 2; 2], y.shape);
+        assert_eq!(y_data, y.to_vec());
+        let mut gx = dev.new_tensor_by_constant(x.shape, 0.);
+        dev.permute_dims_bw(&y, &[1, 2, 0], &mut gx);
+        assert_eq!(x_data, gx.to_vec());
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_permute_dims_invalid() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_constant(shape![2, 3], 0.);
+        dev.permute_dims_fw(&x, &[1, 1]);
+    }
+
+    #[test]
+    fn check_flip() {
+        let dev = D::Naive::new();
+        let x_data = vec![1., 2., 3., 4., 5., 6.];
+        let y_data = vec![4., 5., 6., 1., 2., 3.];
+        let x = dev.new_tensor_by_slice(shape![3, 2], &x_data);
+        let y = dev.flip_fw(&x, 1);
+        assert_eq!(y_data, y.to_vec());
+        let mut gx = dev.new_tensor_by_constant(x.shape, 0.);
+        dev.flip_bw(&y, 1, &mut gx);
+        assert_eq!(x_data, gx.to_vec());
+    }
 }
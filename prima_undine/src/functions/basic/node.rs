@@ -1,7 +1,44 @@
 use std::borrow::Borrow;
 
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{operators as op, Node, Shape};
+use crate::{operators as op, DType, Device, Error, Node, Result, Shape};
+
+macro_rules! define_try_ab {
+    ( $fn:ident, $op:ident, $scalar_l:ident, $scalar_r:ident ) => {
+        fn $fn<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+            let rhs = rhs.borrow();
+            let ys = if self.shape().is_scalar() {
+                Node::try_create(op::$scalar_l::new(self.device()), &[rhs, self])?
+            } else if rhs.shape().is_scalar() {
+                Node::try_create(op::$scalar_r::new(self.device()), &[self, rhs])?
+            } else {
+                Node::try_create(op::$op::new(self.device()), &[self, rhs])?
+            };
+            Ok(ys.into_iter().next().unwrap())
+        }
+    };
+}
+
+// Nodes are calculated lazily, so only the arguments are checked here.
+macro_rules! define_try_x {
+    ( $fn:ident, $op:ident ) => {
+        fn $fn(&self) -> Result<Self> {
+            Ok(Node::try_create(op::$op::new(self.device()), &[self])?
+                .pop()
+                .unwrap())
+        }
+    };
+}
+
+macro_rules! define_try_dim {
+    ( $fn:ident, $op:ident ) => {
+        fn $fn(&self, dim: u32) -> Result<Self> {
+            Ok(Node::try_create(op::$op::new(self.device(), dim), &[self])?
+                .pop()
+                .unwrap())
+        }
+    };
+}
 
 impl<'arg, 'dev> BasicFunctions for Node<'arg, 'dev> {
     // core
@@ -205,6 +242,18 @@ impl<'arg, 'dev> BasicFunctions for Node<'arg, 'dev> {
             .unwrap()
     }
 
+    fn permute_dims(&self, perm: &[u32]) -> Self {
+        Node::create(op::PermuteDims::new(self.device(), perm), &[self])
+            .pop()
+            .unwrap()
+    }
+
+    fn flip(&self, dim: u32) -> Self {
+        Node::create(op::Flip::new(self.device(), dim), &[self])
+            .pop()
+            .unwrap()
+    }
+
     fn triangular_l(&self, k: u32) -> Self {
         Node::create(op::TriangularL::new(self.device(), k), &[self])
             .pop()
@@ -261,6 +310,12 @@ impl<'arg, 'dev> BasicFunctions for Node<'arg, 'dev> {
             .unwrap()
     }
 
+    fn pick_by<T: Borrow<Self>>(&self, ids: T, dim: u32) -> Self {
+        Node::create(op::PickBy::new(self.device(), dim), &[self, ids.borrow()])
+            .pop()
+            .unwrap()
+    }
+
     fn concat(xs: &[&Self], dim: u32) -> Self {
         assert!(xs.len() != 0);
         Node::create(op::Concat::new(xs[0].device(), dim), xs)
@@ -303,10 +358,518 @@ impl<'arg, 'dev> BasicFunctions for Node<'arg, 'dev> {
             .unwrap()
     }
 
+    fn batch_pick_by<T: Borrow<Self>>(&self, ids: T) -> Self {
+        Node::create(op::BatchPickBy::new(self.device()), &[self, ids.borrow()])
+            .pop()
+            .unwrap()
+    }
+
     fn batch_concat(xs: &[&Self]) -> Self {
         assert!(xs.len() != 0);
         Node::create(op::BatchConcat::new(xs[0].device()), xs)
             .pop()
             .unwrap()
     }
+
+    // others
+
+    fn stop_gradient(&self) -> Self {
+        Node::create(op::StopGradient::new(self.device()), &[self])
+            .pop()
+            .unwrap()
+    }
+
+    fn cast(&self, dtype: DType) -> Self {
+        Node::create(op::Cast::new(self.device(), dtype), &[self])
+            .pop()
+            .unwrap()
+    }
+
+    // convolution
+
+    fn conv2d<T: Borrow<Self>>(
+        &self,
+        w: T,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Self {
+        let w = w.borrow();
+        Node::create(
+            op::Conv2d::new(
+                self.device(),
+                padding0,
+                padding1,
+                stride0,
+                stride1,
+                dilation0,
+                dilation1,
+            ),
+            &[self, w],
+        )
+        .pop()
+        .unwrap()
+    }
+
+    fn max_pool2d(
+        &self,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Self {
+        Node::create(
+            op::MaxPooling2d::new(
+                self.device(),
+                window0,
+                window1,
+                padding0,
+                padding1,
+                stride0,
+                stride1,
+            ),
+            &[self],
+        )
+        .pop()
+        .unwrap()
+    }
+
+    // fallible versions
+
+    define_try_ab!(try_add, Add, AddScalar, AddScalar);
+    define_try_ab!(try_sub, Sub, SubScalarL, SubScalarR);
+    define_try_ab!(try_mul, Mul, MulScalar, MulScalar);
+    define_try_ab!(try_div, Div, DivScalarL, DivScalarR);
+    define_try_ab!(try_pow, Pow, PowScalarL, PowScalarR);
+
+    define_try_x!(try_sqrt, Sqrt);
+    define_try_x!(try_abs, Abs);
+    define_try_x!(try_sin, Sin);
+    define_try_x!(try_cos, Cos);
+    define_try_x!(try_tan, Tan);
+    define_try_x!(try_exp, Exp);
+    define_try_x!(try_ln, Ln);
+    define_try_x!(try_tanh, Tanh);
+    define_try_x!(try_sigmoid, Sigmoid);
+    define_try_x!(try_softplus, Softplus);
+
+    fn try_matmul<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+        let rhs = rhs.borrow();
+        Ok(
+            Node::try_create(op::Matmul::new(self.device()), &[self, rhs])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    define_try_dim!(try_sum, Sum);
+    define_try_dim!(try_max, Max);
+    define_try_dim!(try_min, Min);
+
+    fn try_broadcast(&self, dim: u32, size: u32) -> Result<Self> {
+        Ok(
+            Node::try_create(op::Broadcast::new(self.device(), dim, size), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    define_try_dim!(try_logsumexp, Logsumexp);
+
+    fn try_ln_softmax(&self, dim: u32) -> Result<Self> {
+        let lse = self.try_logsumexp(dim)?;
+        self.try_sub(lse.try_broadcast(dim, self.shape()[dim])?)
+    }
+
+    fn try_softmax(&self, dim: u32) -> Result<Self> {
+        self.try_ln_softmax(dim)?.try_exp()
+    }
+
+    fn try_softmax_cross_entropy<T: Borrow<Self>>(&self, t: T, dim: u32) -> Result<Self> {
+        Ok(Node::try_create(
+            op::SoftmaxCrossEntropy::new(self.device(), dim),
+            &[self, t.borrow()],
+        )?
+        .pop()
+        .unwrap())
+    }
+
+    fn try_sparse_softmax_cross_entropy(&self, ids: &[u32], dim: u32) -> Result<Self> {
+        Ok(Node::try_create(
+            op::SparseSoftmaxCrossEntropy::new(self.device(), ids, dim),
+            &[self],
+        )?
+        .pop()
+        .unwrap())
+    }
+
+    fn try_transpose(&self) -> Result<Self> {
+        Ok(
+            Node::try_create(op::Transpose::new(self.device()), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_permute_dims(&self, perm: &[u32]) -> Result<Self> {
+        Ok(
+            Node::try_create(op::PermuteDims::new(self.device(), perm), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_slice(&self, dim: u32, lower: u32, upper: u32) -> Result<Self> {
+        Ok(
+            Node::try_create(op::Slice::new(self.device(), dim, lower, upper), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_split(&self, dim: u32, n: u32) -> Result<Vec<Self>> {
+        Node::try_create(op::Split::new(self.device(), dim, n), &[self])
+    }
+
+    fn try_pick(&self, ids: &[u32], dim: u32) -> Result<Self> {
+        Ok(
+            Node::try_create(op::Pick::new(self.device(), ids, dim), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_pick_by<T: Borrow<Self>>(&self, ids: T, dim: u32) -> Result<Self> {
+        Ok(
+            Node::try_create(op::PickBy::new(self.device(), dim), &[self, ids.borrow()])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_concat(xs: &[&Self], dim: u32) -> Result<Self> {
+        if xs.is_empty() {
+            return Err(Error::InvalidArgument {
+                op: "Concat".to_string(),
+                message: "no arguments".to_string(),
+            });
+        }
+        Ok(Node::try_create(op::Concat::new(xs[0].device(), dim), xs)?
+            .pop()
+            .unwrap())
+    }
+
+    fn try_reshape(&self, shape: Shape) -> Result<Self> {
+        Ok(
+            Node::try_create(op::Reshape::new(self.device(), shape), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_batch_slice(&self, lower: u32, upper: u32) -> Result<Self> {
+        Ok(
+            Node::try_create(op::BatchSlice::new(self.device(), lower, upper), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_batch_split(&self, n: u32) -> Result<Vec<Self>> {
+        Node::try_create(op::BatchSplit::new(self.device(), n), &[self])
+    }
+
+    fn try_batch_pick(&self, ids: &[u32]) -> Result<Self> {
+        Ok(
+            Node::try_create(op::BatchPick::new(self.device(), ids), &[self])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_batch_pick_by<T: Borrow<Self>>(&self, ids: T) -> Result<Self> {
+        Ok(
+            Node::try_create(op::BatchPickBy::new(self.device()), &[self, ids.borrow()])?
+                .pop()
+                .unwrap(),
+        )
+    }
+
+    fn try_batch_concat(xs: &[&Self]) -> Result<Self> {
+        if xs.is_empty() {
+            return Err(Error::InvalidArgument {
+                op: "BatchConcat".to_string(),
+                message: "no arguments".to_string(),
+            });
+        }
+        Ok(Node::try_create(op::BatchConcat::new(xs[0].device()), xs)?
+            .pop()
+            .unwrap())
+    }
+
+    fn try_conv2d<T: Borrow<Self>>(
+        &self,
+        w: T,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Result<Self> {
+        let w = w.borrow();
+        Ok(Node::try_create(
+            op::Conv2d::new(
+                self.device(),
+                padding0,
+                padding1,
+                stride0,
+                stride1,
+                dilation0,
+                dilation1,
+            ),
+            &[self, w],
+        )?
+        .pop()
+        .unwrap())
+    }
+
+    fn try_max_pool2d(
+        &self,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Result<Self> {
+        Ok(Node::try_create(
+            op::MaxPooling2d::new(
+                self.device(),
+                window0,
+                window1,
+                padding0,
+                padding1,
+                stride0,
+                stride1,
+            ),
+            &[self],
+        )?
+        .pop()
+        .unwrap())
+    }
+}
+
+impl<'arg, 'dev> Node<'arg, 'dev> {
+    pub fn constant(device: &'dev Device<'dev>, shape: Shape, k: f32) -> Self {
+        Node::create(op::Constant::new(device, shape, k), &[])
+            .pop()
+            .unwrap()
+    }
+
+    pub fn identity(device: &'dev Device<'dev>, size: u32) -> Self {
+        Node::create(op::Identity::new(device, size), &[])
+            .pop()
+            .unwrap()
+    }
+
+    pub fn copy(&self) -> Self {
+        Node::create(op::Copy::new(self.device()), &[self])
+            .pop()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::functions::BasicFunctions;
+    use crate::{devices as D, initializers as I, DType, Error, Node};
+
+    #[test]
+    fn check_stop_gradient() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![2], &I::Constant::new(3.));
+        {
+            let x = Node::from(&mut p);
+            let y = (x.stop_gradient() * &x).sum(0);
+            assert_eq!(vec![18.], y.to_vec());
+            y.backward();
+        }
+        assert_eq!(vec![3., 3.], p.gradient.to_vec());
+    }
+
+    #[test]
+    fn check_copy() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![2], &I::Constant::new(3.));
+        {
+            let x = Node::from(&mut p);
+            let y = (x.copy() * &x).sum(0);
+            assert_eq!(vec![18.], y.to_vec());
+            y.backward();
+        }
+        assert_eq!(vec![6., 6.], p.gradient.to_vec());
+    }
+
+    #[test]
+    fn check_cast() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![2], &I::Constant::new(3.));
+        {
+            let x = Node::from(&mut p).cast(DType::F16);
+            let y = (&x * &x).sum(0).cast(DType::F32);
+            assert_eq!(vec![18.], y.to_vec());
+            y.backward();
+        }
+        assert_eq!(vec![6., 6.], p.gradient.to_vec());
+    }
+
+    #[test]
+    fn check_parameter_compute_dtype() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![2], &I::Constant::new(3.));
+        p.set_compute_dtype(DType::BF16);
+        {
+            let x = Node::from(&mut p);
+            let y = (&x * &x).sum(0);
+            assert_eq!(vec![18.], y.to_vec());
+            assert_eq!(DType::BF16, y.inner_value().dtype());
+            y.backward();
+        }
+        assert_eq!(DType::F32, p.value.dtype());
+        assert_eq!(DType::F32, p.gradient.dtype());
+        assert_eq!(vec![6., 6.], p.gradient.to_vec());
+    }
+
+    #[test]
+    fn check_pick_by() {
+        let dev = D::Naive::new();
+        let x_data = (0..12).map(|i| i as f32).collect::<Vec<f32>>();
+        let x = dev.new_tensor_by_slice(shape![3, 2; 2], &x_data);
+        let ids = dev.new_tensor_by_data(shape![; 2], &[2u32, 0]);
+        let batch_ids = dev.new_tensor_by_data(shape![; 3], &[1i32, 1, 0]);
+        let x1 = Node::from(&x);
+        let y1 = x1.pick(&[2, 0], 0).batch_pick(&[1, 1, 0]);
+        let x2 = Node::from(&x);
+        let y2 = x2
+            .pick_by(Node::from(&ids), 0)
+            .batch_pick_by(Node::from(&batch_ids));
+        assert_eq!(vec![6., 9., 6., 9., 2., 5.], y1.to_vec());
+        assert_eq!(y1.to_vec(), y2.to_vec());
+        (y1 * 2.).sum(1).batch_sum().backward();
+        (y2 * 2.).sum(1).batch_sum().backward();
+        assert_eq!(x1.inner_gradient().to_vec(), x2.inner_gradient().to_vec());
+    }
+
+    #[test]
+    fn check_try_functions() {
+        let dev = D::Naive::new();
+        let a = Node::from(dev.new_tensor_by_constant(shape![2, 3], 1.));
+        let b = Node::from(dev.new_tensor_by_constant(shape![2, 3], 2.));
+        assert_eq!(
+            Some(Error::ShapeMismatch {
+                op: "Matmul".to_string(),
+                lhs: shape![2, 3],
+                rhs: shape![2, 3],
+            }),
+            a.try_matmul(&b).err().map(|e| e.root().clone())
+        );
+        assert_eq!(
+            vec![6., 6., 6., 6.],
+            a.try_matmul(b.transpose()).unwrap().to_vec()
+        );
+        assert_eq!(shape![2, 3], a.try_add(&b).unwrap().shape());
+        assert!(a.try_split(1, 2).is_err());
+        let e = a.try_slice(1, 2, 4).err().unwrap();
+        assert_eq!(
+            "Slice(dim=1,lower=2,upper=4): invalid range [2, 4) of dim 1 of Shape { [2, 3], 1 }",
+            e.root().to_string()
+        );
+    }
+
+    #[test]
+    fn check_try_reductions() {
+        let dev = D::Naive::new();
+        let x = Node::from(dev.new_tensor_by_slice(shape![2, 3], &[1., 2., 3., -4., 0.5, 6.]));
+        let t = Node::from(dev.new_tensor_by_slice(shape![2, 3], &[1., 0., 0., 1., 0.5, 0.5]));
+        assert_eq!(x.sum(1).to_vec(), x.try_sum(1).unwrap().to_vec());
+        assert_eq!(x.max(0).to_vec(), x.try_max(0).unwrap().to_vec());
+        assert_eq!(x.min(1).to_vec(), x.try_min(1).unwrap().to_vec());
+        assert_eq!(x.pow(&t).to_vec(), x.try_pow(&t).unwrap().to_vec());
+        assert_eq!(x.softmax(0).to_vec(), x.try_softmax(0).unwrap().to_vec());
+        assert_eq!(
+            x.softmax_cross_entropy(&t, 0).to_vec(),
+            x.try_softmax_cross_entropy(&t, 0).unwrap().to_vec()
+        );
+        assert!(x.try_sum(8).is_err());
+        assert!(x.try_max(8).is_err());
+        assert!(x.try_ln_softmax(8).is_err());
+        assert!(x.try_pow(x.transpose()).is_err());
+        assert!(x.try_softmax_cross_entropy(x.transpose(), 0).is_err());
+        assert!(x.try_sparse_softmax_cross_entropy(&[2], 0).is_err());
+        assert!(x.try_pick_by(&x, 0).is_err());
+        assert!(x.try_batch_pick_by(&x).is_err());
+    }
+
+    #[test]
+    fn check_try_create_device_mismatch() {
+        let dev1 = D::Naive::new();
+        let dev2 = D::Naive::new();
+        let a = Node::from(dev1.new_tensor_by_constant(shape![2], 1.));
+        let b = Node::from(dev2.new_tensor_by_constant(shape![2], 1.));
+        assert_eq!(
+            Some(Error::DeviceMismatch {
+                op: "Add".to_string()
+            }),
+            a.try_add(&b).err().map(|e| e.root().clone())
+        );
+    }
+
+    #[test]
+    fn check_graph_error_trace() {
+        let dev = D::Naive::new();
+        let x = Node::from(dev.new_tensor_by_constant(shape![2, 3], 1.)).named("x");
+        let w = Node::from(dev.new_tensor_by_constant(shape![2, 3], 1.)).named("w");
+        let h = (&x + &w).named("h");
+        assert_eq!(Some("h".to_string()), h.label());
+        assert_eq!("Add", h.operator_name());
+        let e = h.try_matmul(&w).err().unwrap();
+        match &e {
+            Error::Graph { trace, .. } => assert_eq!(
+                &vec![
+                    "Add \"h\": Shape { [2, 3], 1 }".to_string(),
+                    "  InputOwner \"x\": Shape { [2, 3], 1 }".to_string(),
+                    "  InputOwner \"w\": Shape { [2, 3], 1 }".to_string(),
+                    "InputOwner \"w\": Shape { [2, 3], 1 }".to_string(),
+                ],
+                trace
+            ),
+            _ => panic!("unexpected error: {:?}", e),
+        }
+        assert!(e
+            .to_string()
+            .starts_with("Matmul: shape mismatch: Shape { [2, 3], 1 } and Shape { [2, 3], 1 }\narguments:\n  Add \"h\""));
+    }
+
+    #[test]
+    #[should_panic(expected = "Matmul: shape mismatch")]
+    fn check_create_shape_mismatch() {
+        let dev = D::Naive::new();
+        let a = Node::from(dev.new_tensor_by_constant(shape![2, 3], 1.));
+        let _ = a.matmul(&a);
+    }
+
+    #[test]
+    fn check_constant_identity() {
+        let dev = D::Naive::new();
+        let c = Node::constant(&dev, shape![2, 2], 2.);
+        let i = Node::identity(&dev, 2);
+        assert_eq!(shape![2, 2], c.shape());
+        assert_eq!(vec![3., 2., 2., 3.], (c + i).to_vec());
+    }
 }
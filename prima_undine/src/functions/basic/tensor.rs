@@ -1,7 +1,90 @@
 use std::borrow::Borrow;
 
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{Shape, Tensor};
+use crate::{shape_ops, DType, Error, Result, Shape, Tensor};
+
+fn check_args(op: &str, xs: &[&Tensor]) -> Result<()> {
+    if xs.is_empty() {
+        return Err(Error::InvalidArgument {
+            op: op.to_string(),
+            message: "no arguments".to_string(),
+        });
+    }
+    for x in &xs[1..] {
+        if x.device() != xs[0].device() {
+            return Err(Error::DeviceMismatch { op: op.to_string() });
+        }
+        if x.dtype() != xs[0].dtype() {
+            return Err(Error::DTypeMismatch {
+                op: op.to_string(),
+                lhs: xs[0].dtype(),
+                rhs: x.dtype(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Selects the kernel of a binary operation, which has separate versions for
+// a scalar operand on either side.
+fn try_ab<'dev>(
+    op: &str,
+    kernels: [&str; 3],
+    a: &Tensor<'dev>,
+    b: &Tensor<'dev>,
+) -> Result<Tensor<'dev>> {
+    check_args(op, &[a, b])?;
+    if a.shape.is_scalar() {
+        let shape = shape_ops::scalar_op(b.shape, a.shape).map_err(|e| e.with_op(op))?;
+        try_fw(kernels[1], &[b, a], &[], &[], shape)
+    } else if b.shape.is_scalar() {
+        let shape = shape_ops::scalar_op(a.shape, b.shape).map_err(|e| e.with_op(op))?;
+        try_fw(kernels[2], &[a, b], &[], &[], shape)
+    } else {
+        let shape = shape_ops::elementwise(a.shape, b.shape).map_err(|e| e.with_op(op))?;
+        try_fw(kernels[0], &[a, b], &[], &[], shape)
+    }
+}
+
+// Allocates the result with the dtype of the first argument and calculates it
+// by the kernel, which may not be implemented for the dtype.
+fn try_fw<'dev>(
+    kernel: &str,
+    xs: &[&Tensor<'dev>],
+    u32data: &[u32],
+    f32data: &[f32],
+    shape: Shape,
+) -> Result<Tensor<'dev>> {
+    let dev = xs[0].device();
+    let mut y = dev.new_tensor_with_dtype(shape, xs[0].dtype());
+    y.alloc();
+    dev.try_call_fw_impl(kernel, xs, u32data, f32data, &mut [&mut y])?;
+    Ok(y)
+}
+
+// Reduction along dim by the kernel.
+fn try_reduce<'dev>(op: &str, kernel: &str, x: &Tensor<'dev>, dim: u32) -> Result<Tensor<'dev>> {
+    let shape = x.shape.try_resize_dim(dim, 1).map_err(|e| e.with_op(op))?;
+    try_fw(kernel, &[x], &[dim], &[], shape)
+}
+
+// The ids may have a different dtype from x, so only the device is checked.
+fn check_ids_device(op: &str, x: &Tensor, ids: &Tensor) -> Result<()> {
+    if ids.device() != x.device() {
+        return Err(Error::DeviceMismatch { op: op.to_string() });
+    }
+    Ok(())
+}
+
+fn check_split(op: &str, x: Shape, total: u32, n: u32) -> Result<()> {
+    if n == 0 || !total.is_multiple_of(n) {
+        return Err(Error::InvalidArgument {
+            op: op.to_string(),
+            message: format!("cannot split {:?} into {}", x, n),
+        });
+    }
+    Ok(())
+}
 
 impl<'arg, 'dev> BasicFunctions for Tensor<'dev> {
     // core
@@ -138,6 +221,14 @@ impl<'arg, 'dev> BasicFunctions for Tensor<'dev> {
         self.device().transpose_fw(self)
     }
 
+    fn permute_dims(&self, perm: &[u32]) -> Self {
+        self.device().permute_dims_fw(self, perm)
+    }
+
+    fn flip(&self, dim: u32) -> Self {
+        self.device().flip_fw(self, dim)
+    }
+
     fn triangular_l(&self, k: u32) -> Self {
         self.device().triangular_l_fw(self, k)
     }
@@ -185,6 +276,10 @@ impl<'arg, 'dev> BasicFunctions for Tensor<'dev> {
         self.device().pick_fw(self, ids, dim)
     }
 
+    fn pick_by<T: Borrow<Self>>(&self, ids: T, dim: u32) -> Self {
+        self.device().pick_by_fw(self, ids.borrow(), dim)
+    }
+
     fn concat(xs: &[&Self], dim: u32) -> Self {
         xs[0].device().concat_fw(xs, dim)
     }
@@ -225,8 +320,431 @@ impl<'arg, 'dev> BasicFunctions for Tensor<'dev> {
         self.device().batch_pick_fw(self, ids)
     }
 
+    fn batch_pick_by<T: Borrow<Self>>(&self, ids: T) -> Self {
+        self.device().batch_pick_by_fw(self, ids.borrow())
+    }
+
     fn batch_concat(xs: &[&Self]) -> Self {
         assert!(xs.len() != 0);
         xs[0].device().batch_concat_fw(xs)
     }
+
+    // others
+
+    fn stop_gradient(&self) -> Self {
+        self.device().copy_tensor(self)
+    }
+
+    fn cast(&self, dtype: DType) -> Self {
+        self.device().cast_tensor(self, dtype)
+    }
+
+    // convolution
+
+    fn conv2d<T: Borrow<Self>>(
+        &self,
+        w: T,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Self {
+        self.device().conv2d_fw(
+            self,
+            w.borrow(),
+            padding0,
+            padding1,
+            stride0,
+            stride1,
+            dilation0,
+            dilation1,
+        )
+    }
+
+    fn max_pool2d(
+        &self,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Self {
+        self.device()
+            .max_pool2d_fw(self, window0, window1, padding0, padding1, stride0, stride1)
+    }
+
+    // fallible versions
+
+    fn try_add<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+        let kernels = ["add_fw_impl", "add_scalar_fw_impl", "add_scalar_fw_impl"];
+        try_ab("add", kernels, self, rhs.borrow())
+    }
+
+    fn try_sub<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+        let kernels = [
+            "sub_fw_impl",
+            "sub_scalar_l_fw_impl",
+            "sub_scalar_r_fw_impl",
+        ];
+        try_ab("sub", kernels, self, rhs.borrow())
+    }
+
+    fn try_mul<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+        let kernels = ["mul_fw_impl", "mul_scalar_fw_impl", "mul_scalar_fw_impl"];
+        try_ab("mul", kernels, self, rhs.borrow())
+    }
+
+    fn try_div<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+        let kernels = [
+            "div_fw_impl",
+            "div_scalar_l_fw_impl",
+            "div_scalar_r_fw_impl",
+        ];
+        try_ab("div", kernels, self, rhs.borrow())
+    }
+
+    fn try_pow<T: Borrow<Self>>(&self, k: T) -> Result<Self> {
+        let kernels = [
+            "powf_fw_impl",
+            "powf_scalar_l_fw_impl",
+            "powf_scalar_r_fw_impl",
+        ];
+        try_ab("pow", kernels, self, k.borrow())
+    }
+
+    fn try_sqrt(&self) -> Result<Self> {
+        try_fw("sqrt_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_abs(&self) -> Result<Self> {
+        try_fw("abs_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_sin(&self) -> Result<Self> {
+        try_fw("sin_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_cos(&self) -> Result<Self> {
+        try_fw("cos_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_tan(&self) -> Result<Self> {
+        try_fw("tan_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_exp(&self) -> Result<Self> {
+        try_fw("exp_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_ln(&self) -> Result<Self> {
+        try_fw("ln_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_tanh(&self) -> Result<Self> {
+        try_fw("tanh_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_sigmoid(&self) -> Result<Self> {
+        try_fw("sigmoid_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_softplus(&self) -> Result<Self> {
+        try_fw("softplus_fw_impl", &[self], &[], &[], self.shape)
+    }
+
+    fn try_matmul<T: Borrow<Self>>(&self, rhs: T) -> Result<Self> {
+        let rhs = rhs.borrow();
+        check_args("matmul", &[self, rhs])?;
+        let shape = shape_ops::matmul(self.shape, rhs.shape)?;
+        try_fw("matmul_fw_impl", &[self, rhs], &[], &[], shape)
+    }
+
+    fn try_sum(&self, dim: u32) -> Result<Self> {
+        try_reduce("sum", "sum_fw_impl", self, dim)
+    }
+
+    fn try_max(&self, dim: u32) -> Result<Self> {
+        try_reduce("max", "max_fw_impl", self, dim)
+    }
+
+    fn try_min(&self, dim: u32) -> Result<Self> {
+        try_reduce("min", "min_fw_impl", self, dim)
+    }
+
+    fn try_broadcast(&self, dim: u32, size: u32) -> Result<Self> {
+        let shape = shape_ops::broadcast(self.shape, dim, size)?;
+        try_fw("broadcast_fw_impl", &[self], &[dim, size], &[], shape)
+    }
+
+    fn try_logsumexp(&self, dim: u32) -> Result<Self> {
+        try_reduce("logsumexp", "logsumexp_fw_impl", self, dim)
+    }
+
+    fn try_ln_softmax(&self, dim: u32) -> Result<Self> {
+        let lse = self.try_logsumexp(dim)?;
+        self.try_sub(lse.try_broadcast(dim, self.shape[dim])?)
+    }
+
+    fn try_softmax(&self, dim: u32) -> Result<Self> {
+        self.try_ln_softmax(dim)?.try_exp()
+    }
+
+    fn try_softmax_cross_entropy<T: Borrow<Self>>(&self, t: T, dim: u32) -> Result<Self> {
+        let t = t.borrow();
+        check_args("softmax_cross_entropy", &[self, t])?;
+        shape_ops::elementwise(self.shape, t.shape)
+            .map_err(|e| e.with_op("softmax_cross_entropy"))?;
+        let y = t.try_mul(self.try_ln_softmax(dim)?)?.try_sum(dim)?;
+        try_fw("neg_fw_impl", &[&y], &[], &[], y.shape)
+    }
+
+    fn try_sparse_softmax_cross_entropy(&self, ids: &[u32], dim: u32) -> Result<Self> {
+        let y = self.try_ln_softmax(dim)?.try_pick(ids, dim)?;
+        try_fw("neg_fw_impl", &[&y], &[], &[], y.shape)
+    }
+
+    fn try_transpose(&self) -> Result<Self> {
+        let shape = shape_ops::transpose(self.shape)?;
+        try_fw("transpose_fw_impl", &[self], &[], &[], shape)
+    }
+
+    fn try_permute_dims(&self, perm: &[u32]) -> Result<Self> {
+        let shape = shape_ops::permute_dims(self.shape, perm)?;
+        try_fw("permute_dims_fw_impl", &[self], perm, &[], shape)
+    }
+
+    fn try_slice(&self, dim: u32, lower: u32, upper: u32) -> Result<Self> {
+        let shape = shape_ops::slice(self.shape, dim, lower, upper)?;
+        try_fw("slice_fw_impl", &[self], &[dim, lower], &[], shape)
+    }
+
+    fn try_split(&self, dim: u32, n: u32) -> Result<Vec<Self>> {
+        check_split("split", self.shape, self.shape[dim], n)?;
+        let skip = self.shape[dim] / n;
+        (0..n)
+            .map(|i| self.try_slice(dim, i * skip, (i + 1) * skip))
+            .collect()
+    }
+
+    fn try_pick(&self, ids: &[u32], dim: u32) -> Result<Self> {
+        let shape = shape_ops::pick(self.shape, ids, dim)?;
+        let mut u32data = vec![dim];
+        u32data.extend_from_slice(ids);
+        try_fw("pick_fw_impl", &[self], &u32data, &[], shape)
+    }
+
+    fn try_pick_by<T: Borrow<Self>>(&self, ids: T, dim: u32) -> Result<Self> {
+        let ids = ids.borrow();
+        check_ids_device("pick_by", self, ids)?;
+        let shape = shape_ops::pick_by(self.shape, ids.shape, dim)?;
+        try_fw("pick_by_fw_impl", &[self, ids], &[dim], &[], shape)
+    }
+
+    fn try_concat(xs: &[&Self], dim: u32) -> Result<Self> {
+        check_args("concat", xs)?;
+        let shape = shape_ops::concat(&xs.iter().map(|x| x.shape).collect::<Vec<Shape>>(), dim)?;
+        try_fw("concat_fw_impl", xs, &[dim], &[], shape)
+    }
+
+    fn try_reshape(&self, shape: Shape) -> Result<Self> {
+        if shape.size() != self.shape.size() {
+            return Err(Error::ShapeMismatch {
+                op: "reshape".to_string(),
+                lhs: self.shape,
+                rhs: shape,
+            });
+        }
+        Ok(self.reshape(shape))
+    }
+
+    fn try_batch_slice(&self, lower: u32, upper: u32) -> Result<Self> {
+        let shape = shape_ops::batch_slice(self.shape, lower, upper)?;
+        try_fw("batch_slice_fw_impl", &[self], &[lower, upper], &[], shape)
+    }
+
+    fn try_batch_split(&self, n: u32) -> Result<Vec<Self>> {
+        check_split("batch_split", self.shape, self.shape.batch(), n)?;
+        let skip = self.shape.batch() / n;
+        (0..n)
+            .map(|i| self.try_batch_slice(i * skip, (i + 1) * skip))
+            .collect()
+    }
+
+    fn try_batch_pick(&self, ids: &[u32]) -> Result<Self> {
+        let shape = shape_ops::batch_pick(self.shape, ids)?;
+        try_fw("batch_pick_fw_impl", &[self], ids, &[], shape)
+    }
+
+    fn try_batch_pick_by<T: Borrow<Self>>(&self, ids: T) -> Result<Self> {
+        let ids = ids.borrow();
+        check_ids_device("batch_pick_by", self, ids)?;
+        let shape = shape_ops::batch_pick_by(self.shape, ids.shape)?;
+        try_fw("batch_pick_by_fw_impl", &[self, ids], &[], &[], shape)
+    }
+
+    fn try_batch_concat(xs: &[&Self]) -> Result<Self> {
+        check_args("batch_concat", xs)?;
+        let shape = shape_ops::batch_concat(&xs.iter().map(|x| x.shape).collect::<Vec<Shape>>())?;
+        try_fw("batch_concat_fw_impl", xs, &[], &[], shape)
+    }
+
+    fn try_conv2d<T: Borrow<Self>>(
+        &self,
+        w: T,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Result<Self> {
+        let w = w.borrow();
+        check_args("conv2d", &[self, w])?;
+        let shape = shape_ops::conv2d(
+            self.shape, w.shape, padding0, padding1, stride0, stride1, dilation0, dilation1,
+        )?;
+        try_fw(
+            "conv2d_fw_impl",
+            &[self, w],
+            &[padding0, padding1, stride0, stride1, dilation0, dilation1],
+            &[],
+            shape,
+        )
+    }
+
+    fn try_max_pool2d(
+        &self,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> Result<Self> {
+        let shape = shape_ops::pool2d(
+            self.shape, window0, window1, padding0, padding1, stride0, stride1,
+        )?;
+        try_fw(
+            "max_pool2d_fw_impl",
+            &[self],
+            &[window0, window1, padding0, padding1, stride0, stride1],
+            &[],
+            shape,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::{DType, Error, Tensor};
+
+    #[test]
+    fn check_try_functions() {
+        let dev = D::Naive::new();
+        let a = dev.new_tensor_by_constant(shape![2, 3], 1.);
+        let b = dev.new_tensor_by_constant(shape![3, 2], 2.);
+        assert_eq!(
+            Err(Error::ShapeMismatch {
+                op: "add".to_string(),
+                lhs: shape![2, 3],
+                rhs: shape![3, 2],
+            }),
+            a.try_add(&b).map(|y| y.shape())
+        );
+        assert_eq!(vec![6., 6., 6., 6.], a.try_matmul(&b).unwrap().to_vec());
+        assert!(a.try_matmul(&a).is_err());
+        assert!(a.try_reshape(shape![3, 3]).is_err());
+        assert_eq!(3, a.try_split(1, 3).unwrap().len());
+        assert!(a.try_batch_split(2).is_err());
+        assert!(Tensor::try_concat(&[], 0).is_err());
+        let c = b.cast(DType::F64);
+        assert_eq!(
+            Err(Error::DTypeMismatch {
+                op: "matmul".to_string(),
+                lhs: DType::F32,
+                rhs: DType::F64,
+            }),
+            a.try_matmul(&c).map(|y| y.shape())
+        );
+    }
+
+    #[test]
+    fn check_try_reductions() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2, 3], &[1., 2., 3., -4., 0.5, 6.]);
+        let t = dev.new_tensor_by_slice(shape![2, 3], &[1., 0., 0., 1., 0.5, 0.5]);
+        assert_eq!(x.sum(1).to_vec(), x.try_sum(1).unwrap().to_vec());
+        assert_eq!(x.max(0).to_vec(), x.try_max(0).unwrap().to_vec());
+        assert_eq!(x.min(1).to_vec(), x.try_min(1).unwrap().to_vec());
+        assert_eq!(x.pow(&t).to_vec(), x.try_pow(&t).unwrap().to_vec());
+        assert_eq!(
+            x.softmax_cross_entropy(&t, 0).to_vec(),
+            x.try_softmax_cross_entropy(&t, 0).unwrap().to_vec()
+        );
+        assert_eq!(
+            x.sparse_softmax_cross_entropy(&[1], 0).to_vec(),
+            x.try_sparse_softmax_cross_entropy(&[1], 0)
+                .unwrap()
+                .to_vec()
+        );
+        assert!(x.try_sum(8).is_err());
+        assert!(x.try_logsumexp(8).is_err());
+        assert!(x.try_softmax(8).is_err());
+        assert!(x.try_pow(x.transpose()).is_err());
+        assert!(x.try_softmax_cross_entropy(x.transpose(), 0).is_err());
+        assert!(x.try_sparse_softmax_cross_entropy(&[2], 0).is_err());
+        let ids = dev.new_tensor_by_data(shape![; 2], &[1u32, 0]);
+        let xb = dev.new_tensor_by_constant(shape![2, 3; 2], 1.);
+        assert_eq!(shape![1, 3; 2], xb.try_pick_by(&ids, 0).unwrap().shape());
+        assert_eq!(shape![2, 3; 2], xb.try_batch_pick_by(&ids).unwrap().shape());
+        assert!(x.try_pick_by(&x, 0).is_err());
+        assert!(x.try_batch_pick_by(&x).is_err());
+    }
+
+    #[test]
+    fn check_try_call_not_implemented() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_data(shape![2], &[1u8, 0]);
+        let mut y = dev.new_tensor_with_dtype(shape![2], DType::U8);
+        y.alloc();
+        let e = dev
+            .try_call_fw_impl("exp_fw_impl", &[&x], &[], &[], &mut [&mut y])
+            .err()
+            .unwrap();
+        assert_eq!(
+            Error::NotImplemented {
+                kernel: "exp_fw_impl:u8".to_string(),
+                device: "Naive".to_string(),
+            },
+            e
+        );
+        assert_eq!("exp_fw_impl:u8 is not implemented on Naive", e.to_string());
+    }
+
+    #[test]
+    fn check_try_functions_not_implemented() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_data(shape![2], &[1u8, 0]);
+        assert_eq!(
+            Err(Error::NotImplemented {
+                kernel: "exp_fw_impl:u8".to_string(),
+                device: "Naive".to_string(),
+            }),
+            x.try_exp().map(|y| y.shape())
+        );
+        assert_eq!(
+            Err(Error::NotImplemented {
+                kernel: "add_fw_impl:u8".to_string(),
+                device: "Naive".to_string(),
+            }),
+            x.try_add(&x).map(|y| y.shape())
+        );
+        let y = dev.new_tensor_by_slice(shape![2], &[0.25, 4.]);
+        assert_eq!(vec![0.5, 2.], y.try_sqrt().unwrap().to_vec());
+    }
 }
@@ -1 +1,2 @@
 pub mod device;
+mod node;
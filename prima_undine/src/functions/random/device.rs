@@ -4,6 +4,7 @@ pub trait RandomDeviceFunctions {
     fn random_bernoulli(&self, shape: Shape, p: f32) -> Tensor;
     fn random_normal(&self, shape: Shape, mean: f32, sd: f32) -> Tensor;
     fn random_uniform(&self, shape: Shape, lower: f32, upper: f32) -> Tensor;
+    fn random_log_normal(&self, shape: Shape, mean: f32, sd: f32) -> Tensor<'_>;
 }
 
 impl<'dev> RandomDeviceFunctions for Device<'dev> {
@@ -33,4 +34,17 @@ impl<'dev> RandomDeviceFunctions for Device<'dev> {
         );
         y
     }
+
+    fn random_log_normal(&self, shape: Shape, mean: f32, sd: f32) -> Tensor<'_> {
+        let mut y = self.new_tensor(shape);
+        y.alloc();
+        self.call_fw_impl(
+            "random_log_normal_impl",
+            &[],
+            &[],
+            &[mean, sd],
+            &mut [&mut y],
+        );
+        y
+    }
 }
@@ -0,0 +1,63 @@
+use crate::{operators as op, Device, Node, Shape};
+
+impl<'arg, 'dev> Node<'arg, 'dev> {
+    pub fn random_bernoulli(device: &'dev Device<'dev>, shape: Shape, p: f32) -> Self {
+        Node::create(op::RandomBernoulli::new(device, shape, p), &[])
+            .pop()
+            .unwrap()
+    }
+
+    pub fn random_normal(device: &'dev Device<'dev>, shape: Shape, mean: f32, sd: f32) -> Self {
+        Node::create(op::RandomNormal::new(device, shape, mean, sd), &[])
+            .pop()
+            .unwrap()
+    }
+
+    pub fn random_uniform(
+        device: &'dev Device<'dev>,
+        shape: Shape,
+        lower: f32,
+        upper: f32,
+    ) -> Self {
+        Node::create(op::RandomUniform::new(device, shape, lower, upper), &[])
+            .pop()
+            .unwrap()
+    }
+
+    pub fn random_log_normal(device: &'dev Device<'dev>, shape: Shape, mean: f32, sd: f32) -> Self {
+        Node::create(op::RandomLogNormal::new(device, shape, mean, sd), &[])
+            .pop()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::functions::BasicFunctions;
+    use crate::{devices as D, initializers as I, Node};
+
+    #[test]
+    fn check_random_nodes() {
+        let dev = D::Naive::new();
+        let b = Node::random_bernoulli(&dev, shape![3, 4], 0.5);
+        let n = Node::random_normal(&dev, shape![3, 4], 0., 1.);
+        let u = Node::random_uniform(&dev, shape![3, 4], 1., 2.);
+        let l = Node::random_log_normal(&dev, shape![3, 4], 0., 1.);
+        assert_eq!(shape![3, 4], n.shape());
+        assert!(b.to_vec().iter().all(|&x| x == 0. || x == 1.));
+        assert!(u.to_vec().iter().all(|&x| 1. <= x && x < 2.));
+        assert!(l.to_vec().iter().all(|&x| x > 0.));
+    }
+
+    #[test]
+    fn check_random_no_gradient() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![4], &I::Constant::new(1.));
+        {
+            let x = Node::from(&mut p);
+            let y = (&x * Node::random_uniform(&dev, shape![4], 2., 3.)).sum(0);
+            y.backward();
+        }
+        assert!(p.gradient.to_vec().iter().all(|&x| 2. <= x && x < 3.));
+    }
+}
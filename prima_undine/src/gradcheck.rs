@@ -0,0 +1,407 @@
+use std::fmt;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::functions::BasicFunctions;
+use crate::{no_grad, DType, Device, Node, Parameter, Shape, Tensor};
+
+// Compares the gradients calculated by Node::backward with central finite
+// differences. The output is weighted by fixed random values so that errors
+// which cancel out in a plain sum are detected.
+//
+// With the default DType::F32, rounding of the forward pass gives the numeric
+// gradients an absolute error of about 1e-7 * |weighted sum of outputs| / eps,
+// so functions with large outputs fail the default tolerances. In that case
+// use GradCheck::new_f64, which evaluates the perturbed forward passes in f64
+// and compares much more tightly, but requires f64 kernels of every function
+// used in f.
+pub struct GradCheck {
+    pub eps: f64,
+    pub atol: f32,
+    pub rtol: f32,
+    // DType in which the perturbed forward passes are evaluated, F32 or F64.
+    pub dtype: DType,
+    // Number of elements listed in the report.
+    pub num_worst: usize,
+}
+
+pub struct GradCheckElement {
+    pub argument: String,
+    pub index: usize,
+    pub analytic: f32,
+    pub numeric: f32,
+    pub error: f32,
+    pub tolerance: f32,
+}
+
+pub struct GradCheckReport {
+    pub num_elements: usize,
+    pub num_failures: usize,
+    // Sorted by the ratio of the error to the tolerance.
+    pub worst: Vec<GradCheckElement>,
+}
+
+impl GradCheckReport {
+    pub fn passed(&self) -> bool {
+        self.num_failures == 0
+    }
+}
+
+impl fmt::Display for GradCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "gradcheck: {} of {} elements out of tolerance",
+            self.num_failures, self.num_elements
+        )?;
+        for e in &self.worst {
+            write!(
+                f,
+                "\n  {}[{}]: analytic {}, numeric {}, error {} (tolerance {})",
+                e.argument, e.index, e.analytic, e.numeric, e.error, e.tolerance
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for GradCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Values of the inputs followed by the parameters.
+struct Inputs<'dev> {
+    values: Vec<Vec<f64>>,
+    shapes: Vec<Shape>,
+    num_inputs: usize,
+    device: &'dev Device<'dev>,
+}
+
+impl<'dev> Inputs<'dev> {
+    fn tensors(&self, dtype: DType) -> Vec<Tensor<'dev>> {
+        self.values
+            .iter()
+            .zip(&self.shapes)
+            .map(|(values, &shape)| match dtype {
+                DType::F32 => {
+                    let values = values.iter().map(|&x| x as f32).collect::<Vec<f32>>();
+                    self.device.new_tensor_by_slice(shape, &values)
+                }
+                DType::F64 => self.device.new_tensor_by_data(shape, values),
+                _ => panic!("gradcheck does not support {}", dtype),
+            })
+            .collect()
+    }
+}
+
+impl GradCheck {
+    pub fn new() -> GradCheck {
+        GradCheck {
+            eps: 3e-3,
+            atol: 1e-3,
+            rtol: 1e-3,
+            dtype: DType::F32,
+            num_worst: 5,
+        }
+    }
+
+    // The tolerances are limited by the analytic gradients, which are still
+    // calculated in f32.
+    pub fn new_f64() -> GradCheck {
+        GradCheck {
+            eps: 1e-6,
+            atol: 1e-4,
+            rtol: 1e-4,
+            dtype: DType::F64,
+            num_worst: 5,
+        }
+    }
+
+    // f receives the nodes of the inputs and the parameters, and must build
+    // the graph deterministically.
+    pub fn check<'dev, F>(
+        &self,
+        inputs: &[&Tensor<'dev>],
+        params: &mut [&mut Parameter<'dev>],
+        f: F,
+    ) -> GradCheckReport
+    where
+        F: for<'a> Fn(&[Node<'a, 'dev>], &[Node<'a, 'dev>]) -> Node<'a, 'dev>,
+    {
+        assert!(
+            !inputs.is_empty() || !params.is_empty(),
+            "gradcheck requires at least one input or parameter"
+        );
+        let device = match inputs.first() {
+            Some(x) => x.device(),
+            None => params[0].value.device(),
+        };
+        let mut inputs = Inputs {
+            values: inputs
+                .iter()
+                .map(|x| x.to_vec())
+                .chain(params.iter().map(|p| p.value.to_vec()))
+                .map(|values| values.into_iter().map(f64::from).collect())
+                .collect(),
+            shapes: inputs
+                .iter()
+                .map(|x| x.shape)
+                .chain(params.iter().map(|p| p.value.shape))
+                .collect(),
+            num_inputs: inputs.len(),
+            device,
+        };
+        let (weights, analytic) = self.analytic(&inputs, params, &f);
+
+        let mut elements = vec![];
+        for (i, grads) in analytic.iter().enumerate() {
+            for (j, &grad) in grads.iter().enumerate() {
+                let orig = inputs.values[i][j];
+                // The perturbed values are rounded to the evaluated dtype,
+                // so the actual step is used as the denominator.
+                let plus = self.round(orig + self.eps);
+                let minus = self.round(orig - self.eps);
+                inputs.values[i][j] = plus;
+                let y_plus = self.evaluate(&inputs, &f, &weights);
+                inputs.values[i][j] = minus;
+                let y_minus = self.evaluate(&inputs, &f, &weights);
+                inputs.values[i][j] = orig;
+                let numeric = ((y_plus - y_minus) / (plus - minus)) as f32;
+                let argument = if i < inputs.num_inputs {
+                    format!("input {}", i)
+                } else {
+                    format!("parameter {}", i - inputs.num_inputs)
+                };
+                elements.push(self.element(argument, j, grad, numeric));
+            }
+        }
+
+        let num_elements = elements.len();
+        let num_failures = elements.iter().filter(|e| e.error > e.tolerance).count();
+        elements.sort_by(|a, b| {
+            (b.error / b.tolerance)
+                .partial_cmp(&(a.error / a.tolerance))
+                .unwrap_or_else(|| b.error.is_nan().cmp(&a.error.is_nan()))
+        });
+        elements.truncate(self.num_worst);
+        GradCheckReport {
+            num_elements,
+            num_failures,
+            worst: elements,
+        }
+    }
+
+    // Returns the output weights and the gradients of the inputs followed by
+    // the parameters.
+    fn analytic<'dev, F>(
+        &self,
+        inputs: &Inputs<'dev>,
+        params: &mut [&mut Parameter<'dev>],
+        f: &F,
+    ) -> (Vec<f32>, Vec<Vec<f32>>)
+    where
+        F: for<'a> Fn(&[Node<'a, 'dev>], &[Node<'a, 'dev>]) -> Node<'a, 'dev>,
+    {
+        // The gradients accumulated in the parameters are restored afterwards.
+        let saved = params
+            .iter()
+            .map(|p| p.gradient.to_vec())
+            .collect::<Vec<Vec<f32>>>();
+        let tensors = inputs.tensors(DType::F32);
+        let (weights, grads) = {
+            let xs = tensors[..inputs.num_inputs]
+                .iter()
+                .map(Node::from)
+                .collect::<Vec<Node>>();
+            let ps = params
+                .iter_mut()
+                .map(|p| Node::from(&mut **p))
+                .collect::<Vec<Node>>();
+            let y = f(&xs, &ps);
+            let shape = y.shape();
+            let mut rng = ChaCha20Rng::seed_from_u64(0);
+            let dist = Uniform::new(0.5, 1.5);
+            let weights = (0..shape.size())
+                .map(|_| dist.sample(&mut rng))
+                .collect::<Vec<f32>>();
+            y.backward_with(&inputs.device.new_tensor_by_slice(shape, &weights));
+            let grads = xs
+                .iter()
+                .chain(&ps)
+                .map(|x| {
+                    let g = x.inner_gradient();
+                    if g.valid() {
+                        g.to_vec()
+                    } else {
+                        vec![0.; g.shape.size() as usize]
+                    }
+                })
+                .collect::<Vec<Vec<f32>>>();
+            (weights, grads)
+        };
+        for (p, values) in params.iter_mut().zip(saved) {
+            inputs
+                .device
+                .reset_tensor_by_slice(&mut p.gradient, &values);
+        }
+        (weights, grads)
+    }
+
+    fn round(&self, x: f64) -> f64 {
+        match self.dtype {
+            DType::F32 => x as f32 as f64,
+            _ => x,
+        }
+    }
+
+    // Weighted sum of the outputs, calculated in self.dtype without building
+    // gradients. The parameters are given as plain values.
+    fn evaluate<'dev, F>(&self, inputs: &Inputs<'dev>, f: &F, weights: &[f32]) -> f64
+    where
+        F: for<'a> Fn(&[Node<'a, 'dev>], &[Node<'a, 'dev>]) -> Node<'a, 'dev>,
+    {
+        let tensors = inputs.tensors(self.dtype);
+        let nodes = tensors.iter().map(Node::from).collect::<Vec<Node>>();
+        let (xs, ps) = nodes.split_at(inputs.num_inputs);
+        let y = no_grad(|| f(xs, ps));
+        y.forward();
+        let ys = match self.dtype {
+            DType::F64 => inputs.device.tensor_to_data::<f64>(&y.inner_value()),
+            _ => y.to_vec().into_iter().map(f64::from).collect(),
+        };
+        ys.iter().zip(weights).map(|(&y, &w)| y * w as f64).sum()
+    }
+
+    fn element(
+        &self,
+        argument: String,
+        index: usize,
+        analytic: f32,
+        numeric: f32,
+    ) -> GradCheckElement {
+        let error = (analytic - numeric).abs();
+        GradCheckElement {
+            argument,
+            index,
+            analytic,
+            numeric,
+            // NaN errors are always out of tolerance.
+            error: if error.is_nan() { f32::INFINITY } else { error },
+            tolerance: self.atol + self.rtol * numeric.abs(),
+        }
+    }
+}
+
+impl Default for GradCheck {
+    fn default() -> GradCheck {
+        GradCheck::new()
+    }
+}
+
+// Checks the gradients with the default tolerances.
+pub fn gradcheck<'dev, F>(
+    inputs: &[&Tensor<'dev>],
+    params: &mut [&mut Parameter<'dev>],
+    f: F,
+) -> GradCheckReport
+where
+    F: for<'a> Fn(&[Node<'a, 'dev>], &[Node<'a, 'dev>]) -> Node<'a, 'dev>,
+{
+    GradCheck::new().check(inputs, params, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::{gradcheck, GradCheck};
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
+    use crate::{initializers as I, Device, Node, Operator, Result, Shape, Tensor};
+
+    #[test]
+    fn check_gradcheck() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![3, 2], &I::Uniform::new(-1., 1.));
+        let mut b = dev.new_parameter(shape![3], &I::Uniform::new(-1., 1.));
+        let x = dev.new_tensor_by_slice(shape![2; 2], &[1., 2., -1., 0.5]);
+        let report = gradcheck(&[&x], &mut [&mut w, &mut b], |xs, ps| {
+            (ps[0].matmul(&xs[0]) + &ps[1]).tanh().exp()
+        });
+        assert!(report.passed(), "{}", report);
+        assert_eq!(4 + 6 + 3, report.num_elements);
+        assert_eq!(5, report.worst.len());
+        let w_grad = w.gradient.to_vec();
+        assert_eq!(vec![0.; 6], w_grad);
+    }
+
+    #[test]
+    fn check_gradcheck_f64() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![8, 6], &I::Uniform::new(-1., 1.));
+        let mut b = dev.new_parameter(shape![8], &I::Uniform::new(-1., 1.));
+        let x = dev.new_tensor_by_slice(shape![6; 3], &generate_values(18));
+        fn f<'a, 'dev>(xs: &[Node<'a, 'dev>], ps: &[Node<'a, 'dev>]) -> Node<'a, 'dev> {
+            (ps[0].matmul(&xs[0]) + &ps[1]).tanh().exp().sum(0) * 10.
+        }
+        // The output is too large for the f32 finite differences.
+        let report = gradcheck(&[&x], &mut [&mut w, &mut b], f);
+        assert!(!report.passed());
+        let report = GradCheck::new_f64().check(&[&x], &mut [&mut w, &mut b], f);
+        assert!(report.passed(), "{}", report);
+        assert_eq!(18 + 48 + 8, report.num_elements);
+    }
+
+    struct WrongSquare<'dev> {
+        device: &'dev Device<'dev>,
+    }
+
+    impl<'arg, 'dev> Operator<'arg, 'dev> for WrongSquare<'dev> {
+        fn name(&self) -> String {
+            "WrongSquare".to_string()
+        }
+
+        fn device(&self) -> &'dev Device<'dev> {
+            self.device
+        }
+
+        fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+            vec![x[0]]
+        }
+
+        fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+            y[0].replace(x[0] * x[0]);
+        }
+
+        // The correct gradient is 2 * x * gy.
+        fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+            *gx[0].borrow_mut() += x[0] * gy[0];
+        }
+    }
+
+    #[test]
+    fn check_gradcheck_wrong_backward() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3], &[1., 0., -5.]);
+        let check = GradCheck {
+            num_worst: 2,
+            ..GradCheck::new()
+        };
+        let report = check.check(&[&x], &mut [], |xs, _| {
+            Node::create(WrongSquare { device: &dev }, &[&xs[0]])
+                .pop()
+                .unwrap()
+        });
+        assert!(!report.passed());
+        assert_eq!(2, report.num_failures);
+        assert_eq!(2, report.worst.len());
+        assert_eq!("input 0", report.worst[0].argument);
+        assert_eq!(2, report.worst[0].index);
+        assert!(report.to_string().starts_with("gradcheck: 2 of 3 elements"));
+    }
+}
@@ -1,17 +1,139 @@
 use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::cmp;
 use std::cmp::{Ordering, Reverse};
-use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::rc::Rc;
+use std::thread::LocalKey;
 
+use crate::error::OrPanic;
+use crate::functions::BasicFunctions;
 use crate::operators as op;
-use crate::{Device, Operator, Parameter, Shape, Tensor};
+use crate::random::RandomizerState;
+use crate::{DType, Device, Error, Operator, Parameter, Result, Shape, Tensor};
+
+mod dump;
+mod grad;
+mod parallel;
+mod static_graph;
+
+pub use static_graph::{GraphPass, StaticGraph};
+
+thread_local! {
+    static NO_GRAD: Cell<bool> = const { Cell::new(false) };
+    static CHECKPOINT: Cell<bool> = const { Cell::new(false) };
+    static PARALLEL: Cell<bool> = const { Cell::new(false) };
+}
+
+// Restores a mode flag when the closure returns or panics.
+struct ModeGuard {
+    flag: &'static LocalKey<Cell<bool>>,
+    prev: bool,
+}
+
+impl ModeGuard {
+    fn enable(flag: &'static LocalKey<Cell<bool>>) -> ModeGuard {
+        let prev = flag.with(|flag| flag.replace(true));
+        ModeGuard { flag, prev }
+    }
+}
+
+impl Drop for ModeGuard {
+    fn drop(&mut self) {
+        let prev = self.prev;
+        self.flag.with(|flag| flag.set(prev));
+    }
+}
+
+// Runs f in inference mode. Nodes created in f are not differentiable, and
+// their intermediate values are released once consumed by the forward
+// calculation.
+pub fn no_grad<F: FnOnce() -> R, R>(f: F) -> R {
+    let _guard = ModeGuard::enable(&NO_GRAD);
+    f()
+}
+
+// Runs f with gradient checkpointing. Values of nodes created in f are
+// released once consumed by the forward calculation and recalculated from the
+// segment inputs during backward. Random values are replayed from the saved
+// randomizer state, so the device needs a randomizer that supports states
+// (e.g. Naive::with_seed) to release them.
+pub fn checkpoint<F: FnOnce() -> R, R>(f: F) -> R {
+    let _guard = ModeGuard::enable(&CHECKPOINT);
+    f()
+}
+
+// Runs f with the parallel scheduler. Forward and backward calculations
+// started in f run independent operators concurrently on the global rayon
+// thread pool, e.g. the two directions of a bidirectional RNN. Operators
+// without arguments still run in order, so random values are the same as
+// without the scheduler, and gradients are accumulated in the same order.
+// Operators that are not parallel safe (see Operator::as_parallel_safe) run
+// on the calling thread.
+pub fn parallel<F: FnOnce() -> R, R>(f: F) -> R {
+    let _guard = ModeGuard::enable(&PARALLEL);
+    f()
+}
+
+fn is_parallel_enabled() -> bool {
+    PARALLEL.with(|parallel| parallel.get())
+}
+
+pub fn is_grad_enabled() -> bool {
+    !NO_GRAD.with(|no_grad| no_grad.get())
+}
+
+// Maximum depth of the chain of nodes reported with graph errors.
+const TRACE_DEPTH: usize = 4;
 
 struct NodeData<'dev> {
     value: RefCell<Tensor<'dev>>,
     gradient: RefCell<Tensor<'dev>>,
+    label: RefCell<Option<String>>,
+}
+
+impl<'dev> NodeData<'dev> {
+    // The gradient takes the dtype of the value, which is known only after
+    // the forward calculation.
+    fn alloc_gradient(&self, k: f32) {
+        let value = self.value.borrow();
+        let mut grad = self.gradient.borrow_mut();
+        *grad = value
+            .device()
+            .new_tensor_with_dtype(value.shape, value.dtype());
+        grad.alloc();
+        grad.reset(k);
+    }
+
+    fn release_value(&self) {
+        let mut value = self.value.borrow_mut();
+        *value = value.device().new_tensor(value.shape);
+    }
+
+    fn release_gradient(&self) {
+        let mut grad = self.gradient.borrow_mut();
+        *grad = grad.device().new_tensor(grad.shape);
+    }
+
+    fn take_gradient(&self) -> Tensor<'dev> {
+        let mut grad = self.gradient.borrow_mut();
+        let empty = grad.device().new_tensor(grad.shape);
+        std::mem::replace(&mut *grad, empty)
+    }
+
+    fn set_gradient(&self, seed: &Tensor) {
+        let value = self.value.borrow();
+        assert!(
+            seed.shape == value.shape,
+            "the seed gradient has shape {:?}, expected {:?}",
+            seed.shape,
+            value.shape
+        );
+        assert!(seed.dtype() == value.dtype());
+        *self.gradient.borrow_mut() = value.device().copy_tensor(seed);
+    }
 }
 
+#[derive(Clone)]
 struct DataRef<'arg, 'dev>
 where
     'dev: 'arg,
@@ -20,12 +142,43 @@ where
     vid: usize,
 }
 
+impl<'arg, 'dev> DataRef<'arg, 'dev> {
+    fn describe(&self) -> String {
+        let mut name = self.op.operator.name();
+        if self.op.rets.len() > 1 {
+            name = format!("{}[{}]", name, self.vid);
+        }
+        let ret = &self.op.rets[self.vid];
+        let shape = ret.value.borrow().shape;
+        match &*ret.label.borrow() {
+            Some(label) => format!("{} \"{}\": {:?}", name, label, shape),
+            None => format!("{}: {:?}", name, shape),
+        }
+    }
+
+    // Appends the node and its ancestors, one line per node indented by depth.
+    fn trace(&self, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{}", "  ".repeat(depth), self.describe()));
+        if depth + 1 < TRACE_DEPTH {
+            for arg in &self.op.args {
+                arg.trace(depth + 1, lines);
+            }
+        }
+    }
+}
+
 struct OperatorInfo<'arg, 'dev> {
     operator: Box<dyn Operator<'arg, 'dev> + 'arg>,
     args: Vec<DataRef<'arg, 'dev>>,
     rets: Vec<NodeData<'arg>>,
     forwarded: Cell<bool>,
     depth: usize,
+    requires_grad: bool,
+    checkpointed: bool,
+    // Whether the values are kept by StaticGraph and never released.
+    pinned: Cell<bool>,
+    // Randomizer state before the first forward of a checkpointed source node.
+    rng_state: RefCell<Option<RandomizerState>>,
 }
 
 struct OperatorInfoCmp<'arg, 'dev>(Rc<OperatorInfo<'arg, 'dev>>);
@@ -58,12 +211,17 @@ impl<'arg, 'dev> OperatorInfo<'arg, 'dev> {
     fn new<T: Operator<'arg, 'dev> + 'arg>(
         op: T,
         xs: &[&Node<'arg, 'dev>],
-    ) -> OperatorInfo<'arg, 'dev> {
-        let arg_shapes = xs
-            .iter()
-            .map(|x| x.inner_value().shape)
-            .collect::<Vec<Shape>>();
-        let ret_shapes = op.forward_shape(&arg_shapes);
+    ) -> Result<OperatorInfo<'arg, 'dev>> {
+        let ret_shapes = OperatorInfo::check(&op, xs).map_err(|error| {
+            let mut trace = vec![];
+            for x in xs {
+                x.data.trace(0, &mut trace);
+            }
+            Error::Graph {
+                error: Box::new(error),
+                trace,
+            }
+        })?;
         let args = xs
             .iter()
             .map(|x| DataRef {
@@ -76,71 +234,199 @@ impl<'arg, 'dev> OperatorInfo<'arg, 'dev> {
             .map(|s| NodeData {
                 value: RefCell::new(op.device().new_tensor(s)),
                 gradient: RefCell::new(op.device().new_tensor(s)),
+                label: RefCell::new(None),
             })
             .collect::<Vec<NodeData>>();
         let depth = xs.iter().map(|x| x.data.op.depth).fold(0, cmp::max) + 1;
-        OperatorInfo {
+        Ok(OperatorInfo {
             operator: Box::new(op),
-            args: args,
-            rets: rets,
+            args,
+            rets,
             forwarded: Cell::new(false),
-            depth: depth,
+            depth,
+            requires_grad: is_grad_enabled(),
+            checkpointed: CHECKPOINT.with(|checkpoint| checkpoint.get()),
+            pinned: Cell::new(false),
+            rng_state: RefCell::new(None),
+        })
+    }
+
+    // Releases the values, which are recalculated by the next forward.
+    fn release_values(&self) {
+        if self.pinned.get() {
+            return;
+        }
+        for ret in &self.rets {
+            ret.release_value();
+        }
+        self.forwarded.set(false);
+    }
+
+    // Whether the values can be released once all consumers are calculated.
+    fn releasable(&self, consumed_without_grad: bool) -> bool {
+        if self.args.is_empty() {
+            // Source nodes can be released only if they can be replayed.
+            self.checkpointed && self.rng_state.borrow().is_some()
+        } else {
+            self.checkpointed || (consumed_without_grad && !self.requires_grad)
+        }
+    }
+
+    fn check<T: Operator<'arg, 'dev> + 'arg>(
+        op: &T,
+        xs: &[&Node<'arg, 'dev>],
+    ) -> Result<Vec<Shape>> {
+        if xs.iter().any(|x| x.device() != op.device()) {
+            return Err(Error::DeviceMismatch { op: op.name() });
         }
+        let arg_shapes = xs
+            .iter()
+            .map(|x| x.inner_value().shape)
+            .collect::<Vec<Shape>>();
+        op.try_forward_shape(&arg_shapes)
+            .map_err(|e| e.with_op(&op.name()))
     }
 }
 
-fn forward<'arg, 'dev>(op_info: Rc<OperatorInfo<'arg, 'dev>>) {
+fn forward<'arg, 'dev>(root: Rc<OperatorInfo<'arg, 'dev>>) {
     let mut reached = HashSet::new();
     let mut queue = VecDeque::new();
     let mut forward_req = BinaryHeap::new();
-    queue.push_back(op_info);
+    // For each operator: the number of consumers not processed yet, and
+    // whether every consumer is non-differentiable.
+    let mut consumers = HashMap::new();
+    queue.push_back(Rc::clone(&root));
     while let Some(op_info) = queue.pop_front() {
+        // The values of a forwarded operator are available, so the operators
+        // it depends on are not needed even if their values were released.
+        if op_info.forwarded.get() {
+            continue;
+        }
         for arg in &op_info.args {
             let ptr = &*arg.op as *const OperatorInfo<'arg, 'dev>;
             if !reached.contains(&ptr) {
                 queue.push_back(Rc::clone(&arg.op));
                 reached.insert(ptr);
             }
+            let entry = consumers.entry(ptr).or_insert((0, true));
+            entry.0 += 1;
+            entry.1 &= !op_info.requires_grad;
         }
         forward_req.push(Reverse(OperatorInfoCmp(op_info)));
     }
+    let mut order = vec![];
     while let Some(Reverse(OperatorInfoCmp(op_info))) = forward_req.pop() {
-        if op_info.forwarded.get() {
-            continue;
+        order.push(op_info);
+    }
+    let mut finish = |op_info: &Rc<OperatorInfo<'arg, 'dev>>| {
+        for arg in &op_info.args {
+            let ptr = &*arg.op as *const OperatorInfo<'arg, 'dev>;
+            let entry = consumers.get_mut(&ptr).unwrap();
+            entry.0 -= 1;
+            if entry.0 == 0 && arg.op.releasable(entry.1) {
+                arg.op.release_values();
+            }
         }
-        op_info.forwarded.set(true);
-        let xs = op_info
-            .args
-            .iter()
-            .map(|data| data.op.rets[data.vid].value.borrow())
-            .collect::<Vec<Ref<Tensor<'arg>>>>();
-        let mut ys = op_info
-            .rets
-            .iter()
-            .map(|ret| ret.value.borrow_mut())
-            .collect::<Vec<RefMut<Tensor<'arg>>>>();
-        let xs_ref = xs.iter().map(|x| &**x).collect::<Vec<&Tensor<'arg>>>();
-        let mut ys_ref = ys
-            .iter_mut()
-            .map(|y| &mut **y)
-            .collect::<Vec<&mut Tensor<'arg>>>();
-        op_info.operator.forward(&xs_ref, &mut ys_ref);
+    };
+    if is_parallel_enabled() {
+        parallel::forward(&order, &mut finish);
+    } else {
+        for op_info in &order {
+            forward_operator(op_info);
+            finish(op_info);
+        }
+    }
+}
+
+fn forward_operator<'arg, 'dev>(op_info: &OperatorInfo<'arg, 'dev>) {
+    forward_operator_with(op_info, &op_info.args);
+}
+
+// Calculates the operator with the given arguments instead of its own, which
+// must have the same values.
+fn forward_operator_with<'arg, 'dev>(
+    op_info: &OperatorInfo<'arg, 'dev>,
+    args: &[DataRef<'arg, 'dev>],
+) {
+    if op_info.forwarded.get() {
+        return;
+    }
+    op_info.forwarded.set(true);
+    let device = op_info.operator.device();
+    let replay = if op_info.checkpointed && args.is_empty() {
+        let saved = op_info.rng_state.borrow().clone();
+        match saved {
+            // The state was saved, so the randomizer supports it.
+            Some(state) => {
+                let current = device.randomizer_state().unwrap();
+                device.set_randomizer_state(&state).unwrap();
+                Some(current)
+            }
+            None => {
+                *op_info.rng_state.borrow_mut() = device.randomizer_state();
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let xs = args
+        .iter()
+        .map(|data| data.op.rets[data.vid].value.borrow())
+        .collect::<Vec<Ref<Tensor<'arg>>>>();
+    let xs_ref = xs.iter().map(|x| &**x).collect::<Vec<&Tensor<'arg>>>();
+    forward_values(op_info, &xs_ref);
+    if let Some(state) = replay {
+        device.set_randomizer_state(&state).unwrap();
+    }
+}
+
+fn forward_values<'arg, 'dev>(op_info: &OperatorInfo<'arg, 'dev>, xs: &[&Tensor<'arg>]) {
+    let mut ys = op_info
+        .rets
+        .iter()
+        .map(|ret| ret.value.borrow_mut())
+        .collect::<Vec<RefMut<Tensor<'arg>>>>();
+    let mut ys_ref = ys
+        .iter_mut()
+        .map(|y| &mut **y)
+        .collect::<Vec<&mut Tensor<'arg>>>();
+    op_info.operator.forward(xs, &mut ys_ref);
+}
+
+// Recalculates values released by the forward calculation.
+fn recompute<'arg, 'dev>(op_info: &OperatorInfo<'arg, 'dev>) {
+    if op_info.forwarded.get() {
+        return;
     }
+    for arg in &op_info.args {
+        recompute(&arg.op);
+    }
+    forward_operator(op_info);
 }
 
-fn backward<'arg, 'dev>(op_info: Rc<OperatorInfo<'arg, 'dev>>) {
+// Without a seed, the gradients of all outputs of the root are set to 1.
+fn backward<'arg, 'dev>(root: Rc<OperatorInfo<'arg, 'dev>>, seed: Option<(usize, &Tensor)>) {
     let mut reached = HashSet::new();
     let mut queue = VecDeque::new();
     let mut backward_req = BinaryHeap::new();
-    {
-        for ret in &op_info.rets {
-            let mut grad = ret.gradient.borrow_mut();
-            grad.alloc();
-            grad.reset(1.);
+    assert!(
+        root.requires_grad,
+        "backward() is called on a node created in no_grad mode"
+    );
+    for (i, ret) in root.rets.iter().enumerate() {
+        match seed {
+            Some((vid, seed)) if vid == i => ret.set_gradient(seed),
+            Some(_) => ret.alloc_gradient(0.),
+            None => ret.alloc_gradient(1.),
         }
     }
-    queue.push_back(op_info);
+    queue.push_back(Rc::clone(&root));
     while let Some(op_info) = queue.pop_front() {
+        // Nodes created in no_grad mode are treated as constants.
+        if !op_info.requires_grad {
+            continue;
+        }
         for arg in &op_info.args {
             let ptr = &*arg.op as *const OperatorInfo<'arg, 'dev>;
             if !reached.contains(&ptr) {
@@ -150,39 +436,148 @@ fn backward<'arg, 'dev>(op_info: Rc<OperatorInfo<'arg, 'dev>>) {
         }
         backward_req.push(OperatorInfoCmp(op_info));
     }
+    let mut order = vec![];
     while let Some(OperatorInfoCmp(op_info)) = backward_req.pop() {
-        let xs = op_info
-            .args
-            .iter()
-            .map(|data| data.op.rets[data.vid].value.borrow())
-            .collect::<Vec<Ref<Tensor<'arg>>>>();
-        let ys = op_info
-            .rets
-            .iter()
-            .map(|ret| ret.value.borrow())
-            .collect::<Vec<Ref<Tensor<'arg>>>>();
-        let gys = op_info
-            .rets
-            .iter()
-            .map(|ret| ret.gradient.borrow())
-            .collect::<Vec<Ref<Tensor<'arg>>>>();
-        let gxs = op_info
+        order.push(op_info);
+    }
+    // All consumers of an intermediate node are deeper than the node and
+    // have already been processed, so its gradient and value are no longer
+    // used. Released values are recalculated by the next forward.
+    let mut finish = |op_info: &Rc<OperatorInfo<'arg, 'dev>>| {
+        if op_info.args.is_empty() {
+            return;
+        }
+        for ret in &op_info.rets {
+            ret.release_gradient();
+        }
+        if !Rc::ptr_eq(op_info, &root) {
+            op_info.release_values();
+        }
+    };
+    if is_parallel_enabled() {
+        parallel::backward(&order, &mut finish);
+    } else {
+        for op_info in &order {
+            for arg in &op_info.args {
+                recompute(&arg.op);
+            }
+            recompute(op_info);
+            backward_operator(op_info);
+            finish(op_info);
+        }
+    }
+}
+
+fn backward_operator<'arg, 'dev>(op_info: &OperatorInfo<'arg, 'dev>) {
+    let xs = op_info
+        .args
+        .iter()
+        .map(|data| data.op.rets[data.vid].value.borrow())
+        .collect::<Vec<Ref<Tensor<'arg>>>>();
+    let ys = op_info
+        .rets
+        .iter()
+        .map(|ret| ret.value.borrow())
+        .collect::<Vec<Ref<Tensor<'arg>>>>();
+    let gys = op_info
+        .rets
+        .iter()
+        .map(|ret| ret.gradient.borrow())
+        .collect::<Vec<Ref<Tensor<'arg>>>>();
+    let gxs = op_info
+        .args
+        .iter()
+        .map(|data| {
+            let ret = &data.op.rets[data.vid];
+            if !ret.gradient.borrow().valid() {
+                ret.alloc_gradient(0.);
+            }
+            &ret.gradient
+        })
+        .collect::<Vec<&RefCell<Tensor<'arg>>>>();
+    let xs_ref = xs.iter().map(|x| &**x).collect::<Vec<&Tensor<'arg>>>();
+    let ys_ref = ys.iter().map(|y| &**y).collect::<Vec<&Tensor<'arg>>>();
+    let gys_ref = gys.iter().map(|gy| &**gy).collect::<Vec<&Tensor<'arg>>>();
+    op_info.operator.backward(&xs_ref, &ys_ref, &gys_ref, &gxs);
+}
+
+// Calculates only the gradients of the targets. Operators that do not depend
+// on any target are skipped, and parameters are not updated.
+fn gradients<'arg, 'dev>(root: &Node<'arg, 'dev>, xs: &[&Node<'arg, 'dev>]) -> Vec<Tensor<'arg>> {
+    assert!(
+        root.data.op.requires_grad,
+        "gradients() is called on a node created in no_grad mode"
+    );
+    root.forward();
+    let root_op = &root.data.op;
+    let ptr = |op_info: &OperatorInfo| op_info as *const OperatorInfo as *const ();
+    let targets = xs
+        .iter()
+        .map(|x| ptr(&x.data.op))
+        .collect::<HashSet<*const ()>>();
+    let ops = grad::collect(root_op, &targets);
+    let relevant = ops
+        .iter()
+        .map(|op_info| ptr(op_info))
+        .collect::<HashSet<*const ()>>();
+    // Gradients left by previous calculations must not be accumulated.
+    for op_info in &ops {
+        for ret in &op_info.rets {
+            ret.release_gradient();
+        }
+    }
+    for (i, ret) in root_op.rets.iter().enumerate() {
+        ret.alloc_gradient(if i == root.data.vid { 1. } else { 0. });
+    }
+    let mut found = HashMap::new();
+    for op_info in &ops {
+        if op_info
             .args
             .iter()
-            .map(|data| {
-                let mut gx = data.op.rets[data.vid].gradient.borrow_mut();
-                if !gx.valid() {
-                    gx.alloc();
-                    gx.reset(0.);
-                }
-                &data.op.rets[data.vid].gradient
-            })
-            .collect::<Vec<&RefCell<Tensor<'arg>>>>();
-        let xs_ref = xs.iter().map(|x| &**x).collect::<Vec<&Tensor<'arg>>>();
-        let ys_ref = ys.iter().map(|y| &**y).collect::<Vec<&Tensor<'arg>>>();
-        let gys_ref = gys.iter().map(|gy| &**gy).collect::<Vec<&Tensor<'arg>>>();
-        op_info.operator.backward(&xs_ref, &ys_ref, &gys_ref, &gxs);
+            .any(|arg| relevant.contains(&ptr(&arg.op)))
+        {
+            for arg in &op_info.args {
+                recompute(&arg.op);
+            }
+            recompute(op_info);
+            backward_operator(op_info);
+        }
+        // All consumers have been processed, so the gradients are complete.
+        for (i, ret) in op_info.rets.iter().enumerate() {
+            if targets.contains(&ptr(op_info)) && ret.gradient.borrow().valid() {
+                found.insert((ptr(op_info), i), ret.take_gradient());
+            } else {
+                ret.release_gradient();
+            }
+        }
+        if !op_info.args.is_empty() && !Rc::ptr_eq(op_info, root_op) {
+            op_info.release_values();
+        }
+    }
+    // Arguments that do not depend on the targets also received gradients.
+    for op_info in &ops {
+        for arg in &op_info.args {
+            arg.op.rets[arg.vid].release_gradient();
+        }
+    }
+    for ret in &root_op.rets {
+        ret.release_gradient();
     }
+    xs.iter()
+        .map(|x| {
+            let device = x.device();
+            match found.get(&(ptr(&x.data.op), x.data.vid)) {
+                Some(g) => device.copy_tensor(g),
+                None => {
+                    let value = x.inner_value();
+                    let mut g = device.new_tensor_with_dtype(value.shape, value.dtype());
+                    g.alloc();
+                    g.reset(0.);
+                    g
+                }
+            }
+        })
+        .collect()
 }
 
 impl<'arg, 'dev> Node<'arg, 'dev> {
@@ -190,15 +585,22 @@ impl<'arg, 'dev> Node<'arg, 'dev> {
         op: T,
         xs: &[&Node<'arg, 'dev>],
     ) -> Vec<Node<'arg, 'dev>> {
-        let op_info = Rc::new(OperatorInfo::new(op, xs));
-        (0..op_info.rets.len())
+        Node::try_create(op, xs).or_panic()
+    }
+
+    pub fn try_create<T: Operator<'arg, 'dev> + 'arg>(
+        op: T,
+        xs: &[&Node<'arg, 'dev>],
+    ) -> Result<Vec<Node<'arg, 'dev>>> {
+        let op_info = Rc::new(OperatorInfo::new(op, xs)?);
+        Ok((0..op_info.rets.len())
             .map(|i| Node {
                 data: DataRef {
                     op: Rc::clone(&op_info),
                     vid: i,
                 },
             })
-            .collect::<Vec<Node<'arg, 'dev>>>()
+            .collect::<Vec<Node<'arg, 'dev>>>())
     }
 
     pub fn inner_value(&self) -> Ref<Tensor> {
@@ -212,13 +614,72 @@ impl<'arg, 'dev> Node<'arg, 'dev> {
     pub fn device(&self) -> &'dev Device<'dev> {
         self.data.op.operator.device()
     }
+
+    // Sets a label shown with the operator name in graph errors.
+    pub fn named(self, label: &str) -> Self {
+        *self.data.op.rets[self.data.vid].label.borrow_mut() = Some(label.to_string());
+        self
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.op.rets[self.data.vid].label.borrow().clone()
+    }
+
+    pub fn operator_name(&self) -> String {
+        self.data.op.operator.name()
+    }
+
+    pub fn requires_grad(&self) -> bool {
+        self.data.op.requires_grad
+    }
+
+    // Graphviz DOT representation of the graph reachable from this node.
+    pub fn dump_dot(&self) -> String {
+        dump::dump_dot(&self.data.op)
+    }
+
+    pub fn dump_json(&self) -> String {
+        dump::dump_json(&self.data.op)
+    }
+
     pub fn forward(&self) {
         forward(Rc::clone(&self.data.op));
     }
 
+    // Gradients of this node with respect to xs, built as nodes that can be
+    // differentiated again.
+    pub fn grad(&self, xs: &[&Node<'arg, 'dev>]) -> Vec<Node<'arg, 'dev>> {
+        grad::grad(self, xs)
+    }
+
     pub fn backward(&self) {
         self.forward();
-        backward(Rc::clone(&self.data.op));
+        backward(Rc::clone(&self.data.op), None);
+    }
+
+    // Backpropagates the given gradient of this node, e.g. to calculate
+    // vector-Jacobian products of non-scalar nodes.
+    pub fn backward_with(&self, seed: &Tensor) {
+        self.forward();
+        backward(Rc::clone(&self.data.op), Some((self.data.vid, seed)));
+    }
+
+    // Gradients of this node with respect to xs. Parameter gradients are not
+    // changed.
+    pub fn gradients(&self, xs: &[&Node<'arg, 'dev>]) -> Vec<Tensor<'arg>> {
+        gradients(self, xs)
+    }
+}
+
+// Clones refer to the same node in the graph.
+impl<'arg, 'dev> Clone for Node<'arg, 'dev> {
+    fn clone(&self) -> Self {
+        Node {
+            data: DataRef {
+                op: Rc::clone(&self.data.op),
+                vid: self.data.vid,
+            },
+        }
     }
 }
 
@@ -239,3 +700,262 @@ impl<'arg, 'dev> From<&'arg mut Parameter<'dev>> for Node<'arg, 'dev> {
         Node::create(op::Parameter::new(item), &[]).pop().unwrap()
     }
 }
+
+// Uses the parameter as a constant, e.g. for inference with a model shared
+// by several threads. The gradient is not accumulated.
+impl<'arg, 'dev> From<&'arg Parameter<'dev>> for Node<'arg, 'dev> {
+    fn from(item: &'arg Parameter<'dev>) -> Self {
+        let node = Node::create(op::Input::new(&item.value), &[])
+            .pop()
+            .unwrap();
+        match item.compute_dtype() {
+            DType::F32 => node,
+            dtype => node.cast(dtype),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::operators::CustomFunction;
+    use crate::{checkpoint, initializers as I, no_grad, Node};
+
+    // Square whose forward calculations are counted.
+    fn counted_square(count: &AtomicUsize) -> CustomFunction<'_> {
+        CustomFunction::new("CountedSquare")
+            .forward(move |xs| {
+                count.fetch_add(1, Ordering::Relaxed);
+                vec![xs[0] * xs[0]]
+            })
+            .backward(|xs, _ys, gys| vec![Some(2. * xs[0] * gys[0])])
+    }
+
+    #[test]
+    fn check_backward_releases_intermediates() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(2.));
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let w = Node::from(&mut w);
+        let x = Node::from(&x);
+        let h = w.matmul(&x);
+        let y = (&h * &h).sum(0);
+        y.backward();
+        assert!(!h.inner_value().valid());
+        assert!(!h.inner_gradient().valid());
+        assert!(y.inner_value().valid());
+        assert!(x.inner_value().valid());
+        assert!(x.inner_gradient().valid());
+        let x_grad = x.inner_gradient().to_vec();
+        assert_eq!(vec![48., 48.], x_grad);
+        assert_eq!(72., y.to_float());
+        assert_eq!(vec![6., 6.], h.to_vec());
+        assert!(h.inner_value().valid());
+    }
+
+    #[test]
+    fn check_backward_with() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3], &[1., 2., 3.]);
+        let seed = dev.new_tensor_by_slice(shape![3], &[1., -1., 2.]);
+        let x = Node::from(&x);
+        let y = &x * &x;
+        y.backward_with(&seed);
+        let x_grad = x.inner_gradient().to_vec();
+        assert_eq!(vec![2., -4., 12.], x_grad);
+    }
+
+    #[test]
+    #[should_panic(expected = "seed gradient")]
+    fn check_backward_with_wrong_shape() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3], &[1., 2., 3.]);
+        let seed = dev.new_tensor_by_slice(shape![2], &[1., 1.]);
+        let x = Node::from(&x);
+        let y = &x * &x;
+        y.backward_with(&seed);
+    }
+
+    #[test]
+    fn check_gradients() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(2.));
+        w.reset_gradient();
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let z = dev.new_tensor_by_slice(shape![2], &[3., 4.]);
+        {
+            let w = Node::from(&mut w);
+            let x = Node::from(&x);
+            let z = Node::from(&z);
+            let h = w.matmul(&x);
+            let y = (&h * &h).sum(0) + (&z * &z).sum(0);
+            let gs = y.gradients(&[&x, &h, &x]);
+            assert_eq!(vec![48., 48.], gs[0].to_vec());
+            assert_eq!(vec![12., 12.], gs[1].to_vec());
+            assert_eq!(vec![48., 48.], gs[2].to_vec());
+            // The branch of z does not depend on the targets.
+            assert!(!z.inner_gradient().valid());
+            assert!(!x.inner_gradient().valid());
+            // Repeated calls do not accumulate the gradients.
+            let gs = y.gradients(&[&x]);
+            assert_eq!(vec![48., 48.], gs[0].to_vec());
+        }
+        assert_eq!(vec![0., 0., 0., 0.], w.gradient.to_vec());
+    }
+
+    #[test]
+    fn check_gradients_unrelated() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let z = dev.new_tensor_by_slice(shape![3], &[3., 4., 5.]);
+        let x = Node::from(&x);
+        let z = Node::from(&z);
+        let y = x.sum(0);
+        let gs = y.gradients(&[&z, &y]);
+        assert_eq!(vec![0., 0., 0.], gs[0].to_vec());
+        assert_eq!(1., gs[1].to_float());
+    }
+
+    #[test]
+    fn check_no_grad() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(2.));
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let w = Node::from(&mut w);
+        let x = Node::from(&x);
+        let (h, y) = no_grad(|| {
+            assert!(!crate::is_grad_enabled());
+            let h = w.matmul(&x);
+            let y = (&h * &h).sum(0);
+            (h, y)
+        });
+        assert!(crate::is_grad_enabled());
+        assert!(!y.requires_grad());
+        assert_eq!(72., y.to_float());
+        assert!(!h.inner_value().valid());
+        assert!(!y.inner_gradient().valid());
+        assert_eq!(vec![6., 6.], h.to_vec());
+    }
+
+    #[test]
+    fn check_no_grad_constant() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(2.));
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let w = Node::from(&mut w);
+        let x = Node::from(&x);
+        let h = no_grad(|| &x * 2.);
+        let y = w.matmul(&h).sum(0);
+        assert!(y.requires_grad());
+        y.backward();
+        assert!(!x.inner_gradient().valid());
+        let w_grad = w.inner_gradient().to_vec();
+        assert_eq!(vec![2., 2., 4., 4.], w_grad);
+    }
+
+    #[test]
+    #[should_panic(expected = "no_grad mode")]
+    fn check_no_grad_backward() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let x = Node::from(&x);
+        let y = no_grad(|| x.sum(0));
+        y.backward();
+    }
+
+    #[test]
+    fn check_checkpoint() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(0.5));
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let w = Node::from(&mut w);
+        let x = Node::from(&x);
+        let (h1, h2) = checkpoint(|| {
+            let h1 = w.matmul(&x).tanh();
+            let h2 = w.matmul(&h1).tanh();
+            (h1, h2)
+        });
+        let y = (&h2 * &h2).sum(0);
+        assert!(y.to_float() > 0.);
+        assert!(!h1.inner_value().valid());
+        assert!(!h2.inner_value().valid());
+        y.backward();
+        let x_grad = x.inner_gradient().to_vec();
+
+        let dev2 = D::Naive::new();
+        let mut w2 = dev2.new_parameter(shape![2, 2], &I::Constant::new(0.5));
+        let x2 = dev2.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let w2 = Node::from(&mut w2);
+        let x2 = Node::from(&x2);
+        let h = w2.matmul(w2.matmul(&x2).tanh()).tanh();
+        let y2 = (&h * &h).sum(0);
+        y2.backward();
+        let expected_x_grad = x2.inner_gradient().to_vec();
+        assert_vector_ulps_eq!(expected_x_grad, x_grad);
+        let w_grad = w.inner_gradient().to_vec();
+        let expected_w_grad = w2.inner_gradient().to_vec();
+        assert_vector_ulps_eq!(expected_w_grad, w_grad);
+    }
+
+    #[test]
+    fn check_checkpoint_random_replay() {
+        let dev = D::Naive::with_seed(42);
+        let x = dev.new_tensor_by_constant(shape![100], 1.);
+        let x = Node::from(&x);
+        let (mask, y) = checkpoint(|| {
+            let mask = Node::random_bernoulli(&dev, shape![100], 0.5);
+            let y = (&x * &mask).sum(0);
+            (mask, y)
+        });
+        y.forward();
+        assert!(!mask.inner_value().valid());
+        let state = dev.randomizer_state();
+        y.backward();
+        assert_eq!(state, dev.randomizer_state());
+        let x_grad = x.inner_gradient().to_vec();
+        let y_val = y.to_float();
+        assert_eq!(y_val, x_grad.iter().sum::<f32>());
+        assert!(x_grad.iter().all(|&g| g == 0. || g == 1.));
+    }
+
+    #[test]
+    fn check_checkpoint_recomputes_once() {
+        let dev = D::Naive::new();
+        let count = AtomicUsize::new(0);
+        let square = counted_square(&count);
+        let mut w = dev.new_parameter(shape![2], &I::Constant::new(0.5));
+        let w = Node::from(&mut w);
+        let h = checkpoint(|| square.call(&[&w]).pop().unwrap());
+        let y = (&h * 3.).sum(0);
+        y.forward();
+        assert!(!h.inner_value().valid());
+        assert_eq!(1.5, y.to_float());
+        y.backward();
+        // Once in the forward calculation and once in the backward.
+        assert_eq!(2, count.load(Ordering::Relaxed));
+        assert_eq!(vec![3., 3.], w.inner_gradient().to_vec());
+    }
+
+    #[test]
+    fn check_no_grad_forward_once() {
+        let dev = D::Naive::new();
+        let count = AtomicUsize::new(0);
+        let square = counted_square(&count);
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let x = Node::from(&x);
+        let (h, y) = no_grad(|| {
+            let h = square.call(&[&x]).pop().unwrap();
+            let y = h.sum(0);
+            (h, y)
+        });
+        y.forward();
+        assert!(!h.inner_value().valid());
+        y.forward();
+        assert_eq!(5., y.to_float());
+        assert_eq!(vec![5.], y.to_vec());
+        assert_eq!(1, count.load(Ordering::Relaxed));
+    }
+}
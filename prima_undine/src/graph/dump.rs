@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use super::OperatorInfo;
+use crate::Shape;
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Parameter,
+    Input,
+    Source,
+    Operator,
+}
+
+impl Kind {
+    fn of(op_info: &OperatorInfo) -> Kind {
+        if op_info.operator.is_parameter() {
+            Kind::Parameter
+        } else if op_info.operator.is_input() {
+            Kind::Input
+        } else if op_info.args.is_empty() {
+            Kind::Source
+        } else {
+            Kind::Operator
+        }
+    }
+}
+
+// Operators reachable from the root, ordered by depth. Ties keep the order
+// of discovery so that the output is deterministic.
+fn collect<'arg, 'dev>(root: &Rc<OperatorInfo<'arg, 'dev>>) -> Vec<Rc<OperatorInfo<'arg, 'dev>>> {
+    let mut reached = HashSet::new();
+    let mut queue = VecDeque::new();
+    let mut ops = vec![];
+    reached.insert(&**root as *const OperatorInfo<'arg, 'dev>);
+    queue.push_back(Rc::clone(root));
+    while let Some(op_info) = queue.pop_front() {
+        for arg in &op_info.args {
+            let ptr = &*arg.op as *const OperatorInfo<'arg, 'dev>;
+            if !reached.contains(&ptr) {
+                queue.push_back(Rc::clone(&arg.op));
+                reached.insert(ptr);
+            }
+        }
+        ops.push(op_info);
+    }
+    ops.sort_by_key(|op_info| op_info.depth);
+    ops
+}
+
+fn ids(ops: &[Rc<OperatorInfo>]) -> HashMap<*const (), usize> {
+    ops.iter()
+        .enumerate()
+        .map(|(i, op_info)| (&**op_info as *const OperatorInfo as *const (), i))
+        .collect()
+}
+
+fn id_of(ids: &HashMap<*const (), usize>, op_info: &OperatorInfo) -> usize {
+    ids[&(op_info as *const OperatorInfo as *const ())]
+}
+
+// Splits "Slice(dim=1,lower=0,upper=2)" into "Slice" and "dim=1,lower=0,upper=2".
+fn split_name(name: &str) -> (&str, &str) {
+    match name.find('(') {
+        Some(pos) if name.ends_with(')') => (&name[..pos], &name[pos + 1..name.len() - 1]),
+        _ => (name, ""),
+    }
+}
+
+#[derive(Serialize)]
+struct JsonGraph {
+    nodes: Vec<JsonNode>,
+    edges: Vec<JsonEdge>,
+}
+
+#[derive(Serialize)]
+struct JsonNode {
+    id: usize,
+    operator: String,
+    params: String,
+    kind: Kind,
+    depth: usize,
+    outputs: Vec<JsonOutput>,
+}
+
+#[derive(Serialize)]
+struct JsonOutput {
+    dims: Vec<u32>,
+    batch: u32,
+    label: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonEdge {
+    from: usize,
+    output: usize,
+    to: usize,
+    input: usize,
+}
+
+fn escape(s: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(ret, "\\u{:04x}", c as u32).unwrap(),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn shape_to_string(shape: Shape) -> String {
+    let dims = shape
+        .dims()
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>()
+        .join("x");
+    if shape.batch() == 1 {
+        format!("[{}]", dims)
+    } else {
+        format!("[{}]x{}", dims, shape.batch())
+    }
+}
+
+pub(super) fn dump_dot(root: &Rc<OperatorInfo>) -> String {
+    let ops = collect(root);
+    let ids = ids(&ops);
+    let mut ret = String::from("digraph {\n");
+    for (i, op_info) in ops.iter().enumerate() {
+        let mut label = op_info.operator.name();
+        for data in &op_info.rets {
+            label.push('\n');
+            if let Some(name) = &*data.label.borrow() {
+                label.push_str(&format!("{}: ", name));
+            }
+            label.push_str(&shape_to_string(data.value.borrow().shape));
+        }
+        let style = match Kind::of(op_info) {
+            Kind::Parameter => "shape=box, style=filled, fillcolor=lightblue",
+            Kind::Input => "shape=box, style=filled, fillcolor=lightgray",
+            Kind::Source => "shape=box",
+            Kind::Operator => "shape=ellipse",
+        };
+        writeln!(ret, "  n{} [label=\"{}\", {}];", i, escape(&label), style).unwrap();
+    }
+    for (i, op_info) in ops.iter().enumerate() {
+        for arg in &op_info.args {
+            let from = id_of(&ids, &arg.op);
+            if arg.op.rets.len() > 1 {
+                writeln!(ret, "  n{} -> n{} [label=\"{}\"];", from, i, arg.vid).unwrap();
+            } else {
+                writeln!(ret, "  n{} -> n{};", from, i).unwrap();
+            }
+        }
+    }
+    ret.push_str("}\n");
+    ret
+}
+
+pub(super) fn dump_json(root: &Rc<OperatorInfo>) -> String {
+    let ops = collect(root);
+    let ids = ids(&ops);
+    let mut graph = JsonGraph {
+        nodes: vec![],
+        edges: vec![],
+    };
+    for (i, op_info) in ops.iter().enumerate() {
+        let name = op_info.operator.name();
+        let (operator, params) = split_name(&name);
+        let outputs = op_info
+            .rets
+            .iter()
+            .map(|data| {
+                let shape = data.value.borrow().shape;
+                JsonOutput {
+                    dims: shape.dims().to_vec(),
+                    batch: shape.batch(),
+                    label: data.label.borrow().clone(),
+                }
+            })
+            .collect();
+        graph.nodes.push(JsonNode {
+            id: i,
+            operator: operator.to_string(),
+            params: params.to_string(),
+            kind: Kind::of(op_info),
+            depth: op_info.depth,
+            outputs,
+        });
+        for (j, arg) in op_info.args.iter().enumerate() {
+            graph.edges.push(JsonEdge {
+                from: id_of(&ids, &arg.op),
+                output: arg.vid,
+                to: i,
+                input: j,
+            });
+        }
+    }
+    serde_json::to_string(&graph).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::{initializers as I, Node};
+
+    #[test]
+    fn check_dump_dot() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(1.));
+        let x = dev.new_tensor_by_constant(shape![2], 1.);
+        let w = Node::from(&mut w);
+        let x = Node::from(&x).named("x");
+        let y = w.matmul(&x);
+        assert_eq!(
+            "digraph {\n  \
+             n0 [label=\"Parameter\\n[2x2]\", shape=box, style=filled, fillcolor=lightblue];\n  \
+             n1 [label=\"Input\\nx: [2]\", shape=box, style=filled, fillcolor=lightgray];\n  \
+             n2 [label=\"Matmul\\n[2]\", shape=ellipse];\n  \
+             n0 -> n2;\n  \
+             n1 -> n2;\n\
+             }\n",
+            y.dump_dot()
+        );
+    }
+
+    #[test]
+    fn check_dump_json() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_constant(shape![4; 3], 1.);
+        let x = Node::from(&x);
+        let ys = x.split(0, 2);
+        let y = ys[1].slice(0, 0, 1);
+        assert_eq!(
+            concat!(
+                "{\"nodes\":[",
+                "{\"id\":0,\"operator\":\"Input\",\"params\":\"\",\"kind\":\"input\",\"depth\":1,",
+                "\"outputs\":[{\"dims\":[4],\"batch\":3,\"label\":null}]},",
+                "{\"id\":1,\"operator\":\"Split\",\"params\":\"dim=0,n=2\",\"kind\":\"operator\",\"depth\":2,",
+                "\"outputs\":[{\"dims\":[2],\"batch\":3,\"label\":null},{\"dims\":[2],\"batch\":3,\"label\":null}]},",
+                "{\"id\":2,\"operator\":\"Slice\",\"params\":\"dim=0,lower=0,upper=1\",\"kind\":\"operator\",\"depth\":3,",
+                "\"outputs\":[{\"dims\":[],\"batch\":3,\"label\":null}]}",
+                "],\"edges\":[",
+                "{\"from\":0,\"output\":0,\"to\":1,\"input\":0},",
+                "{\"from\":1,\"output\":1,\"to\":2,\"input\":0}",
+                "]}"
+            ),
+            y.dump_json()
+        );
+    }
+}
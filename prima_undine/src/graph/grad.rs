@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::{DataRef, OperatorInfo};
+use crate::functions::BasicFunctions;
+use crate::Node;
+
+fn node<'arg, 'dev>(op: &Rc<OperatorInfo<'arg, 'dev>>, vid: usize) -> Node<'arg, 'dev> {
+    Node {
+        data: DataRef {
+            op: Rc::clone(op),
+            vid,
+        },
+    }
+}
+
+fn key(op: &OperatorInfo, vid: usize) -> (*const (), usize) {
+    (op as *const OperatorInfo as *const (), vid)
+}
+
+// Differentiable operators between the targets and the root, ordered from
+// the root.
+pub(super) fn collect<'arg, 'dev>(
+    root: &Rc<OperatorInfo<'arg, 'dev>>,
+    targets: &HashSet<*const ()>,
+) -> Vec<Rc<OperatorInfo<'arg, 'dev>>> {
+    let mut reached = HashSet::new();
+    let mut stack = vec![Rc::clone(root)];
+    let mut ops = vec![];
+    reached.insert(&**root as *const OperatorInfo as *const ());
+    while let Some(op_info) = stack.pop() {
+        if !op_info.requires_grad {
+            continue;
+        }
+        for arg in &op_info.args {
+            let ptr = &*arg.op as *const OperatorInfo as *const ();
+            if reached.insert(ptr) {
+                stack.push(Rc::clone(&arg.op));
+            }
+        }
+        ops.push(op_info);
+    }
+    ops.sort_by_key(|op_info| op_info.depth);
+    // Operators that depend on one of the targets.
+    let mut relevant = HashSet::new();
+    for op_info in &ops {
+        let ptr = &**op_info as *const OperatorInfo as *const ();
+        if targets.contains(&ptr)
+            || op_info
+                .args
+                .iter()
+                .any(|arg| relevant.contains(&(&*arg.op as *const OperatorInfo as *const ())))
+        {
+            relevant.insert(ptr);
+        }
+    }
+    ops.retain(|op_info| relevant.contains(&(&**op_info as *const OperatorInfo as *const ())));
+    ops.reverse();
+    ops
+}
+
+type Gradients<'arg, 'dev> = HashMap<(*const (), usize), Node<'arg, 'dev>>;
+
+// Adds the gradients of the arguments of an operator.
+fn propagate<'arg, 'dev>(
+    op_info: &Rc<OperatorInfo<'arg, 'dev>>,
+    gys: &[Node<'arg, 'dev>],
+    grads: &mut Gradients<'arg, 'dev>,
+) {
+    let xs = op_info
+        .args
+        .iter()
+        .map(|arg| node(&arg.op, arg.vid))
+        .collect::<Vec<Node>>();
+    let ys = (0..op_info.rets.len())
+        .map(|i| node(op_info, i))
+        .collect::<Vec<Node>>();
+    let gxs = op_info
+        .operator
+        .backward_node(
+            &xs.iter().collect::<Vec<&Node>>(),
+            &ys.iter().collect::<Vec<&Node>>(),
+            &gys.iter().collect::<Vec<&Node>>(),
+        )
+        .unwrap_or_else(|| {
+            panic!(
+                "{} does not support higher-order gradients",
+                op_info.operator.name()
+            )
+        });
+    for ((arg, x), gx) in op_info.args.iter().zip(&xs).zip(gxs) {
+        if let Some(gx) = gx {
+            assert!(gx.shape() == x.shape());
+            let k = key(&arg.op, arg.vid);
+            let sum = match grads.remove(&k) {
+                Some(prev) => prev + gx,
+                None => gx,
+            };
+            grads.insert(k, sum);
+        }
+    }
+}
+
+pub(super) fn grad<'arg, 'dev>(
+    root: &Node<'arg, 'dev>,
+    xs: &[&Node<'arg, 'dev>],
+) -> Vec<Node<'arg, 'dev>> {
+    assert!(
+        root.data.op.requires_grad,
+        "grad() is called on a node created in no_grad mode"
+    );
+    root.forward();
+    let device = root.device();
+    let targets = xs
+        .iter()
+        .map(|x| &*x.data.op as *const OperatorInfo as *const ())
+        .collect::<HashSet<*const ()>>();
+    let mut grads = HashMap::new();
+    grads.insert(
+        key(&root.data.op, root.data.vid),
+        Node::constant(device, root.shape(), 1.),
+    );
+    for op_info in collect(&root.data.op, &targets) {
+        let gys = (0..op_info.rets.len())
+            .map(|i| grads.remove(&key(&op_info, i)))
+            .collect::<Vec<Option<Node>>>();
+        if gys.iter().all(|gy| gy.is_none()) {
+            continue;
+        }
+        let gys = gys
+            .into_iter()
+            .enumerate()
+            .map(|(i, gy)| {
+                gy.unwrap_or_else(|| {
+                    Node::constant(device, op_info.rets[i].value.borrow().shape, 0.)
+                })
+            })
+            .collect::<Vec<Node>>();
+        if !op_info.args.is_empty() {
+            propagate(&op_info, &gys, &mut grads);
+        }
+        // Gradients of the targets are kept until the end.
+        if targets.contains(&(&*op_info as *const OperatorInfo as *const ())) {
+            for (i, gy) in gys.into_iter().enumerate() {
+                grads.insert(key(&op_info, i), gy);
+            }
+        }
+    }
+    xs.iter()
+        .map(|x| match grads.remove(&key(&x.data.op, x.data.vid)) {
+            Some(g) => g,
+            None => Node::constant(device, x.shape(), 0.),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::test_utils::generate_values;
+    use crate::{initializers as I, Node};
+
+    #[test]
+    fn check_grad_of_grad() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![3], &[1., 2., -3.]);
+        let x = Node::from(&x);
+        let y = (&x * &x * &x).sum(0);
+        let gx = y.grad(&[&x]).pop().unwrap();
+        let gx_val = gx.to_vec();
+        assert_vector_ulps_eq!(vec![3., 12., 27.], gx_val);
+        let ggx = gx.sum(0).grad(&[&x]).pop().unwrap();
+        let ggx_val = ggx.to_vec();
+        assert_vector_ulps_eq!(vec![6., 12., -18.], ggx_val);
+        let gggx = ggx.sum(0).grad(&[&x]).pop().unwrap();
+        let gggx_val = gggx.to_vec();
+        assert_vector_ulps_eq!(vec![6., 6., 6.], gggx_val);
+    }
+
+    #[test]
+    fn check_grad_matches_backward() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![3, 2], &I::Constant::new(0.5));
+        let x = dev.new_tensor_by_slice(shape![2; 2], &[1., 2., -1., 0.5]);
+        let t = dev.new_tensor_by_slice(shape![3; 2], &[1., 0., 0., 0., 0., 1.]);
+        let w = Node::from(&mut w);
+        let x = Node::from(&x);
+        let t = Node::from(&t);
+        let h = w.matmul(&x).tanh().softmax_cross_entropy(&t, 0);
+        let y = h.batch_sum() / 2.;
+        let gs = y.grad(&[&w, &x]);
+        let gw = gs[0].to_vec();
+        let gx = gs[1].to_vec();
+        y.backward();
+        let expected_gw = w.inner_gradient().to_vec();
+        let expected_gx = x.inner_gradient().to_vec();
+        assert_vector_ulps_eq!(expected_gw, gw);
+        assert_vector_ulps_eq!(expected_gx, gx);
+    }
+
+    #[test]
+    fn check_grad_matches_backward_operators() {
+        let dev = D::Naive::new();
+        let a = dev.new_tensor_by_slice(
+            shape![2, 3; 2],
+            &[
+                0.5, -1., 2., 1.5, 0.25, -0.5, 1., 2., -3., 0.75, -0.25, 1.25,
+            ],
+        );
+        let b = dev.new_tensor_by_slice(shape![3, 2], &[1., 2., 0.5, -1., 0.25, 3.]);
+        let a = Node::from(&a);
+        let b = Node::from(&b);
+        let h1 = (a.matmul(&b) * 2.).transpose().sigmoid();
+        let hs = a.split(1, 3);
+        let h2 = Node::concat(&[&hs[2], &hs[0], &hs[1]], 1)
+            .abs()
+            .permute_dims(&[1, 0]);
+        let h3 = (a.slice(0, 1, 2).broadcast(0, 2) / (2. - a.sin().exp())).flip(1);
+        let h4 = (a.max(1).broadcast(1, 3) - a.min(0).broadcast(0, 2))
+            .reshape(shape![6; 2])
+            .logsumexp(0);
+        let h5 = (a.powf(2.) + a.batch_sum()).cos();
+        let y = (h1.flatten().sum(0)
+            + h2.softplus().flatten().sum(0)
+            + h3.tanh().flatten().sum(0)
+            + h4.sqrt().ln())
+        .batch_sum()
+            + h5.flatten().sum(0) * Node::batch_concat(&[&h4, &h4]).batch_sum();
+        let gs = y.grad(&[&a, &b]);
+        let ga = gs[0].to_vec();
+        let gb = gs[1].to_vec();
+        y.backward();
+        let expected_ga = a.inner_gradient().to_vec();
+        let expected_gb = b.inner_gradient().to_vec();
+        assert_vector_ulps_eq!(expected_ga, ga, max_ulps = 16);
+        assert_vector_ulps_eq!(expected_gb, gb, max_ulps = 16);
+    }
+
+    #[test]
+    fn check_grad_matches_backward_picks() {
+        let dev = D::Naive::new();
+        let a = dev.new_tensor_by_slice(
+            shape![3, 2; 2],
+            &[
+                0.5, -1., 2., 1.5, 0.25, -0.5, 1., 2., -3., 0.75, -0.25, 1.25,
+            ],
+        );
+        let ids2 = dev.new_tensor_by_data(shape![; 2], &[0u32, 2]);
+        let ids3 = dev.new_tensor_by_data(shape![; 3], &[1i32, 0, 1]);
+        let a = Node::from(&a);
+        let ids2 = Node::from(&ids2);
+        let ids3 = Node::from(&ids3);
+        let h1 = a.pick(&[2, 0], 0).batch_pick(&[1, 1, 0]);
+        let h2 = a.pick_by(&ids2, 0).batch_pick_by(&ids3);
+        let hs = a.batch_split(2);
+        let h3 = (&hs[1] - &hs[0] * 2.).elu(0.5);
+        let y = (h1.tanh() * h2).flatten().sum(0).batch_sum() + (&h3 * &h3).flatten().sum(0);
+        let ga = y.grad(&[&a]).pop().unwrap().to_vec();
+        y.backward();
+        let expected_ga = a.inner_gradient().to_vec();
+        assert_vector_ulps_eq!(expected_ga, ga, max_ulps = 16);
+    }
+
+    #[test]
+    fn check_hessian_vector_product() {
+        // f(x) = sum(exp(x_0 * x_1)), Hv for v = (1, 0)
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let v = dev.new_tensor_by_slice(shape![2], &[1., 0.]);
+        let x = Node::from(&x);
+        let v = Node::from(&v);
+        let f = (x.slice(0, 0, 1) * x.slice(0, 1, 2)).exp();
+        let g = f.grad(&[&x]).pop().unwrap();
+        let hv = (g * v).sum(0).grad(&[&x]).pop().unwrap();
+        let e = 2f32.exp();
+        let hv_val = hv.to_vec();
+        assert_vector_ulps_eq!(vec![4. * e, 3. * e], hv_val);
+    }
+
+    #[test]
+    fn check_gradient_penalty() {
+        // y = w * x, penalty = sum((dy/dx)^2) = sum(w^2)
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2], &I::Constant::new(3.));
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        {
+            let w = Node::from(&mut w);
+            let x = Node::from(&x);
+            let y = (&w * &x).relu().sum(0);
+            let g = y.grad(&[&x]).pop().unwrap();
+            let penalty = (&g * &g).sum(0);
+            assert_eq!(18., penalty.to_float());
+            penalty.backward();
+        }
+        assert_eq!(vec![6., 6.], w.gradient.to_vec());
+    }
+
+    // WGAN-GP style penalty (|dD/dx| - 1)^2 of a convolutional critic D.
+    fn conv2d_penalty<'arg, 'dev>(x: &Node<'arg, 'dev>, w: &Node<'arg, 'dev>) -> Node<'arg, 'dev> {
+        let d = x
+            .conv2d(w, 0, 0, 1, 1, 1, 1)
+            .max_pool2d(2, 2, 0, 0, 1, 1)
+            .elu(1.)
+            .flatten()
+            .sum(0);
+        let g = d.grad(&[x]).pop().unwrap();
+        let norm = (&g * &g).flatten().sum(0).sqrt();
+        (norm - 1.).powi(2).batch_sum()
+    }
+
+    #[test]
+    fn check_gradient_penalty_conv2d() {
+        let dev = D::Naive::new();
+        let x_data = generate_values(32)
+            .iter()
+            .map(|v| v / 5.)
+            .collect::<Vec<f32>>();
+        let w_data = vec![0.3, -0.2, 0.5, 0.1, -0.4, 0.25, 0.15, -0.35];
+        let x = dev.new_tensor_by_slice(shape![4, 4, 1; 2], &x_data);
+        let mut w = dev.new_parameter(shape![2, 2, 1, 2], &I::Constant::new(0.));
+        dev.reset_tensor_by_slice(&mut w.value, &w_data);
+        {
+            let x = Node::from(&x);
+            let w = Node::from(&mut w);
+            conv2d_penalty(&x, &w).backward();
+        }
+        let gw = w.gradient.to_vec();
+        // central differences
+        let eps = 1e-2;
+        for i in 0..w_data.len() {
+            let penalty = |d: f32| {
+                let mut data = w_data.clone();
+                data[i] += d;
+                let w = dev.new_tensor_by_slice(shape![2, 2, 1, 2], &data);
+                let penalty = conv2d_penalty(&Node::from(&x), &Node::from(&w));
+                penalty.to_float()
+            };
+            let expected = (penalty(eps) - penalty(-eps)) / (2. * eps);
+            assert!(
+                (expected - gw[i]).abs() <= 1e-2 * (1. + expected.abs()),
+                "gw[{}]: expected {}, got {}",
+                i,
+                expected,
+                gw[i]
+            );
+        }
+    }
+
+    #[test]
+    fn check_grad_unrelated() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let z = dev.new_tensor_by_slice(shape![2], &[3., 4.]);
+        let x = Node::from(&x);
+        let z = Node::from(&z);
+        let y = x.pick(&[0], 0).sum(0);
+        let gz = y.grad(&[&z]).pop().unwrap();
+        assert_eq!(vec![0., 0.], gz.to_vec());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support higher-order gradients")]
+    fn check_grad_not_supported() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let x = Node::from(&x);
+        let y = x.sparse_softmax_cross_entropy(&[0], 0);
+        let _ = y.grad(&[&x]);
+    }
+}
@@ -0,0 +1,527 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+use rayon::prelude::*;
+
+use super::{backward_operator, forward_operator, recompute, OperatorInfo};
+use crate::{Operator, Tensor};
+
+// The graph is not thread-safe, so the scheduler keeps all accesses to it on
+// the calling thread:
+//
+// - Operators are run in waves. Before a wave starts, the calling thread
+//   takes the RefCell borrows of every value and gradient the wave uses, so
+//   a conflicting access panics there instead of racing.
+// - The worker threads receive only plain references to the borrowed tensors
+//   and the gradients of the arguments, which are moved out of the graph
+//   during the wave. Only operators that return themselves from
+//   Operator::as_parallel_safe, which are Sync, are given to the workers.
+//   The others run on the calling thread, as do operators without
+//   arguments, which may use the randomizer.
+//
+// A wave contains all operators that are ready, whatever their depth, so
+// independent branches of different lengths run side by side.
+
+type Key = (*const (), usize);
+
+fn key(op_info: &OperatorInfo, vid: usize) -> Key {
+    (op_info as *const OperatorInfo as *const (), vid)
+}
+
+fn ptr(op_info: &OperatorInfo) -> *const () {
+    op_info as *const OperatorInfo as *const ()
+}
+
+// Operators in `order` with the indices of their predecessors. Each operator
+// is ready once all its predecessors have finished.
+struct Schedule {
+    waiting: Vec<usize>,
+    successors: Vec<Vec<usize>>,
+    ready: Vec<usize>,
+}
+
+impl Schedule {
+    fn new(predecessors: Vec<Vec<usize>>) -> Schedule {
+        let mut successors = vec![vec![]; predecessors.len()];
+        for (i, preds) in predecessors.iter().enumerate() {
+            for &p in preds {
+                successors[p].push(i);
+            }
+        }
+        let waiting = predecessors.iter().map(Vec::len).collect::<Vec<usize>>();
+        let ready = (0..waiting.len()).filter(|&i| waiting[i] == 0).collect();
+        Schedule {
+            waiting,
+            successors,
+            ready,
+        }
+    }
+
+    // Operators ready to run, in the serial order.
+    fn next_wave(&mut self) -> Vec<usize> {
+        let mut wave = mem::take(&mut self.ready);
+        wave.sort_unstable();
+        wave
+    }
+
+    fn finish(&mut self, i: usize) {
+        for &j in &self.successors[i] {
+            self.waiting[j] -= 1;
+            if self.waiting[j] == 0 {
+                self.ready.push(j);
+            }
+        }
+    }
+}
+
+// Calculates the operators in `order`, which is the serial order. Each
+// operator waits only for its own arguments.
+pub(super) fn forward<'arg, 'dev, F>(order: &[Rc<OperatorInfo<'arg, 'dev>>], finish: &mut F)
+where
+    F: FnMut(&Rc<OperatorInfo<'arg, 'dev>>),
+{
+    let index = order
+        .iter()
+        .enumerate()
+        .map(|(i, op_info)| (ptr(op_info), i))
+        .collect::<HashMap<*const (), usize>>();
+    let predecessors = order
+        .iter()
+        .map(|op_info| {
+            let mut preds = op_info
+                .args
+                .iter()
+                .filter_map(|arg| index.get(&ptr(&arg.op)).copied())
+                .collect::<Vec<usize>>();
+            preds.sort_unstable();
+            preds.dedup();
+            preds
+        })
+        .collect();
+    let mut schedule = Schedule::new(predecessors);
+    loop {
+        let wave = schedule.next_wave();
+        if wave.is_empty() {
+            break;
+        }
+        let mut tasks = vec![];
+        for &i in &wave {
+            let op_info = &order[i];
+            match op_info.operator.as_parallel_safe() {
+                Some(operator) if !op_info.args.is_empty() && !op_info.forwarded.get() => {
+                    op_info.forwarded.set(true);
+                    tasks.push((&**op_info, operator));
+                }
+                _ => forward_operator(op_info),
+            }
+        }
+        forward_wave(&tasks);
+        for &i in &wave {
+            finish(&order[i]);
+            schedule.finish(i);
+        }
+    }
+}
+
+type ParallelSafeOperator<'a, 'arg, 'dev> = &'a (dyn Operator<'arg, 'dev> + Sync + 'a);
+
+struct ForwardTask<'a, 'arg, 'dev> {
+    operator: ParallelSafeOperator<'a, 'arg, 'dev>,
+    xs: Vec<&'a Tensor<'arg>>,
+    ys: Vec<&'a mut Tensor<'arg>>,
+}
+
+fn forward_wave<'a, 'arg, 'dev>(
+    ops: &[(
+        &'a OperatorInfo<'arg, 'dev>,
+        ParallelSafeOperator<'a, 'arg, 'dev>,
+    )],
+) {
+    let xs = ops
+        .iter()
+        .map(|(op_info, _)| {
+            op_info
+                .args
+                .iter()
+                .map(|arg| arg.op.rets[arg.vid].value.borrow())
+                .collect::<Vec<Ref<Tensor<'arg>>>>()
+        })
+        .collect::<Vec<_>>();
+    let mut ys = ops
+        .iter()
+        .map(|(op_info, _)| {
+            op_info
+                .rets
+                .iter()
+                .map(|ret| ret.value.borrow_mut())
+                .collect::<Vec<RefMut<Tensor<'arg>>>>()
+        })
+        .collect::<Vec<_>>();
+    let mut tasks = ops
+        .iter()
+        .zip(&xs)
+        .zip(&mut ys)
+        .map(|((&(_, operator), xs), ys)| ForwardTask {
+            operator,
+            xs: xs.iter().map(|x| &**x).collect(),
+            ys: ys.iter_mut().map(|y| &mut **y).collect(),
+        })
+        .collect::<Vec<ForwardTask>>();
+    tasks
+        .par_iter_mut()
+        .for_each(|task| task.operator.forward(&task.xs, &mut task.ys));
+}
+
+// Backpropagates the operators in `order`, which is the serial order. An
+// operator waits for its consumers, and for the operators before it that
+// accumulate into the same gradients. The gradients are then summed in the
+// serial order and are identical to those without the scheduler.
+pub(super) fn backward<'arg, 'dev, F>(order: &[Rc<OperatorInfo<'arg, 'dev>>], finish: &mut F)
+where
+    F: FnMut(&Rc<OperatorInfo<'arg, 'dev>>),
+{
+    let mut last_writer = HashMap::new();
+    let predecessors = order
+        .iter()
+        .enumerate()
+        .map(|(i, op_info)| {
+            let mut preds = (0..op_info.rets.len())
+                .filter_map(|vid| last_writer.get(&key(op_info, vid)).copied())
+                .collect::<Vec<usize>>();
+            for arg in &op_info.args {
+                if let Some(j) = last_writer.insert(key(&arg.op, arg.vid), i) {
+                    if j != i {
+                        preds.push(j);
+                    }
+                }
+            }
+            preds.sort_unstable();
+            preds.dedup();
+            preds
+        })
+        .collect();
+    let mut schedule = Schedule::new(predecessors);
+    loop {
+        let wave = schedule.next_wave();
+        if wave.is_empty() {
+            break;
+        }
+        for &i in &wave {
+            for arg in &order[i].args {
+                recompute(&arg.op);
+            }
+            recompute(&order[i]);
+        }
+        let mut tasks = vec![];
+        for &i in &wave {
+            match order[i].operator.as_parallel_safe() {
+                Some(operator) => tasks.push((&*order[i], operator)),
+                None => backward_operator(&order[i]),
+            }
+        }
+        if tasks.len() == 1 {
+            backward_operator(tasks[0].0);
+        } else {
+            backward_wave(&tasks);
+        }
+        for &i in &wave {
+            finish(&order[i]);
+            schedule.finish(i);
+        }
+    }
+}
+
+struct BackwardTask<'a, 'arg, 'dev> {
+    operator: ParallelSafeOperator<'a, 'arg, 'dev>,
+    xs: Vec<&'a Tensor<'arg>>,
+    ys: Vec<&'a Tensor<'arg>>,
+    gys: Vec<&'a Tensor<'arg>>,
+    // Gradients of the distinct arguments, and the index of each argument.
+    gxs: Vec<RefCell<Tensor<'arg>>>,
+    slots: Vec<usize>,
+}
+
+fn backward_wave<'a, 'arg, 'dev>(
+    ops: &[(
+        &'a OperatorInfo<'arg, 'dev>,
+        ParallelSafeOperator<'a, 'arg, 'dev>,
+    )],
+) {
+    // Gradients of the arguments are borrowed once even if an argument is
+    // used twice, e.g. x * x.
+    let mut tasks = vec![];
+    let mut guards = vec![];
+    for (op_info, _) in ops {
+        let mut slots = vec![];
+        let mut keys = vec![];
+        let mut grads = vec![];
+        for arg in &op_info.args {
+            let k = key(&arg.op, arg.vid);
+            match keys.iter().position(|&other| other == k) {
+                Some(slot) => slots.push(slot),
+                None => {
+                    slots.push(keys.len());
+                    keys.push(k);
+                    let ret = &arg.op.rets[arg.vid];
+                    if !ret.gradient.borrow().valid() {
+                        ret.alloc_gradient(0.);
+                    }
+                    grads.push(ret.gradient.borrow_mut());
+                }
+            }
+        }
+        guards.push(grads);
+        tasks.push(slots);
+    }
+    // Gradients are moved to the tasks and written back after the wave.
+    let mut gxs = guards
+        .iter_mut()
+        .map(|guards| {
+            guards
+                .iter_mut()
+                .map(|g| {
+                    let empty = g.device().new_tensor(g.shape);
+                    RefCell::new(mem::replace(&mut **g, empty))
+                })
+                .collect::<Vec<RefCell<Tensor<'arg>>>>()
+        })
+        .collect::<Vec<_>>();
+    let values = ops
+        .iter()
+        .map(|(op_info, _)| {
+            let xs = op_info
+                .args
+                .iter()
+                .map(|arg| arg.op.rets[arg.vid].value.borrow())
+                .collect::<Vec<Ref<Tensor<'arg>>>>();
+            let ys = op_info
+                .rets
+                .iter()
+                .map(|ret| ret.value.borrow())
+                .collect::<Vec<Ref<Tensor<'arg>>>>();
+            let gys = op_info
+                .rets
+                .iter()
+                .map(|ret| ret.gradient.borrow())
+                .collect::<Vec<Ref<Tensor<'arg>>>>();
+            (xs, ys, gys)
+        })
+        .collect::<Vec<_>>();
+    let mut backward_tasks = ops
+        .iter()
+        .zip(&values)
+        .zip(tasks)
+        .zip(gxs.iter_mut())
+        .map(
+            |(((&(_, operator), (xs, ys, gys)), slots), gxs)| BackwardTask {
+                operator,
+                xs: xs.iter().map(|x| &**x).collect(),
+                ys: ys.iter().map(|y| &**y).collect(),
+                gys: gys.iter().map(|gy| &**gy).collect(),
+                gxs: mem::take(gxs),
+                slots,
+            },
+        )
+        .collect::<Vec<BackwardTask>>();
+    backward_tasks.par_iter_mut().for_each(|task| {
+        let gxs = task
+            .slots
+            .iter()
+            .map(|&slot| &task.gxs[slot])
+            .collect::<Vec<&RefCell<Tensor<'arg>>>>();
+        task.operator.backward(&task.xs, &task.ys, &task.gys, &gxs);
+    });
+    for (task, guards) in backward_tasks.into_iter().zip(guards.iter_mut()) {
+        for (gx, guard) in task.gxs.into_iter().zip(guards.iter_mut()) {
+            **guard = gx.into_inner();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::{initializers as I, parallel, Device, Node, Operator, Parameter, Shape, Tensor};
+
+    // Returns the output and accumulates the gradients of the parameters.
+    fn bidirectional<'dev>(
+        dev: &'dev Device<'dev>,
+        wf: &mut Parameter<'dev>,
+        wb: &mut Parameter<'dev>,
+        xs: &[Tensor<'dev>],
+    ) -> f32 {
+        let wf = Node::from(wf);
+        let wb = Node::from(wb);
+        let xs = xs.iter().map(Node::from).collect::<Vec<Node>>();
+        let mut hf = Node::constant(dev, shape![3], 0.);
+        let mut hb = Node::constant(dev, shape![3], 0.);
+        for i in 0..xs.len() {
+            hf = (wf.matmul(&hf) + &xs[i]).tanh();
+            hb = (wb.matmul(&hb) + &xs[xs.len() - 1 - i]).tanh();
+        }
+        let y = (hf * hb).sum(0);
+        let value = y.to_float();
+        y.backward();
+        value
+    }
+
+    #[test]
+    fn check_parallel_bidirectional() {
+        let dev = D::Naive::new();
+        let mut wf = dev.new_parameter(shape![3, 3], &I::Uniform::new(-1., 1.));
+        let mut wb = dev.new_parameter(shape![3, 3], &I::Uniform::new(-1., 1.));
+        let xs = (0..4)
+            .map(|i| dev.new_tensor_by_slice(shape![3], &[i as f32, 1., -0.5 * i as f32]))
+            .collect::<Vec<_>>();
+        let expected = bidirectional(&dev, &mut wf, &mut wb, &xs);
+        let expected_wf = wf.gradient.to_vec();
+        let expected_wb = wb.gradient.to_vec();
+        wf.reset_gradient();
+        wb.reset_gradient();
+        let y = parallel(|| bidirectional(&dev, &mut wf, &mut wb, &xs));
+        assert_eq!(expected, y);
+        assert_eq!(expected_wf, wf.gradient.to_vec());
+        assert_eq!(expected_wb, wb.gradient.to_vec());
+    }
+
+    // Heads reading the same argument.
+    fn heads<'arg, 'dev>(x: &Node<'arg, 'dev>) -> Node<'arg, 'dev> {
+        let hs = (1..5).map(|k| (x * k as f32).tanh()).collect::<Vec<Node>>();
+        hs.iter()
+            .fold(Node::constant(x.device(), shape![2], 0.), |acc, h| acc + h)
+            .sum(0)
+    }
+
+    #[test]
+    fn check_parallel_shared_argument() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[0.5, -1.]);
+        let x1 = Node::from(&x);
+        heads(&x1).backward();
+        let expected = x1.inner_gradient().to_vec();
+        let x2 = Node::from(&x);
+        parallel(|| heads(&x2).backward());
+        let x_grad = x2.inner_gradient().to_vec();
+        assert_eq!(expected, x_grad);
+    }
+
+    // Branches of different lengths, one of which uses an argument twice.
+    fn uneven<'arg, 'dev>(x: &Node<'arg, 'dev>, w: &Node<'arg, 'dev>) -> Node<'arg, 'dev> {
+        let mut a = x.tanh();
+        for _ in 0..5 {
+            a = (w.matmul(&a) * &a).sin();
+        }
+        let b = (x * x).exp();
+        (a + b).sum(0)
+    }
+
+    #[test]
+    fn check_parallel_uneven_branches() {
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[0.5, -1.]);
+        let w = dev.new_tensor_by_slice(shape![2, 2], &[0.25, -0.5, 1., 0.75]);
+        let (x1, w1) = (Node::from(&x), Node::from(&w));
+        uneven(&x1, &w1).backward();
+        let (x2, w2) = (Node::from(&x), Node::from(&w));
+        parallel(|| uneven(&x2, &w2).backward());
+        let expected_x = x1.inner_gradient().to_vec();
+        let expected_w = w1.inner_gradient().to_vec();
+        assert_eq!(expected_x, x2.inner_gradient().to_vec());
+        assert_eq!(expected_w, w2.inner_gradient().to_vec());
+    }
+
+    #[test]
+    fn check_parallel_random() {
+        let values = [false, true]
+            .iter()
+            .map(|&enabled| {
+                let dev = D::Naive::with_seed(1);
+                let run = || {
+                    let a = Node::random_normal(&dev, shape![4], 0., 1.).exp();
+                    let b = Node::random_normal(&dev, shape![4], 0., 1.).tanh();
+                    (a - b).to_vec()
+                };
+                if enabled {
+                    parallel(run)
+                } else {
+                    run()
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values[0], values[1]);
+    }
+
+    // Doubles the argument and counts the calls in a Cell, so it is not Sync.
+    struct CountedDouble<'a, 'dev> {
+        device: &'dev Device<'dev>,
+        calls: &'a Cell<usize>,
+    }
+
+    impl<'a, 'arg, 'dev> Operator<'arg, 'dev> for CountedDouble<'a, 'dev> {
+        fn name(&self) -> String {
+            "CountedDouble".to_string()
+        }
+
+        fn device(&self) -> &'dev Device<'dev> {
+            self.device
+        }
+
+        fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+            vec![x[0]]
+        }
+
+        fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+            self.calls.set(self.calls.get() + 1);
+            y[0].replace(x[0] * 2.);
+        }
+
+        fn backward(
+            &self,
+            _x: &[&Tensor],
+            _y: &[&Tensor],
+            gy: &[&Tensor],
+            gx: &[&RefCell<Tensor>],
+        ) {
+            self.calls.set(self.calls.get() + 1);
+            *gx[0].borrow_mut() += gy[0] * 2.;
+        }
+    }
+
+    fn counted_double<'arg, 'dev>(
+        x: &Node<'arg, 'dev>,
+        calls: &'arg Cell<usize>,
+    ) -> Node<'arg, 'dev> {
+        let op = CountedDouble {
+            device: x.device(),
+            calls,
+        };
+        Node::create(op, &[x]).pop().unwrap()
+    }
+
+    #[test]
+    fn check_parallel_not_parallel_safe() {
+        let calls = Cell::new(0);
+        let dev = D::Naive::new();
+        let x = dev.new_tensor_by_slice(shape![2], &[0.5, -1.]);
+        let x = Node::from(&x);
+        let y = parallel(|| {
+            let a = counted_double(&x.tanh(), &calls);
+            let b = counted_double(&x.exp(), &calls);
+            let y = (a + b).sum(0);
+            y.backward();
+            y
+        });
+        // Forward and backward of both operators.
+        assert_eq!(4, calls.get());
+        let x_grad = x.inner_gradient().to_vec();
+        let expected = (2. * (x.tanh() + x.exp())).sum(0);
+        let expected_grad = expected.gradients(&[&x]).remove(0).to_vec();
+        assert_vector_ulps_eq!(vec![expected.to_float()], vec![y.to_float()]);
+        assert_vector_ulps_eq!(expected_grad, x_grad);
+    }
+}
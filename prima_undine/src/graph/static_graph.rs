@@ -0,0 +1,728 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::{forward_operator_with, recompute, DataRef, OperatorInfo};
+use crate::operators as op;
+use crate::{DType, Device, Node, Shape, Tensor};
+
+// Optimizations of the calculation done by StaticGraph::run. The recorded
+// graph is not changed, so backward() gives the same gradients, but the
+// passes are meant for graphs that are only run forward: the operators
+// skipped by EliminateIdentities and the members of fused groups other than
+// the root have no values after run, and backward() calculates them again
+// with their own kernels, as for checkpointed operators. Nodes calculated
+// outside a StaticGraph are not optimized, e.g. `1. * self` of a disabled
+// dropout in prima_undine_contrib still launches its kernel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphPass {
+    // Calculates operators that depend only on inputs and constants once.
+    FoldConstants,
+    // Skips additions of 0 and multiplications by 1.
+    EliminateIdentities,
+    // Skips operators whose results are not used.
+    EliminateDeadNodes,
+    // Calculates trees of elementwise operators with one kernel.
+    FuseElementwise,
+}
+
+impl GraphPass {
+    pub const ALL: [GraphPass; 4] = [
+        GraphPass::FoldConstants,
+        GraphPass::EliminateIdentities,
+        GraphPass::EliminateDeadNodes,
+        GraphPass::FuseElementwise,
+    ];
+}
+
+enum Step<'arg, 'dev> {
+    // An operator calculated with the given arguments.
+    Operator {
+        op: Rc<OperatorInfo<'arg, 'dev>>,
+        args: Vec<DataRef<'arg, 'dev>>,
+    },
+    // Elementwise operators calculated by fused_elementwise_fw_impl. The
+    // members are ordered by depth and the last one is the root. They are
+    // calculated one by one if the kernel is not available.
+    Fused {
+        members: Vec<(Rc<OperatorInfo<'arg, 'dev>>, Vec<DataRef<'arg, 'dev>>)>,
+        args: Vec<DataRef<'arg, 'dev>>,
+        u32data: Vec<u32>,
+        f32data: Vec<f32>,
+    },
+}
+
+impl<'arg, 'dev> Step<'arg, 'dev> {
+    fn op(&self) -> &Rc<OperatorInfo<'arg, 'dev>> {
+        match self {
+            Step::Operator { op, .. } => op,
+            Step::Fused { members, .. } => &members[members.len() - 1].0,
+        }
+    }
+
+    fn args(&self) -> &[DataRef<'arg, 'dev>] {
+        match self {
+            Step::Operator { args, .. } | Step::Fused { args, .. } => args,
+        }
+    }
+
+    fn remap(&mut self, map: &HashMap<(*const (), usize), DataRef<'arg, 'dev>>) {
+        let remap_args = |args: &mut Vec<DataRef<'arg, 'dev>>| {
+            for arg in args.iter_mut() {
+                if let Some(to) = map.get(&(ptr(&arg.op), arg.vid)) {
+                    *arg = to.clone();
+                }
+            }
+        };
+        match self {
+            Step::Operator { args, .. } => remap_args(args),
+            Step::Fused { members, args, .. } => {
+                remap_args(args);
+                for (_, args) in members.iter_mut() {
+                    remap_args(args);
+                }
+            }
+        }
+    }
+
+    fn forward(&self) {
+        match self {
+            Step::Operator { op, args } => forward_operator_with(op, args),
+            Step::Fused {
+                members,
+                args,
+                u32data,
+                f32data,
+            } => {
+                if !forward_fused(&members[members.len() - 1].0, args, u32data, f32data) {
+                    for (op, args) in members {
+                        forward_operator_with(op, args);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn ptr(op_info: &OperatorInfo) -> *const () {
+    op_info as *const OperatorInfo as *const ()
+}
+
+fn shape_of(data: &DataRef) -> Shape {
+    data.op.rets[data.vid].value.borrow().shape
+}
+
+// Returns false if the arguments are not F32 or the device has no kernel.
+fn forward_fused<'arg, 'dev>(
+    root: &OperatorInfo<'arg, 'dev>,
+    args: &[DataRef<'arg, 'dev>],
+    u32data: &[u32],
+    f32data: &[f32],
+) -> bool {
+    if root.forwarded.get() {
+        return true;
+    }
+    let xs = args
+        .iter()
+        .map(|data| data.op.rets[data.vid].value.borrow())
+        .collect::<Vec<_>>();
+    if xs.iter().any(|x| x.dtype() != DType::F32) {
+        return false;
+    }
+    let xs_ref = xs.iter().map(|x| &**x).collect::<Vec<&Tensor>>();
+    let ret = &root.rets[0];
+    let mut y = root.operator.device().new_tensor(ret.value.borrow().shape);
+    y.alloc();
+    let device = root.operator.device();
+    if device
+        .try_call_fw_impl(
+            "fused_elementwise_fw_impl",
+            &xs_ref,
+            u32data,
+            f32data,
+            &mut [&mut y],
+        )
+        .is_err()
+    {
+        return false;
+    }
+    *ret.value.borrow_mut() = y;
+    root.forwarded.set(true);
+    true
+}
+
+// Operators without arguments whose values never change.
+fn is_constant_source(op_info: &OperatorInfo) -> bool {
+    op_info.args.is_empty() && op_info.operator.is_constant()
+}
+
+// A graph traced once and calculated again with new inputs. The operators
+// are ordered when the graph is traced, so running it does not build nodes
+// or schedule operators.
+pub struct StaticGraph<'arg, 'dev> {
+    inputs: Vec<Node<'arg, 'dev>>,
+    outputs: Vec<Node<'arg, 'dev>>,
+    // All operators except the placeholders, ordered by depth.
+    ops: Vec<Rc<OperatorInfo<'arg, 'dev>>>,
+    // Calculation done by run, ordered by depth.
+    steps: Vec<Step<'arg, 'dev>>,
+    // Operators calculated by constant folding, and those used by the steps.
+    folded: HashSet<*const ()>,
+    constants: Vec<Rc<OperatorInfo<'arg, 'dev>>>,
+}
+impl<'arg, 'dev> StaticGraph<'arg, 'dev> {
+    // Calls f with placeholders of the given shapes and records the graph of
+    // the returned nodes. f may also use parameters and other nodes.
+    pub fn trace<F>(device: &'dev Device<'dev>, shapes: &[Shape], f: F) -> StaticGraph<'arg, 'dev>
+    where
+        F: FnOnce(&[Node<'arg, 'dev>]) -> Vec<Node<'arg, 'dev>>,
+    {
+        let inputs = shapes
+            .iter()
+            .map(|&s| {
+                Node::create(op::Placeholder::new(device, s), &[])
+                    .pop()
+                    .unwrap()
+            })
+            .collect::<Vec<Node>>();
+        let outputs = f(&inputs);
+        let placeholders = inputs
+            .iter()
+            .map(|x| &*x.data.op as *const OperatorInfo as *const ())
+            .collect::<HashSet<*const ()>>();
+        let mut reached = HashSet::new();
+        let mut stack = vec![];
+        for y in &outputs {
+            if reached.insert(&*y.data.op as *const OperatorInfo as *const ()) {
+                stack.push(Rc::clone(&y.data.op));
+            }
+        }
+        let mut ops = vec![];
+        while let Some(op_info) = stack.pop() {
+            for arg in &op_info.args {
+                if reached.insert(&*arg.op as *const OperatorInfo as *const ()) {
+                    stack.push(Rc::clone(&arg.op));
+                }
+            }
+            if !placeholders.contains(&(&*op_info as *const OperatorInfo as *const ())) {
+                ops.push(op_info);
+            }
+        }
+        ops.sort_by_key(|op_info| op_info.depth);
+        let steps = ops
+            .iter()
+            .map(|op_info| Step::Operator {
+                op: Rc::clone(op_info),
+                args: op_info.args.clone(),
+            })
+            .collect();
+        StaticGraph {
+            inputs,
+            outputs,
+            ops,
+            steps,
+            folded: HashSet::new(),
+            constants: vec![],
+        }
+    }
+
+    pub fn inputs(&self) -> &[Node<'arg, 'dev>] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[Node<'arg, 'dev>] {
+        &self.outputs
+    }
+
+    pub fn num_operators(&self) -> usize {
+        self.ops.len()
+    }
+
+    // Number of kernels launched by run, excluding folded constants.
+    pub fn num_steps(&self) -> usize {
+        self.steps.len()
+    }
+
+    // Applies all passes.
+    pub fn optimize(&mut self) {
+        self.optimize_with(&GraphPass::ALL);
+    }
+
+    pub fn optimize_with(&mut self, passes: &[GraphPass]) {
+        for pass in passes {
+            match pass {
+                GraphPass::FoldConstants => self.fold_constants(),
+                GraphPass::EliminateIdentities => self.eliminate_identities(),
+                GraphPass::EliminateDeadNodes => self.eliminate_dead_nodes(),
+                GraphPass::FuseElementwise => self.fuse_elementwise(),
+            }
+        }
+        let used = self
+            .steps
+            .iter()
+            .flat_map(|step| step.args().iter().map(|arg| &arg.op))
+            .chain(self.outputs.iter().map(|y| &y.data.op))
+            .filter(|op_info| self.folded.contains(&ptr(op_info)))
+            .map(|op_info| (ptr(op_info), Rc::clone(op_info)))
+            .collect::<HashMap<_, _>>();
+        for op_info in &self.constants {
+            op_info.pinned.set(false);
+        }
+        self.constants = used.into_values().collect();
+        // The folded values are kept by the graph, so backward() does not
+        // release them.
+        for op_info in &self.constants {
+            recompute(op_info);
+            op_info.pinned.set(true);
+        }
+    }
+
+    // Operators with only constant arguments are constants, except random
+    // operators, parameters and placeholders, which have no arguments.
+    fn fold_constants(&mut self) {
+        for op_info in &self.ops {
+            if is_constant_source(op_info)
+                || (!op_info.args.is_empty()
+                    && op_info
+                        .args
+                        .iter()
+                        .all(|arg| self.folded.contains(&ptr(&arg.op))))
+            {
+                self.folded.insert(ptr(op_info));
+            }
+        }
+        let folded = &self.folded;
+        self.steps.retain(|step| match step {
+            Step::Operator { op, .. } => !folded.contains(&ptr(op)),
+            Step::Fused { .. } => true,
+        });
+    }
+
+    // Consumers of identity operators use the argument instead. Graph
+    // outputs are kept so that their values are available.
+    fn eliminate_identities(&mut self) {
+        let outputs = self.output_set();
+        let mut map = HashMap::new();
+        let steps = std::mem::take(&mut self.steps);
+        for mut step in steps {
+            step.remap(&map);
+            if let Step::Operator { op, args } = &step {
+                let identity = op.operator.elementwise().is_some_and(|e| e.is_identity());
+                if identity && !outputs.contains(&ptr(op)) {
+                    map.insert((ptr(op), 0), args[0].clone());
+                    continue;
+                }
+            }
+            self.steps.push(step);
+        }
+    }
+
+    fn eliminate_dead_nodes(&mut self) {
+        let mut used = self.output_set();
+        let mut steps = vec![];
+        while let Some(step) = self.steps.pop() {
+            if used.contains(&ptr(step.op())) {
+                used.extend(step.args().iter().map(|arg| ptr(&arg.op)));
+                steps.push(step);
+            }
+        }
+        steps.reverse();
+        self.steps = steps;
+    }
+
+    // Each group is a tree of elementwise operators with the same shape. The
+    // operators other than the root must be used only by the group.
+    fn fuse_elementwise(&mut self) {
+        let outputs = self.output_set();
+        let mut num_uses = HashMap::new();
+        for step in &self.steps {
+            for arg in step.args() {
+                *num_uses.entry(ptr(&arg.op)).or_insert(0) += 1;
+            }
+        }
+        let fusable = |step: &Step| match step {
+            Step::Operator { op, args } => {
+                let shape = op.rets[0].value.borrow().shape;
+                op.operator.elementwise().is_some() && args.iter().all(|arg| shape_of(arg) == shape)
+            }
+            Step::Fused { .. } => false,
+        };
+        let index = self
+            .steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| fusable(step))
+            .map(|(i, step)| (ptr(step.op()), i))
+            .collect::<HashMap<*const (), usize>>();
+        let mut absorbed = vec![false; self.steps.len()];
+        let mut groups = HashMap::new();
+        for root in (0..self.steps.len()).rev() {
+            if absorbed[root] || !fusable(&self.steps[root]) {
+                continue;
+            }
+            let mut members = vec![root];
+            let mut stack = vec![root];
+            while let Some(i) = stack.pop() {
+                for arg in self.steps[i].args() {
+                    let p = ptr(&arg.op);
+                    if let Some(&j) = index.get(&p) {
+                        if !absorbed[j] && num_uses[&p] == 1 && !outputs.contains(&p) {
+                            absorbed[j] = true;
+                            members.push(j);
+                            stack.push(j);
+                        }
+                    }
+                }
+            }
+            if members.len() > 1 {
+                groups.insert(root, members);
+            }
+        }
+        let mut steps = self.steps.drain(..).map(Some).collect::<Vec<_>>();
+        for i in 0..steps.len() {
+            if let Some(members) = groups.get(&i) {
+                let mut members = members
+                    .iter()
+                    .map(|&j| match steps[j].take() {
+                        Some(Step::Operator { op, args }) => (op, args),
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>();
+                members.sort_by_key(|(op, _)| op.depth);
+                steps[i] = Some(fused_step(members));
+            }
+        }
+        self.steps = steps.into_iter().flatten().collect();
+    }
+
+    fn output_set(&self) -> HashSet<*const ()> {
+        self.outputs.iter().map(|y| ptr(&y.data.op)).collect()
+    }
+
+    // Calculates all outputs with new input values. The values and the
+    // gradients of the previous run are discarded.
+    pub fn run(&self, inputs: &[&Tensor]) -> &[Node<'arg, 'dev>] {
+        assert!(
+            inputs.len() == self.inputs.len(),
+            "the static graph takes {} inputs, but {} are given",
+            self.inputs.len(),
+            inputs.len()
+        );
+        for op_info in &self.ops {
+            for ret in &op_info.rets {
+                if !self.folded.contains(&ptr(op_info)) {
+                    ret.release_value();
+                }
+                ret.release_gradient();
+            }
+            if !self.folded.contains(&ptr(op_info)) {
+                op_info.forwarded.set(false);
+            }
+        }
+        for (i, (node, x)) in self.inputs.iter().zip(inputs).enumerate() {
+            let ret = &node.data.op.rets[0];
+            let shape = ret.value.borrow().shape;
+            assert!(
+                x.shape == shape,
+                "input {} of the static graph has shape {:?}, but {:?} is given",
+                i,
+                shape,
+                x.shape
+            );
+            *ret.value.borrow_mut() = node.device().copy_tensor(x);
+            ret.release_gradient();
+            node.data.op.forwarded.set(true);
+        }
+        for step in &self.steps {
+            step.forward();
+        }
+        &self.outputs
+    }
+}
+
+// Leaf arguments take the first registers of the fused kernel, and the
+// members take the following ones.
+fn fused_step<'arg, 'dev>(
+    members: Vec<(Rc<OperatorInfo<'arg, 'dev>>, Vec<DataRef<'arg, 'dev>>)>,
+) -> Step<'arg, 'dev> {
+    let member_ids = members
+        .iter()
+        .enumerate()
+        .map(|(i, (op, _))| (ptr(op), i))
+        .collect::<HashMap<*const (), usize>>();
+    let mut args: Vec<DataRef> = vec![];
+    for (_, member_args) in &members {
+        for arg in member_args {
+            let p = ptr(&arg.op);
+            let known = args
+                .iter()
+                .any(|leaf| ptr(&leaf.op) == p && leaf.vid == arg.vid);
+            if !member_ids.contains_key(&p) && !known {
+                args.push(arg.clone());
+            }
+        }
+    }
+    let register = |arg: &DataRef| -> u32 {
+        let p = ptr(&arg.op);
+        match member_ids.get(&p) {
+            Some(&i) => (args.len() + i) as u32,
+            None => args
+                .iter()
+                .position(|leaf| ptr(&leaf.op) == p && leaf.vid == arg.vid)
+                .unwrap() as u32,
+        }
+    };
+    let mut u32data = vec![];
+    let mut f32data = vec![];
+    for (op, member_args) in &members {
+        let (code, k) = op.operator.elementwise().unwrap().encode();
+        let a = register(&member_args[0]);
+        let b = member_args.get(1).map_or(a, &register);
+        u32data.extend(&[code, a, b]);
+        f32data.push(k);
+    }
+    Step::Fused {
+        members,
+        args,
+        u32data,
+        f32data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{GraphPass, StaticGraph};
+    use crate::device_impl::FunctionFwImpl;
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::{initializers as I, Device, Node, Shape, Tensor};
+
+    #[test]
+    fn check_static_graph() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(0.5));
+        let w = Node::from(&mut w);
+        let graph = StaticGraph::trace(&dev, &[shape![2], shape![2]], |xs| {
+            let h = (w.matmul(&xs[0]) + &xs[1]).tanh();
+            vec![h.sum(0), h]
+        });
+        assert_eq!(5, graph.num_operators());
+        for &(a, b) in &[(1., 2.), (-1., 0.5), (0., 3.)] {
+            let x1 = dev.new_tensor_by_slice(shape![2], &[a, b]);
+            let x2 = dev.new_tensor_by_slice(shape![2], &[b, a]);
+            let ys = graph.run(&[&x1, &x2]);
+            let y0 = ys[0].inner_value().to_float();
+            let y1 = ys[1].inner_value().to_vec();
+            let x1 = Node::from(dev.copy_tensor(&x1));
+            let x2 = Node::from(dev.copy_tensor(&x2));
+            let expected = (w.matmul(x1) + x2).tanh();
+            let expected_y1 = expected.to_vec();
+            let expected_y0 = expected.sum(0).to_float();
+            assert_vector_ulps_eq!(expected_y1, y1);
+            assert_vector_ulps_eq!(vec![expected_y0], vec![y0]);
+        }
+    }
+
+    #[test]
+    fn check_static_graph_backward() {
+        let dev = D::Naive::new();
+        let graph = StaticGraph::trace(&dev, &[shape![3]], |xs| vec![(&xs[0] * &xs[0]).sum(0)]);
+        for &k in &[1., 2.] {
+            let x = dev.new_tensor_by_slice(shape![3], &[k, -k, 2. * k]);
+            let ys = graph.run(&[&x]);
+            ys[0].backward();
+            let x_grad = graph.inputs()[0].inner_gradient().to_vec();
+            assert_vector_ulps_eq!(vec![2. * k, -2. * k, 4. * k], x_grad);
+        }
+    }
+
+    #[test]
+    fn check_static_graph_unrolled() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(0.25));
+        let w = Node::from(&mut w);
+        let shapes = vec![shape![2]; 3];
+        let graph = StaticGraph::trace(&dev, &shapes, |xs| {
+            let mut h = Node::constant(&dev, shape![2], 0.);
+            let mut ys = vec![];
+            for x in xs {
+                h = (w.matmul(&h) + x).tanh();
+                ys.push(h.sum(0));
+            }
+            ys
+        });
+        let xs = (0..3)
+            .map(|i| dev.new_tensor_by_slice(shape![2], &[i as f32, 1.]))
+            .collect::<Vec<_>>();
+        let ys = graph.run(&xs.iter().collect::<Vec<_>>());
+        let last = ys[2].inner_value().to_float();
+        let mut h = Node::constant(&dev, shape![2], 0.);
+        for x in &xs {
+            h = (w.matmul(&h) + Node::from(dev.copy_tensor(x))).tanh();
+        }
+        let expected = h.sum(0).to_float();
+        assert_vector_ulps_eq!(vec![expected], vec![last]);
+    }
+
+    #[test]
+    #[should_panic(expected = "input 0 of the static graph has shape")]
+    fn check_static_graph_wrong_shape() {
+        let dev = D::Naive::new();
+        let graph = StaticGraph::trace(&dev, &[shape![3]], |xs| vec![xs[0].sum(0)]);
+        let x = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        graph.run(&[&x]);
+    }
+
+    fn trace_elementwise<'arg, 'dev>(
+        dev: &'dev Device<'dev>,
+        shape: Shape,
+    ) -> StaticGraph<'arg, 'dev> {
+        StaticGraph::trace(dev, &[shape, shape], |xs| {
+            let c = (Node::constant(dev, shape, 0.5) * 3.).exp();
+            let h = (&xs[0] + &xs[1]).tanh() * 2. * 1. + c + 0.;
+            vec![h.sigmoid() - &xs[0]]
+        })
+    }
+
+    #[test]
+    fn check_static_graph_optimize() {
+        let dev = D::Naive::new();
+        // Larger than the grain of the parallel kernels.
+        let shape = shape![100, 200];
+        let mut graph = trace_elementwise(&dev, shape);
+        let mut optimized = trace_elementwise(&dev, shape);
+        optimized.optimize();
+        assert_eq!(11, graph.num_steps());
+        assert_eq!(1, optimized.num_steps());
+        graph.optimize_with(&[GraphPass::EliminateIdentities]);
+        assert_eq!(9, graph.num_steps());
+        for k in 0..2 {
+            let a = (0..20000)
+                .map(|i| (i + k) as f32 * 1e-3 - 10.)
+                .collect::<Vec<f32>>();
+            let b = (0..20000)
+                .map(|i| (i * 7 % 13) as f32 - 6.)
+                .collect::<Vec<f32>>();
+            let a = dev.new_tensor_by_slice(shape, &a);
+            let b = dev.new_tensor_by_slice(shape, &b);
+            let expected = graph.run(&[&a, &b])[0].inner_value().to_vec();
+            let y = optimized.run(&[&a, &b])[0].inner_value().to_vec();
+            assert_vector_ulps_eq!(expected, y);
+        }
+    }
+
+    #[test]
+    fn check_static_graph_optimize_backward() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![2, 2], &I::Constant::new(0.5));
+        let w = Node::from(&mut w);
+        let mut graph = StaticGraph::trace(&dev, &[shape![2]], |xs| {
+            let b = Node::constant(&dev, shape![2], 1.) * 2.;
+            let h = (1. * w.matmul(&xs[0]) + b).tanh() * 3.;
+            vec![h.sum(0)]
+        });
+        graph.optimize();
+        // Parameter, MatMul, the fused elementwise operators and Sum.
+        assert_eq!(4, graph.num_steps());
+        for &k in &[1., -2.] {
+            let x = dev.new_tensor_by_slice(shape![2], &[k, 1.]);
+            let ys = graph.run(&[&x]);
+            ys[0].backward();
+            let y = ys[0].inner_value().to_float();
+            let x_grad = graph.inputs()[0].inner_gradient().to_vec();
+            let x = Node::from(dev.copy_tensor(&x));
+            let b = Node::constant(&dev, shape![2], 1.) * 2.;
+            let expected = ((w.matmul(&x) + b).tanh() * 3.).sum(0);
+            let expected_y = expected.to_float();
+            let expected_grad = expected.gradients(&[&x]).remove(0).to_vec();
+            assert_vector_ulps_eq!(vec![expected_y], vec![y]);
+            assert_vector_ulps_eq!(expected_grad, x_grad);
+        }
+    }
+
+    // Tanh whose calculations are counted.
+    struct CountedTanhFwImpl<'a> {
+        count: &'a AtomicUsize,
+    }
+
+    impl FunctionFwImpl for CountedTanhFwImpl<'_> {
+        fn call(&self, xs: &[&Tensor], _u32data: &[u32], _f32data: &[f32], ys: &mut [&mut Tensor]) {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            let y = xs[0]
+                .to_vec()
+                .iter()
+                .map(|x| x.tanh())
+                .collect::<Vec<f32>>();
+            ys[0].device().reset_tensor_by_slice(ys[0], &y);
+        }
+    }
+
+    #[test]
+    fn check_static_graph_optimize_backward_recomputes() {
+        let count = AtomicUsize::new(0);
+        let mut dev = D::Naive::new();
+        dev.register_fw_impl("tanh_fw_impl", CountedTanhFwImpl { count: &count });
+        let mut graph = StaticGraph::trace(&dev, &[shape![2]], |xs| {
+            vec![((&xs[0] * 2.).tanh() * 3.).sum(0)]
+        });
+        graph.optimize();
+        // The fused elementwise operators and Sum.
+        assert_eq!(2, graph.num_steps());
+        for (i, &k) in [1., -2.].iter().enumerate() {
+            let x = dev.new_tensor_by_slice(shape![2], &[k, 0.5]);
+            let ys = graph.run(&[&x]);
+            // Tanh is calculated by the fused kernel.
+            assert_eq!(i, count.load(Ordering::Relaxed));
+            ys[0].backward();
+            // The value of Tanh is calculated again only for its gradient.
+            assert_eq!(i + 1, count.load(Ordering::Relaxed));
+            let x_grad = graph.inputs()[0].inner_gradient().to_vec();
+            let expected_grad = [k, 0.5]
+                .iter()
+                .map(|&x: &f32| 6. * (1. - (2. * x).tanh().powi(2)))
+                .collect::<Vec<f32>>();
+            assert_vector_ulps_eq!(expected_grad, x_grad);
+        }
+    }
+
+    #[test]
+    fn check_static_graph_fold_constants() {
+        let dev = D::Naive::new();
+        let mut graph = StaticGraph::trace(&dev, &[shape![3]], |xs| {
+            let c = Node::constant(&dev, shape![3], 2.).exp();
+            vec![&xs[0] * &c, c]
+        });
+        graph.optimize_with(&[GraphPass::FoldConstants]);
+        assert_eq!(1, graph.num_steps());
+        let x = dev.new_tensor_by_slice(shape![3], &[1., 2., 3.]);
+        let ys = graph.run(&[&x]);
+        let y0 = ys[0].inner_value().to_vec();
+        let y1 = ys[1].inner_value().to_vec();
+        let e = 2f32.exp();
+        assert_vector_ulps_eq!(vec![e, 2. * e, 3. * e], y0);
+        assert_vector_ulps_eq!(vec![e; 3], y1);
+    }
+
+    #[test]
+    fn check_static_graph_fold_constants_backward() {
+        let dev = D::Naive::new();
+        let mut w = dev.new_parameter(shape![3], &I::Constant::new(1.));
+        let w = Node::from(&mut w);
+        let mut graph = StaticGraph::trace(&dev, &[shape![3]], |xs| {
+            let c = Node::constant(&dev, shape![3], 2.).exp();
+            vec![(&xs[0] * &c * &w).sum(0), c]
+        });
+        graph.optimize_with(&[GraphPass::FoldConstants]);
+        let e = 2f32.exp();
+        for _ in 0..2 {
+            let x = dev.new_tensor_by_slice(shape![3], &[1., 2., 3.]);
+            let ys = graph.run(&[&x]);
+            ys[0].backward();
+            // The folded value is not released by backward().
+            assert!(ys[1].inner_value().valid());
+            let w_grad = w.inner_gradient().to_vec();
+            assert_vector_ulps_eq!(vec![e, 2. * e, 3. * e], w_grad);
+        }
+    }
+}
@@ -5,10 +5,14 @@ mod macros;
 #[macro_use]
 mod test_utils;
 
+mod data_parallel;
 mod device;
 pub mod device_impl;
 pub mod devices;
+mod dtype;
+mod error;
 pub mod functions;
+mod gradcheck;
 mod graph;
 mod initializer;
 pub mod initializers;
@@ -24,17 +28,26 @@ mod shape;
 mod shape_ops;
 mod tensor;
 
+pub use data_parallel::{AllReduce, DataParallel, InProcessAllReduce, TcpAllReduce};
 pub use device::Device;
 pub use device_impl::DeviceImpl;
-pub use graph::Node;
+pub use dtype::{DType, Element};
+pub use error::{Error, Result};
+pub use gradcheck::{gradcheck, GradCheck, GradCheckElement, GradCheckReport};
+pub use graph::{checkpoint, is_grad_enabled, no_grad, parallel, GraphPass, Node, StaticGraph};
+pub use half::{bf16, f16};
 pub use initializer::Initializer;
 pub use model::Model;
-pub use operator::Operator;
+pub use operator::{Elementwise, Operator};
+pub use operators::CustomFunction;
+pub use optimizer::LossScaler;
 pub use optimizer::Optimizer;
 pub use optimizer::OptimizerBase;
 pub use parameter::Parameter;
 pub use random::DefaultRandomizer;
 pub use random::Randomizer;
+pub use random::RandomizerState;
+pub use random::SeededRandomizer;
 pub use shape::Shape;
 pub use tensor::Tensor;
 
@@ -10,3 +10,12 @@ macro_rules! shape {
         shape![$($dims),*]
     };
 }
+
+// Implements Operator::as_parallel_safe for an operator that is Sync.
+macro_rules! parallel_safe {
+    () => {
+        fn as_parallel_safe(&self) -> Option<&(dyn crate::Operator<'arg, 'dev> + Sync)> {
+            Some(self)
+        }
+    };
+}
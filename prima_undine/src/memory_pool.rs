@@ -86,3 +86,47 @@ impl<'dev> Drop for MemoryPool<'dev> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+
+    #[test]
+    fn check_memory_pool_concurrent() {
+        let dev = D::Naive::new();
+        let dev = &dev;
+        thread::scope(|s| {
+            let handles = (0..8)
+                .map(|t| {
+                    s.spawn(move || {
+                        let mut held = vec![];
+                        for n in 0..200 {
+                            // Sizes over several buckets of the pool.
+                            let size = 1 + (t * 37 + n * 101) % 3000;
+                            let values = (0..size)
+                                .map(|i| (t * 10000 + n + i) as f32)
+                                .collect::<Vec<f32>>();
+                            let x = dev.new_tensor_by_slice(shape![size as u32], &values);
+                            held.push((x, values));
+                            if held.len() > 4 {
+                                let (x, values) = held.remove(n % held.len());
+                                assert_eq!(values, x.to_vec());
+                            }
+                        }
+                        for (x, values) in held {
+                            assert_eq!(values, x.to_vec());
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        });
+        // All memory is returned to the pool.
+        assert!(dev.mem_pool.mem_shift.lock().unwrap().is_empty());
+    }
+}
@@ -1,5 +1,11 @@
 use crate::{Device, Parameter};
 
+// Models holding only parameters and other data without interior
+// mutability are Sync, so one model can be shared read-only by several
+// threads, e.g. with std::thread::scope. Nodes are not thread-safe, so each
+// thread builds its own graphs and uses the parameters through
+// Node::from(&Parameter), which does not accumulate gradients. Training and
+// optimizer updates still require &mut access.
 pub trait Model<'dev> {
     fn parameters(&self) -> Vec<&Parameter<'dev>>;
     fn parameters_mut(&mut self) -> Vec<&mut Parameter<'dev>>;
@@ -50,3 +56,82 @@ impl<'dev> Model<'dev> for Vec<Parameter<'dev>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::Model;
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::optimizers as O;
+    use crate::{initializers as I, no_grad, Device, Node, Optimizer, Parameter};
+
+    struct Mlp<'dev> {
+        w1: Parameter<'dev>,
+        b1: Parameter<'dev>,
+        w2: Parameter<'dev>,
+    }
+
+    impl<'dev> Model<'dev> for Mlp<'dev> {
+        fn parameters(&self) -> Vec<&Parameter<'dev>> {
+            vec![&self.w1, &self.b1, &self.w2]
+        }
+        fn parameters_mut(&mut self) -> Vec<&mut Parameter<'dev>> {
+            vec![&mut self.w1, &mut self.b1, &mut self.w2]
+        }
+    }
+
+    impl<'dev> Mlp<'dev> {
+        fn predict(&self, device: &'dev Device<'dev>, x: &[f32]) -> Vec<f32> {
+            let x = Node::from(device.new_tensor_by_slice(shape![4], x));
+            let w1 = Node::from(&self.w1);
+            let b1 = Node::from(&self.b1);
+            let w2 = Node::from(&self.w2);
+            no_grad(|| w2.matmul((w1.matmul(x) + b1).tanh()).softmax(0).to_vec())
+        }
+    }
+
+    fn assert_sync<T: Sync>(_: &T) {}
+
+    #[test]
+    fn check_model_shared_across_threads() {
+        let dev = D::Naive::new();
+        let mut model = Mlp {
+            w1: dev.new_parameter(shape![16, 4], &I::Uniform::new(-1., 1.)),
+            b1: dev.new_parameter(shape![16], &I::Uniform::new(-1., 1.)),
+            w2: dev.new_parameter(shape![3, 16], &I::Uniform::new(-1., 1.)),
+        };
+        // Parameters with optimizer statistics are shared as well.
+        O::Adam::new(0.001, 0.9, 0.999, 1e-8).configure_parameters(&mut model.parameters_mut());
+        assert_sync(&model);
+        let inputs = (0..8)
+            .map(|i| (0..4).map(|j| ((i * 4 + j) as f32).sin()).collect())
+            .collect::<Vec<Vec<f32>>>();
+        let expected = inputs
+            .iter()
+            .map(|x| model.predict(&dev, x))
+            .collect::<Vec<Vec<f32>>>();
+        let model = &model;
+        let dev = &dev;
+        thread::scope(|s| {
+            let handles = (0..8)
+                .map(|t| {
+                    let inputs = &inputs;
+                    let expected = &expected;
+                    s.spawn(move || {
+                        for n in 0..50 {
+                            let i = (t + n) % inputs.len();
+                            assert_eq!(expected[i], model.predict(dev, &inputs[i]));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        });
+        let w1_grad = model.w1.gradient.to_vec();
+        assert_eq!(vec![0.; 64], w1_grad);
+    }
+}
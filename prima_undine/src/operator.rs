@@ -1,6 +1,99 @@
 use std::cell::RefCell;
 
-use crate::{Device, Shape, Tensor};
+use crate::{Device, Node, Result, Shape, Tensor};
+
+// Elementwise calculations that graph optimizations can fuse into one kernel.
+// Constants are the parameters of the operators with a constant operand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Elementwise {
+    Neg,
+    Exp,
+    Ln,
+    Tanh,
+    Sigmoid,
+    Sqrt,
+    Sin,
+    Cos,
+    Abs,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    AddConst(f32),
+    SubConstL(f32),
+    SubConstR(f32),
+    MulConst(f32),
+    DivConstL(f32),
+    DivConstR(f32),
+}
+
+impl Elementwise {
+    pub fn num_args(self) -> usize {
+        match self {
+            Elementwise::Add | Elementwise::Sub | Elementwise::Mul | Elementwise::Div => 2,
+            _ => 1,
+        }
+    }
+
+    // Whether the result is always equal to the argument.
+    pub fn is_identity(self) -> bool {
+        match self {
+            Elementwise::AddConst(k) | Elementwise::SubConstR(k) => k == 0.,
+            Elementwise::MulConst(k) | Elementwise::DivConstR(k) => k == 1.,
+            _ => false,
+        }
+    }
+
+    // Operation code and constant passed to fused kernels.
+    pub(crate) fn encode(self) -> (u32, f32) {
+        match self {
+            Elementwise::Neg => (0, 0.),
+            Elementwise::Exp => (1, 0.),
+            Elementwise::Ln => (2, 0.),
+            Elementwise::Tanh => (3, 0.),
+            Elementwise::Sigmoid => (4, 0.),
+            Elementwise::Sqrt => (5, 0.),
+            Elementwise::Sin => (6, 0.),
+            Elementwise::Cos => (7, 0.),
+            Elementwise::Abs => (8, 0.),
+            Elementwise::Add => (9, 0.),
+            Elementwise::Sub => (10, 0.),
+            Elementwise::Mul => (11, 0.),
+            Elementwise::Div => (12, 0.),
+            Elementwise::AddConst(k) => (13, k),
+            Elementwise::SubConstL(k) => (14, k),
+            Elementwise::SubConstR(k) => (15, k),
+            Elementwise::MulConst(k) => (16, k),
+            Elementwise::DivConstL(k) => (17, k),
+            Elementwise::DivConstR(k) => (18, k),
+        }
+    }
+
+    pub(crate) fn decode(code: u32, k: f32) -> Elementwise {
+        match code {
+            0 => Elementwise::Neg,
+            1 => Elementwise::Exp,
+            2 => Elementwise::Ln,
+            3 => Elementwise::Tanh,
+            4 => Elementwise::Sigmoid,
+            5 => Elementwise::Sqrt,
+            6 => Elementwise::Sin,
+            7 => Elementwise::Cos,
+            8 => Elementwise::Abs,
+            9 => Elementwise::Add,
+            10 => Elementwise::Sub,
+            11 => Elementwise::Mul,
+            12 => Elementwise::Div,
+            13 => Elementwise::AddConst(k),
+            14 => Elementwise::SubConstL(k),
+            15 => Elementwise::SubConstR(k),
+            16 => Elementwise::MulConst(k),
+            17 => Elementwise::DivConstL(k),
+            18 => Elementwise::DivConstR(k),
+            _ => panic!("unknown elementwise operation code: {}", code),
+        }
+    }
+}
 
 pub trait Operator<'arg, 'dev> {
     fn name(&self) -> String;
@@ -8,4 +101,53 @@ pub trait Operator<'arg, 'dev> {
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape>;
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]);
     fn backward(&self, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]);
+
+    // Checks the shapes of the arguments and returns an error instead of
+    // panicking. The graph calls this instead of forward_shape, so operators
+    // with invalid arguments should override it.
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(self.forward_shape(x))
+    }
+
+    // Returns the operator if it can be called from several threads at once.
+    // The parallel scheduler calls such operators of independent nodes
+    // concurrently, and the others on the calling thread. Operators that are
+    // Sync return Some(self).
+    fn as_parallel_safe(&self) -> Option<&(dyn Operator<'arg, 'dev> + Sync)> {
+        None
+    }
+
+    // Builds the gradients of the arguments as nodes so that they can be
+    // differentiated again. An element is None if the argument receives no
+    // gradient. Returns None if the operator does not support it.
+    #[allow(clippy::type_complexity)]
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        _gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        None
+    }
+
+    // Whether the operator provides a parameter or an input of the graph.
+    fn is_parameter(&self) -> bool {
+        false
+    }
+
+    fn is_input(&self) -> bool {
+        false
+    }
+
+    // Whether the values of an operator without arguments never change, so
+    // that the operators depending only on it can be calculated once.
+    fn is_constant(&self) -> bool {
+        false
+    }
+
+    // The calculation of the operator if it is elementwise over arguments of
+    // the same shape.
+    fn elementwise(&self) -> Option<Elementwise> {
+        None
+    }
 }
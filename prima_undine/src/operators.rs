@@ -9,32 +9,35 @@ mod batch_slice;
 mod batch_split;
 mod batch_sum;
 mod broadcast;
+mod cast;
 mod concat;
-//mod constant;
-//mod conv2d;
-//mod copy;
+mod constant;
+mod conv2d;
+mod copy;
 mod cos;
+mod custom;
 mod div;
 mod elu;
 mod exp;
-//mod flip;
-//mod identity;
+mod flip;
+mod identity;
 mod input;
 mod ln;
 mod logsumexp;
 mod matmul;
 mod max;
-//mod max_pooling2d;
+mod max_pooling2d;
 mod min;
 mod mul;
 mod neg;
 mod parameter;
-//mod permute_dims;
+mod permute_dims;
 mod pick;
+mod placeholder;
 mod powf;
 mod powi;
 mod prelu;
-//mod random;
+mod random;
 mod reshape;
 mod sigmoid;
 mod sin;
@@ -43,7 +46,7 @@ mod softmax_cross_entropy;
 mod softplus;
 mod split;
 mod sqrt;
-//mod stop_gradient;
+mod stop_gradient;
 mod sub;
 mod sum;
 mod tan;
@@ -54,8 +57,15 @@ mod triangular_u;
 
 // input
 
+pub use constant::Constant;
+pub use identity::Identity;
 pub use input::{Input, InputOwner};
 pub use parameter::Parameter;
+pub use placeholder::Placeholder;
+
+// random
+
+pub use random::{RandomBernoulli, RandomLogNormal, RandomNormal, RandomUniform};
 
 // arithmetic
 
@@ -100,7 +110,9 @@ pub use sum::Sum;
 
 // matrix
 
+pub use flip::Flip;
 pub use matmul::Matmul;
+pub use permute_dims::PermuteDims;
 pub use transpose::Transpose;
 pub use triangular_l::TriangularL;
 pub use triangular_u::TriangularU;
@@ -112,20 +124,35 @@ pub use prelu::PReLU;
 
 // manipulation
 
-pub use pick::Pick;
+pub use pick::{Pick, PickBy};
 pub use slice::Slice;
 pub use split::Split;
 
 // batch
 
 pub use batch_concat::BatchConcat;
-pub use batch_pick::BatchPick;
+pub use batch_pick::{BatchPick, BatchPickBy};
 pub use batch_slice::BatchSlice;
 pub use batch_split::BatchSplit;
 pub use batch_sum::BatchSum;
 
+// convolution
+
+pub use conv2d::Conv2d;
+pub use max_pooling2d::MaxPooling2d;
+
 // slice
 
 pub use concat::Concat;
 
 pub use reshape::Reshape;
+
+// others
+
+pub use cast::Cast;
+pub use copy::Copy;
+pub use stop_gradient::StopGradient;
+
+// custom
+
+pub use custom::{Custom, CustomFunction};
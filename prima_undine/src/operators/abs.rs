@@ -1,3 +1,15 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Abs, abs_fw, abs_bw);
+use super::common::unit_gradient;
+
+define_operator_x!(Abs, abs_fw, abs_bw; abs_bw_node, Abs);
+
+fn abs_bw_node<'arg, 'dev>(
+    op: &Abs<'dev>,
+    x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * unit_gradient(x, y, |x, y, gy, gx| op.device.abs_bw(x, y, gy, gx))
+}
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 
 use crate::functions::{ArithmeticDeviceFunctions, BasicFunctions};
-use crate::{Device, Tensor};
+use crate::{Device, Node, Tensor};
+
+use super::common::{reduce_all, reduce_batch};
 
 define_operator_ab!(
     Add,
@@ -9,10 +11,12 @@ define_operator_ab!(
     |device: &Device, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         device.add_bw_a(x[0], x[1], y[0], gy[0], &mut *gx[0].borrow_mut());
         device.add_bw_b(x[0], x[1], y[0], gy[0], &mut *gx[1].borrow_mut());
-    }
+    },
+    add_bw_node,
+    Add
 );
 
-define_operator_x!(AddConst, add_const_fw, add_const_bw, k, f32);
+define_operator_x!(AddConst, add_const_fw, add_const_bw, k, f32; add_const_bw_node, AddConst);
 
 define_operator_ab!(
     AddScalar,
@@ -20,5 +24,37 @@ define_operator_ab!(
     |_device: &Device, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         *gx[0].borrow_mut() += gy[0];
         *gx[1].borrow_mut() += gy[0].flatten().sum(0);
-    }
+    },
+    add_scalar_bw_node
 );
+
+fn add_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(gy[0].clone(), x[0].shape())),
+        Some(reduce_batch(gy[0].clone(), x[1].shape())),
+    ]
+}
+
+fn add_scalar_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(gy[0].clone(), x[0].shape())),
+        Some(reduce_all(gy[0].clone(), x[1].shape())),
+    ]
+}
+
+fn add_const_bw_node<'arg, 'dev>(
+    _op: &AddConst<'dev>,
+    _x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy.clone()
+}
@@ -1,7 +1,8 @@
 use std::cell::RefCell;
 
+use crate::error::OrPanic;
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(BatchConcat);
 impl<'arg, 'dev> Operator<'arg, 'dev> for BatchConcat<'dev> {
@@ -14,7 +15,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchConcat<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::batch_concat(x)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::batch_concat(x)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -30,4 +35,25 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchConcat<'dev> {
             offset += span;
         }
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let mut offset = 0;
+        Some(
+            x.iter()
+                .map(|xi| {
+                    let span = xi.shape().batch();
+                    let gx = gy[0].batch_slice(offset, offset + span);
+                    offset += span;
+                    Some(gx)
+                })
+                .collect(),
+        )
+    }
+
+    parallel_safe!();
 }
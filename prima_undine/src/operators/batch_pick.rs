@@ -1,7 +1,10 @@
 use std::cell::RefCell;
 
+use crate::error::OrPanic;
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+use super::common::{batch_pick_bw_node, read_ids};
 
 pub struct BatchPick<'dev> {
     device: &'dev crate::Device<'dev>,
@@ -27,7 +30,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchPick<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::batch_pick(x[0], &self.ids)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::batch_pick(x[0], &self.ids)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -39,4 +46,69 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchPick<'dev> {
             .device()
             .batch_pick_bw(gy[0], &self.ids, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let gx = batch_pick_bw_node(gy[0], &self.ids, x[0].shape());
+        Some(vec![Some(gx)])
+    }
+
+    parallel_safe!();
+}
+
+// BatchPick with the ids given by the second argument, a u32 or i32 tensor
+// with one id in each batch. The ids receive no gradient.
+pub struct BatchPickBy<'dev> {
+    device: &'dev crate::Device<'dev>,
+}
+
+impl<'dev> BatchPickBy<'dev> {
+    pub fn new(device: &'dev Device<'dev>) -> Self {
+        Self { device }
+    }
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for BatchPickBy<'dev> {
+    fn name(&self) -> String {
+        "BatchPickBy()".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::batch_pick_by(x[0], x[1])?])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(x[0].batch_pick_by(x[1]));
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        gy[0]
+            .device()
+            .batch_pick_by_bw(gy[0], x[1], &mut gx[0].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let ids = read_ids(x[1]);
+        let gx = batch_pick_bw_node(gy[0], &ids, x[0].shape());
+        Some(vec![Some(gx), None])
+    }
+
+    parallel_safe!();
 }
@@ -1,7 +1,10 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+use super::common::zeros;
 
 define_operator_struct!(BatchSlice, lower, u32, upper, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSlice<'dev> {
@@ -19,7 +22,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSlice<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::batch_slice(x[0], self.lower, self.upper)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::batch_slice(x[0], self.lower, self.upper)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -30,4 +37,29 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSlice<'dev> {
         self.device()
             .batch_slice_bw(gy[0], self.lower, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let shape = x[0].shape();
+        let mut xs = vec![];
+        if self.lower > 0 {
+            xs.push(zeros(self.device, shape.resize_batch(self.lower)));
+        }
+        xs.push(gy[0].clone());
+        if self.upper < shape.batch() {
+            xs.push(zeros(
+                self.device,
+                shape.resize_batch(shape.batch() - self.upper),
+            ));
+        }
+        Some(vec![Some(Node::batch_concat(
+            &xs.iter().collect::<Vec<&Node>>(),
+        ))])
+    }
+
+    parallel_safe!();
 }
@@ -1,7 +1,8 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Error, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(BatchSplit, n, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSplit<'dev> {
@@ -14,10 +15,19 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSplit<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
         let total = x[0].batch();
+        if self.n == 0 || !total.is_multiple_of(self.n) {
+            return Err(Error::InvalidArgument {
+                op: "batch_split".to_string(),
+                message: format!("cannot split batch of {:?} into {}", x[0], self.n),
+            });
+        }
         let size = total / self.n;
-        assert!(size * self.n == total);
-        vec![x[0].resize_batch(size); self.n as usize]
+        Ok(vec![x[0].resize_batch(size); self.n as usize])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -33,7 +43,18 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSplit<'dev> {
         let skip = total / self.n;
         for i in 0..self.n {
             self.device()
-                .batch_slice_bw(gy[0], i * skip, &mut *gx[0].borrow_mut());
+                .batch_slice_bw(gy[i as usize], i * skip, &mut gx[0].borrow_mut());
         }
     }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(Node::batch_concat(gy))])
+    }
+
+    parallel_safe!();
 }
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{Device, Operator, Shape, Tensor};
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Node, Operator, Shape, Tensor};
+
+use super::common::zeros;
 
 define_operator_struct!(BatchSum);
 impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSum<'dev> {
@@ -24,4 +26,15 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for BatchSum<'dev> {
     fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
         *gx[0].borrow_mut() += gy[0];
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0] + zeros(self.device, x[0].shape()))])
+    }
+
+    parallel_safe!();
 }
@@ -1,7 +1,8 @@
 use std::cell::RefCell;
 
+use crate::error::OrPanic;
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(Broadcast, dim, u32, size, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Broadcast<'dev> {
@@ -19,7 +20,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Broadcast<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::broadcast(x[0], self.dim, self.size)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::broadcast(x[0], self.dim, self.size)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -29,4 +34,15 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Broadcast<'dev> {
     fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
         *gx[0].borrow_mut() += gy[0].sum(self.dim);
     }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0].sum(self.dim))])
+    }
+
+    parallel_safe!();
 }
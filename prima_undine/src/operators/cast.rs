@@ -0,0 +1,42 @@
+use std::cell::RefCell;
+
+use crate::functions::BasicFunctions;
+use crate::{DType, Device, Node, Operator, Shape, Tensor};
+
+define_operator_struct!(Cast, dtype, DType);
+impl<'arg, 'dev> Operator<'arg, 'dev> for Cast<'dev> {
+    fn name(&self) -> String {
+        format!("Cast(dtype={})", self.dtype)
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        vec![x[0]]
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device.cast_tensor(x[0], self.dtype));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        let mut gx = gx[0].borrow_mut();
+        let g = self.device.cast_tensor(gy[0], gx.dtype());
+        *gx += &g;
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        x[0].forward();
+        let dtype = x[0].inner_value().dtype();
+        Some(vec![Some(gy[0].cast(dtype))])
+    }
+
+    parallel_safe!();
+}
@@ -14,7 +14,7 @@ macro_rules! define_operator_struct {
         impl<'dev> $name<'dev> {
             pub fn new(device: &'dev crate::Device $(, $param: $type)* ) -> $name<'dev> {
                 $name {
-                    device: device,
+                    device,
                     $($param: $param,)*
                 }
             }
@@ -23,8 +23,18 @@ macro_rules! define_operator_struct {
     ($name:ident) => { define_operator_struct!($name,); };
 }
 
+// Operator::elementwise, taking the parameters of the operator as constants.
+macro_rules! define_elementwise {
+    ([] $(, $param:ident)*) => {};
+    ([$elem:ident] $(, $param:ident)*) => {
+        fn elementwise(&self) -> Option<crate::Elementwise> {
+            Some(crate::Elementwise::$elem $((self.$param))*)
+        }
+    };
+}
+
 macro_rules! define_operator_ab {
-    ($name:ident, $fw:ident, $bwfunc:expr) => {
+    ($name:ident, $fw:ident, $bwfunc:expr $(, $bwnode:path $(, $elem:ident)?)?) => {
         define_operator_struct!($name);
         impl<'arg, 'dev> crate::Operator<'arg, 'dev> for $name<'dev> {
             fn name(&self) -> String {
@@ -34,7 +44,10 @@ macro_rules! define_operator_ab {
                 self.device
             }
             fn forward_shape(&self, x: &[crate::Shape]) -> Vec<crate::Shape> {
-                vec![crate::shape_ops::elementwise(x[0], x[1])]
+                crate::error::OrPanic::or_panic(self.try_forward_shape(x))
+            }
+            fn try_forward_shape(&self, x: &[crate::Shape]) -> crate::Result<Vec<crate::Shape>> {
+                Ok(vec![crate::shape_ops::elementwise(x[0], x[1])?])
             }
             fn forward(&self, x: &[&crate::Tensor], y: &mut [&mut crate::Tensor<'arg>]) {
                 y[0].replace(self.device.$fw(x[0], x[1]));
@@ -48,6 +61,22 @@ macro_rules! define_operator_ab {
             ) {
                 $bwfunc(self.device, x, y, gy, gx);
             }
+            parallel_safe!();
+            $(
+            fn backward_node(
+                &self,
+                x: &[&crate::Node<'arg, 'dev>],
+                y: &[&crate::Node<'arg, 'dev>],
+                gy: &[&crate::Node<'arg, 'dev>],
+            ) -> Option<Vec<Option<crate::Node<'arg, 'dev>>>> {
+                Some($bwnode(x, y, gy))
+            }
+            $(
+            fn elementwise(&self) -> Option<crate::Elementwise> {
+                Some(crate::Elementwise::$elem)
+            }
+            )?
+            )?
         }
     };
     ($name:ident) => {
@@ -56,7 +85,7 @@ macro_rules! define_operator_ab {
 }
 
 macro_rules! define_operator_x {
-    ($name:ident, $fw:ident, $bw:ident, $($param:ident, $type:ty),*) => {
+    ($name:ident, $fw:ident, $bw:ident $(, $param:ident, $type:ty)* $(; $bwnode:path $(, $elem:ident)?)?) => {
         define_operator_struct!($name $(, $param, $type)* );
         impl<'arg, 'dev> crate::Operator<'arg, 'dev> for $name<'dev> {
             fn name(&self) -> String {
@@ -74,7 +103,128 @@ macro_rules! define_operator_x {
             fn backward(&self, x: &[&crate::Tensor], y: &[&crate::Tensor], gy: &[&crate::Tensor], gx: &[&std::cell::RefCell<crate::Tensor>]) {
                 self.device.$bw(x[0], y[0], gy[0], $(self.$param,)* &mut *gx[0].borrow_mut());
             }
+            parallel_safe!();
+            $(
+            fn backward_node(
+                &self,
+                x: &[&crate::Node<'arg, 'dev>],
+                y: &[&crate::Node<'arg, 'dev>],
+                gy: &[&crate::Node<'arg, 'dev>],
+            ) -> Option<Vec<Option<crate::Node<'arg, 'dev>>>> {
+                Some(vec![Some($bwnode(self, x[0], y[0], gy[0]))])
+            }
+            )?
+            define_elementwise!([$($($elem)?)?] $(, $param)*);
         }
     };
-    ($name:ident, $fw:ident, $bw:ident) => { define_operator_x!($name, $fw, $bw,); };
+}
+
+// Helpers for backward_node.
+
+use crate::functions::BasicFunctions;
+use crate::{DType, Device, Node, Shape, Tensor};
+
+// Sums up the gradient over the minibatch if the argument was broadcasted.
+pub(crate) fn reduce_batch<'arg, 'dev>(g: Node<'arg, 'dev>, shape: Shape) -> Node<'arg, 'dev> {
+    if shape.batch() == 1 && g.shape().batch() > 1 {
+        g.batch_sum()
+    } else {
+        g
+    }
+}
+
+// Gradient of a scalar argument.
+pub(crate) fn reduce_all<'arg, 'dev>(g: Node<'arg, 'dev>, shape: Shape) -> Node<'arg, 'dev> {
+    reduce_batch(g.flatten().sum(0), shape)
+}
+
+pub(crate) fn zeros<'arg, 'dev>(device: &'dev Device<'dev>, shape: Shape) -> Node<'arg, 'dev> {
+    Node::constant(device, shape, 0.)
+}
+
+// Local derivatives of piecewise linear functions, calculated by the tensor
+// backward with a unit gradient. The result is a constant node.
+pub(crate) fn unit_gradient<'arg, 'dev, F>(
+    x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    f: F,
+) -> Node<'arg, 'dev>
+where
+    F: Fn(&Tensor, &Tensor, &Tensor, &mut Tensor),
+{
+    x.forward();
+    y.forward();
+    let device = x.device();
+    let x = x.inner_value();
+    let y = y.inner_value();
+    let gy = device.new_tensor_by_constant(y.shape, 1.);
+    let mut gx = device.new_tensor_by_constant(x.shape, 0.);
+    f(&x, &y, &gy, &mut gx);
+    Node::from(gx)
+}
+
+// Ids given by a u32 or i32 tensor.
+pub(crate) fn read_ids(ids: &Node) -> Vec<u32> {
+    ids.forward();
+    let device = ids.device();
+    let ids = ids.inner_value();
+    match ids.dtype() {
+        DType::U32 => device.tensor_to_data::<u32>(&ids),
+        DType::I32 => device
+            .tensor_to_data::<i32>(&ids)
+            .into_iter()
+            .map(|id| id as u32)
+            .collect(),
+        dtype => panic!("ids must be u32 or i32, but {} is given", dtype),
+    }
+}
+
+// Gradient of pick: gy is broadcasted over the dimension and masked by the
+// one-hot vectors of the ids.
+pub(crate) fn pick_bw_node<'arg, 'dev>(
+    gy: &Node<'arg, 'dev>,
+    ids: &[u32],
+    dim: u32,
+    shape: Shape,
+) -> Node<'arg, 'dev> {
+    let n = shape[dim];
+    let mask_shape = gy.shape().resize_dim(dim, n);
+    let base = mask_shape.lower_volume(dim);
+    let volume = mask_shape.volume();
+    let skip_i = if ids.len() > 1 { 1 } else { 0 };
+    let mask = (0..mask_shape.size())
+        .map(|i| {
+            let id = ids[(i / volume) as usize * skip_i];
+            if (i % volume) / base % n == id {
+                1.
+            } else {
+                0.
+            }
+        })
+        .collect::<Vec<f32>>();
+    let mask = gy.device().new_tensor_by_slice(mask_shape, &mask);
+    reduce_batch(gy.broadcast(dim, n) * Node::from(mask), shape)
+}
+
+// Gradient of batch_pick: each batch of the argument receives the sum of the
+// batches of gy picked from it.
+pub(crate) fn batch_pick_bw_node<'arg, 'dev>(
+    gy: &Node<'arg, 'dev>,
+    ids: &[u32],
+    shape: Shape,
+) -> Node<'arg, 'dev> {
+    let gs = (0..shape.batch())
+        .map(|b| {
+            ids.iter()
+                .enumerate()
+                .filter(|&(_, &id)| id == b)
+                .map(|(j, _)| gy.batch_slice(j as u32, j as u32 + 1))
+                .fold(None, |acc, g| match acc {
+                    Some(acc) => Some(acc + g),
+                    None => Some(g),
+                })
+                .unwrap_or_else(|| zeros(gy.device(), shape.resize_batch(1)))
+        })
+        .collect::<Vec<Node>>();
+    Node::batch_concat(&gs.iter().collect::<Vec<&Node>>())
 }
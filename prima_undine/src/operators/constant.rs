@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use crate::{Device, Operator, Shape, Tensor};
+
+define_operator_struct!(Constant, shape, Shape, k, f32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for Constant<'dev> {
+    fn name(&self) -> String {
+        format!("Constant(shape={:?},k={})", self.shape, self.k)
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![self.shape]
+    }
+
+    fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].alloc();
+        y[0].reset(self.k);
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+
+use crate::error::OrPanic;
+use crate::functions::BasicDeviceFunctions;
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+#[derive(Clone, Copy)]
+struct Conv2dParams {
+    padding0: u32,
+    padding1: u32,
+    stride0: u32,
+    stride1: u32,
+    dilation0: u32,
+    dilation1: u32,
+}
+
+impl Conv2dParams {
+    fn shape(&self, x: Shape, w: Shape) -> Result<Shape> {
+        shape_ops::conv2d(
+            x,
+            w,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+            self.dilation0,
+            self.dilation1,
+        )
+    }
+
+    fn fw<'dev>(&self, device: &'dev Device<'dev>, x: &Tensor, w: &Tensor) -> Tensor<'dev> {
+        device.conv2d_fw(
+            x,
+            w,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+            self.dilation0,
+            self.dilation1,
+        )
+    }
+
+    // The device functions take y only to check its shape, so gy is given.
+    fn bw_x(&self, device: &Device, x: &Tensor, w: &Tensor, gy: &Tensor, gx: &mut Tensor) {
+        device.conv2d_bw_x(
+            x,
+            w,
+            gy,
+            gy,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+            self.dilation0,
+            self.dilation1,
+            gx,
+        );
+    }
+
+    fn bw_w(&self, device: &Device, x: &Tensor, w: &Tensor, gy: &Tensor, gw: &mut Tensor) {
+        device.conv2d_bw_w(
+            x,
+            w,
+            gy,
+            gy,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+            self.dilation0,
+            self.dilation1,
+            gw,
+        );
+    }
+
+    fn fw_node<'arg, 'dev>(
+        self,
+        device: &'dev Device<'dev>,
+        x: &Node<'arg, 'dev>,
+        w: &Node<'arg, 'dev>,
+    ) -> Node<'arg, 'dev> {
+        Node::create(Conv2d { device, p: self }, &[x, w])
+            .pop()
+            .unwrap()
+    }
+
+    fn bw_x_node<'arg, 'dev>(
+        self,
+        device: &'dev Device<'dev>,
+        x: &Node<'arg, 'dev>,
+        w: &Node<'arg, 'dev>,
+        gy: &Node<'arg, 'dev>,
+    ) -> Node<'arg, 'dev> {
+        Node::create(Conv2dBwX { device, p: self }, &[x, w, gy])
+            .pop()
+            .unwrap()
+    }
+
+    fn bw_w_node<'arg, 'dev>(
+        self,
+        device: &'dev Device<'dev>,
+        x: &Node<'arg, 'dev>,
+        w: &Node<'arg, 'dev>,
+        gy: &Node<'arg, 'dev>,
+    ) -> Node<'arg, 'dev> {
+        Node::create(Conv2dBwW { device, p: self }, &[x, w, gy])
+            .pop()
+            .unwrap()
+    }
+}
+
+pub struct Conv2d<'dev> {
+    device: &'dev Device<'dev>,
+    p: Conv2dParams,
+}
+
+impl<'dev> Conv2d<'dev> {
+    pub fn new(
+        device: &'dev Device<'dev>,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+        dilation0: u32,
+        dilation1: u32,
+    ) -> Conv2d<'dev> {
+        Conv2d {
+            device,
+            p: Conv2dParams {
+                padding0,
+                padding1,
+                stride0,
+                stride1,
+                dilation0,
+                dilation1,
+            },
+        }
+    }
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for Conv2d<'dev> {
+    fn name(&self) -> String {
+        format!(
+            "Conv2d(padding0={},padding1={},stride0={},stride1={},dilation0={},dilation1={})",
+            self.p.padding0,
+            self.p.padding1,
+            self.p.stride0,
+            self.p.stride1,
+            self.p.dilation0,
+            self.p.dilation1
+        )
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![self.p.shape(x[0], x[1])?])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.p.fw(self.device, x[0], x[1]));
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.p
+            .bw_x(self.device, x[0], x[1], gy[0], &mut gx[0].borrow_mut());
+        self.p
+            .bw_w(self.device, x[0], x[1], gy[0], &mut gx[1].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![
+            Some(self.p.bw_x_node(self.device, x[0], x[1], gy[0])),
+            Some(self.p.bw_w_node(self.device, x[0], x[1], gy[0])),
+        ])
+    }
+
+    parallel_safe!();
+}
+
+// Gradient of Conv2d with respect to x, calculated from (x, w, gy). It is
+// linear in w and gy, and x is used only for its shape.
+struct Conv2dBwX<'dev> {
+    device: &'dev Device<'dev>,
+    p: Conv2dParams,
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for Conv2dBwX<'dev> {
+    fn name(&self) -> String {
+        "Conv2dBwX".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        self.p.shape(x[0], x[1])?;
+        Ok(vec![x[0]])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        let mut g = self.device.new_tensor_with_dtype(x[0].shape, x[2].dtype());
+        g.alloc();
+        g.reset(0.);
+        self.p.bw_x(self.device, x[0], x[1], x[2], &mut g);
+        y[0].replace(g);
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.p
+            .bw_w(self.device, gy[0], x[1], x[2], &mut gx[1].borrow_mut());
+        *gx[2].borrow_mut() += self.p.fw(self.device, gy[0], x[1]);
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![
+            None,
+            Some(self.p.bw_w_node(self.device, gy[0], x[1], x[2])),
+            Some(self.p.fw_node(self.device, gy[0], x[1])),
+        ])
+    }
+
+    parallel_safe!();
+}
+
+// Gradient of Conv2d with respect to w, calculated from (x, w, gy). It is
+// linear in x and gy, and w is used only for its shape.
+struct Conv2dBwW<'dev> {
+    device: &'dev Device<'dev>,
+    p: Conv2dParams,
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for Conv2dBwW<'dev> {
+    fn name(&self) -> String {
+        "Conv2dBwW".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        self.p.shape(x[0], x[1])?;
+        Ok(vec![x[1]])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        let mut g = self.device.new_tensor_with_dtype(x[1].shape, x[2].dtype());
+        g.alloc();
+        g.reset(0.);
+        self.p.bw_w(self.device, x[0], x[1], x[2], &mut g);
+        y[0].replace(g);
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.p
+            .bw_x(self.device, x[0], gy[0], x[2], &mut gx[0].borrow_mut());
+        *gx[2].borrow_mut() += self.p.fw(self.device, x[0], gy[0]);
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![
+            Some(self.p.bw_x_node(self.device, x[0], gy[0], x[2])),
+            None,
+            Some(self.p.fw_node(self.device, x[0], gy[0])),
+        ])
+    }
+
+    parallel_safe!();
+}
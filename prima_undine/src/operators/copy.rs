@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+
+use crate::{Device, Node, Operator, Shape, Tensor};
+
+define_operator_struct!(Copy);
+impl<'arg, 'dev> Operator<'arg, 'dev> for Copy<'dev> {
+    fn name(&self) -> String {
+        "Copy".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        vec![x[0]]
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device.copy_tensor(x[0]));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        *gx[0].borrow_mut() += gy[0];
+    }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0].clone())])
+    }
+
+    parallel_safe!();
+}
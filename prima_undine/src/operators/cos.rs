@@ -1,3 +1,13 @@
-use crate::functions::BasicDeviceFunctions;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::Node;
 
-define_operator_x!(Cos, cos_fw, cos_bw);
+define_operator_x!(Cos, cos_fw, cos_bw; cos_bw_node, Cos);
+
+fn cos_bw_node<'arg, 'dev>(
+    _op: &Cos<'dev>,
+    x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    -gy * x.sin()
+}
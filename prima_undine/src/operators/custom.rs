@@ -0,0 +1,419 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crate::error::OrPanic;
+use crate::{Device, Error, Node, Operator, Result, Shape, Tensor};
+
+type ShapeFn<'f> = dyn Fn(&[Shape]) -> Result<Vec<Shape>> + Send + Sync + 'f;
+type ForwardFn<'f> = dyn for<'t> Fn(&[&Tensor<'t>]) -> Vec<Tensor<'t>> + Send + Sync + 'f;
+type BackwardFn<'f> = dyn for<'t> Fn(&[&Tensor<'t>], &[&Tensor<'t>], &[&Tensor<'t>]) -> Vec<Option<Tensor<'t>>>
+    + Send
+    + Sync
+    + 'f;
+
+enum Forward<'f> {
+    Closure(Box<ForwardFn<'f>>),
+    // Name of a kernel registered with Device::register_fw_impl.
+    Kernel(String),
+}
+
+enum Backward<'f> {
+    Closure(Box<BackwardFn<'f>>),
+    // Names of the kernels registered with Device::register_bw_impl, one for
+    // each argument.
+    Kernels(Vec<String>),
+}
+
+struct Definition<'f> {
+    name: String,
+    shape: Option<Box<ShapeFn<'f>>>,
+    forward: Option<Forward<'f>>,
+    backward: Option<Backward<'f>>,
+    u32data: Vec<u32>,
+    f32data: Vec<f32>,
+}
+
+// Builder of user-defined operators.
+//
+// Without a shape function, all arguments must have the same shape and the
+// operator returns one node of that shape. The backward function returns the
+// gradient of each argument, or None if the argument receives no gradient.
+pub struct CustomFunction<'f> {
+    def: Arc<Definition<'f>>,
+}
+
+impl<'f> CustomFunction<'f> {
+    pub fn new(name: &str) -> CustomFunction<'f> {
+        CustomFunction {
+            def: Arc::new(Definition {
+                name: name.to_string(),
+                shape: None,
+                forward: None,
+                backward: None,
+                u32data: vec![],
+                f32data: vec![],
+            }),
+        }
+    }
+
+    fn update<F: FnOnce(&mut Definition<'f>)>(mut self, f: F) -> Self {
+        f(Arc::get_mut(&mut self.def).expect("the custom function is already in use"));
+        self
+    }
+
+    pub fn shape<F>(self, f: F) -> Self
+    where
+        F: Fn(&[Shape]) -> Result<Vec<Shape>> + Send + Sync + 'f,
+    {
+        self.update(|def| def.shape = Some(Box::new(f)))
+    }
+
+    pub fn forward<F>(self, f: F) -> Self
+    where
+        F: for<'t> Fn(&[&Tensor<'t>]) -> Vec<Tensor<'t>> + Send + Sync + 'f,
+    {
+        self.update(|def| def.forward = Some(Forward::Closure(Box::new(f))))
+    }
+
+    pub fn backward<F>(self, f: F) -> Self
+    where
+        F: for<'t> Fn(&[&Tensor<'t>], &[&Tensor<'t>], &[&Tensor<'t>]) -> Vec<Option<Tensor<'t>>>
+            + Send
+            + Sync
+            + 'f,
+    {
+        self.update(|def| def.backward = Some(Backward::Closure(Box::new(f))))
+    }
+
+    // The kernel receives the arguments and writes the allocated outputs.
+    pub fn forward_impl(self, name: &str) -> Self {
+        self.update(|def| def.forward = Some(Forward::Kernel(name.to_string())))
+    }
+
+    // Each kernel accumulates the gradient of the corresponding argument.
+    pub fn backward_impl(self, names: &[&str]) -> Self {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        self.update(|def| def.backward = Some(Backward::Kernels(names)))
+    }
+
+    // Parameters passed to the kernels.
+    pub fn u32data(self, data: &[u32]) -> Self {
+        self.update(|def| def.u32data = data.to_vec())
+    }
+
+    pub fn f32data(self, data: &[f32]) -> Self {
+        self.update(|def| def.f32data = data.to_vec())
+    }
+
+    pub fn call<'arg, 'dev>(&self, xs: &[&Node<'arg, 'dev>]) -> Vec<Node<'arg, 'dev>>
+    where
+        'f: 'arg,
+    {
+        self.try_call(xs).or_panic()
+    }
+
+    pub fn try_call<'arg, 'dev>(&self, xs: &[&Node<'arg, 'dev>]) -> Result<Vec<Node<'arg, 'dev>>>
+    where
+        'f: 'arg,
+    {
+        if self.def.forward.is_none() {
+            return Err(Error::InvalidArgument {
+                op: self.def.name.clone(),
+                message: "custom operator has no forward function".to_string(),
+            });
+        }
+        if xs.is_empty() {
+            return Err(Error::InvalidArgument {
+                op: self.def.name.clone(),
+                message: "custom operator requires at least one argument".to_string(),
+            });
+        }
+        let op = Custom {
+            device: xs[0].device(),
+            def: Arc::clone(&self.def),
+        };
+        Node::try_create(op, xs)
+    }
+}
+
+pub struct Custom<'f, 'dev> {
+    device: &'dev Device<'dev>,
+    def: Arc<Definition<'f>>,
+}
+
+impl<'f, 'arg, 'dev> Operator<'arg, 'dev> for Custom<'f, 'dev> {
+    fn name(&self) -> String {
+        self.def.name.clone()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        let shapes = match &self.def.shape {
+            Some(f) => f(x)?,
+            None => {
+                if let Some(s) = x.iter().find(|&&s| s != x[0]) {
+                    return Err(Error::ShapeMismatch {
+                        op: self.def.name.clone(),
+                        lhs: x[0],
+                        rhs: *s,
+                    });
+                }
+                vec![x[0]]
+            }
+        };
+        if let Some(Backward::Kernels(names)) = &self.def.backward {
+            if names.len() != x.len() {
+                return Err(Error::InvalidArgument {
+                    op: self.def.name.clone(),
+                    message: format!("{} backward kernels for {} arguments", names.len(), x.len()),
+                });
+            }
+        }
+        Ok(shapes)
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        match self.def.forward.as_ref().unwrap() {
+            Forward::Closure(f) => {
+                let rets = f(x);
+                assert!(
+                    rets.len() == y.len(),
+                    "{} returned {} values, expected {}",
+                    self.def.name,
+                    rets.len(),
+                    y.len()
+                );
+                for (y, ret) in y.iter_mut().zip(rets) {
+                    assert!(
+                        ret.shape == y.shape,
+                        "{} returned a value of shape {:?}, expected {:?}",
+                        self.def.name,
+                        ret.shape,
+                        y.shape
+                    );
+                    y.replace(ret);
+                }
+            }
+            Forward::Kernel(name) => {
+                let dtype = x[0].dtype();
+                let mut rets = y
+                    .iter()
+                    .map(|y| {
+                        let mut ret = self.device.new_tensor_with_dtype(y.shape, dtype);
+                        ret.alloc();
+                        ret
+                    })
+                    .collect::<Vec<Tensor>>();
+                let mut rets_ref = rets.iter_mut().collect::<Vec<&mut Tensor>>();
+                self.device.call_fw_impl(
+                    name,
+                    x,
+                    &self.def.u32data,
+                    &self.def.f32data,
+                    &mut rets_ref,
+                );
+                for (y, ret) in y.iter_mut().zip(rets) {
+                    y.replace(ret);
+                }
+            }
+        }
+    }
+
+    fn backward(&self, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        match &self.def.backward {
+            Some(Backward::Closure(f)) => {
+                let grads = f(x, y, gy);
+                assert!(
+                    grads.len() == gx.len(),
+                    "{} returned {} gradients, expected {}",
+                    self.def.name,
+                    grads.len(),
+                    gx.len()
+                );
+                for (gx, grad) in gx.iter().zip(grads) {
+                    if let Some(grad) = grad {
+                        *gx.borrow_mut() += &grad;
+                    }
+                }
+            }
+            Some(Backward::Kernels(names)) => {
+                for (gx, name) in gx.iter().zip(names) {
+                    self.device.call_bw_impl(
+                        name,
+                        x,
+                        y,
+                        gy,
+                        &self.def.u32data,
+                        &self.def.f32data,
+                        &mut gx.borrow_mut(),
+                    );
+                }
+            }
+            None => panic!("custom operator {} has no backward function", self.def.name),
+        }
+    }
+
+    parallel_safe!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CustomFunction;
+    use crate::device_impl::{FunctionBwImpl, FunctionFwImpl};
+    use crate::devices as D;
+    use crate::functions::BasicFunctions;
+    use crate::{gradcheck, Error, Node, Shape, Tensor};
+
+    #[test]
+    fn check_custom_closure() {
+        let dev = D::Naive::new();
+        let square = CustomFunction::new("Square")
+            .forward(|xs| vec![xs[0] * xs[0]])
+            .backward(|xs, _ys, gys| vec![Some(2. * xs[0] * gys[0])]);
+        let x = dev.new_tensor_by_slice(shape![3], &[1., -2., 3.]);
+        let x = Node::from(&x);
+        let y = square.call(&[&x]).pop().unwrap();
+        assert_eq!("Square", y.operator_name());
+        assert_eq!(vec![1., 4., 9.], y.to_vec());
+        y.sum(0).backward();
+        let x_grad = x.inner_gradient().to_vec();
+        assert_eq!(vec![2., -4., 6.], x_grad);
+    }
+
+    #[test]
+    fn check_custom_shape() {
+        let dev = D::Naive::new();
+        let outer = CustomFunction::new("Outer")
+            .shape(|xs: &[Shape]| {
+                if xs.iter().all(|x| x.is_column_vector()) {
+                    Ok(vec![shape![xs[0][0], xs[1][0]]])
+                } else {
+                    Err(Error::InvalidArgument {
+                        op: "Outer".to_string(),
+                        message: "arguments must be column vectors".to_string(),
+                    })
+                }
+            })
+            .forward(|xs| vec![xs[0].matmul(&xs[1].transpose())])
+            .backward(|xs, _ys, gys| {
+                vec![
+                    Some(gys[0].matmul(xs[1])),
+                    Some(gys[0].transpose().matmul(xs[0])),
+                ]
+            });
+        let a = dev.new_tensor_by_slice(shape![2], &[1., 2.]);
+        let b = dev.new_tensor_by_slice(shape![3], &[3., 4., 5.]);
+        let report = gradcheck(&[&a, &b], &mut [], |xs, _| {
+            outer.call(&[&xs[0], &xs[1]]).pop().unwrap()
+        });
+        assert!(report.passed(), "{}", report);
+        let c = dev.new_tensor_by_slice(shape![3, 2], &[0.; 6]);
+        let err = outer
+            .try_call(&[&Node::from(&a), &Node::from(&c)])
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("column vectors"));
+    }
+
+    #[test]
+    fn check_custom_default_shape() {
+        let dev = D::Naive::new();
+        let add = CustomFunction::new("MyAdd").forward(|xs| vec![xs[0] + xs[1]]);
+        let a = Node::from(dev.new_tensor_by_slice(shape![2], &[1., 2.]));
+        let b = Node::from(dev.new_tensor_by_slice(shape![3], &[1., 2., 3.]));
+        let err = add.try_call(&[&a, &b]).err().unwrap();
+        assert!(err.to_string().contains("MyAdd"));
+    }
+
+    #[test]
+    fn check_custom_invalid_call() {
+        let dev = D::Naive::new();
+        let x = Node::from(dev.new_tensor_by_slice(shape![2], &[1., 2.]));
+        let nothing = CustomFunction::new("Nothing");
+        match nothing.try_call(&[&x]) {
+            Err(Error::InvalidArgument { op, message }) => {
+                assert_eq!("Nothing", op);
+                assert!(message.contains("no forward function"));
+            }
+            _ => panic!("expected InvalidArgument"),
+        }
+        let double = CustomFunction::new("Double").forward(|xs| vec![2. * xs[0]]);
+        match double.try_call(&[]) {
+            Err(Error::InvalidArgument { op, message }) => {
+                assert_eq!("Double", op);
+                assert!(message.contains("at least one argument"));
+            }
+            _ => panic!("expected InvalidArgument"),
+        }
+    }
+
+    struct CubeFwImpl {}
+
+    impl FunctionFwImpl for CubeFwImpl {
+        fn call(&self, xs: &[&Tensor], _u32data: &[u32], f32data: &[f32], ys: &mut [&mut Tensor]) {
+            let x = xs[0].to_vec();
+            let y = x
+                .iter()
+                .map(|&x| f32data[0] * x * x * x)
+                .collect::<Vec<f32>>();
+            ys[0].device().reset_tensor_by_slice(ys[0], &y);
+        }
+    }
+
+    struct CubeBwImpl {}
+
+    impl FunctionBwImpl for CubeBwImpl {
+        fn call(
+            &self,
+            xs: &[&Tensor],
+            _ys: &[&Tensor],
+            gys: &[&Tensor],
+            _u32data: &[u32],
+            f32data: &[f32],
+            gx: &mut Tensor,
+        ) {
+            let x = xs[0].to_vec();
+            let gy = gys[0].to_vec();
+            let g = gx
+                .to_vec()
+                .iter()
+                .zip(x.iter().zip(&gy))
+                .map(|(&g, (&x, &gy))| g + 3. * f32data[0] * x * x * gy)
+                .collect::<Vec<f32>>();
+            gx.device().reset_tensor_by_slice(gx, &g);
+        }
+    }
+
+    #[test]
+    fn check_custom_kernel() {
+        let mut dev = D::Naive::new();
+        dev.register_fw_impl("cube_fw_impl", CubeFwImpl {});
+        dev.register_bw_impl("cube_bw_impl", CubeBwImpl {});
+        let cube = CustomFunction::new("Cube")
+            .forward_impl("cube_fw_impl")
+            .f32data(&[2.])
+            .backward_impl(&["cube_bw_impl"]);
+        let x = dev.new_tensor_by_slice(shape![3], &[1., -2., 3.]);
+        let report = gradcheck(&[&x], &mut [], |xs, _| cube.call(&[&xs[0]]).pop().unwrap());
+        assert!(report.passed(), "{}", report);
+        let x = Node::from(&x);
+        let y = cube.call(&[&x]).pop().unwrap();
+        assert_eq!(vec![2., -16., 54.], y.to_vec());
+    }
+
+    #[test]
+    #[should_panic(expected = "no backward function")]
+    fn check_custom_no_backward() {
+        let dev = D::Naive::new();
+        let double = CustomFunction::new("Double").forward(|xs| vec![2. * xs[0]]);
+        let x = Node::from(dev.new_tensor_by_slice(shape![2], &[1., 2.]));
+        double.call(&[&x]).pop().unwrap().sum(0).backward();
+    }
+}
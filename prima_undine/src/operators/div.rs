@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 
 use crate::functions::{ArithmeticDeviceFunctions, BasicFunctions};
-use crate::{Device, Tensor};
+use crate::{Device, Node, Tensor};
+
+use super::common::{reduce_all, reduce_batch};
 
 define_operator_ab!(
     Div,
@@ -9,11 +11,13 @@ define_operator_ab!(
     |device: &Device, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         device.div_bw_a(x[0], x[1], y[0], gy[0], &mut *gx[0].borrow_mut());
         device.div_bw_b(x[0], x[1], y[0], gy[0], &mut *gx[1].borrow_mut());
-    }
+    },
+    div_bw_node,
+    Div
 );
 
-define_operator_x!(DivConstL, div_const_l_fw, div_const_l_bw, k, f32);
-define_operator_x!(DivConstR, div_const_r_fw, div_const_r_bw, k, f32);
+define_operator_x!(DivConstL, div_const_l_fw, div_const_l_bw, k, f32; div_const_l_bw_node, DivConstL);
+define_operator_x!(DivConstR, div_const_r_fw, div_const_r_bw, k, f32; div_const_r_bw_node, DivConstR);
 
 define_operator_ab!(
     DivScalarL,
@@ -22,7 +26,8 @@ define_operator_ab!(
         let ref a = gy[0] / x[0];
         *gx[0].borrow_mut() -= &(a * y[0]);
         *gx[1].borrow_mut() += &a.flatten().sum(0);
-    }
+    },
+    div_scalar_l_bw_node
 );
 
 define_operator_ab!(
@@ -32,5 +37,63 @@ define_operator_ab!(
         let ref a = gy[0] / x[1];
         *gx[0].borrow_mut() += a;
         *gx[1].borrow_mut() -= (a * y[0]).flatten().sum(0);
-    }
+    },
+    div_scalar_r_bw_node
 );
+
+fn div_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    let a = gy[0] / x[1];
+    let b = -&a * y[0];
+    vec![
+        Some(reduce_batch(a, x[0].shape())),
+        Some(reduce_batch(b, x[1].shape())),
+    ]
+}
+
+fn div_scalar_l_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    let a = gy[0] / x[0];
+    let b = -&a * y[0];
+    vec![
+        Some(reduce_batch(b, x[0].shape())),
+        Some(reduce_all(a, x[1].shape())),
+    ]
+}
+
+fn div_scalar_r_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    let a = gy[0] / x[1];
+    let b = -&a * y[0];
+    vec![
+        Some(reduce_batch(a, x[0].shape())),
+        Some(reduce_all(b, x[1].shape())),
+    ]
+}
+
+fn div_const_l_bw_node<'arg, 'dev>(
+    _op: &DivConstL<'dev>,
+    x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    -gy * y / x
+}
+
+fn div_const_r_bw_node<'arg, 'dev>(
+    op: &DivConstR<'dev>,
+    _x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy / op.k
+}
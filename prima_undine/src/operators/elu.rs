@@ -1,3 +1,18 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(ELU, prelu_fw, prelu_bw, a, f32);
+use super::common::unit_gradient;
+
+define_operator_x!(ELU, elu_fw, elu_bw, a, f32; elu_bw_node);
+
+// The derivative is 1 for positive x and y + a otherwise, which depends on x
+// through y.
+fn elu_bw_node<'arg, 'dev>(
+    op: &ELU<'dev>,
+    x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    let positive = unit_gradient(x, y, |x, y, gy, gx| op.device.prelu_bw(x, y, gy, 0., gx));
+    gy * (&positive + (1. - &positive) * (y + op.a))
+}
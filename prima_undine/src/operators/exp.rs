@@ -1,3 +1,13 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Exp, exp_fw, exp_bw);
+define_operator_x!(Exp, exp_fw, exp_bw; exp_bw_node, Exp);
+
+fn exp_bw_node<'arg, 'dev>(
+    _op: &Exp<'dev>,
+    _x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * y
+}
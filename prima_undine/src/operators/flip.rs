@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Node, Operator, Shape, Tensor};
+
+define_operator_struct!(Flip, dim, u32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for Flip<'dev> {
+    fn name(&self) -> String {
+        "Flip(dim=".to_string() + &self.dim.to_string() + ")"
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        vec![x[0]]
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device().flip_fw(x[0], self.dim));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.device()
+            .flip_bw(gy[0], self.dim, &mut gx[0].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0].flip(self.dim))])
+    }
+
+    parallel_safe!();
+}
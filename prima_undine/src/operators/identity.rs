@@ -0,0 +1,34 @@
+use std::cell::RefCell;
+
+use crate::{Device, Operator, Shape, Tensor};
+
+define_operator_struct!(Identity, size, u32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for Identity<'dev> {
+    fn name(&self) -> String {
+        "Identity(size=".to_string() + &self.size.to_string() + ")"
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![shape![self.size, self.size]]
+    }
+
+    fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].alloc();
+        self.device
+            .call_fw_impl("identity_impl", &[], &[], &[], &mut [&mut *y[0]]);
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
+
+    parallel_safe!();
+}
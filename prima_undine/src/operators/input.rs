@@ -32,6 +32,14 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Input<'arg, 'dev> {
     fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
         // NOP
     }
+
+    fn is_input(&self) -> bool {
+        true
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
 }
 
 pub struct InputOwner<'dev> {
@@ -65,4 +73,12 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for InputOwner<'dev> {
     fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
         // NOP
     }
+
+    fn is_input(&self) -> bool {
+        true
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
 }
@@ -1,3 +1,13 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Ln, ln_fw, ln_bw);
+define_operator_x!(Ln, ln_fw, ln_bw; ln_bw_node, Ln);
+
+fn ln_bw_node<'arg, 'dev>(
+    _op: &Ln<'dev>,
+    x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy / x
+}
@@ -1,7 +1,8 @@
 use std::cell::RefCell;
 
+use crate::error::OrPanic;
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{Device, Operator, Shape, Tensor};
+use crate::{Device, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(Logsumexp, dim, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Logsumexp<'dev> {
@@ -14,7 +15,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Logsumexp<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![x[0].resize_dim(self.dim, 1)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![x[0].try_resize_dim(self.dim, 1)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -26,4 +31,17 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Logsumexp<'dev> {
         *gx[0].borrow_mut() +=
             (x[0] - y[0].broadcast(self.dim, size)).exp() * gy[0].broadcast(self.dim, size);
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let size = x[0].shape()[self.dim];
+        let gx = (x[0] - y[0].broadcast(self.dim, size)).exp() * gy[0].broadcast(self.dim, size);
+        Some(vec![Some(gx)])
+    }
+
+    parallel_safe!();
 }
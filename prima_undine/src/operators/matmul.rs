@@ -1,7 +1,10 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+use super::common::reduce_batch;
 
 define_operator_struct!(Matmul);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Matmul<'dev> {
@@ -14,7 +17,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Matmul<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::matmul(x[0], x[1])]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::matmul(x[0], x[1])?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -27,4 +34,18 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Matmul<'dev> {
         self.device()
             .matmul_bw_b(x[0], x[1], y[0], gy[0], &mut *gx[1].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![
+            Some(reduce_batch(gy[0].matmul(x[1].transpose()), x[0].shape())),
+            Some(reduce_batch(x[0].transpose().matmul(gy[0]), x[1].shape())),
+        ])
+    }
+
+    parallel_safe!();
 }
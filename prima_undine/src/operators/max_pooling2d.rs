@@ -0,0 +1,255 @@
+use std::cell::RefCell;
+
+use crate::error::OrPanic;
+use crate::functions::BasicDeviceFunctions;
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+#[derive(Clone, Copy)]
+struct Pool2dParams {
+    window0: u32,
+    window1: u32,
+    padding0: u32,
+    padding1: u32,
+    stride0: u32,
+    stride1: u32,
+}
+
+impl Pool2dParams {
+    fn shape(&self, x: Shape) -> Result<Shape> {
+        shape_ops::pool2d(
+            x,
+            self.window0,
+            self.window1,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+        )
+    }
+
+    // The device function takes y only to check its shape, so gy is given.
+    fn bw(&self, device: &Device, x: &Tensor, gy: &Tensor, gx: &mut Tensor) {
+        device.max_pool2d_bw(
+            x,
+            gy,
+            gy,
+            self.window0,
+            self.window1,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+            gx,
+        );
+    }
+
+    fn pick<'dev>(&self, device: &'dev Device<'dev>, x: &Tensor, z: &Tensor) -> Tensor<'dev> {
+        device.max_pool2d_pick_fw(
+            x,
+            z,
+            self.window0,
+            self.window1,
+            self.padding0,
+            self.padding1,
+            self.stride0,
+            self.stride1,
+        )
+    }
+
+    fn bw_node<'arg, 'dev>(
+        self,
+        device: &'dev Device<'dev>,
+        x: &Node<'arg, 'dev>,
+        gy: &Node<'arg, 'dev>,
+    ) -> Node<'arg, 'dev> {
+        Node::create(MaxPooling2dBw { device, p: self }, &[x, gy])
+            .pop()
+            .unwrap()
+    }
+
+    fn pick_node<'arg, 'dev>(
+        self,
+        device: &'dev Device<'dev>,
+        x: &Node<'arg, 'dev>,
+        z: &Node<'arg, 'dev>,
+    ) -> Node<'arg, 'dev> {
+        Node::create(MaxPooling2dPick { device, p: self }, &[x, z])
+            .pop()
+            .unwrap()
+    }
+}
+
+pub struct MaxPooling2d<'dev> {
+    device: &'dev Device<'dev>,
+    p: Pool2dParams,
+}
+
+impl<'dev> MaxPooling2d<'dev> {
+    pub fn new(
+        device: &'dev Device<'dev>,
+        window0: u32,
+        window1: u32,
+        padding0: u32,
+        padding1: u32,
+        stride0: u32,
+        stride1: u32,
+    ) -> MaxPooling2d<'dev> {
+        MaxPooling2d {
+            device,
+            p: Pool2dParams {
+                window0,
+                window1,
+                padding0,
+                padding1,
+                stride0,
+                stride1,
+            },
+        }
+    }
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for MaxPooling2d<'dev> {
+    fn name(&self) -> String {
+        format!(
+            "MaxPooling2d(window0={},window1={},padding0={},padding1={},stride0={},stride1={})",
+            self.p.window0,
+            self.p.window1,
+            self.p.padding0,
+            self.p.padding1,
+            self.p.stride0,
+            self.p.stride1
+        )
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![self.p.shape(x[0])?])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device.max_pool2d_fw(
+            x[0],
+            self.p.window0,
+            self.p.window1,
+            self.p.padding0,
+            self.p.padding1,
+            self.p.stride0,
+            self.p.stride1,
+        ));
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.p.bw(self.device, x[0], gy[0], &mut gx[0].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(self.p.bw_node(self.device, x[0], gy[0]))])
+    }
+
+    parallel_safe!();
+}
+
+// Gradient of MaxPooling2d calculated from (x, gy). The maxima are locally
+// constant, so x receives no gradient.
+struct MaxPooling2dBw<'dev> {
+    device: &'dev Device<'dev>,
+    p: Pool2dParams,
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for MaxPooling2dBw<'dev> {
+    fn name(&self) -> String {
+        "MaxPooling2dBw".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        self.p.shape(x[0])?;
+        Ok(vec![x[0]])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        let mut g = self.device.new_tensor_with_dtype(x[0].shape, x[1].dtype());
+        g.alloc();
+        g.reset(0.);
+        self.p.bw(self.device, x[0], x[1], &mut g);
+        y[0].replace(g);
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        *gx[1].borrow_mut() += self.p.pick(self.device, x[0], gy[0]);
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![None, Some(self.p.pick_node(self.device, x[0], gy[0]))])
+    }
+
+    parallel_safe!();
+}
+
+// Elements of z at the maxima of the windows of x, the transpose of
+// MaxPooling2dBw with respect to gy.
+struct MaxPooling2dPick<'dev> {
+    device: &'dev Device<'dev>,
+    p: Pool2dParams,
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for MaxPooling2dPick<'dev> {
+    fn name(&self) -> String {
+        "MaxPooling2dPick".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![self.p.shape(x[0])?])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.p.pick(self.device, x[0], x[1]));
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.p.bw(self.device, x[0], gy[0], &mut gx[1].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![None, Some(self.p.bw_node(self.device, x[0], gy[0]))])
+    }
+
+    parallel_safe!();
+}
@@ -1,7 +1,10 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Node, Operator, Result, Shape, Tensor};
+
+use super::common::unit_gradient;
 
 define_operator_struct!(Min, dim, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Min<'dev> {
@@ -14,7 +17,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Min<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![x[0].resize_dim(self.dim, 1)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![x[0].try_resize_dim(self.dim, 1)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -25,4 +32,19 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Min<'dev> {
         self.device()
             .min_bw(x[0], y[0], gy[0], self.dim, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let size = x[0].shape()[self.dim];
+        let mask = unit_gradient(x[0], y[0], |x, y, gy, gx| {
+            self.device.min_bw(x, y, gy, self.dim, gx)
+        });
+        Some(vec![Some(gy[0].broadcast(self.dim, size) * mask)])
+    }
+
+    parallel_safe!();
 }
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 
 use crate::functions::{ArithmeticDeviceFunctions, BasicFunctions};
-use crate::{Device, Tensor};
+use crate::{Device, Node, Tensor};
+
+use super::common::{reduce_all, reduce_batch};
 
 define_operator_ab!(
     Mul,
@@ -9,10 +11,12 @@ define_operator_ab!(
     |device: &Device, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         device.mul_bw_a(x[0], x[1], y[0], gy[0], &mut *gx[0].borrow_mut());
         device.mul_bw_b(x[0], x[1], y[0], gy[0], &mut *gx[1].borrow_mut());
-    }
+    },
+    mul_bw_node,
+    Mul
 );
 
-define_operator_x!(MulConst, mul_const_fw, mul_const_bw, k, f32);
+define_operator_x!(MulConst, mul_const_fw, mul_const_bw, k, f32; mul_const_bw_node, MulConst);
 
 define_operator_ab!(
     MulScalar,
@@ -20,5 +24,37 @@ define_operator_ab!(
     |_device: &Device, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         *gx[0].borrow_mut() += x[1] * gy[0];
         *gx[1].borrow_mut() += (x[0] * gy[0]).flatten().sum(0);
-    }
+    },
+    mul_scalar_bw_node
 );
+
+fn mul_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(gy[0] * x[1], x[0].shape())),
+        Some(reduce_batch(gy[0] * x[0], x[1].shape())),
+    ]
+}
+
+fn mul_scalar_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(gy[0] * x[1], x[0].shape())),
+        Some(reduce_all(gy[0] * x[0], x[1].shape())),
+    ]
+}
+
+fn mul_const_bw_node<'arg, 'dev>(
+    op: &MulConst<'dev>,
+    _x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * op.k
+}
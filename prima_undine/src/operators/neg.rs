@@ -1,3 +1,13 @@
 use crate::functions::ArithmeticDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Neg, neg_fw, neg_bw);
+define_operator_x!(Neg, neg_fw, neg_bw; neg_bw_node, Neg);
+
+fn neg_bw_node<'arg, 'dev>(
+    _op: &Neg<'dev>,
+    _x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    -gy
+}
@@ -1,17 +1,20 @@
 use std::cell::RefCell;
+use std::sync::Mutex;
 
-use crate::{Device, Operator, Shape, Tensor};
+use crate::{DType, Device, Operator, Shape, Tensor};
 
 pub struct Parameter<'arg, 'dev> {
     value: &'arg Tensor<'dev>,
-    gradient: RefCell<&'arg mut Tensor<'dev>>,
+    gradient: Mutex<&'arg mut Tensor<'dev>>,
+    dtype: DType,
 }
 
 impl<'arg, 'dev> Parameter<'arg, 'dev> {
     pub fn new(parameter: &'arg mut crate::Parameter<'dev>) -> Parameter<'arg, 'dev> {
         Parameter {
+            dtype: parameter.compute_dtype(),
             value: &parameter.value,
-            gradient: RefCell::new(&mut parameter.gradient),
+            gradient: Mutex::new(&mut parameter.gradient),
         }
     }
 }
@@ -30,10 +33,22 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Parameter<'arg, 'dev> {
     }
 
     fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
-        y[0].refer(self.value);
+        if self.dtype == DType::F32 {
+            y[0].refer(self.value);
+        } else {
+            y[0].replace(self.device().cast_tensor(self.value, self.dtype));
+        }
     }
 
     fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
-        **self.gradient.borrow_mut() += gy[0];
+        if gy[0].dtype() == DType::F32 {
+            **self.gradient.lock().unwrap() += gy[0];
+        } else {
+            **self.gradient.lock().unwrap() += self.device().cast_tensor(gy[0], DType::F32);
+        }
+    }
+
+    fn is_parameter(&self) -> bool {
+        true
     }
 }
@@ -0,0 +1,61 @@
+use std::cell::RefCell;
+
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+pub struct PermuteDims<'dev> {
+    device: &'dev crate::Device<'dev>,
+    perm: Vec<u32>,
+}
+
+impl<'dev> PermuteDims<'dev> {
+    pub fn new(device: &'dev Device<'dev>, perm: &[u32]) -> PermuteDims<'dev> {
+        PermuteDims {
+            device,
+            perm: perm.to_vec(),
+        }
+    }
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for PermuteDims<'dev> {
+    fn name(&self) -> String {
+        format!("PermuteDims(perm={:?})", self.perm)
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::permute_dims(x[0], &self.perm)?])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device().permute_dims_fw(x[0], &self.perm));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        self.device()
+            .permute_dims_bw(gy[0], &self.perm, &mut gx[0].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let mut inv = vec![0; self.perm.len()];
+        for (i, &p) in self.perm.iter().enumerate() {
+            inv[p as usize] = i as u32;
+        }
+        Some(vec![Some(gy[0].permute_dims(&inv))])
+    }
+
+    parallel_safe!();
+}
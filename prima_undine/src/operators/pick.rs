@@ -1,7 +1,10 @@
 use std::cell::RefCell;
 
+use crate::error::OrPanic;
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+use super::common::{pick_bw_node, read_ids};
 
 pub struct Pick<'dev> {
     device: &'dev crate::Device<'dev>,
@@ -29,7 +32,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Pick<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::pick(x[0], &self.ids, self.dim)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::pick(x[0], &self.ids, self.dim)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -41,4 +48,70 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Pick<'dev> {
             .device()
             .pick_bw(gy[0], &self.ids, self.dim, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let gx = pick_bw_node(gy[0], &self.ids, self.dim, x[0].shape());
+        Some(vec![Some(gx)])
+    }
+
+    parallel_safe!();
+}
+
+// Pick with the ids given by the second argument, a u32 or i32 tensor with
+// one id in each batch. The ids receive no gradient.
+pub struct PickBy<'dev> {
+    device: &'dev crate::Device<'dev>,
+    dim: u32,
+}
+
+impl<'dev> PickBy<'dev> {
+    pub fn new(device: &'dev Device<'dev>, dim: u32) -> PickBy<'dev> {
+        PickBy { device, dim }
+    }
+}
+
+impl<'arg, 'dev> Operator<'arg, 'dev> for PickBy<'dev> {
+    fn name(&self) -> String {
+        "PickBy(dim=".to_string() + &self.dim.to_string() + ")"
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::pick_by(x[0], x[1], self.dim)?])
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(x[0].pick_by(x[1], self.dim));
+    }
+
+    fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
+        gy[0]
+            .device()
+            .pick_by_bw(gy[0], x[1], self.dim, &mut gx[0].borrow_mut());
+    }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let ids = read_ids(x[1]);
+        let gx = pick_bw_node(gy[0], &ids, self.dim, x[0].shape());
+        Some(vec![Some(gx), None])
+    }
+
+    parallel_safe!();
 }
@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use crate::{Device, Operator, Shape, Tensor};
+
+// Input of a static graph. The value is set by StaticGraph::run.
+define_operator_struct!(Placeholder, shape, Shape);
+impl<'arg, 'dev> Operator<'arg, 'dev> for Placeholder<'dev> {
+    fn name(&self) -> String {
+        "Placeholder".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![self.shape]
+    }
+
+    fn forward(&self, _x: &[&Tensor], _y: &mut [&mut Tensor<'arg>]) {
+        panic!("placeholders are calculated only by StaticGraph::run");
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+
+    fn is_input(&self) -> bool {
+        true
+    }
+}
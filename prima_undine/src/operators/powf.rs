@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{Device, Tensor};
+use crate::{Device, Node, Tensor};
+
+use super::common::{reduce_all, reduce_batch};
 
 define_operator_ab!(
     Pow,
@@ -9,11 +11,12 @@ define_operator_ab!(
     |device: &Device, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         device.powf_bw_a(x[0], x[1], y[0], gy[0], &mut *gx[0].borrow_mut());
         device.powf_bw_b(x[0], x[1], y[0], gy[0], &mut *gx[1].borrow_mut());
-    }
+    },
+    pow_bw_node
 );
 
 define_operator_x!(PowConstL, powf_const_l_fw, powf_const_l_bw, k, f32);
-define_operator_x!(PowConstR, powf_const_r_fw, powf_const_r_bw, k, f32);
+define_operator_x!(PowConstR, powf_const_r_fw, powf_const_r_bw, k, f32; pow_const_r_bw_node);
 
 define_operator_ab!(
     PowScalarL,
@@ -22,7 +25,8 @@ define_operator_ab!(
         let a = gy[0] * y[0];
         *gx[0].borrow_mut() += &a * x[1].ln();
         *gx[1].borrow_mut() += (a * x[0] / x[1]).flatten().sum(0);
-    }
+    },
+    pow_scalar_l_bw_node
 );
 
 define_operator_ab!(
@@ -32,5 +36,57 @@ define_operator_ab!(
         let a = gy[0] * y[0];
         *gx[0].borrow_mut() += &a * x[1] / x[0];
         *gx[1].borrow_mut() += (a * x[0].ln()).flatten().sum(0);
-    }
+    },
+    pow_scalar_r_bw_node
 );
+
+fn pow_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    let a = gy[0] * y[0];
+    let b = &a * x[0].ln();
+    let a = a * x[1] / x[0];
+    vec![
+        Some(reduce_batch(a, x[0].shape())),
+        Some(reduce_batch(b, x[1].shape())),
+    ]
+}
+
+fn pow_scalar_l_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    let a = gy[0] * y[0];
+    let b = &a * x[0] / x[1];
+    let a = a * x[1].ln();
+    vec![
+        Some(reduce_batch(a, x[0].shape())),
+        Some(reduce_all(b, x[1].shape())),
+    ]
+}
+
+fn pow_scalar_r_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    let a = gy[0] * y[0];
+    let b = &a * x[0].ln();
+    let a = a * x[1] / x[0];
+    vec![
+        Some(reduce_batch(a, x[0].shape())),
+        Some(reduce_all(b, x[1].shape())),
+    ]
+}
+
+fn pow_const_r_bw_node<'arg, 'dev>(
+    op: &PowConstR<'dev>,
+    x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * x.powf(op.k - 1.) * op.k
+}
@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{Device, Operator, Shape, Tensor};
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Node, Operator, Shape, Tensor};
 
 define_operator_struct!(Powi, k, i32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Powi<'dev> {
@@ -25,4 +25,18 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Powi<'dev> {
         self.device()
             .powi_bw(x[0], y[0], gy[0], self.k, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        if self.k == 0 {
+            return Some(vec![None]);
+        }
+        Some(vec![Some(gy[0] * x[0].powi(self.k - 1) * self.k as f32)])
+    }
+
+    parallel_safe!();
 }
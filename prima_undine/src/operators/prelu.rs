@@ -1,3 +1,15 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(PReLU, prelu_fw, prelu_bw, a, f32);
+use super::common::unit_gradient;
+
+define_operator_x!(PReLU, prelu_fw, prelu_bw, a, f32; prelu_bw_node);
+
+fn prelu_bw_node<'arg, 'dev>(
+    op: &PReLU<'dev>,
+    x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * unit_gradient(x, y, |x, y, gy, gx| op.device.prelu_bw(x, y, gy, op.a, gx))
+}
@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+
+use crate::functions::RandomDeviceFunctions;
+use crate::{Device, Operator, Shape, Tensor};
+
+define_operator_struct!(RandomBernoulli, shape, Shape, p, f32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for RandomBernoulli<'dev> {
+    fn name(&self) -> String {
+        format!("RandomBernoulli(shape={:?},p={})", self.shape, self.p)
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![self.shape]
+    }
+
+    fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device.random_bernoulli(self.shape, self.p));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+}
+
+define_operator_struct!(RandomNormal, shape, Shape, mean, f32, sd, f32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for RandomNormal<'dev> {
+    fn name(&self) -> String {
+        format!(
+            "RandomNormal(shape={:?},mean={},sd={})",
+            self.shape, self.mean, self.sd
+        )
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![self.shape]
+    }
+
+    fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device.random_normal(self.shape, self.mean, self.sd));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+}
+
+define_operator_struct!(RandomUniform, shape, Shape, lower, f32, upper, f32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for RandomUniform<'dev> {
+    fn name(&self) -> String {
+        format!(
+            "RandomUniform(shape={:?},lower={},upper={})",
+            self.shape, self.lower, self.upper
+        )
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![self.shape]
+    }
+
+    fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(
+            self.device
+                .random_uniform(self.shape, self.lower, self.upper),
+        );
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+}
+
+define_operator_struct!(RandomLogNormal, shape, Shape, mean, f32, sd, f32);
+impl<'arg, 'dev> Operator<'arg, 'dev> for RandomLogNormal<'dev> {
+    fn name(&self) -> String {
+        format!(
+            "RandomLogNormal(shape={:?},mean={},sd={})",
+            self.shape, self.mean, self.sd
+        )
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
+        vec![self.shape]
+    }
+
+    fn forward(&self, _x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(
+            self.device
+                .random_log_normal(self.shape, self.mean, self.sd),
+        );
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+}
@@ -1,6 +1,8 @@
 use std::cell::RefCell;
 
-use crate::{Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::BasicFunctions;
+use crate::{Device, Error, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(Reshape, shape, Shape);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Reshape<'dev> {
@@ -12,8 +14,19 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Reshape<'dev> {
         self.device
     }
 
-    fn forward_shape(&self, _x: &[Shape]) -> Vec<Shape> {
-        vec![self.shape]
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        if x[0].size() != self.shape.size() {
+            return Err(Error::ShapeMismatch {
+                op: "reshape".to_string(),
+                lhs: x[0],
+                rhs: self.shape,
+            });
+        }
+        Ok(vec![self.shape])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -28,4 +41,15 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Reshape<'dev> {
         t.update_shape(gx.shape);
         *gx += t;
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0].reshape(x[0].shape()))])
+    }
+
+    parallel_safe!();
 }
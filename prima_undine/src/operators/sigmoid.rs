@@ -1,3 +1,13 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Sigmoid, sigmoid_fw, sigmoid_bw);
+define_operator_x!(Sigmoid, sigmoid_fw, sigmoid_bw; sigmoid_bw_node, Sigmoid);
+
+fn sigmoid_bw_node<'arg, 'dev>(
+    _op: &Sigmoid<'dev>,
+    _x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * y * (1. - y)
+}
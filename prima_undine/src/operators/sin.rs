@@ -1,3 +1,13 @@
-use crate::functions::BasicDeviceFunctions;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::Node;
 
-define_operator_x!(Sin, sin_fw, sin_bw);
+define_operator_x!(Sin, sin_fw, sin_bw; sin_bw_node, Sin);
+
+fn sin_bw_node<'arg, 'dev>(
+    _op: &Sin<'dev>,
+    x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * x.cos()
+}
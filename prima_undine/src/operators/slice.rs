@@ -1,7 +1,10 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
+
+use super::common::zeros;
 
 define_operator_struct!(Slice, dim, u32, lower, u32, upper, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Slice<'dev> {
@@ -21,7 +24,13 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Slice<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::slice(x[0], self.dim, self.lower, self.upper)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::slice(
+            x[0], self.dim, self.lower, self.upper,
+        )?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -35,4 +44,30 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Slice<'dev> {
         self.device()
             .slice_bw(gy[0], self.dim, self.lower, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let shape = x[0].shape().resize_batch(gy[0].shape().batch());
+        let mut xs = vec![];
+        if self.lower > 0 {
+            xs.push(zeros(self.device, shape.resize_dim(self.dim, self.lower)));
+        }
+        xs.push(gy[0].clone());
+        if self.upper < shape[self.dim] {
+            xs.push(zeros(
+                self.device,
+                shape.resize_dim(self.dim, shape[self.dim] - self.upper),
+            ));
+        }
+        Some(vec![Some(Node::concat(
+            &xs.iter().collect::<Vec<&Node>>(),
+            self.dim,
+        ))])
+    }
+
+    parallel_safe!();
 }
@@ -1,7 +1,8 @@
 use std::cell::RefCell;
 
+use crate::error::OrPanic;
 use crate::functions::{BasicDeviceFunctions, BasicFunctions};
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(SoftmaxCrossEntropy, dim, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for SoftmaxCrossEntropy<'dev> {
@@ -14,7 +15,13 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for SoftmaxCrossEntropy<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::elementwise(x[0], x[1]).resize_dim(self.dim, 1)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![
+            shape_ops::elementwise(x[0], x[1])?.try_resize_dim(self.dim, 1)?
+        ])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -27,6 +34,22 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for SoftmaxCrossEntropy<'dev> {
         *gx[0].borrow_mut() += (ln_softmax_x.exp() - x[1]) * &bcast_gy;
         *gx[1].borrow_mut() -= ln_softmax_x * &bcast_gy;
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        let ln_softmax_x = x[0].ln_softmax(self.dim);
+        let bcast_gy = gy[0].broadcast(self.dim, x[0].shape()[self.dim]);
+        Some(vec![
+            Some((ln_softmax_x.exp() - x[1]) * &bcast_gy),
+            Some(-ln_softmax_x * bcast_gy),
+        ])
+    }
+
+    parallel_safe!();
 }
 
 pub struct SparseSoftmaxCrossEntropy<'dev> {
@@ -59,7 +82,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for SparseSoftmaxCrossEntropy<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::pick(x[0], &self.ids, self.dim)]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::pick(x[0], &self.ids, self.dim)?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -73,4 +100,6 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for SparseSoftmaxCrossEntropy<'dev> {
             .device()
             .pick_bw(&-gy[0], &self.ids, self.dim, &mut *gx[0].borrow_mut());
     }
+
+    parallel_safe!();
 }
@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{Device, Operator, Shape, Tensor};
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Node, Operator, Shape, Tensor};
 
 define_operator_struct!(Softplus);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Softplus<'dev> {
@@ -24,4 +24,15 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Softplus<'dev> {
     fn backward(&self, x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]) {
         *gx[0].borrow_mut() += self.device().sigmoid_fw(x[0]) * gy[0];
     }
+
+    fn backward_node(
+        &self,
+        x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0] * x[0].sigmoid())])
+    }
+
+    parallel_safe!();
 }
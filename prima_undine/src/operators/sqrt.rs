@@ -1,3 +1,13 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Sqrt, sqrt_fw, sqrt_bw);
+define_operator_x!(Sqrt, sqrt_fw, sqrt_bw; sqrt_bw_node, Sqrt);
+
+fn sqrt_bw_node<'arg, 'dev>(
+    _op: &Sqrt<'dev>,
+    _x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy / y * 0.5
+}
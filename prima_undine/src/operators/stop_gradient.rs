@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+
+use crate::{Device, Node, Operator, Shape, Tensor};
+
+define_operator_struct!(StopGradient);
+impl<'arg, 'dev> Operator<'arg, 'dev> for StopGradient<'dev> {
+    fn name(&self) -> String {
+        "StopGradient".to_string()
+    }
+
+    fn device(&self) -> &'dev Device<'dev> {
+        self.device
+    }
+
+    fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
+        vec![x[0]]
+    }
+
+    fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
+        y[0].replace(self.device.copy_tensor(x[0]));
+    }
+
+    fn backward(&self, _x: &[&Tensor], _y: &[&Tensor], _gy: &[&Tensor], _gx: &[&RefCell<Tensor>]) {
+        // NOP
+    }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        _gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![None])
+    }
+
+    parallel_safe!();
+}
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 
 use crate::functions::{ArithmeticDeviceFunctions, BasicFunctions};
-use crate::{Device, Tensor};
+use crate::{Device, Node, Tensor};
+
+use super::common::{reduce_all, reduce_batch};
 
 define_operator_ab!(
     Sub,
@@ -9,11 +11,13 @@ define_operator_ab!(
     |device: &Device, x: &[&Tensor], y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         device.sub_bw_a(x[0], x[1], y[0], gy[0], &mut *gx[0].borrow_mut());
         device.sub_bw_b(x[0], x[1], y[0], gy[0], &mut *gx[1].borrow_mut());
-    }
+    },
+    sub_bw_node,
+    Sub
 );
 
-define_operator_x!(SubConstL, sub_const_l_fw, sub_const_l_bw, k, f32);
-define_operator_x!(SubConstR, sub_const_r_fw, sub_const_r_bw, k, f32);
+define_operator_x!(SubConstL, sub_const_l_fw, sub_const_l_bw, k, f32; sub_const_l_bw_node, SubConstL);
+define_operator_x!(SubConstR, sub_const_r_fw, sub_const_r_bw, k, f32; sub_const_r_bw_node, SubConstR);
 
 define_operator_ab!(
     SubScalarL,
@@ -21,7 +25,8 @@ define_operator_ab!(
     |_device: &Device, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         *gx[0].borrow_mut() -= gy[0];
         *gx[1].borrow_mut() += gy[0].flatten().sum(0);
-    }
+    },
+    sub_scalar_l_bw_node
 );
 
 define_operator_ab!(
@@ -30,5 +35,57 @@ define_operator_ab!(
     |_device: &Device, _x: &[&Tensor], _y: &[&Tensor], gy: &[&Tensor], gx: &[&RefCell<Tensor>]| {
         *gx[0].borrow_mut() += gy[0];
         *gx[1].borrow_mut() -= gy[0].flatten().sum(0);
-    }
+    },
+    sub_scalar_r_bw_node
 );
+
+fn sub_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(gy[0].clone(), x[0].shape())),
+        Some(reduce_batch(-gy[0], x[1].shape())),
+    ]
+}
+
+fn sub_scalar_l_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(-gy[0], x[0].shape())),
+        Some(reduce_all(gy[0].clone(), x[1].shape())),
+    ]
+}
+
+fn sub_scalar_r_bw_node<'arg, 'dev>(
+    x: &[&Node<'arg, 'dev>],
+    _y: &[&Node<'arg, 'dev>],
+    gy: &[&Node<'arg, 'dev>],
+) -> Vec<Option<Node<'arg, 'dev>>> {
+    vec![
+        Some(reduce_batch(gy[0].clone(), x[0].shape())),
+        Some(reduce_all(-gy[0], x[1].shape())),
+    ]
+}
+
+fn sub_const_l_bw_node<'arg, 'dev>(
+    _op: &SubConstL<'dev>,
+    _x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    -gy
+}
+
+fn sub_const_r_bw_node<'arg, 'dev>(
+    _op: &SubConstR<'dev>,
+    _x: &Node<'arg, 'dev>,
+    _y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy.clone()
+}
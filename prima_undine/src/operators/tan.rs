@@ -1,3 +1,13 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Tan, tan_fw, tan_bw);
+define_operator_x!(Tan, tan_fw, tan_bw; tan_bw_node);
+
+fn tan_bw_node<'arg, 'dev>(
+    _op: &Tan<'dev>,
+    _x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * (1. + y * y)
+}
@@ -1,3 +1,13 @@
 use crate::functions::BasicDeviceFunctions;
+use crate::Node;
 
-define_operator_x!(Tanh, tanh_fw, tanh_bw);
+define_operator_x!(Tanh, tanh_fw, tanh_bw; tanh_bw_node, Tanh);
+
+fn tanh_bw_node<'arg, 'dev>(
+    _op: &Tanh<'dev>,
+    _x: &Node<'arg, 'dev>,
+    y: &Node<'arg, 'dev>,
+    gy: &Node<'arg, 'dev>,
+) -> Node<'arg, 'dev> {
+    gy * (1. - y * y)
+}
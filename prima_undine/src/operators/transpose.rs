@@ -1,7 +1,8 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{shape_ops, Device, Operator, Shape, Tensor};
+use crate::error::OrPanic;
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{shape_ops, Device, Node, Operator, Result, Shape, Tensor};
 
 define_operator_struct!(Transpose);
 impl<'arg, 'dev> Operator<'arg, 'dev> for Transpose<'dev> {
@@ -14,7 +15,11 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Transpose<'dev> {
     }
 
     fn forward_shape(&self, x: &[Shape]) -> Vec<Shape> {
-        vec![shape_ops::transpose(x[0])]
+        self.try_forward_shape(x).or_panic()
+    }
+
+    fn try_forward_shape(&self, x: &[Shape]) -> Result<Vec<Shape>> {
+        Ok(vec![shape_ops::transpose(x[0])?])
     }
 
     fn forward(&self, x: &[&Tensor], y: &mut [&mut Tensor<'arg>]) {
@@ -25,4 +30,15 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for Transpose<'dev> {
         self.device
             .transpose_bw(x[0], y[0], gy[0], &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0].transpose())])
+    }
+
+    parallel_safe!();
 }
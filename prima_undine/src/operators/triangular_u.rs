@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 
-use crate::functions::BasicDeviceFunctions;
-use crate::{Device, Operator, Shape, Tensor};
+use crate::functions::{BasicDeviceFunctions, BasicFunctions};
+use crate::{Device, Node, Operator, Shape, Tensor};
 
 define_operator_struct!(TriangularU, k, u32);
 impl<'arg, 'dev> Operator<'arg, 'dev> for TriangularU<'dev> {
@@ -25,4 +25,15 @@ impl<'arg, 'dev> Operator<'arg, 'dev> for TriangularU<'dev> {
         self.device
             .triangular_u_bw(x[0], y[0], gy[0], self.k, &mut *gx[0].borrow_mut());
     }
+
+    fn backward_node(
+        &self,
+        _x: &[&Node<'arg, 'dev>],
+        _y: &[&Node<'arg, 'dev>],
+        gy: &[&Node<'arg, 'dev>],
+    ) -> Option<Vec<Option<Node<'arg, 'dev>>>> {
+        Some(vec![Some(gy[0].triangular_u(self.k))])
+    }
+
+    parallel_safe!();
 }
@@ -1,6 +1,49 @@
 use crate::functions::BasicFunctions;
 use crate::{Model, Parameter};
 
+// Number of successive steps without overflow after which the dynamic loss
+// scale is doubled.
+const LOSS_SCALE_GROWTH_INTERVAL: u32 = 2000;
+
+pub struct LossScaler {
+    pub(crate) scale: f32,
+    pub(crate) dynamic: bool,
+    good_steps: u32,
+}
+
+impl LossScaler {
+    pub(crate) fn new() -> LossScaler {
+        LossScaler {
+            scale: 1.,
+            dynamic: false,
+            good_steps: 0,
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.dynamic || self.scale != 1.
+    }
+
+    // Returns false if the step has to be skipped.
+    pub(crate) fn update(&mut self, overflow: bool) -> bool {
+        if overflow {
+            if self.dynamic {
+                self.scale /= 2.;
+                self.good_steps = 0;
+            }
+            return false;
+        }
+        if self.dynamic {
+            self.good_steps += 1;
+            if self.good_steps == LOSS_SCALE_GROWTH_INTERVAL {
+                self.scale *= 2.;
+                self.good_steps = 0;
+            }
+        }
+        true
+    }
+}
+
 pub trait OptimizerBase {
     fn epoch(&mut self) -> &mut u32;
     fn get_learning_rate_scaling(&self) -> f32;
@@ -9,6 +52,11 @@ pub trait OptimizerBase {
     fn set_weight_decay(&mut self, strength: f32);
     fn get_gradient_clipping(&self) -> f32;
     fn set_gradient_clipping(&mut self, threshold: f32);
+    fn get_loss_scale(&self) -> f32;
+    fn set_loss_scale(&mut self, scale: f32);
+    fn get_dynamic_loss_scaling(&self) -> bool;
+    fn set_dynamic_loss_scaling(&mut self, enabled: bool);
+    fn loss_scaler(&mut self) -> &mut LossScaler;
 }
 
 pub trait Optimizer: OptimizerBase {
@@ -20,6 +68,23 @@ pub trait Optimizer: OptimizerBase {
         }
     }
     fn update_parameters(&mut self, parameters: &mut [&mut Parameter]) {
+        if self.loss_scaler().enabled() {
+            // inf * 0 and nan * 0 are both nan.
+            let overflow = parameters.iter().any(|param| {
+                let g = &param.gradient * 0.;
+                !g.flatten().sum(0).to_float().is_finite()
+            });
+            let loss_scale = self.get_loss_scale();
+            if !self.loss_scaler().update(overflow) {
+                for param in parameters.iter_mut() {
+                    param.reset_gradient();
+                }
+                return;
+            }
+            for param in parameters.iter_mut() {
+                param.gradient *= 1. / loss_scale;
+            }
+        }
         let l2_strength = self.get_weight_decay();
         if l2_strength > 0. {
             for param in parameters.iter_mut() {
@@ -54,3 +119,41 @@ pub trait Optimizer: OptimizerBase {
         self.update_parameters(&mut model.parameters_mut());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::functions::BasicFunctions;
+    use crate::optimizers::SGD;
+    use crate::{devices as D, initializers as I, Optimizer, OptimizerBase};
+
+    #[test]
+    fn check_loss_scaling() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![2], &I::Constant::new(1.));
+        let mut optimizer = SGD::new(0.5);
+        optimizer.set_loss_scale(8.);
+        p.gradient = dev.new_tensor_by_slice(shape![2], &[8., -16.]);
+        optimizer.update_parameters(&mut [&mut p]);
+        assert_eq!(vec![0.5, 2.], p.value.to_vec());
+        assert_eq!(8., optimizer.get_loss_scale());
+    }
+
+    #[test]
+    fn check_dynamic_loss_scaling() {
+        let dev = D::Naive::new();
+        let mut p = dev.new_parameter(shape![2], &I::Constant::new(1.));
+        let mut optimizer = SGD::new(0.5);
+        optimizer.set_loss_scale(65536.);
+        optimizer.set_dynamic_loss_scaling(true);
+        p.gradient = dev.new_tensor_by_slice(shape![2], &[f32::INFINITY, 1.]);
+        optimizer.update_parameters(&mut [&mut p]);
+        assert_eq!(vec![1., 1.], p.value.to_vec());
+        assert_eq!(vec![0., 0.], p.gradient.to_vec());
+        assert_eq!(32768., optimizer.get_loss_scale());
+        for _ in 0..2000 {
+            p.gradient.reset(0.);
+            optimizer.update_parameters(&mut [&mut p]);
+        }
+        assert_eq!(65536., optimizer.get_loss_scale());
+    }
+}
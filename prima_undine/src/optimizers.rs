@@ -5,6 +5,7 @@ macro_rules! define_optimizer_struct {
             lr_scale: f32,
             l2_strength: f32,
             clip_threshold: f32,
+            loss_scaler: crate::optimizer::LossScaler,
             $( $attr: $type, )*
         }
 
@@ -15,6 +16,7 @@ macro_rules! define_optimizer_struct {
                     lr_scale: 1.,
                     l2_strength: 0.,
                     clip_threshold: 0.,
+                    loss_scaler: crate::optimizer::LossScaler::new(),
                     $( $attr: $attr, )*
                 }
             }
@@ -51,6 +53,27 @@ macro_rules! define_optimizer_struct {
                 assert!(threshold >= 0.);
                 self.clip_threshold = threshold;
             }
+
+            fn get_loss_scale(&self) -> f32 {
+                self.loss_scaler.scale
+            }
+
+            fn set_loss_scale(&mut self, scale: f32) {
+                assert!(scale > 0.);
+                self.loss_scaler.scale = scale;
+            }
+
+            fn get_dynamic_loss_scaling(&self) -> bool {
+                self.loss_scaler.dynamic
+            }
+
+            fn set_dynamic_loss_scaling(&mut self, enabled: bool) {
+                self.loss_scaler.dynamic = enabled;
+            }
+
+            fn loss_scaler(&mut self) -> &mut crate::optimizer::LossScaler {
+                &mut self.loss_scaler
+            }
         }
     };
 }
@@ -16,13 +16,13 @@ impl Optimizer for Adam {
     fn update_parameter(&self, scale: f32, parameter: &mut Parameter) {
         let epoch = (self.epoch + 1) as f32;
         let g = &parameter.gradient;
-        let mut m1 = parameter.stats["Adam.m1"].borrow_mut();
-        let mut m2 = parameter.stats["Adam.m2"].borrow_mut();
+        let m1 = parameter.stats.get_mut("Adam.m1").unwrap();
         *m1 *= self.beta1;
         *m1 += (1. - self.beta1) * g;
+        let mm1 = &*m1 / (1. - self.beta1.powf(epoch));
+        let m2 = parameter.stats.get_mut("Adam.m2").unwrap();
         *m2 *= self.beta2;
         *m2 += (1. - self.beta2) * g * g;
-        let mm1 = &*m1 / (1. - self.beta1.powf(epoch));
         let mm2 = &*m2 / (1. - self.beta2.powf(epoch));
         parameter.value -= (scale * self.alpha) * mm1 / (mm2.sqrt() + self.eps);
     }
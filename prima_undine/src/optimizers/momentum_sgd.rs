@@ -11,7 +11,7 @@ impl Optimizer for MomentumSGD {
 
     fn update_parameter(&self, scale: f32, parameter: &mut Parameter) {
         let mdiff = (scale * self.eta) * &parameter.gradient;
-        let mut m = parameter.stats["MomentumSGD.m"].borrow_mut();
+        let m = parameter.stats.get_mut("MomentumSGD.m").unwrap();
         *m *= self.momentum;
         *m -= &mdiff;
         parameter.value += &*m;
@@ -1,16 +1,17 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ops::Deref;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{Device, Shape, Tensor};
+use crate::{DType, Device, Shape, Tensor};
 
 #[derive(Serialize, Deserialize)]
 pub struct Parameter<'dev> {
     pub value: Tensor<'dev>,
     pub gradient: Tensor<'dev>,
-    pub stats: HashMap<String, RefCell<Tensor<'dev>>>,
+    pub stats: HashMap<String, Tensor<'dev>>,
+    #[serde(skip)]
+    compute_dtype: DType,
 }
 
 impl<'dev> Parameter<'dev> {
@@ -21,6 +22,7 @@ impl<'dev> Parameter<'dev> {
             value: value,
             gradient: gradient,
             stats: HashMap::new(),
+            compute_dtype: DType::F32,
         }
     }
 
@@ -28,7 +30,7 @@ impl<'dev> Parameter<'dev> {
         self.value.move_to_device(device);
         self.gradient.move_to_device(device);
         for (_, stat) in self.stats.iter_mut() {
-            stat.borrow_mut().move_to_device(device);
+            stat.move_to_device(device);
         }
     }
 
@@ -36,6 +38,16 @@ impl<'dev> Parameter<'dev> {
         self.value.shape
     }
 
+    // The value and the gradient are always kept in f32, and the value is
+    // casted to this dtype when the parameter is used in a graph.
+    pub fn compute_dtype(&self) -> DType {
+        self.compute_dtype
+    }
+
+    pub fn set_compute_dtype(&mut self, dtype: DType) {
+        self.compute_dtype = dtype;
+    }
+
     pub fn reset_gradient(&mut self) {
         self.gradient.reset(0.);
     }
@@ -43,7 +55,7 @@ impl<'dev> Parameter<'dev> {
     pub fn add_stat(&mut self, name: &str, shape: Shape) {
         assert!(!self.stats.contains_key(name));
         let stat = self.value.device().new_tensor_by_constant(shape, 0.);
-        self.stats.insert(name.to_string(), RefCell::new(stat));
+        self.stats.insert(name.to_string(), stat);
     }
 
     pub fn has_stat(&self, name: &str) -> bool {
@@ -1,11 +1,35 @@
 use rand::distributions::{Bernoulli, Distribution, Uniform};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use rand_distr::{LogNormal, Normal};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
 
 pub trait Randomizer: Send + Sync {
     fn fill_bernoulli(&mut self, p: f32, data: &mut [f32]);
     fn fill_uniform(&mut self, lower: f32, upper: f32, data: &mut [f32]);
     fn fill_normal(&mut self, mean: f32, sd: f32, data: &mut [f32]);
     fn fill_log_normal(&mut self, mean: f32, sd: f32, data: &mut [f32]);
+
+    // Returns None if the randomizer does not support saving its state.
+    fn state(&self) -> Option<RandomizerState> {
+        None
+    }
+
+    fn set_state(&mut self, _state: &RandomizerState) -> Result<()> {
+        Err(Error::InvalidArgument {
+            op: "set_state".to_string(),
+            message: "the randomizer does not support restoring its state".to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RandomizerState {
+    pub seed: u64,
+    pub stream: u64,
+    pub word_pos: u128,
 }
 
 pub struct DefaultRandomizer;
@@ -49,3 +73,127 @@ impl Randomizer for DefaultRandomizer {
         }
     }
 }
+
+pub struct SeededRandomizer {
+    seed: u64,
+    stream: u64,
+    rng: ChaCha20Rng,
+}
+
+impl SeededRandomizer {
+    pub fn new(seed: u64) -> SeededRandomizer {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        // fills the internal buffer so that get_word_pos() is valid before any sampling
+        rng.set_word_pos(0);
+        SeededRandomizer {
+            seed,
+            stream: 0,
+            rng,
+        }
+    }
+}
+
+impl Randomizer for SeededRandomizer {
+    fn fill_bernoulli(&mut self, p: f32, data: &mut [f32]) {
+        let dist = Bernoulli::new(p as f64).unwrap();
+        for d in data {
+            *d = dist.sample(&mut self.rng) as u32 as f32;
+        }
+    }
+
+    fn fill_uniform(&mut self, lower: f32, upper: f32, data: &mut [f32]) {
+        let dist = Uniform::new(lower, upper);
+        for d in data {
+            *d = dist.sample(&mut self.rng);
+        }
+    }
+
+    fn fill_normal(&mut self, mean: f32, sd: f32, data: &mut [f32]) {
+        let dist = Normal::new(mean, sd).unwrap();
+        for d in data {
+            *d = dist.sample(&mut self.rng);
+        }
+    }
+
+    fn fill_log_normal(&mut self, mean: f32, sd: f32, data: &mut [f32]) {
+        let dist = LogNormal::new(mean, sd).unwrap();
+        for d in data {
+            *d = dist.sample(&mut self.rng);
+        }
+    }
+
+    fn state(&self) -> Option<RandomizerState> {
+        Some(RandomizerState {
+            seed: self.seed,
+            stream: self.stream,
+            word_pos: self.rng.get_word_pos(),
+        })
+    }
+
+    fn set_state(&mut self, state: &RandomizerState) -> Result<()> {
+        let mut rng = ChaCha20Rng::seed_from_u64(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(state.word_pos);
+        self.seed = state.seed;
+        self.stream = state.stream;
+        self.rng = rng;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices as D;
+    use crate::functions::{BasicFunctions, RandomDeviceFunctions};
+
+    #[test]
+    fn check_seeded_randomizer_reproducible() {
+        let mut r1 = SeededRandomizer::new(42);
+        let mut r2 = SeededRandomizer::new(42);
+        let mut d1 = vec![0.; 16];
+        let mut d2 = vec![0.; 16];
+        r1.fill_normal(0., 1., &mut d1);
+        r2.fill_normal(0., 1., &mut d2);
+        assert_eq!(d1, d2);
+        r1.fill_uniform(-1., 1., &mut d1);
+        r2.fill_uniform(-1., 1., &mut d2);
+        assert_eq!(d1, d2);
+    }
+
+    #[test]
+    fn check_seeded_randomizer_state() {
+        let mut r = SeededRandomizer::new(1);
+        let mut d = vec![0.; 7];
+        r.fill_normal(0., 1., &mut d);
+        let state = r.state().unwrap();
+        let mut expected = vec![0.; 10];
+        r.fill_bernoulli(0.5, &mut expected);
+        let mut r2 = SeededRandomizer::new(2);
+        r2.set_state(&state).unwrap();
+        let mut d2 = vec![0.; 10];
+        r2.fill_bernoulli(0.5, &mut d2);
+        assert_eq!(expected, d2);
+    }
+
+    #[test]
+    fn check_naive_with_seed() {
+        let dev1 = D::Naive::with_seed(123);
+        let dev2 = D::Naive::with_seed(123);
+        let state = dev1.randomizer_state().unwrap();
+        let y1 = dev1.random_normal(shape![3, 4; 2], 1., 2.);
+        let y2 = dev2.random_normal(shape![3, 4; 2], 1., 2.);
+        assert_eq!(y1.to_vec(), y2.to_vec());
+        dev1.set_randomizer_state(&state).unwrap();
+        let y3 = dev1.random_normal(shape![3, 4; 2], 1., 2.);
+        assert_eq!(y1.to_vec(), y3.to_vec());
+    }
+
+    #[test]
+    fn check_randomizer_without_state() {
+        let dev = D::Naive::new();
+        assert_eq!(None, dev.randomizer_state());
+        let state = SeededRandomizer::new(1).state().unwrap();
+        assert!(dev.set_randomizer_state(&state).is_err());
+    }
+}
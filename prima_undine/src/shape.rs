@@ -5,6 +5,9 @@ use std::ops::Index;
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::error::OrPanic;
+use crate::{Error, Result};
+
 const MAX_DEPTH: u32 = 8;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -17,25 +20,38 @@ pub struct Shape {
 
 impl Shape {
     pub fn new(dims: &[u32], batch: u32) -> Shape {
-        assert!(dims.len() <= MAX_DEPTH as usize);
-        assert!(batch >= 1);
+        Shape::try_new(dims, batch).or_panic()
+    }
+
+    pub fn try_new(dims: &[u32], batch: u32) -> Result<Shape> {
+        if dims.len() > MAX_DEPTH as usize {
+            return Err(Error::InvalidArgument {
+                op: "Shape::new".to_string(),
+                message: format!("depth {} exceeds {}", dims.len(), MAX_DEPTH),
+            });
+        }
+        if batch == 0 || dims.contains(&0) {
+            return Err(Error::InvalidArgument {
+                op: "Shape::new".to_string(),
+                message: format!("zero-sized shape: {:?} x {}", dims, batch),
+            });
+        }
         let mut dims_filled = [1; MAX_DEPTH as usize];
         let mut volume = 1;
         let mut depth = 0;
         for i in 0..dims.len() {
-            assert!(dims[i] != 0);
             dims_filled[i] = dims[i];
             if dims[i] != 1 {
                 depth = i as u32 + 1;
             }
             volume *= dims[i];
         }
-        Shape {
+        Ok(Shape {
             dims: dims_filled,
             batch: batch,
             depth: depth,
             volume: volume,
-        }
+        })
     }
 
     pub fn depth(&self) -> u32 {
@@ -120,6 +136,16 @@ impl Shape {
         ret
     }
 
+    pub fn try_resize_dim(&self, dim: u32, m: u32) -> Result<Shape> {
+        if dim >= MAX_DEPTH || m == 0 {
+            return Err(Error::InvalidArgument {
+                op: "Shape::resize_dim".to_string(),
+                message: format!("cannot resize dim {} of {:?} to {}", dim, self, m),
+            });
+        }
+        Ok(self.resize_dim(dim, m))
+    }
+
     pub fn resize_batch(&self, batch: u32) -> Shape {
         let mut ret = *self;
         ret.update_batch(batch);
@@ -173,17 +199,17 @@ struct ShapeSerde {
 }
 
 impl<'de> Deserialize<'de> for Shape {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s: ShapeSerde = Deserialize::deserialize(deserializer)?;
-        Ok(Shape::new(&s.dims, s.batch))
+        Shape::try_new(&s.dims, s.batch).map_err(serde::de::Error::custom)
     }
 }
 
 impl Serialize for Shape {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
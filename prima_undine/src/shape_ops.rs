@@ -1,86 +1,265 @@
 use std::cmp;
 
-use crate::Shape;
+use crate::{Error, Result, Shape};
 
-pub fn scalar_op(x: Shape, k: Shape) -> Shape {
-    assert!(k.is_scalar() && x.has_compatible_batch(k));
-    x.resize_batch(cmp::max(x.batch(), k.batch()))
+fn mismatch(op: &str, lhs: Shape, rhs: Shape) -> Error {
+    Error::ShapeMismatch {
+        op: op.to_string(),
+        lhs,
+        rhs,
+    }
+}
+
+fn invalid_shape(op: &str, shape: Shape) -> Error {
+    Error::InvalidShape {
+        op: op.to_string(),
+        shape,
+    }
+}
+
+fn invalid_argument(op: &str, message: String) -> Error {
+    Error::InvalidArgument {
+        op: op.to_string(),
+        message,
+    }
+}
+
+pub fn scalar_op(x: Shape, k: Shape) -> Result<Shape> {
+    if !(k.is_scalar() && x.has_compatible_batch(k)) {
+        return Err(mismatch("scalar_op", x, k));
+    }
+    Ok(x.resize_batch(cmp::max(x.batch(), k.batch())))
+}
+
+pub fn elementwise(a: Shape, b: Shape) -> Result<Shape> {
+    if !(a.has_same_dims(b) && a.has_compatible_batch(b)) {
+        return Err(mismatch("elementwise", a, b));
+    }
+    Ok(a.resize_batch(cmp::max(a.batch(), b.batch())))
 }
 
-pub fn elementwise(a: Shape, b: Shape) -> Shape {
-    assert!(a.has_same_dims(b) && a.has_compatible_batch(b));
-    a.resize_batch(cmp::max(a.batch(), b.batch()))
+pub fn broadcast(x: Shape, dim: u32, size: u32) -> Result<Shape> {
+    if !(x[dim] == 1 && size != 0) {
+        return Err(invalid_argument(
+            "broadcast",
+            format!("cannot broadcast dim {} of {:?} to {}", dim, x, size),
+        ));
+    }
+    x.try_resize_dim(dim, size)
+        .map_err(|e| e.with_op("broadcast"))
 }
 
-pub fn broadcast(x: Shape, dim: u32, size: u32) -> Shape {
-    assert!(x[dim] == 1 && size != 0);
-    x.resize_dim(dim, size)
+pub fn matmul(l: Shape, r: Shape) -> Result<Shape> {
+    if !(l.is_matrix() && r.is_matrix() && l[1] == r[0] && l.has_compatible_batch(r)) {
+        return Err(mismatch("matmul", l, r));
+    }
+    Shape::try_new(&[l[0], r[1]], cmp::max(l.batch(), r.batch()))
 }
 
-pub fn matmul(l: Shape, r: Shape) -> Shape {
-    assert!(l.is_matrix() && r.is_matrix() && l[1] == r[0] && l.has_compatible_batch(r));
-    Shape::new(&[l[0], r[1]], cmp::max(l.batch(), r.batch()))
+pub fn transpose(x: Shape) -> Result<Shape> {
+    if !x.is_matrix() {
+        return Err(invalid_shape("transpose", x));
+    }
+    Shape::try_new(&[x[1], x[0]], x.batch())
 }
 
-pub fn transpose(x: Shape) -> Shape {
-    assert!(x.is_matrix());
-    Shape::new(&[x[1], x[0]], x.batch())
+pub fn permute_dims(x: Shape, perm: &[u32]) -> Result<Shape> {
+    let ndims = perm.len();
+    if (x.depth() as usize) > ndims {
+        return Err(invalid_argument(
+            "permute_dims",
+            format!("{:?} is not a permutation for {:?}", perm, x),
+        ));
+    }
+    let mut dims = vec![0; ndims];
+    let mut used = vec![false; ndims];
+    for i in 0..ndims {
+        let j = perm[i] as usize;
+        if j >= ndims || used[j] {
+            return Err(invalid_argument(
+                "permute_dims",
+                format!("{:?} is not a permutation for {:?}", perm, x),
+            ));
+        }
+        used[j] = true;
+        dims[i] = x[j as u32];
+    }
+    Shape::try_new(&dims, x.batch())
 }
 
-pub fn slice(x: Shape, dim: u32, lower: u32, upper: u32) -> Shape {
-    assert!(lower < upper);
-    assert!(upper <= x[dim]);
-    x.resize_dim(dim, upper - lower)
+pub fn slice(x: Shape, dim: u32, lower: u32, upper: u32) -> Result<Shape> {
+    if !(lower < upper && upper <= x[dim]) {
+        return Err(invalid_argument(
+            "slice",
+            format!(
+                "invalid range [{}, {}) of dim {} of {:?}",
+                lower, upper, dim, x
+            ),
+        ));
+    }
+    x.try_resize_dim(dim, upper - lower)
+        .map_err(|e| e.with_op("slice"))
 }
 
-pub fn pick(x: Shape, ids: &[u32], dim: u32) -> Shape {
+pub fn pick(x: Shape, ids: &[u32], dim: u32) -> Result<Shape> {
     let n = x[dim];
     let bi = ids.len() as u32;
-    assert!(bi != 0 && (x.batch() == bi || !x.has_batch() || bi == 1));
-    for i in 0..bi as usize {
-        assert!(ids[i] < n);
+    if !(bi != 0 && (x.batch() == bi || !x.has_batch() || bi == 1)) {
+        return Err(invalid_argument("pick", format!("{} ids for {:?}", bi, x)));
     }
-    x.resize_dim(dim, 1).resize_batch(cmp::max(x.batch(), bi))
+    if let Some(id) = ids.iter().find(|&&id| id >= n) {
+        return Err(invalid_argument(
+            "pick",
+            format!("id {} is out of range of dim {} of {:?}", id, dim, x),
+        ));
+    }
+    let y = x.try_resize_dim(dim, 1).map_err(|e| e.with_op("pick"))?;
+    Ok(y.resize_batch(cmp::max(x.batch(), bi)))
 }
 
-pub fn concat(xs: &[Shape], dim: u32) -> Shape {
-    assert!(xs.len() >= 1);
+// The ids are given as a tensor holding one id in each batch. Their range is
+// checked by the kernel.
+pub fn pick_by(x: Shape, ids: Shape, dim: u32) -> Result<Shape> {
+    let bi = ids.batch();
+    if !(ids.is_scalar() && (x.batch() == bi || !x.has_batch() || bi == 1)) {
+        return Err(mismatch("pick", x, ids));
+    }
+    let y = x.try_resize_dim(dim, 1).map_err(|e| e.with_op("pick"))?;
+    Ok(y.resize_batch(cmp::max(x.batch(), bi)))
+}
+
+pub fn concat(xs: &[Shape], dim: u32) -> Result<Shape> {
+    if xs.is_empty() {
+        return Err(invalid_argument("concat", "no arguments".to_string()));
+    }
     let mut s0 = xs[0];
     let mut sum = s0[dim];
     for i in 1..xs.len() {
         let s = xs[i];
-        assert!(s0.has_same_loo_dims(s, dim) && s0.has_compatible_batch(s));
+        if !(s0.has_same_loo_dims(s, dim) && s0.has_compatible_batch(s)) {
+            return Err(mismatch("concat", s0, s));
+        }
         if !s0.has_batch() {
             s0.update_batch(s.batch());
         }
         sum += s[dim];
     }
-    s0.resize_dim(dim, sum)
+    s0.try_resize_dim(dim, sum).map_err(|e| e.with_op("concat"))
 }
 
-pub fn batch_pick(x: Shape, ids: &[u32]) -> Shape {
+pub fn batch_pick(x: Shape, ids: &[u32]) -> Result<Shape> {
     let n = x.batch();
     let bi = ids.len() as u32;
-    assert!(bi != 0);
-    for &id in ids {
-        assert!(id < n);
+    if bi == 0 {
+        return Err(invalid_argument("batch_pick", "no ids".to_string()));
+    }
+    if let Some(id) = ids.iter().find(|&&id| id >= n) {
+        return Err(invalid_argument(
+            "batch_pick",
+            format!("id {} is out of range of batch of {:?}", id, x),
+        ));
     }
-    x.resize_batch(bi)
+    Ok(x.resize_batch(bi))
 }
 
-pub fn batch_slice(x: Shape, lower: u32, upper: u32) -> Shape {
-    assert!(lower < upper && upper <= x.batch());
-    x.resize_batch(upper - lower)
+pub fn batch_pick_by(x: Shape, ids: Shape) -> Result<Shape> {
+    if !ids.is_scalar() {
+        return Err(mismatch("batch_pick", x, ids));
+    }
+    Ok(x.resize_batch(ids.batch()))
 }
 
-pub fn batch_concat(xs: &[Shape]) -> Shape {
-    assert!(xs.len() != 0);
+pub fn batch_slice(x: Shape, lower: u32, upper: u32) -> Result<Shape> {
+    if !(lower < upper && upper <= x.batch()) {
+        return Err(invalid_argument(
+            "batch_slice",
+            format!("invalid range [{}, {}) of batch of {:?}", lower, upper, x),
+        ));
+    }
+    Ok(x.resize_batch(upper - lower))
+}
+
+pub fn batch_concat(xs: &[Shape]) -> Result<Shape> {
+    if xs.is_empty() {
+        return Err(invalid_argument("batch_concat", "no arguments".to_string()));
+    }
     let s0 = xs[0];
     let mut sum = s0.batch();
     for i in 1..xs.len() {
         let s = xs[i];
-        assert!(s0.has_same_dims(s));
+        if !s0.has_same_dims(s) {
+            return Err(mismatch("batch_concat", s0, s));
+        }
         sum += s.batch();
     }
-    s0.resize_batch(sum)
+    Ok(s0.resize_batch(sum))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d(
+    x: Shape,
+    w: Shape,
+    padding0: u32,
+    padding1: u32,
+    stride0: u32,
+    stride1: u32,
+    dilation0: u32,
+    dilation1: u32,
+) -> Result<Shape> {
+    if !(x.depth() <= 3 && w.depth() <= 4) {
+        return Err(mismatch("conv2d", x, w));
+    }
+    if !(stride0 != 0 && stride1 != 0 && dilation0 != 0 && dilation1 != 0) {
+        return Err(invalid_argument(
+            "conv2d",
+            "strides and dilations must be positive".to_string(),
+        ));
+    }
+    let x0 = x[0] + 2 * padding0;
+    let x1 = x[1] + 2 * padding1;
+    let w0 = (w[0] - 1) * dilation0 + 1;
+    let w1 = (w[1] - 1) * dilation1 + 1;
+    if !(x0 >= w0 && x1 >= w1 && x[2] == w[2] && x.has_compatible_batch(w)) {
+        return Err(mismatch("conv2d", x, w));
+    }
+    Shape::try_new(
+        &[(x0 - w0) / stride0 + 1, (x1 - w1) / stride1 + 1, w[3]],
+        cmp::max(x.batch(), w.batch()),
+    )
+}
+
+pub fn pool2d(
+    x: Shape,
+    window0: u32,
+    window1: u32,
+    padding0: u32,
+    padding1: u32,
+    stride0: u32,
+    stride1: u32,
+) -> Result<Shape> {
+    if x.depth() > 3 {
+        return Err(invalid_shape("pool2d", x));
+    }
+    if !(window0 != 0 && window1 != 0 && stride0 != 0 && stride1 != 0) {
+        return Err(invalid_argument(
+            "pool2d",
+            "windows and strides must be positive".to_string(),
+        ));
+    }
+    let x0 = x[0] + 2 * padding0;
+    let x1 = x[1] + 2 * padding1;
+    if !(x0 >= window0 && x1 >= window1) {
+        return Err(invalid_argument(
+            "pool2d",
+            format!("window {}x{} is larger than {:?}", window0, window1, x),
+        ));
+    }
+    Shape::try_new(
+        &[
+            (x0 - window0) / stride0 + 1,
+            (x1 - window1) / stride1 + 1,
+            x[2],
+        ],
+        x.batch(),
+    )
 }
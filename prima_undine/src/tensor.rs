@@ -5,7 +5,7 @@ use std::sync::atomic::{AtomicPtr, Ordering};
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{Device, Parameter, Shape};
+use crate::{DType, Device, Parameter, Shape};
 
 pub struct Tensor<'dev>
 where
@@ -13,6 +13,7 @@ where
 {
     device: Option<&'dev Device<'dev>>,
     pub(crate) shape: Shape,
+    pub(crate) dtype: DType,
     handle: AtomicPtr<c_void>,
     host_values: Option<Vec<f32>>,
     reference: Option<&'dev Tensor<'dev>>,
@@ -20,9 +21,14 @@ where
 
 impl<'dev> Tensor<'dev> {
     pub fn new(device: &'dev Device<'dev>, shape: Shape) -> Tensor<'dev> {
+        Tensor::new_with_dtype(device, shape, DType::F32)
+    }
+
+    pub fn new_with_dtype(device: &'dev Device<'dev>, shape: Shape, dtype: DType) -> Tensor<'dev> {
         Tensor {
             device: Some(device),
             shape: shape,
+            dtype,
             handle: AtomicPtr::new(ptr::null_mut()),
             host_values: None,
             reference: None,
@@ -33,6 +39,7 @@ impl<'dev> Tensor<'dev> {
         Tensor {
             device: None,
             shape: shape,
+            dtype: DType::F32,
             handle: AtomicPtr::new(ptr::null_mut()),
             host_values: Some(values),
             reference: None,
@@ -42,7 +49,7 @@ impl<'dev> Tensor<'dev> {
     pub fn move_to_device(&mut self, device: &'dev Device<'dev>) {
         assert!(self.host_values.is_some());
         self.device = Some(device);
-        self.handle = self.device.unwrap().new_handle(self.shape.size());
+        self.handle = self.device.unwrap().new_handle(self.storage_size());
         let values = self.host_values.take().unwrap();
         device.reset_tensor_by_slice(self, &values);
     }
@@ -50,7 +57,15 @@ impl<'dev> Tensor<'dev> {
     pub fn alloc(&mut self) {
         assert!(!self.valid());
         assert!(self.host_values.is_none());
-        self.handle = self.device.unwrap().new_handle(self.shape.size());
+        self.handle = self.device.unwrap().new_handle(self.storage_size());
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.dtype
+    }
+
+    pub(crate) fn storage_size(&self) -> u32 {
+        self.dtype.words(self.shape.size())
     }
 
     pub fn valid(&self) -> bool {
@@ -62,6 +77,7 @@ impl<'dev> Tensor<'dev> {
         assert!(!self.valid());
         assert_eq!(self.shape, other.shape);
         assert!(self.host_values.is_none());
+        self.dtype = other.dtype;
         self.handle
             .store(other.handle.load(Ordering::Acquire), Ordering::Release);
         self.reference = Some(other);
@@ -72,6 +88,7 @@ impl<'dev> Tensor<'dev> {
         assert!(!self.valid());
         assert_eq!(self.shape, other.shape);
         assert!(self.host_values.is_none());
+        self.dtype = other.dtype;
         self.handle
             .store(other.handle.load(Ordering::Acquire), Ordering::Release);
         other.handle.store(ptr::null_mut(), Ordering::Release);
@@ -124,6 +141,10 @@ impl<'dev> Serialize for Tensor<'dev> {
     where
         S: Serializer,
     {
+        assert!(
+            self.dtype == DType::F32,
+            "only f32 tensors can be serialized"
+        );
         let mut s = serializer.serialize_struct("Tensor", 2)?;
         s.serialize_field("shape", &self.shape)?;
         s.serialize_field("values", &self.device().tensor_to_vector(self))?;
@@ -136,7 +157,7 @@ where
     'dev: 'arg,
 {
     fn from(item: &'arg Tensor<'dev>) -> Self {
-        let mut t = item.device().new_tensor(item.shape);
+        let mut t = item.device().new_tensor_with_dtype(item.shape, item.dtype);
         t.refer(item);
         t
     }
@@ -36,3 +36,11 @@ macro_rules! generate_bw_testset {
         (y, gx)
     }};
 }
+
+// Deterministic values in [-5, 5) without a regular pattern, for tensors
+// large enough to be split over threads.
+pub fn generate_values(n: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| ((i * 7919) % 1000) as f32 / 100. - 5.)
+        .collect()
+}
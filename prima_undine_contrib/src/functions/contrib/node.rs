@@ -1,5 +1,4 @@
 use prima_undine::functions::BasicFunctions;
-use prima_undine::functions::RandomDeviceFunctions;
 use prima_undine::Node;
 
 use super::ContribFunctions;
@@ -22,7 +21,7 @@ impl<'arg, 'dev> ContribFunctions for Node<'arg, 'dev> {
             0. * self
         } else {
             let p = 1. - rate;
-            (1. / p) * self * Self::from(self.device().random_bernoulli(self.shape(), p))
+            (1. / p) * self * Node::random_bernoulli(self.device(), self.shape(), p)
         }
     }
 
@@ -34,14 +33,14 @@ impl<'arg, 'dev> ContribFunctions for Node<'arg, 'dev> {
         } else {
             let p = 1. - rate;
             let rb_shape = self.shape().resize_dim(dim, 1);
-            let rb = self.device().random_bernoulli(rb_shape, p);
-            (1. / p) * self * Self::from(rb.broadcast(dim, self.shape()[dim]))
+            let rb = Node::random_bernoulli(self.device(), rb_shape, p);
+            (1. / p) * self * rb.broadcast(dim, self.shape()[dim])
         }
     }
 
     fn slice_sum(xs: &[&Self]) -> Self {
         assert!(xs.len() != 0);
-        let mut ret = Self::from(xs[0].device().new_tensor_by_constant(xs[0].shape(), 0.));
+        let mut ret = Node::constant(xs[0].device(), xs[0].shape(), 0.);
         for x in xs {
             ret = ret + *x;
         }